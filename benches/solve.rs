@@ -0,0 +1,159 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use levenberg_marquardt::{
+    optimize_problem, optimize_report, Config, HessianClosureProblem, Method, SolveMethod,
+};
+use nalgebra::{
+    dimension::U1, Cholesky, DMatrix, DVector, Dynamic, Matrix, MatrixN, VecStorage, VectorN, U20,
+};
+
+type Model = VectorN<f64, U20>;
+type Residuals = Matrix<f64, U1, Dynamic, VecStorage<f64, U1, Dynamic>>;
+
+/// Term `k` of the Fourier basis at `x`.
+fn fourier(k: usize, x: f64) -> f64 {
+    let frequency = (k / 2 + 1) as f64;
+    if k % 2 == 0 {
+        (frequency * x).sin()
+    } else {
+        (frequency * x).cos()
+    }
+}
+
+/// The Fourier basis of a 20-parameter series at `x`.
+fn basis(x: f64) -> Model {
+    Model::from_fn(|k, _| fourier(k, x))
+}
+
+fn samples() -> Vec<(f64, f64)> {
+    let truth = Model::from_fn(|k, _| 1.0 / (k + 1) as f64);
+    (0..200)
+        .map(|x| {
+            let x = f64::from(x) * 0.03;
+            (x, basis(x).dot(&truth))
+        })
+        .collect()
+}
+
+fn damped_hessian(samples: &[(f64, f64)]) -> MatrixN<f64, U20> {
+    let hessian = samples
+        .iter()
+        .fold(MatrixN::<f64, U20>::zeros(), |hessian, &(x, _)| {
+            let jacobian = basis(x);
+            hessian + jacobian * jacobian.transpose()
+        });
+    let mut damped = hessian;
+    damped.set_diagonal(&(hessian.diagonal() * 1.5));
+    damped
+}
+
+/// The same as [`damped_hessian`] for a series of `p` parameters.
+fn dynamic_damped_hessian(samples: &[(f64, f64)], p: usize) -> DMatrix<f64> {
+    let hessian = samples
+        .iter()
+        .fold(DMatrix::zeros(p, p), |hessian, &(x, _)| {
+            let jacobian = DVector::from_fn(p, |k, _| fourier(k, x));
+            hessian + &jacobian * jacobian.transpose()
+        });
+    let mut damped = hessian.clone();
+    damped.set_diagonal(&(hessian.diagonal() * 1.5));
+    damped
+}
+
+fn solve(c: &mut Criterion) {
+    let samples = samples();
+    let damped = damped_hessian(&samples);
+    let gradients = Model::repeat(1.0);
+
+    let mut group = c.benchmark_group("damped_system_20");
+    group.bench_function("inverse", |b| {
+        b.iter(|| {
+            black_box(damped)
+                .try_inverse()
+                .map(|inverse| inverse * black_box(&gradients))
+        })
+    });
+    group.bench_function("cholesky", |b| {
+        b.iter(|| Cholesky::new(black_box(damped)).map(|c| c.solve(black_box(&gradients))))
+    });
+    group.finish();
+
+    // The damped system is solved with a Cholesky decomposition, which exploits its symmetry.
+    // When it has none, only the step is needed, so it is solved with an LU decomposition
+    // rather than by forming the inverse.
+    for &p in &[30, 50] {
+        let damped = dynamic_damped_hessian(&samples, p);
+        let gradients = DVector::repeat(p, 1.0);
+        // The permutation which swaps every pair of parameters is indefinite, so a Gauss-Newton
+        // step with it as the Hessian has no Cholesky decomposition and takes the LU fallback.
+        // `r = 3 - p` is solved exactly by that step, so each run solves the system once, along
+        // with the few evaluations of a single iteration around it.
+        let swaps = DMatrix::from_fn(p, p, |i, j| if i ^ 1 == j { 1.0 } else { 0.0 });
+        let identity = DMatrix::<f64>::identity(p, p);
+        let mut group = c.benchmark_group(format!("damped_system_{}", p));
+        group.bench_function("inverse", |b| {
+            b.iter(|| {
+                black_box(&damped)
+                    .clone()
+                    .try_inverse()
+                    .map(|inverse| inverse * black_box(&gradients))
+            })
+        });
+        group.bench_function("lu", |b| {
+            b.iter(|| {
+                optimize_problem(
+                    Config {
+                        method: Method::GaussNewton,
+                        threshold: 1e-12,
+                        ..Config::default()
+                    },
+                    DVector::zeros(p),
+                    &HessianClosureProblem::new(
+                        |model: &DVector<f64>, delta| model + delta,
+                        |model: &DVector<f64>| {
+                            Residuals::from_iterator(p, model.iter().map(|&x| 3.0 - x))
+                        },
+                        |_: &DVector<f64>| identity.column_iter().map(|column| column.into_owned()),
+                        |_: &DVector<f64>| black_box(&swaps).clone(),
+                    ),
+                )
+            })
+        });
+        group.bench_function("cholesky", |b| {
+            b.iter(|| {
+                Cholesky::new(black_box(&damped).clone()).map(|c| c.solve(black_box(&gradients)))
+            })
+        });
+        group.finish();
+    }
+
+    let mut group = c.benchmark_group("optimize_20");
+    for &(name, solve_method) in &[
+        ("normal_equations", SolveMethod::NormalEquations),
+        ("qr", SolveMethod::Qr),
+    ] {
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                optimize_report(
+                    Config {
+                        max_iterations: 10,
+                        solve_method,
+                        ..Config::default()
+                    },
+                    Model::zeros(),
+                    |model, delta| model + delta,
+                    |model| {
+                        Residuals::from_iterator(
+                            samples.len(),
+                            samples.iter().map(|&(x, y)| y - basis(x).dot(model)),
+                        )
+                    },
+                    |_| samples.iter().map(|&(x, _)| basis(x)),
+                )
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, solve);
+criterion_main!(benches);
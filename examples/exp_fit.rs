@@ -0,0 +1,75 @@
+//! Fits a sum of two exponential decays, `y = a·exp(-b·t) + c·exp(-d·t)`, to noisy samples.
+//!
+//! This is the classic stiff problem for Levenberg-Marquardt, since the two decays are hard to
+//! tell apart and the model is nonlinear in the rates. Run it with
+//! `cargo run --example exp_fit`.
+
+use levenberg_marquardt::{optimize, Config};
+use nalgebra::{dimension::U1, Dynamic, Matrix, VecStorage, Vector4};
+use pcg_rand::Pcg64;
+use rand::distributions::{Distribution, Uniform};
+
+type Residuals = Matrix<f64, U1, Dynamic, VecStorage<f64, U1, Dynamic>>;
+
+/// The model with the parameters `(a, b, c, d)` evaluated at `t`.
+fn model(parameters: &Vector4<f64>, t: f64) -> f64 {
+    parameters.x * (-parameters.y * t).exp() + parameters.z * (-parameters.w * t).exp()
+}
+
+/// The Jacobian of the negative residual `model - y` at `t`, which is the gradient of the
+/// model with respect to each parameter.
+fn jacobian(parameters: &Vector4<f64>, t: f64) -> Vector4<f64> {
+    let fast = (-parameters.y * t).exp();
+    let slow = (-parameters.w * t).exp();
+    Vector4::new(
+        fast,
+        -parameters.x * t * fast,
+        slow,
+        -parameters.z * t * slow,
+    )
+}
+
+fn main() {
+    let truth = Vector4::new(3.0, 2.0, 1.0, 0.2);
+
+    // Sample the model over a few time constants of the slow decay with a little noise.
+    let mut rng = Pcg64::new_unseeded();
+    let noise = Uniform::new(-0.005, 0.005);
+    let samples: Vec<(f64, f64)> = (0..100)
+        .map(|i| {
+            let t = f64::from(i) * 0.1;
+            (t, model(&truth, t) + noise.sample(&mut rng))
+        })
+        .collect();
+
+    // The decays are interchangeable, so start with the first one faster to pick an ordering.
+    let init = Vector4::new(1.0, 1.0, 1.0, 0.1);
+    let fit = optimize(
+        Config::default(),
+        init,
+        |parameters, delta: Vector4<f64>| parameters + delta,
+        |parameters| {
+            Residuals::from_iterator(
+                samples.len(),
+                samples.iter().map(|&(t, y)| y - model(parameters, t)),
+            )
+        },
+        |parameters| {
+            let parameters = *parameters;
+            samples.iter().map(move |&(t, _)| jacobian(&parameters, t))
+        },
+    );
+
+    println!("truth:     {:?}", truth.as_slice());
+    println!("recovered: {:?}", fit.as_slice());
+    assert!(
+        (fit - truth).amax() < 0.05,
+        "the fit didn't recover the parameters"
+    );
+}
+
+#[cfg(test)]
+#[test]
+fn recovers_parameters() {
+    main();
+}
@@ -0,0 +1,300 @@
+use crate::{solve, LeastSquaresProblem};
+use alloc::vec::Vec;
+use core::{cell::RefCell, slice};
+use nalgebra::{
+    allocator::Allocator, DefaultAllocator, Dim, DimName, Dynamic, Matrix, MatrixMN, RealField,
+    VecStorage, VectorN, U1,
+};
+
+/// A handle to a residual block of a [`BlockProblem`], which is returned when the block is
+/// added and stays valid until it is removed.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BlockId(u64);
+
+/// The Jacobian of a block and its contribution to `JJᵀ`, along with the model they were
+/// computed at.
+struct Linearization<M, N, P, J>
+where
+    N: RealField,
+    P: Dim,
+    J: Dim,
+    DefaultAllocator: Allocator<N, P, J>,
+    DefaultAllocator: Allocator<N, P, P>,
+{
+    model: M,
+    jacobian: MatrixMN<N, P, J>,
+    hessian: MatrixMN<N, P, P>,
+}
+
+struct Block<M, N, P, J, B>
+where
+    N: RealField,
+    P: Dim,
+    J: Dim,
+    DefaultAllocator: Allocator<N, P, J>,
+    DefaultAllocator: Allocator<N, P, P>,
+{
+    id: BlockId,
+    data: B,
+    cache: RefCell<Option<Linearization<M, N, P, J>>>,
+}
+
+/// A [`LeastSquaresProblem`] made of residual blocks which can be added and removed between
+/// solves, such as the measurements in the window of a sliding-window estimator.
+///
+/// This requires the `alloc` feature. Each block is a sample with `J` residuals, which are
+/// computed from the model and the data of the block by `residuals`, and whose Jacobian is
+/// computed by `jacobian`. The samples are in the order that the blocks were added, and
+/// removing a block keeps the order of the rest. Solve it with
+/// [`optimize_problem`](crate::optimize_problem) or step it with
+/// [`LevenbergMarquardt`](crate::LevenbergMarquardt).
+///
+/// When the normal equations are accumulated, every block caches its Jacobian and its
+/// contribution `J Jᵀ` to the approximate Hessian along with the model they were computed at.
+/// The cache of a block is reused whenever the normal equations are needed again at a model
+/// which is equal to that one, so only the blocks which are new or changed are linearized
+/// again. That happens at the first linearization after the window changes, either when
+/// optimization is started again from the previous solution, or when the
+/// [`LevenbergMarquardt`](crate::LevenbergMarquardt) that solved the previous window is
+/// [reevaluated](crate::LevenbergMarquardt::reevaluate) with the new blocks. Every accepted
+/// step changes the model, so later linearizations recompute every block.
+///
+/// The cache is invalidated by these rules:
+///
+/// * A block that was just added has no cache, and removing a block drops its cache.
+/// * [`block_mut`](Self::block_mut) drops the cache of that block, since its data may change.
+/// * A cache is only used at a model that compares equal to the one it was computed at, so a
+///   model with a different number of parameters never reuses one.
+/// * [`invalidate`](Self::invalidate) drops every cache. It must be called when something
+///   else that the closures depend on changes, since that can't be detected.
+///
+/// The cache is only used when solving the normal equations. With
+/// [`SolveMethod::Qr`](crate::SolveMethod::Qr), with `compensated_accumulation`, or with a
+/// `jacobian_refresh_interval` above `1`, the Jacobians are needed directly, so the cached
+/// Jacobians are reused but the Hessian of every block is formed again. Every block keeps a
+/// copy of the model to compare against, which is why the model must be `Clone` and
+/// `PartialEq`, so the cache takes memory proportional to the number of blocks times the size
+/// of the model and `P²`.
+pub struct BlockProblem<M, N, P, J, B, A, R, JF>
+where
+    N: RealField,
+    P: Dim,
+    J: Dim,
+    DefaultAllocator: Allocator<N, P, J>,
+    DefaultAllocator: Allocator<N, P, P>,
+{
+    apply_delta: A,
+    residuals: R,
+    jacobian: JF,
+    blocks: Vec<Block<M, N, P, J, B>>,
+    next_id: u64,
+}
+
+impl<M, N, P, J, B, A, R, JF> BlockProblem<M, N, P, J, B, A, R, JF>
+where
+    N: RealField,
+    P: Dim,
+    J: Dim,
+    DefaultAllocator: Allocator<N, P, J>,
+    DefaultAllocator: Allocator<N, P, P>,
+{
+    /// Creates a problem without any blocks.
+    pub fn new(apply_delta: A, residuals: R, jacobian: JF) -> Self {
+        Self {
+            apply_delta,
+            residuals,
+            jacobian,
+            blocks: Vec::new(),
+            next_id: 0,
+        }
+    }
+
+    /// Adds a block after every other one and returns its handle.
+    pub fn add_block(&mut self, data: B) -> BlockId {
+        let id = BlockId(self.next_id);
+        self.next_id += 1;
+        self.blocks.push(Block {
+            id,
+            data,
+            cache: RefCell::new(None),
+        });
+        id
+    }
+
+    /// Removes a block and returns its data, or `None` if it was already removed.
+    pub fn remove_block(&mut self, id: BlockId) -> Option<B> {
+        let index = self.index(id)?;
+        Some(self.blocks.remove(index).data)
+    }
+
+    /// The data of a block, or `None` if it was removed.
+    pub fn block(&self, id: BlockId) -> Option<&B> {
+        self.index(id).map(|index| &self.blocks[index].data)
+    }
+
+    /// The data of a block for modifying it, or `None` if it was removed.
+    ///
+    /// This drops the cache of the block.
+    pub fn block_mut(&mut self, id: BlockId) -> Option<&mut B> {
+        let index = self.index(id)?;
+        let block = &mut self.blocks[index];
+        *block.cache.get_mut() = None;
+        Some(&mut block.data)
+    }
+
+    /// The handles of every block, in the order of their samples.
+    pub fn block_ids(&self) -> impl Iterator<Item = BlockId> + '_ {
+        self.blocks.iter().map(|block| block.id)
+    }
+
+    /// The number of blocks.
+    pub fn len(&self) -> usize {
+        self.blocks.len()
+    }
+
+    /// Whether there are no blocks.
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    /// Drops the cache of every block.
+    pub fn invalidate(&mut self) {
+        for block in &mut self.blocks {
+            *block.cache.get_mut() = None;
+        }
+    }
+
+    fn index(&self, id: BlockId) -> Option<usize> {
+        // The blocks are always in the order that they were added, so their ids are sorted.
+        self.blocks.binary_search_by_key(&id, |block| block.id).ok()
+    }
+}
+
+/// The Jacobians of every block of a [`BlockProblem`], which come from the cache of a block when
+/// it was computed at the same model.
+pub struct BlockJacobians<'a, M, N, P, J, B, JF>
+where
+    N: RealField,
+    P: Dim,
+    J: Dim,
+    DefaultAllocator: Allocator<N, P, J>,
+    DefaultAllocator: Allocator<N, P, P>,
+{
+    model: M,
+    jacobian: &'a JF,
+    blocks: slice::Iter<'a, Block<M, N, P, J, B>>,
+}
+
+impl<'a, M, N, P, J, B, JF> Iterator for BlockJacobians<'a, M, N, P, J, B, JF>
+where
+    M: PartialEq,
+    N: RealField,
+    P: Dim,
+    J: Dim,
+    JF: Fn(&M, &B) -> MatrixMN<N, P, J>,
+    DefaultAllocator: Allocator<N, P, J>,
+    DefaultAllocator: Allocator<N, P, P>,
+{
+    type Item = MatrixMN<N, P, J>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let block = self.blocks.next()?;
+        Some(match &*block.cache.borrow() {
+            Some(cache) if cache.model == self.model => cache.jacobian.clone(),
+            _ => (self.jacobian)(&self.model, &block.data),
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.blocks.size_hint()
+    }
+}
+
+impl<M, N, P, J, B, A, R, JF> LeastSquaresProblem<N, P, Dynamic, J>
+    for BlockProblem<M, N, P, J, B, A, R, JF>
+where
+    M: Clone + PartialEq,
+    N: RealField,
+    P: Dim,
+    J: DimName,
+    A: Fn(&M, VectorN<N, P>) -> M,
+    R: Fn(&M, &B) -> VectorN<N, J>,
+    JF: Fn(&M, &B) -> MatrixMN<N, P, J>,
+    DefaultAllocator: Allocator<N, P>,
+    DefaultAllocator: Allocator<N, J>,
+    DefaultAllocator: Allocator<N, P, J>,
+    DefaultAllocator: Allocator<N, P, P>,
+    DefaultAllocator: Allocator<N, J, Dynamic, Buffer = VecStorage<N, J, Dynamic>>,
+{
+    type Model = M;
+    type ResidualStorage = VecStorage<N, J, Dynamic>;
+    type JacobianStorage = <DefaultAllocator as Allocator<N, P, J>>::Buffer;
+    type Jacobians<'a>
+        = BlockJacobians<'a, M, N, P, J, B, JF>
+    where
+        Self: 'a;
+
+    fn apply_delta(&self, model: &M, delta: VectorN<N, P>) -> M {
+        (self.apply_delta)(model, delta)
+    }
+
+    fn residuals(&self, model: &M) -> Matrix<N, J, Dynamic, VecStorage<N, J, Dynamic>> {
+        let mut residuals = Vec::with_capacity(self.blocks.len() * J::dim());
+        for block in &self.blocks {
+            residuals.extend((self.residuals)(model, &block.data).iter().copied());
+        }
+        Matrix::from_data(VecStorage::new(
+            J::name(),
+            Dynamic::new(self.blocks.len()),
+            residuals,
+        ))
+    }
+
+    fn jacobians(&self, model: &M) -> Self::Jacobians<'_> {
+        BlockJacobians {
+            model: model.clone(),
+            jacobian: &self.jacobian,
+            blocks: self.blocks.iter(),
+        }
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn normal_equations(
+        &self,
+        model: &M,
+        residuals: &Matrix<N, J, Dynamic, VecStorage<N, J, Dynamic>>,
+    ) -> Option<(MatrixMN<N, P, P>, VectorN<N, P>)>
+    where
+        N: RealField,
+        P: Dim,
+        J: DimName,
+        DefaultAllocator: Allocator<N, P, P>,
+        DefaultAllocator: Allocator<N, J, P>,
+    {
+        let mut normal_equations: Option<(MatrixMN<N, P, P>, VectorN<N, P>)> = None;
+        for (block, res) in self.blocks.iter().zip(residuals.column_iter()) {
+            let mut cache = block.cache.borrow_mut();
+            let cache = match &mut *cache {
+                Some(cache) if cache.model == *model => cache,
+                cache => {
+                    let jacobian = (self.jacobian)(model, &block.data);
+                    let hessian = &jacobian * jacobian.transpose();
+                    cache.insert(Linearization {
+                        model: model.clone(),
+                        jacobian,
+                        hessian,
+                    })
+                }
+            };
+            let contribution = &cache.jacobian * res;
+            normal_equations = Some(match normal_equations {
+                Some((hessian, gradients)) => (hessian + &cache.hessian, gradients + contribution),
+                None => (cache.hessian.clone(), contribution),
+            });
+        }
+        Some(normal_equations.unwrap_or_else(|| {
+            let p = solve::initial_dim::<P>();
+            (MatrixMN::zeros_generic(p, p), VectorN::zeros_generic(p, U1))
+        }))
+    }
+}
@@ -0,0 +1,143 @@
+use crate::LeastSquaresProblem;
+use nalgebra::{
+    allocator::Allocator, DefaultAllocator, Dim, DimName, Matrix, MatrixMN, RealField, VectorN,
+};
+
+/// Adapts a [`LeastSquaresProblem`] over a parameter vector so that every guess is clamped
+/// between lower and upper bounds.
+///
+/// The model must be the parameter vector itself. Every guess is clamped into the box after the
+/// step is applied and before its residuals are evaluated, so the sum-of-squares that decides
+/// whether a step is accepted is the one of the clamped guess. The initial guess isn't
+/// clamped by the optimizer, so it should be passed through [`BoundedProblem::clamp`] first.
+///
+/// This is a simple projected Levenberg-Marquardt, which works well when the optimum is inside
+/// the box. When a constraint is active at the optimum, the steps keep pushing against it and
+/// get clamped, which can slow convergence. Either bound can be `None` to leave that side
+/// unconstrained, and individual components can be made unbounded with an infinite bound.
+pub struct BoundedProblem<LSP, N, P>
+where
+    N: RealField,
+    P: Dim,
+    DefaultAllocator: Allocator<N, P>,
+{
+    problem: LSP,
+    lower: Option<VectorN<N, P>>,
+    upper: Option<VectorN<N, P>>,
+}
+
+impl<LSP, N, P> BoundedProblem<LSP, N, P>
+where
+    N: RealField,
+    P: Dim,
+    DefaultAllocator: Allocator<N, P>,
+{
+    /// Constrains the parameters of `problem` between `lower` and `upper`.
+    pub fn new(problem: LSP, lower: Option<VectorN<N, P>>, upper: Option<VectorN<N, P>>) -> Self {
+        Self {
+            problem,
+            lower,
+            upper,
+        }
+    }
+
+    /// Projects the parameters into the box.
+    pub fn clamp(&self, mut parameters: VectorN<N, P>) -> VectorN<N, P> {
+        if let Some(lower) = &self.lower {
+            parameters = parameters.zip_map(lower, |parameter, lower| parameter.max(lower));
+        }
+        if let Some(upper) = &self.upper {
+            parameters = parameters.zip_map(upper, |parameter, upper| parameter.min(upper));
+        }
+        parameters
+    }
+}
+
+impl<N, P, S, J, LSP> LeastSquaresProblem<N, P, S, J> for BoundedProblem<LSP, N, P>
+where
+    N: RealField,
+    P: Dim,
+    S: Dim,
+    J: Dim,
+    LSP: LeastSquaresProblem<N, P, S, J, Model = VectorN<N, P>>,
+    DefaultAllocator: Allocator<N, P>,
+{
+    type Model = VectorN<N, P>;
+    type ResidualStorage = LSP::ResidualStorage;
+    type JacobianStorage = LSP::JacobianStorage;
+    type Jacobians<'a>
+        = LSP::Jacobians<'a>
+    where
+        Self: 'a;
+
+    fn apply_delta(&self, model: &Self::Model, delta: VectorN<N, P>) -> Self::Model {
+        self.problem.apply_delta(model, delta)
+    }
+
+    fn try_apply_delta(&self, model: &Self::Model, delta: VectorN<N, P>) -> Option<Self::Model> {
+        self.problem.try_apply_delta(model, delta)
+    }
+
+    fn residuals(&self, model: &Self::Model) -> Matrix<N, J, S, Self::ResidualStorage> {
+        self.problem.residuals(model)
+    }
+
+    fn jacobians(&self, model: &Self::Model) -> Self::Jacobians<'_> {
+        self.problem.jacobians(model)
+    }
+
+    fn try_jacobians(&self, model: &Self::Model) -> Option<Self::Jacobians<'_>> {
+        self.problem.try_jacobians(model)
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn residuals_and_jacobians(
+        &self,
+        model: &Self::Model,
+    ) -> (
+        Matrix<N, J, S, Self::ResidualStorage>,
+        Option<Self::Jacobians<'_>>,
+    ) {
+        self.problem.residuals_and_jacobians(model)
+    }
+
+    fn normal_equations(
+        &self,
+        model: &Self::Model,
+        residuals: &Matrix<N, J, S, Self::ResidualStorage>,
+    ) -> Option<(MatrixMN<N, P, P>, VectorN<N, P>)>
+    where
+        J: DimName,
+        DefaultAllocator: Allocator<N, P, P>,
+        DefaultAllocator: Allocator<N, J, P>,
+    {
+        self.problem.normal_equations(model, residuals)
+    }
+
+    fn modifies_normal_equations(&self) -> bool {
+        self.problem.modifies_normal_equations()
+    }
+
+    fn sum_of_squares(
+        &self,
+        model: &Self::Model,
+        residuals: &Matrix<N, J, S, Self::ResidualStorage>,
+    ) -> N
+    where
+        N: RealField,
+    {
+        self.problem.sum_of_squares(model, residuals)
+    }
+
+    fn normalize(&self, model: Self::Model) -> Self::Model {
+        // The step is clamped before the residuals are evaluated so that the sum-of-squares
+        // that decides whether the step is accepted is the one of the clamped guess.
+        self.clamp(self.problem.normalize(model))
+    }
+
+    fn try_normalize(&self, model: Self::Model) -> Option<Self::Model> {
+        self.problem
+            .try_normalize(model)
+            .map(|model| self.clamp(model))
+    }
+}
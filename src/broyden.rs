@@ -0,0 +1,96 @@
+use alloc::vec::Vec;
+use nalgebra::{
+    allocator::Allocator, storage::Storage, DefaultAllocator, Dim, DimName, Matrix, MatrixSliceMN,
+    RealField, Scalar, VectorN,
+};
+
+/// The stacked Jacobian at the current guess, which is kept so that it can be updated with
+/// Broyden's method rather than evaluated again.
+///
+/// The Jacobian of every residual is stored as a column of `p` entries, in the same order as
+/// the residuals are stored in the residual matrix. Without any samples `p` is unknown and left
+/// at zero, so the chunks are never shorter than one entry.
+#[derive(Clone)]
+pub(crate) struct Broyden<N> {
+    jacobian: Vec<N>,
+    p: usize,
+    /// The number of updates since the Jacobians were last evaluated.
+    pub(crate) updates: usize,
+}
+
+impl<N> Broyden<N>
+where
+    N: RealField,
+{
+    /// Stacks the Jacobians of every sample.
+    pub(crate) fn new<P, J, JS>(jacobians: impl Iterator<Item = Matrix<N, P, J, JS>>) -> Self
+    where
+        P: Dim,
+        J: Dim,
+        JS: Storage<N, P, J>,
+    {
+        let mut p = 0;
+        let mut jacobian = Vec::new();
+        for sample in jacobians {
+            p = sample.nrows();
+            jacobian.extend(sample.iter().copied());
+        }
+        Self {
+            jacobian,
+            p,
+            updates: 0,
+        }
+    }
+
+    /// Applies Broyden's rank-one update `J += δ(Δf - Jᵀδ)ᵀ/δᵀδ` for the step `delta`, where
+    /// `Δf` is the change in the negative residuals from `old` to `new`.
+    ///
+    /// This is the smallest change to the Jacobian which makes it agree with the change in the
+    /// residuals that was actually observed along the step.
+    pub(crate) fn update<P, J, S, OS, NS>(
+        &mut self,
+        delta: &VectorN<N, P>,
+        old: &Matrix<N, J, S, OS>,
+        new: &Matrix<N, J, S, NS>,
+    ) where
+        P: Dim,
+        J: Dim,
+        S: Dim,
+        OS: Storage<N, J, S>,
+        NS: Storage<N, J, S>,
+        DefaultAllocator: Allocator<N, P>,
+    {
+        self.updates += 1;
+        let length = delta.norm_squared();
+        if length == N::zero() {
+            return;
+        }
+        for (column, (&old, &new)) in self
+            .jacobian
+            .chunks_mut(self.p.max(1))
+            .zip(old.iter().zip(new.iter()))
+        {
+            let predicted = column
+                .iter()
+                .zip(delta.iter())
+                .fold(N::zero(), |sum, (&entry, &step)| sum + entry * step);
+            let scale = (old - new - predicted) / length;
+            for (entry, &step) in column.iter_mut().zip(delta.iter()) {
+                *entry += step * scale;
+            }
+        }
+    }
+
+    /// The Jacobian of each sample.
+    pub(crate) fn jacobians<P, J>(&self) -> impl Iterator<Item = MatrixSliceMN<'_, N, P, J>>
+    where
+        P: Dim,
+        J: DimName,
+        N: Scalar,
+    {
+        let p = P::from_usize(self.p);
+        self.jacobian
+            .chunks((self.p * J::dim()).max(1))
+            .map(move |sample| MatrixSliceMN::from_slice_generic(sample, p, J::name()))
+    }
+}
@@ -0,0 +1,200 @@
+use crate::{
+    Config, ConfigError, DampingMode, DampingStrategy, InitialLambda, Method, SolveMethod,
+    ThresholdKind,
+};
+use nalgebra::RealField;
+use num_traits::FromPrimitive;
+
+/// Builds a [`Config`] one field at a time, starting from [`Config::default`].
+///
+/// Created by [`Config::builder`]. Unlike a struct literal, [`ConfigBuilder::build`] checks that
+/// the config is valid before it can be used.
+///
+/// ```
+/// use levenberg_marquardt::Config;
+///
+/// let config = Config::<f64>::builder()
+///     .max_iterations(200)
+///     .threshold(1e-12)
+///     .build()
+///     .unwrap();
+/// assert_eq!(config.max_iterations, 200);
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ConfigBuilder<N> {
+    config: Config<N>,
+}
+
+impl<N> Config<N>
+where
+    N: FromPrimitive,
+{
+    /// Creates a [`ConfigBuilder`] with every field set to its default.
+    pub fn builder() -> ConfigBuilder<N> {
+        ConfigBuilder {
+            config: Self::default(),
+        }
+    }
+}
+
+impl<N> ConfigBuilder<N> {
+    pub fn max_iterations(mut self, max_iterations: usize) -> Self {
+        self.config.max_iterations = max_iterations;
+        self
+    }
+
+    pub fn consecutive_divergence_limit(mut self, consecutive_divergence_limit: usize) -> Self {
+        self.config.consecutive_divergence_limit = consecutive_divergence_limit;
+        self
+    }
+
+    pub fn initial_lambda(mut self, initial_lambda: impl Into<InitialLambda<N>>) -> Self {
+        self.config.initial_lambda = initial_lambda.into();
+        self
+    }
+
+    pub fn lambda_converge(mut self, lambda_converge: N) -> Self {
+        self.config.lambda_convege = lambda_converge;
+        self
+    }
+
+    pub fn lambda_diverge(mut self, lambda_diverge: N) -> Self {
+        self.config.lambda_diverge = lambda_diverge;
+        self
+    }
+
+    pub fn threshold(mut self, threshold: N) -> Self {
+        self.config.threshold = threshold;
+        self
+    }
+
+    pub fn gradient_threshold(mut self, gradient_threshold: N) -> Self {
+        self.config.gradient_threshold = gradient_threshold;
+        self
+    }
+
+    pub fn ftol(mut self, ftol: N) -> Self {
+        self.config.ftol = ftol;
+        self
+    }
+
+    pub fn damping_strategy(mut self, damping_strategy: DampingStrategy) -> Self {
+        self.config.damping_strategy = damping_strategy;
+        self
+    }
+
+    pub fn damping_mode(mut self, damping_mode: DampingMode) -> Self {
+        self.config.damping_mode = damping_mode;
+        self
+    }
+
+    pub fn min_lambda(mut self, min_lambda: N) -> Self {
+        self.config.min_lambda = min_lambda;
+        self
+    }
+
+    pub fn max_lambda(mut self, max_lambda: N) -> Self {
+        self.config.max_lambda = max_lambda;
+        self
+    }
+
+    pub fn solve_method(mut self, solve_method: SolveMethod<N>) -> Self {
+        self.config.solve_method = solve_method;
+        self
+    }
+
+    pub fn geodesic_acceleration(mut self, geodesic_acceleration: bool) -> Self {
+        self.config.geodesic_acceleration = geodesic_acceleration;
+        self
+    }
+
+    pub fn acceleration_ratio(mut self, acceleration_ratio: N) -> Self {
+        self.config.acceleration_ratio = acceleration_ratio;
+        self
+    }
+
+    pub fn initial_trust_radius(mut self, initial_trust_radius: N) -> Self {
+        self.config.initial_trust_radius = initial_trust_radius;
+        self
+    }
+
+    pub fn method(mut self, method: Method) -> Self {
+        self.config.method = method;
+        self
+    }
+
+    pub fn lambda_candidates(mut self, lambda_candidates: usize) -> Self {
+        self.config.lambda_candidates = lambda_candidates;
+        self
+    }
+
+    pub fn line_search(mut self, line_search: bool) -> Self {
+        self.config.line_search = line_search;
+        self
+    }
+
+    pub fn line_search_min_alpha(mut self, line_search_min_alpha: N) -> Self {
+        self.config.line_search_min_alpha = line_search_min_alpha;
+        self
+    }
+
+    pub fn condition_warning_threshold(mut self, condition_warning_threshold: N) -> Self {
+        self.config.condition_warning_threshold = condition_warning_threshold;
+        self
+    }
+
+    pub fn max_function_evaluations(mut self, max_function_evaluations: usize) -> Self {
+        self.config.max_function_evaluations = max_function_evaluations;
+        self
+    }
+
+    pub fn initial_lambda_from(mut self, initial_lambda_from: Option<N>) -> Self {
+        self.config.initial_lambda_from = initial_lambda_from;
+        self
+    }
+
+    pub fn threshold_kind(mut self, threshold_kind: ThresholdKind) -> Self {
+        self.config.threshold_kind = threshold_kind;
+        self
+    }
+
+    pub fn restart_limit(mut self, restart_limit: usize) -> Self {
+        self.config.restart_limit = restart_limit;
+        self
+    }
+
+    pub fn jacobian_refresh_interval(mut self, jacobian_refresh_interval: usize) -> Self {
+        self.config.jacobian_refresh_interval = jacobian_refresh_interval;
+        self
+    }
+
+    pub fn stall_iterations(mut self, stall_iterations: usize) -> Self {
+        self.config.stall_iterations = stall_iterations;
+        self
+    }
+
+    pub fn relative_stall_tol(mut self, relative_stall_tol: N) -> Self {
+        self.config.relative_stall_tol = relative_stall_tol;
+        self
+    }
+
+    pub fn compensated_accumulation(mut self, compensated_accumulation: bool) -> Self {
+        self.config.compensated_accumulation = compensated_accumulation;
+        self
+    }
+
+    pub fn max_step_norm(mut self, max_step_norm: Option<N>) -> Self {
+        self.config.max_step_norm = max_step_norm;
+        self
+    }
+}
+
+impl<N> ConfigBuilder<N>
+where
+    N: RealField,
+{
+    /// Returns the config, or the first invariant from [`Config::validate`] which it violates.
+    pub fn build(self) -> Result<Config<N>, ConfigError> {
+        self.config.validate().map(|()| self.config)
+    }
+}
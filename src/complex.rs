@@ -0,0 +1,137 @@
+use crate::LeastSquaresProblem;
+use core::marker::PhantomData;
+use nalgebra::{
+    allocator::Allocator,
+    dimension::{DimNameAdd, DimNameSum},
+    storage::{Owned, Storage},
+    ComplexField, DefaultAllocator, Dim, DimName, Matrix, MatrixMN, VectorN,
+};
+
+/// Adapts closures with complex residuals of real parameters into a real [`LeastSquaresProblem`],
+/// as when fitting a model in the frequency domain.
+///
+/// `residuals` returns a matrix of any [`ComplexField`] whose real field is the scalar of the
+/// parameters, such as [`Complex<f64>`](nalgebra::Complex) for `f64`, and `jacobians` returns the
+/// Jacobians of the negative residuals with the same layout as in [`optimize`](crate::optimize),
+/// where the entry for residual `k` and parameter `p` is `∂(-Re r_k)/∂p + i∂(-Im r_k)/∂p`. The
+/// cost is `Σ|r|² = Σ r̄r`, so the approximate Hessian is `Re(JJᴴ)` with the conjugate transpose
+/// of each Jacobian and the gradients are `Re(Jr̄)`. This is done by splitting every sample into
+/// the real parts of its residuals followed by their imaginary parts, which is exactly
+/// equivalent, so both parts count as residuals for `threshold_kind`.
+///
+/// The parameters being real is what makes this valid without any conditions on the model.
+/// Wirtinger calculus treats `z` and `z̄` as independent in order to differentiate a real cost of
+/// complex variables, and a real parameter is its own conjugate, so both of its Wirtinger
+/// derivatives reduce to the ordinary derivative given above. In particular, the residuals need
+/// not be holomorphic in anything, so `|z|`, `z̄` and the like are fine. Complex parameters, such
+/// as a complex gain, must be split into a real parameter for their real part and another for
+/// their imaginary part, since the optimizer only ever takes real steps.
+pub struct ComplexProblem<M, A, R, JF> {
+    apply_delta: A,
+    residuals: R,
+    jacobians: JF,
+    model: PhantomData<fn(&M) -> M>,
+}
+
+impl<M, A, R, JF> ComplexProblem<M, A, R, JF> {
+    /// Bundles the closures into a problem.
+    pub fn new(apply_delta: A, residuals: R, jacobians: JF) -> Self {
+        Self {
+            apply_delta,
+            residuals,
+            jacobians,
+            model: PhantomData,
+        }
+    }
+}
+
+/// Splits each complex Jacobian into its real part followed by its imaginary part.
+pub struct ComplexJacobians<IJ, J> {
+    jacobians: IJ,
+    dim: PhantomData<J>,
+}
+
+impl<C, P, J, JS, IJ> Iterator for ComplexJacobians<IJ, J>
+where
+    C: ComplexField,
+    P: Dim,
+    J: DimName + DimNameAdd<J>,
+    JS: Storage<C, P, J>,
+    IJ: Iterator<Item = Matrix<C, P, J, JS>>,
+    DefaultAllocator: Allocator<C::RealField, P, DimNameSum<J, J>>,
+{
+    type Item = MatrixMN<C::RealField, P, DimNameSum<J, J>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let jacobian = self.jacobians.next()?;
+        let columns = J::dim();
+        Some(MatrixMN::from_fn_generic(
+            jacobian.data.shape().0,
+            DimNameSum::<J, J>::name(),
+            |i, k| {
+                if k < columns {
+                    jacobian[(i, k)].real()
+                } else {
+                    jacobian[(i, k - columns)].imaginary()
+                }
+            },
+        ))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.jacobians.size_hint()
+    }
+}
+
+impl<M, C, P, S, J, RS, JS, A, R, JF, IJ> LeastSquaresProblem<C::RealField, P, S, DimNameSum<J, J>>
+    for ComplexProblem<M, A, R, JF>
+where
+    C: ComplexField,
+    P: Dim,
+    S: Dim,
+    J: DimName + DimNameAdd<J>,
+    RS: Storage<C, J, S>,
+    JS: Storage<C, P, J>,
+    A: Fn(&M, VectorN<C::RealField, P>) -> M,
+    R: Fn(&M) -> Matrix<C, J, S, RS>,
+    JF: Fn(&M) -> IJ,
+    IJ: Iterator<Item = Matrix<C, P, J, JS>>,
+    DefaultAllocator: Allocator<C::RealField, P>,
+    DefaultAllocator: Allocator<C::RealField, DimNameSum<J, J>, S>,
+    DefaultAllocator: Allocator<C::RealField, P, DimNameSum<J, J>>,
+{
+    type Model = M;
+    type ResidualStorage = Owned<C::RealField, DimNameSum<J, J>, S>;
+    type JacobianStorage = Owned<C::RealField, P, DimNameSum<J, J>>;
+    type Jacobians<'a>
+        = ComplexJacobians<IJ, J>
+    where
+        Self: 'a;
+
+    fn apply_delta(&self, model: &M, delta: VectorN<C::RealField, P>) -> M {
+        (self.apply_delta)(model, delta)
+    }
+
+    fn residuals(&self, model: &M) -> MatrixMN<C::RealField, DimNameSum<J, J>, S> {
+        let residuals = (self.residuals)(model);
+        let rows = J::dim();
+        MatrixMN::from_fn_generic(
+            DimNameSum::<J, J>::name(),
+            residuals.data.shape().1,
+            |k, i| {
+                if k < rows {
+                    residuals[(k, i)].real()
+                } else {
+                    residuals[(k - rows, i)].imaginary()
+                }
+            },
+        )
+    }
+
+    fn jacobians(&self, model: &M) -> Self::Jacobians<'_> {
+        ComplexJacobians {
+            jacobians: (self.jacobians)(model),
+            dim: PhantomData,
+        }
+    }
+}
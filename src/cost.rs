@@ -0,0 +1,119 @@
+use crate::LeastSquaresProblem;
+use nalgebra::{
+    allocator::Allocator, DefaultAllocator, Dim, DimName, Matrix, MatrixMN, RealField, VectorN,
+};
+
+/// Adapts a [`LeastSquaresProblem`] so that its sum-of-squares is computed from the residuals by
+/// a closure instead of as their squared norm.
+///
+/// This is useful when the natural metric of the residuals isn't Euclidean, such as angular
+/// residuals which should be compared after wrapping them, or for a robust aggregate over all of
+/// the residuals. The cost replaces `residuals.norm_squared()` everywhere that the
+/// sum-of-squares is used, which is the test for whether a step is accepted, the gain ratio,
+/// every termination condition such as `threshold` and `ftol`, and the reported
+/// sum-of-squares. The gradients and the approximate Hessian are still computed from the
+/// residuals and Jacobians as usual.
+///
+/// Since the steps are still solved from the linearization of the squared norm, `cost` should
+/// be consistent with it, at least near the solution. A cost which disagrees with the
+/// linearization gives gain ratios which don't mean anything, so steps which help are rejected
+/// and steps which don't are accepted.
+pub struct CostProblem<LSP, C> {
+    problem: LSP,
+    cost: C,
+}
+
+impl<LSP, C> CostProblem<LSP, C> {
+    /// Computes the sum-of-squares of `problem` from its residuals with `cost`.
+    pub fn new(problem: LSP, cost: C) -> Self {
+        Self { problem, cost }
+    }
+}
+
+impl<N, P, S, J, LSP, C> LeastSquaresProblem<N, P, S, J> for CostProblem<LSP, C>
+where
+    N: RealField,
+    P: Dim,
+    S: Dim,
+    J: Dim,
+    LSP: LeastSquaresProblem<N, P, S, J>,
+    C: Fn(&Matrix<N, J, S, LSP::ResidualStorage>) -> N,
+    DefaultAllocator: Allocator<N, P>,
+{
+    type Model = LSP::Model;
+    type ResidualStorage = LSP::ResidualStorage;
+    type JacobianStorage = LSP::JacobianStorage;
+    type Jacobians<'a>
+        = LSP::Jacobians<'a>
+    where
+        Self: 'a;
+
+    fn apply_delta(&self, model: &Self::Model, delta: VectorN<N, P>) -> Self::Model {
+        self.problem.apply_delta(model, delta)
+    }
+
+    fn try_apply_delta(&self, model: &Self::Model, delta: VectorN<N, P>) -> Option<Self::Model> {
+        self.problem.try_apply_delta(model, delta)
+    }
+
+    fn residuals(&self, model: &Self::Model) -> Matrix<N, J, S, Self::ResidualStorage> {
+        self.problem.residuals(model)
+    }
+
+    fn jacobians(&self, model: &Self::Model) -> Self::Jacobians<'_> {
+        self.problem.jacobians(model)
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn residuals_and_jacobians(
+        &self,
+        model: &Self::Model,
+    ) -> (
+        Matrix<N, J, S, Self::ResidualStorage>,
+        Option<Self::Jacobians<'_>>,
+    ) {
+        self.problem.residuals_and_jacobians(model)
+    }
+
+    fn try_jacobians(&self, model: &Self::Model) -> Option<Self::Jacobians<'_>> {
+        self.problem.try_jacobians(model)
+    }
+
+    fn normal_equations(
+        &self,
+        model: &Self::Model,
+        residuals: &Matrix<N, J, S, Self::ResidualStorage>,
+    ) -> Option<(MatrixMN<N, P, P>, VectorN<N, P>)>
+    where
+        N: RealField,
+        P: Dim,
+        J: DimName,
+        DefaultAllocator: Allocator<N, P, P>,
+        DefaultAllocator: Allocator<N, J, P>,
+    {
+        self.problem.normal_equations(model, residuals)
+    }
+
+    fn modifies_normal_equations(&self) -> bool {
+        self.problem.modifies_normal_equations()
+    }
+
+    fn sum_of_squares(
+        &self,
+        _: &Self::Model,
+        residuals: &Matrix<N, J, S, Self::ResidualStorage>,
+    ) -> N
+    where
+        N: RealField,
+    {
+        (self.cost)(residuals)
+    }
+
+    fn normalize(&self, model: Self::Model) -> Self::Model {
+        self.problem.normalize(model)
+    }
+
+    fn try_normalize(&self, model: Self::Model) -> Option<Self::Model> {
+        self.problem.try_normalize(model)
+    }
+}
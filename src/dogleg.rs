@@ -0,0 +1,287 @@
+use crate::{
+    solve::{self, LinearSystem},
+    statistics,
+    step::linearize,
+    Config, LeastSquaresProblem, MinimizationReport, OptimizeError, TerminationReason, Workspace,
+};
+use core::mem;
+use nalgebra::{
+    allocator::Allocator,
+    constraint::{DimEq, ShapeConstraint},
+    dimension::{DimMin, DimMinimum},
+    DefaultAllocator, Dim, DimName, RealField, VectorN, U1,
+};
+use num_traits::FromPrimitive;
+
+/// Powell's dogleg method, which is used by [`optimize_dogleg`](crate::optimize_dogleg).
+///
+/// Each iteration takes the point within the trust radius along the path from the current
+/// guess to the Cauchy point, which minimizes the linearization along the gradient, and then
+/// on to the Gauss-Newton point. The radius grows when the linearization predicts the
+/// reduction in the sum-of-squares well and shrinks when it doesn't.
+pub(crate) fn minimize<N, P, S, J, LSP>(
+    config: Config<N>,
+    init: LSP::Model,
+    problem: &LSP,
+) -> Result<MinimizationReport<LSP::Model, N, P>, OptimizeError>
+where
+    N: RealField + FromPrimitive,
+    P: DimMin<P>,
+    S: Dim,
+    J: DimName,
+    LSP: LeastSquaresProblem<N, P, S, J>,
+    DefaultAllocator: Allocator<N, J, P>,
+    DefaultAllocator: Allocator<N, P, P>,
+    DefaultAllocator: Allocator<N, P>,
+    ShapeConstraint: DimEq<DimMinimum<P, P>, P>,
+{
+    let two = N::one() + N::one();
+    let quarter = N::one() / (two * two);
+    let three_quarters = N::one() - quarter;
+
+    let mut guess = init;
+    let (residuals, jacobians) = problem.residuals_and_jacobians(&guess);
+    let mut sum_of_squares = problem.sum_of_squares(&guess, &residuals);
+    let total = N::from_usize(residuals.len()).ok_or(OptimizeError::ConversionFailed)?;
+    let p = solve::initial_dim::<P>();
+    let mut system = LinearSystem::zeros(config.solve_method, p);
+    let mut gradients = VectorN::<N, P>::zeros_generic(p, U1);
+    let mut workspace = Workspace::new();
+    let mut radius = config.initial_trust_radius;
+    let mut iterations = 0;
+    let mut rank = None;
+    let mut residual_evaluations = 1;
+    let mut jacobian_evaluations = 1;
+    let mut accepted_steps = 0;
+    let mut rejected_steps = 0;
+    let mut consecutive_rejections = 0;
+    let mut restarts = 0;
+    let mut consecutive_failed_jacobians = 0;
+    let mut consecutive_stalls = 0;
+    let mut still_improving = false;
+
+    let linearized = linearize(
+        &config,
+        problem,
+        &guess,
+        &residuals,
+        jacobians,
+        &mut system,
+        &mut gradients,
+    );
+    let termination = loop {
+        if iterations == 0
+            && (config.below_threshold(sum_of_squares, total)
+                || (linearized && gradients.iter().all(|&gradient| gradient == N::zero())))
+        {
+            break TerminationReason::AlreadyOptimal;
+        }
+        if !linearized {
+            break TerminationReason::JacobianFailed;
+        }
+        let gradient_norm = gradients
+            .iter()
+            .fold(N::zero(), |norm, gradient| norm.max(gradient.abs()));
+        if gradient_norm < config.gradient_threshold {
+            break TerminationReason::GradientTooSmall;
+        }
+        if iterations == config.max_iterations {
+            break TerminationReason::MaxIterations;
+        }
+        if residual_evaluations >= config.max_function_evaluations {
+            break TerminationReason::BudgetExhausted;
+        }
+        iterations += 1;
+
+        // The Gauss-Newton point solves JJᵀδ = g, and the Cauchy point minimizes the
+        // linearization along the gradient.
+        let zeros = VectorN::<N, P>::zeros_generic(system.dim(), U1);
+        let gauss_newton = system.solve(&gradients, &zeros, N::zero(), &mut workspace);
+        rank = workspace.rank;
+        let curvature = system.quadratic_form(&gradients);
+        let cauchy = if curvature > N::zero() {
+            &gradients * (gradients.norm_squared() / curvature)
+        } else {
+            zeros
+        };
+        let step = dogleg(gauss_newton, cauchy, radius);
+        let step_norm = step.norm();
+
+        // The linearization predicts that the sum-of-squares reduces by 2δᵀg - δᵀJJᵀδ.
+        let predicted = two * step.dot(&gradients) - system.quadratic_form(&step);
+        // A guess which can't be normalized is treated like one whose sum-of-squares isn't
+        // finite.
+        let candidate = problem
+            .try_apply_delta(&guess, step)
+            .and_then(|new_guess| problem.try_normalize(new_guess))
+            .map(|new_guess| {
+                let (new_residuals, new_jacobians) = problem.residuals_and_jacobians(&new_guess);
+                residual_evaluations += 1;
+                let new_sum = problem.sum_of_squares(&new_guess, &new_residuals);
+                (new_guess, new_residuals, new_jacobians, new_sum)
+            })
+            .filter(|(_, _, _, new_sum)| new_sum.is_finite());
+        let ratio = candidate
+            .as_ref()
+            .map(|(_, _, _, new_sum)| (sum_of_squares - *new_sum) / predicted);
+
+        // Shrink the radius when the linearization is a poor predictor and grow it when it
+        // is good enough that the radius is what limited the step. If the step wasn't finite
+        // then the linearization was clearly wrong too.
+        match ratio {
+            Some(ratio) if ratio >= quarter => {
+                if ratio > three_quarters && step_norm * two > radius {
+                    radius = radius.max(step_norm * two);
+                }
+            }
+            _ => radius = step_norm * quarter,
+        }
+
+        let mut reduction_too_small = false;
+        let candidate = candidate.filter(|(_, _, _, new_sum)| {
+            *new_sum < sum_of_squares && ratio.map_or(false, |ratio| ratio > N::zero())
+        });
+        let accepted = candidate.is_some();
+        let linearized =
+            candidate.and_then(|(new_guess, new_residuals, new_jacobians, new_sum)| {
+                let linearized = linearize(
+                    &config,
+                    problem,
+                    &new_guess,
+                    &new_residuals,
+                    new_jacobians,
+                    &mut workspace.system,
+                    &mut workspace.gradients,
+                );
+                jacobian_evaluations += 1;
+                if linearized {
+                    Some((new_guess, new_sum))
+                } else {
+                    None
+                }
+            });
+        match linearized {
+            Some((new_guess, new_sum)) => {
+                let reduction = (sum_of_squares - new_sum) / sum_of_squares;
+                reduction_too_small = reduction < config.ftol;
+                still_improving = reduction > config.ftol;
+                if reduction < config.relative_stall_tol {
+                    consecutive_stalls += 1;
+                } else {
+                    consecutive_stalls = 0;
+                }
+                debug_assert!(
+                    new_sum < sum_of_squares,
+                    "an accepted step increased the sum-of-squares from {} to {}",
+                    sum_of_squares,
+                    new_sum,
+                );
+                mem::swap(&mut system, &mut workspace.system);
+                mem::swap(&mut gradients, &mut workspace.gradients);
+                guess = new_guess;
+                sum_of_squares = new_sum;
+                consecutive_rejections = 0;
+                consecutive_failed_jacobians = 0;
+                accepted_steps += 1;
+            }
+            None => {
+                consecutive_rejections += 1;
+                rejected_steps += 1;
+                if accepted {
+                    consecutive_failed_jacobians += 1;
+                } else {
+                    consecutive_failed_jacobians = 0;
+                }
+            }
+        }
+
+        if consecutive_rejections == config.consecutive_divergence_limit
+            && restarts < config.restart_limit
+        {
+            // The trust region may have shrunk too far to make progress, so start over.
+            restarts += 1;
+            radius = config.initial_trust_radius;
+            consecutive_rejections = 0;
+            consecutive_failed_jacobians = 0;
+        } else if consecutive_rejections == config.consecutive_divergence_limit {
+            break if consecutive_failed_jacobians == consecutive_rejections {
+                TerminationReason::JacobianFailed
+            } else {
+                TerminationReason::ConsecutiveDivergence
+            };
+        } else if config.below_threshold(sum_of_squares, total) {
+            break TerminationReason::BelowThreshold;
+        } else if reduction_too_small {
+            break TerminationReason::ReductionTooSmall;
+        } else if config.stall_iterations != 0 && consecutive_stalls >= config.stall_iterations {
+            break TerminationReason::Stalled;
+        }
+    };
+
+    let condition_estimate = if linearized {
+        system.condition_estimate()
+    } else {
+        None
+    };
+    let rank_warning = linearized
+        && condition_estimate.map_or(true, |estimate| {
+            estimate > config.condition_warning_threshold
+        });
+    let reduced_chi_squared = if linearized {
+        statistics::reduced_chi_square(sum_of_squares, residuals.len(), gradients.len())
+    } else {
+        None
+    };
+    Ok(MinimizationReport {
+        model: guess,
+        termination,
+        iterations,
+        sum_of_squares,
+        residual_evaluations,
+        jacobian_evaluations,
+        accepted_steps,
+        rejected_steps,
+        rank,
+        condition_estimate,
+        rank_warning,
+        lambda: None,
+        final_hessian: system.hessian(),
+        final_gradient: gradients,
+        still_improving,
+        restarts,
+        gauss_newton_iterations: 0,
+        damped_iterations: 0,
+        reduced_chi_squared,
+    })
+}
+
+/// Finds the point along the dogleg path from zero to `cauchy` and then to `gauss_newton`
+/// which is as far along as possible without leaving the trust radius.
+///
+/// If the Gauss-Newton point couldn't be solved for, the path stops at the Cauchy point.
+fn dogleg<N, P>(
+    gauss_newton: Option<VectorN<N, P>>,
+    cauchy: VectorN<N, P>,
+    radius: N,
+) -> VectorN<N, P>
+where
+    N: RealField,
+    P: Dim,
+    DefaultAllocator: Allocator<N, P>,
+{
+    let cauchy_norm = cauchy.norm();
+    match gauss_newton {
+        Some(gauss_newton) if gauss_newton.norm() <= radius => gauss_newton,
+        _ if cauchy_norm >= radius => cauchy * (radius / cauchy_norm),
+        Some(gauss_newton) => {
+            // Solve |c + τ(n - c)|² = radius² for the positive root τ.
+            let leg = gauss_newton - &cauchy;
+            let a = leg.norm_squared();
+            let b = cauchy.dot(&leg);
+            let c = cauchy_norm * cauchy_norm - radius * radius;
+            let tau = (-b + (b * b - a * c).sqrt()) / a;
+            cauchy + leg * tau
+        }
+        None => cauchy,
+    }
+}
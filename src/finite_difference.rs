@@ -0,0 +1,236 @@
+use nalgebra::{
+    allocator::Allocator,
+    dimension::{DimName, DimNameMul, DimNameProd},
+    storage::Storage,
+    DefaultAllocator, Dim, Matrix, MatrixMN, RealField, VectorN,
+};
+
+/// Numerically approximates the Jacobians of each sample using forward differences.
+///
+/// This can be passed as the `jacobians` argument of [`optimize`](crate::optimize) as
+/// `|model| forward_difference_jacobians(model, &apply_delta, &residuals, epsilon)` when you
+/// only have a residual function and don't want to derive the Jacobian by hand.
+///
+/// Each parameter is perturbed one at a time by calling `apply_delta` with a unit delta scaled
+/// by `epsilon` and the change in `residuals` is used to approximate the Jacobian. Just like
+/// the Jacobians which `optimize` expects, the result is the Jacobian of the negative residuals
+/// with each row corresponding to a parameter and each column to a row of the residual matrix.
+///
+/// Unlike [`optimize`](crate::optimize), this requires the number of parameters `P` to be
+/// known at compile time.
+///
+/// Every call costs `P + 1` evaluations of `residuals`, which is often much more expensive
+/// than an analytic Jacobian. The approximation has an error proportional to `epsilon`, but
+/// making `epsilon` too small causes catastrophic cancellation when the residuals are
+/// subtracted. A reasonable starting point is the square root of the machine epsilon of `N`
+/// times the typical magnitude of the parameters (around `1e-4` for `f32` and `1e-8` for `f64`).
+pub fn forward_difference_jacobians<M, N, P, S, J, RS>(
+    model: &M,
+    apply_delta: impl Fn(&M, VectorN<N, P>) -> M,
+    residuals: impl Fn(&M) -> Matrix<N, J, S, RS>,
+    epsilon: N,
+) -> impl Iterator<Item = MatrixMN<N, P, J>>
+where
+    N: RealField,
+    P: DimName + DimNameMul<J>,
+    S: Dim,
+    J: DimName,
+    RS: Storage<N, J, S>,
+    DefaultAllocator: Allocator<N, P>,
+    DefaultAllocator: Allocator<N, P, J>,
+    DefaultAllocator: Allocator<N, DimNameProd<P, J>, S>,
+{
+    let base = residuals(model);
+    difference_jacobians(
+        model,
+        apply_delta,
+        residuals,
+        &VectorN::<N, P>::repeat(epsilon),
+        Difference::Forward(base),
+    )
+}
+
+/// Numerically approximates the Jacobians of each sample using central differences.
+///
+/// This is used exactly like [`forward_difference_jacobians`], but each parameter is perturbed
+/// by both `+epsilon` and `-epsilon`. The error of the approximation is proportional to
+/// `epsilon²` rather than `epsilon`, which is often the difference between converging and
+/// stalling on stiff problems near the optimum.
+///
+/// Every call costs `2P` evaluations of `residuals`, twice as many as forward differences.
+/// Since the error shrinks faster, a larger `epsilon` can be used, such as the cube root of
+/// the machine epsilon of `N` times the typical magnitude of the parameters (around `1e-2`
+/// for `f32` and `1e-5` for `f64`).
+pub fn central_difference_jacobians<M, N, P, S, J, RS>(
+    model: &M,
+    apply_delta: impl Fn(&M, VectorN<N, P>) -> M,
+    residuals: impl Fn(&M) -> Matrix<N, J, S, RS>,
+    epsilon: N,
+) -> impl Iterator<Item = MatrixMN<N, P, J>>
+where
+    N: RealField,
+    P: DimName + DimNameMul<J>,
+    S: Dim,
+    J: DimName,
+    RS: Storage<N, J, S>,
+    DefaultAllocator: Allocator<N, P>,
+    DefaultAllocator: Allocator<N, P, J>,
+    DefaultAllocator: Allocator<N, DimNameProd<P, J>, S>,
+{
+    difference_jacobians(
+        model,
+        apply_delta,
+        residuals,
+        &VectorN::<N, P>::repeat(epsilon),
+        Difference::Central,
+    )
+}
+
+/// Numerically approximates the Jacobians of each sample using forward differences, perturbing
+/// each parameter by a step relative to its own magnitude, `epsilon * max(|p_i|, epsilon_min)`.
+///
+/// A single step is wrong when the parameters span many orders of magnitude. It is too big
+/// for the small parameters, which makes the approximation inaccurate or even leaves the
+/// domain of the model, and too small for the large ones, where the change in the residuals
+/// is lost to cancellation. Scaling the step by each parameter keeps the relative step the
+/// same for all of them, so `epsilon` should be relative, such as the square root of the
+/// machine epsilon of `N`. `epsilon_min` is the magnitude below which a parameter is stepped
+/// as if it had that magnitude, so that a parameter at zero still gets a nonzero step. It
+/// should be around the smallest magnitude that the parameters are meaningful at.
+///
+/// The model must be the parameter vector itself, since every step is added to it. The steps
+/// are rounded to ones which are exactly representable once added to the parameters.
+pub fn adaptive_forward_difference_jacobians<N, P, S, J, RS>(
+    model: &VectorN<N, P>,
+    residuals: impl Fn(&VectorN<N, P>) -> Matrix<N, J, S, RS>,
+    epsilon: N,
+    epsilon_min: N,
+) -> impl Iterator<Item = MatrixMN<N, P, J>>
+where
+    N: RealField,
+    P: DimName + DimNameMul<J>,
+    S: Dim,
+    J: DimName,
+    RS: Storage<N, J, S>,
+    DefaultAllocator: Allocator<N, P>,
+    DefaultAllocator: Allocator<N, P, J>,
+    DefaultAllocator: Allocator<N, DimNameProd<P, J>, S>,
+{
+    let base = residuals(model);
+    difference_jacobians(
+        model,
+        |model, delta| model + delta,
+        residuals,
+        &relative_steps(model, epsilon, epsilon_min),
+        Difference::Forward(base),
+    )
+}
+
+/// Numerically approximates the Jacobians of each sample using central differences, perturbing
+/// each parameter by a step relative to its own magnitude like
+/// [`adaptive_forward_difference_jacobians`].
+///
+/// As with [`central_difference_jacobians`], `epsilon` can be larger than for forward
+/// differences, such as the cube root of the machine epsilon of `N`.
+pub fn adaptive_central_difference_jacobians<N, P, S, J, RS>(
+    model: &VectorN<N, P>,
+    residuals: impl Fn(&VectorN<N, P>) -> Matrix<N, J, S, RS>,
+    epsilon: N,
+    epsilon_min: N,
+) -> impl Iterator<Item = MatrixMN<N, P, J>>
+where
+    N: RealField,
+    P: DimName + DimNameMul<J>,
+    S: Dim,
+    J: DimName,
+    RS: Storage<N, J, S>,
+    DefaultAllocator: Allocator<N, P>,
+    DefaultAllocator: Allocator<N, P, J>,
+    DefaultAllocator: Allocator<N, DimNameProd<P, J>, S>,
+{
+    difference_jacobians(
+        model,
+        |model, delta| model + delta,
+        residuals,
+        &relative_steps(model, epsilon, epsilon_min),
+        Difference::Central,
+    )
+}
+
+/// The step `epsilon * max(|p_i|, epsilon_min)` of every parameter, rounded so that
+/// `p_i + step` is exact.
+fn relative_steps<N, P>(parameters: &VectorN<N, P>, epsilon: N, epsilon_min: N) -> VectorN<N, P>
+where
+    N: RealField,
+    P: Dim,
+    DefaultAllocator: Allocator<N, P>,
+{
+    parameters.map(|parameter| {
+        let step = epsilon * parameter.abs().max(epsilon_min);
+        // The parameter can't represent every step exactly, so use the one it actually takes.
+        (parameter + step) - parameter
+    })
+}
+
+/// The finite difference scheme used to approximate the Jacobian.
+enum Difference<R> {
+    /// Compare against the residuals of the unperturbed model.
+    Forward(R),
+    /// Compare against the residuals of the model perturbed in the opposite direction.
+    Central,
+}
+
+/// Computes the Jacobian of the negative residuals of every sample using the given difference
+/// scheme, one parameter at a time, perturbing each parameter by its entry of `steps`.
+fn difference_jacobians<M, N, P, S, J, RS>(
+    model: &M,
+    apply_delta: impl Fn(&M, VectorN<N, P>) -> M,
+    residuals: impl Fn(&M) -> Matrix<N, J, S, RS>,
+    steps: &VectorN<N, P>,
+    difference: Difference<Matrix<N, J, S, RS>>,
+) -> impl Iterator<Item = MatrixMN<N, P, J>>
+where
+    N: RealField,
+    P: DimName + DimNameMul<J>,
+    S: Dim,
+    J: DimName,
+    RS: Storage<N, J, S>,
+    DefaultAllocator: Allocator<N, P>,
+    DefaultAllocator: Allocator<N, P, J>,
+    DefaultAllocator: Allocator<N, DimNameProd<P, J>, S>,
+{
+    // Each block of `J` rows contains the derivatives of every sample in respect to one parameter.
+    let mut derivatives = None;
+    for parameter in 0..P::dim() {
+        let epsilon = steps[parameter];
+        let mut delta = VectorN::<N, P>::zeros();
+        delta[parameter] = epsilon;
+        let plus = residuals(&apply_delta(model, delta.clone()));
+        let negative;
+        let (minus, step) = match &difference {
+            Difference::Forward(base) => (base, epsilon),
+            Difference::Central => {
+                negative = residuals(&apply_delta(model, -delta));
+                (&negative, epsilon + epsilon)
+            }
+        };
+
+        let derivatives = derivatives.get_or_insert_with(|| {
+            MatrixMN::<N, DimNameProd<P, J>, S>::zeros_generic(
+                DimNameProd::<P, J>::name(),
+                plus.data.shape().1,
+            )
+        });
+        let mut block = derivatives.fixed_rows_mut::<J>(parameter * J::dim());
+        for (out, (&minus, &plus)) in block.iter_mut().zip(minus.iter().zip(plus.iter())) {
+            *out = (minus - plus) / step;
+        }
+    }
+
+    derivatives.into_iter().flat_map(|derivatives| {
+        (0..derivatives.ncols()).map(move |sample| {
+            let column = derivatives.column(sample);
+            MatrixMN::<N, P, J>::from_fn(|parameter, row| column[parameter * J::dim() + row])
+        })
+    })
+}
@@ -0,0 +1,206 @@
+use crate::LeastSquaresProblem;
+use nalgebra::{
+    allocator::Allocator,
+    storage::{Owned, Storage},
+    DefaultAllocator, Dim, DimName, Matrix, MatrixMN, RealField, VectorN,
+};
+
+/// Adapts a [`LeastSquaresProblem`] so that the parameters which are `true` in a mask are held
+/// exactly constant.
+///
+/// This freezes parameters which are already known, such as the gauge of a gauge-fixed problem
+/// or the calibration from an earlier run, without having to remove them from the model. The
+/// rows of the fixed parameters in every Jacobian are zeroed, so they have no gradient and no
+/// coupling to the free parameters with any solve method. Their components of every `delta`
+/// passed to the wrapped `apply_delta` are also set to zero, so they keep their initial values
+/// as long as `apply_delta` leaves a parameter alone when its step is zero.
+///
+/// A parameter with a zeroed row has no curvature either, so
+/// [`normal_equations`](LeastSquaresProblem::normal_equations) also puts a one on its diagonal
+/// to keep the damped system solvable with any damping, which the optimizer uses with any
+/// config as described for
+/// [`modifies_normal_equations`](LeastSquaresProblem::modifies_normal_equations). An adapter
+/// which sums the masked Jacobians itself, such as a
+/// [`WeightedProblem`](crate::WeightedProblem) around this one, leaves the diagonal at zero,
+/// which makes the damped system singular with
+/// [`DampingMode::Diagonal`](crate::DampingMode::Diagonal), so it should be combined with
+/// [`DampingMode::Identity`](crate::DampingMode::Identity) or
+/// [`DampingMode::AutoScaled`](crate::DampingMode::AutoScaled).
+pub struct FixedProblem<LSP, P>
+where
+    P: Dim,
+    DefaultAllocator: Allocator<bool, P>,
+{
+    problem: LSP,
+    fixed: VectorN<bool, P>,
+}
+
+impl<LSP, P> FixedProblem<LSP, P>
+where
+    P: Dim,
+    DefaultAllocator: Allocator<bool, P>,
+{
+    /// Holds the parameters of `problem` which are `true` in `fixed` constant.
+    pub fn new(problem: LSP, fixed: VectorN<bool, P>) -> Self {
+        Self { problem, fixed }
+    }
+
+    /// Zeroes the components of `delta` which belong to the fixed parameters.
+    ///
+    /// The step should already be zero for them, but a damped system which is solved by LU
+    /// decomposition or a pseudo-inverse may leave rounding errors in them.
+    fn mask<N>(&self, mut delta: VectorN<N, P>) -> VectorN<N, P>
+    where
+        N: RealField,
+        DefaultAllocator: Allocator<N, P>,
+    {
+        for (delta, &fixed) in delta.iter_mut().zip(self.fixed.iter()) {
+            if fixed {
+                *delta = N::zero();
+            }
+        }
+        delta
+    }
+}
+
+impl<N, P, S, J, LSP> LeastSquaresProblem<N, P, S, J> for FixedProblem<LSP, P>
+where
+    N: RealField,
+    P: Dim,
+    S: Dim,
+    J: Dim,
+    LSP: LeastSquaresProblem<N, P, S, J>,
+    DefaultAllocator: Allocator<N, P>,
+    DefaultAllocator: Allocator<N, P, J>,
+    DefaultAllocator: Allocator<bool, P>,
+{
+    type Model = LSP::Model;
+    type ResidualStorage = LSP::ResidualStorage;
+    type JacobianStorage = Owned<N, P, J>;
+    type Jacobians<'a>
+        = FixedJacobians<LSP::Jacobians<'a>, P>
+    where
+        Self: 'a;
+
+    fn apply_delta(&self, model: &Self::Model, delta: VectorN<N, P>) -> Self::Model {
+        self.problem.apply_delta(model, self.mask(delta))
+    }
+
+    fn try_apply_delta(&self, model: &Self::Model, delta: VectorN<N, P>) -> Option<Self::Model> {
+        self.problem.try_apply_delta(model, self.mask(delta))
+    }
+
+    fn residuals(&self, model: &Self::Model) -> Matrix<N, J, S, Self::ResidualStorage> {
+        self.problem.residuals(model)
+    }
+
+    fn jacobians(&self, model: &Self::Model) -> Self::Jacobians<'_> {
+        FixedJacobians {
+            jacobians: self.problem.jacobians(model),
+            fixed: self.fixed.clone(),
+        }
+    }
+
+    fn try_jacobians(&self, model: &Self::Model) -> Option<Self::Jacobians<'_>> {
+        Some(FixedJacobians {
+            jacobians: self.problem.try_jacobians(model)?,
+            fixed: self.fixed.clone(),
+        })
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn residuals_and_jacobians(
+        &self,
+        model: &Self::Model,
+    ) -> (
+        Matrix<N, J, S, Self::ResidualStorage>,
+        Option<Self::Jacobians<'_>>,
+    ) {
+        let (residuals, jacobians) = self.problem.residuals_and_jacobians(model);
+        let jacobians = jacobians.map(|jacobians| FixedJacobians {
+            jacobians,
+            fixed: self.fixed.clone(),
+        });
+        (residuals, jacobians)
+    }
+
+    fn normal_equations(
+        &self,
+        model: &Self::Model,
+        residuals: &Matrix<N, J, S, Self::ResidualStorage>,
+    ) -> Option<(MatrixMN<N, P, P>, VectorN<N, P>)>
+    where
+        N: RealField,
+        P: Dim,
+        J: DimName,
+        DefaultAllocator: Allocator<N, P, P>,
+        DefaultAllocator: Allocator<N, J, P>,
+    {
+        let (mut hessian, mut gradients) = self.problem.normal_equations(model, residuals)?;
+        for (index, _) in self.fixed.iter().enumerate().filter(|&(_, &fixed)| fixed) {
+            // Decoupling the parameter with a unit diagonal and no gradient makes its step
+            // exactly zero, and the damped system stays invertible with any damping.
+            hessian.row_mut(index).fill(N::zero());
+            hessian.column_mut(index).fill(N::zero());
+            hessian[(index, index)] = N::one();
+            gradients[index] = N::zero();
+        }
+        Some((hessian, gradients))
+    }
+
+    fn modifies_normal_equations(&self) -> bool {
+        self.fixed.iter().any(|&fixed| fixed) || self.problem.modifies_normal_equations()
+    }
+
+    fn sum_of_squares(
+        &self,
+        model: &Self::Model,
+        residuals: &Matrix<N, J, S, Self::ResidualStorage>,
+    ) -> N
+    where
+        N: RealField,
+    {
+        self.problem.sum_of_squares(model, residuals)
+    }
+
+    fn normalize(&self, model: Self::Model) -> Self::Model {
+        self.problem.normalize(model)
+    }
+
+    fn try_normalize(&self, model: Self::Model) -> Option<Self::Model> {
+        self.problem.try_normalize(model)
+    }
+}
+
+/// Zeroes the rows of the Jacobian of every sample which belong to the fixed parameters.
+pub struct FixedJacobians<I, P>
+where
+    P: Dim,
+    DefaultAllocator: Allocator<bool, P>,
+{
+    jacobians: I,
+    fixed: VectorN<bool, P>,
+}
+
+impl<I, N, P, J, JS> Iterator for FixedJacobians<I, P>
+where
+    I: Iterator<Item = Matrix<N, P, J, JS>>,
+    N: RealField,
+    P: Dim,
+    J: Dim,
+    JS: Storage<N, P, J>,
+    DefaultAllocator: Allocator<bool, P>,
+    DefaultAllocator: Allocator<N, P, J>,
+{
+    type Item = MatrixMN<N, P, J>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut jacobian = self.jacobians.next()?.into_owned();
+        for (mut row, &fixed) in jacobian.row_iter_mut().zip(self.fixed.iter()) {
+            if fixed {
+                row.fill(N::zero());
+            }
+        }
+        Some(jacobian)
+    }
+}
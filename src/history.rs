@@ -0,0 +1,92 @@
+use nalgebra::RealField;
+
+/// The sum-of-squares and lambda of the last `K` iterations of an optimization, which is filled
+/// in by [`optimize_recorded`](crate::optimize_recorded) or
+/// [`LevenbergMarquardt::run_recorded`](crate::LevenbergMarquardt::run_recorded).
+///
+/// This is backed by two `[N; K]` arrays used as a ring buffer, so it never allocates and works
+/// on embedded targets without an allocator. Only the most recent `K` iterations are retained,
+/// and older ones are overwritten, which is enough to see how the fit behaved in the tail of the
+/// convergence, such as whether it stalled or lambda kept bouncing. To record every iteration,
+/// pass a buffer of `max_iterations` entries to
+/// [`run_with_history`](crate::LevenbergMarquardt::run_with_history) instead.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct HistoryRecorder<N, const K: usize> {
+    sums_of_squares: [N; K],
+    lambdas: [N; K],
+    recorded: usize,
+}
+
+impl<N, const K: usize> HistoryRecorder<N, K>
+where
+    N: RealField,
+{
+    /// Creates a recorder which hasn't recorded any iterations.
+    pub fn new() -> Self {
+        Self {
+            sums_of_squares: [N::zero(); K],
+            lambdas: [N::zero(); K],
+            recorded: 0,
+        }
+    }
+
+    /// Forgets every recorded iteration.
+    pub fn clear(&mut self) {
+        self.recorded = 0;
+    }
+
+    /// Records the best sum-of-squares after an iteration and the lambda that it started from,
+    /// overwriting the oldest iteration once `K` are retained.
+    pub(crate) fn record(&mut self, sum_of_squares: N, lambda: N) {
+        if K != 0 {
+            let index = self.recorded % K;
+            self.sums_of_squares[index] = sum_of_squares;
+            self.lambdas[index] = lambda;
+        }
+        self.recorded += 1;
+    }
+
+    /// The number of iterations recorded since the recorder was created or cleared, including
+    /// the ones which are no longer retained.
+    pub fn recorded(&self) -> usize {
+        self.recorded
+    }
+
+    /// The number of iterations which are retained, which is at most `K`.
+    pub fn len(&self) -> usize {
+        self.recorded.min(K)
+    }
+
+    /// Whether no iterations are retained.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The retained iterations from the oldest to the newest.
+    ///
+    /// Each item is the index of the iteration, the sum-of-squares of the best model after it,
+    /// and the lambda of the first step tested in it, like the entries of
+    /// [`run_with_history`](crate::LevenbergMarquardt::run_with_history) and
+    /// [`run_with_lambda_history`](crate::LevenbergMarquardt::run_with_lambda_history).
+    pub fn iter(&self) -> impl Iterator<Item = (usize, N, N)> + '_ {
+        let first = self.recorded - self.len();
+        (first..self.recorded).map(move |iteration| {
+            let index = iteration % K;
+            (iteration, self.sums_of_squares[index], self.lambdas[index])
+        })
+    }
+
+    /// The newest retained iteration, in the same form as the items of [`iter`](Self::iter).
+    pub fn last(&self) -> Option<(usize, N, N)> {
+        self.iter().last()
+    }
+}
+
+impl<N, const K: usize> Default for HistoryRecorder<N, K>
+where
+    N: RealField,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,165 @@
+use crate::{solve, LeastSquaresProblem, OptimizeError};
+use core::cell::Cell;
+use nalgebra::{
+    allocator::Allocator, DefaultAllocator, Dim, DimName, Matrix, MatrixMN, RealField, VectorN,
+};
+
+/// Adapts a [`LeastSquaresProblem`] so that the approximate Hessian `JJᵀ` of the initial guess
+/// is supplied rather than accumulated from the Jacobians.
+///
+/// This saves summing the outer products of every Jacobian for the first step, which is useful
+/// when warm-starting from a previous fit whose Hessian is already known, such as when tracking
+/// across frames, or when a cheaper approximation is good enough to head downhill. The Hessian
+/// is treated as an approximation of `JJᵀ`, never as the exact Hessian of the sum-of-squares, so
+/// it should be symmetric and positive semi-definite like `JJᵀ`. The gradients of the first step
+/// are still accumulated from the Jacobians and the residuals, and every later linearization
+/// accumulates `JJᵀ` as usual.
+///
+/// The Hessian is taken by the first call to
+/// [`normal_equations`](LeastSquaresProblem::normal_equations), so the problem seeds a single
+/// optimization and then behaves like the wrapped one. Until then the problem
+/// [modifies its normal equations](LeastSquaresProblem::modifies_normal_equations), so the
+/// first step is solved with the normal equations even with [`SolveMethod::Qr`], without
+/// `compensated_accumulation`, and without stacking the Jacobians for Broyden's method, which
+/// starts from the Jacobians of the first accepted step instead. The wrapped problem's own
+/// [`residuals_and_jacobians`](LeastSquaresProblem::residuals_and_jacobians) isn't used, since
+/// Jacobians computed along with the residuals would be accumulated instead.
+///
+/// [`SolveMethod::Qr`]: crate::SolveMethod::Qr
+pub struct InitialHessianProblem<LSP, N, P>
+where
+    N: RealField,
+    P: Dim,
+    DefaultAllocator: Allocator<N, P, P>,
+{
+    problem: LSP,
+    /// The supplied Hessian, which is taken by the first linearization.
+    hessian: Cell<Option<MatrixMN<N, P, P>>>,
+}
+
+impl<LSP, N, P> InitialHessianProblem<LSP, N, P>
+where
+    N: RealField,
+    P: Dim,
+    DefaultAllocator: Allocator<N, P, P>,
+{
+    /// Seeds the first linearization of `problem` at `init` with `hessian`.
+    ///
+    /// Returns [`OptimizeError::HessianDimensionMismatch`] if `hessian` doesn't have a row and a
+    /// column for every parameter. With a statically sized `P` this is already guaranteed by its
+    /// type, while with [`Dynamic`](nalgebra::Dynamic) parameters the number of parameters is
+    /// taken from the first Jacobian of `init`.
+    pub fn new<S, J>(
+        problem: LSP,
+        init: &LSP::Model,
+        hessian: MatrixMN<N, P, P>,
+    ) -> Result<Self, OptimizeError>
+    where
+        S: Dim,
+        J: Dim,
+        LSP: LeastSquaresProblem<N, P, S, J>,
+        DefaultAllocator: Allocator<N, P>,
+    {
+        if P::try_to_usize().is_none() {
+            let parameters = problem
+                .try_jacobians(init)
+                .and_then(|mut jacobians| jacobians.next())
+                .map(|jacobian| jacobian.nrows());
+            if hessian.nrows() != hessian.ncols()
+                || parameters.map_or(false, |parameters| parameters != hessian.nrows())
+            {
+                return Err(OptimizeError::HessianDimensionMismatch);
+            }
+        }
+        Ok(Self {
+            problem,
+            hessian: Cell::new(Some(hessian)),
+        })
+    }
+}
+
+impl<N, P, S, J, LSP> LeastSquaresProblem<N, P, S, J> for InitialHessianProblem<LSP, N, P>
+where
+    N: RealField,
+    P: Dim,
+    S: Dim,
+    J: Dim,
+    LSP: LeastSquaresProblem<N, P, S, J>,
+    DefaultAllocator: Allocator<N, P>,
+    DefaultAllocator: Allocator<N, P, P>,
+{
+    type Model = LSP::Model;
+    type ResidualStorage = LSP::ResidualStorage;
+    type JacobianStorage = LSP::JacobianStorage;
+    type Jacobians<'a>
+        = LSP::Jacobians<'a>
+    where
+        Self: 'a;
+
+    fn apply_delta(&self, model: &Self::Model, delta: VectorN<N, P>) -> Self::Model {
+        self.problem.apply_delta(model, delta)
+    }
+
+    fn try_apply_delta(&self, model: &Self::Model, delta: VectorN<N, P>) -> Option<Self::Model> {
+        self.problem.try_apply_delta(model, delta)
+    }
+
+    fn residuals(&self, model: &Self::Model) -> Matrix<N, J, S, Self::ResidualStorage> {
+        self.problem.residuals(model)
+    }
+
+    fn jacobians(&self, model: &Self::Model) -> Self::Jacobians<'_> {
+        self.problem.jacobians(model)
+    }
+
+    fn try_jacobians(&self, model: &Self::Model) -> Option<Self::Jacobians<'_>> {
+        self.problem.try_jacobians(model)
+    }
+
+    fn normal_equations(
+        &self,
+        model: &Self::Model,
+        residuals: &Matrix<N, J, S, Self::ResidualStorage>,
+    ) -> Option<(MatrixMN<N, P, P>, VectorN<N, P>)>
+    where
+        N: RealField,
+        P: Dim,
+        J: DimName,
+        DefaultAllocator: Allocator<N, P, P>,
+        DefaultAllocator: Allocator<N, J, P>,
+    {
+        match self.hessian.take() {
+            Some(hessian) => {
+                let gradients = solve::gradients(self.problem.try_jacobians(model)?, residuals);
+                Some((hessian, gradients))
+            }
+            None => self.problem.normal_equations(model, residuals),
+        }
+    }
+
+    fn modifies_normal_equations(&self) -> bool {
+        let hessian = self.hessian.take();
+        let seeded = hessian.is_some();
+        self.hessian.set(hessian);
+        seeded || self.problem.modifies_normal_equations()
+    }
+
+    fn sum_of_squares(
+        &self,
+        model: &Self::Model,
+        residuals: &Matrix<N, J, S, Self::ResidualStorage>,
+    ) -> N
+    where
+        N: RealField,
+    {
+        self.problem.sum_of_squares(model, residuals)
+    }
+
+    fn normalize(&self, model: Self::Model) -> Self::Model {
+        self.problem.normalize(model)
+    }
+
+    fn try_normalize(&self, model: Self::Model) -> Option<Self::Model> {
+        self.problem.try_normalize(model)
+    }
+}
@@ -0,0 +1,198 @@
+use crate::central_difference_jacobians;
+#[cfg(feature = "alloc")]
+use crate::solve;
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+use core::fmt;
+use nalgebra::{
+    allocator::Allocator,
+    dimension::{DimName, DimNameMul, DimNameProd},
+    storage::Storage,
+    DefaultAllocator, Dim, Matrix, RealField, VectorN,
+};
+#[cfg(feature = "alloc")]
+use nalgebra::{Dynamic, MatrixMN};
+
+/// Stacks the Jacobians of every sample into the full Jacobian of the negative residuals, with
+/// a row for every residual and a column for every parameter.
+///
+/// This requires the `alloc` feature. It is meant for inspecting the Jacobian while debugging
+/// or for computing statistics that need all of it at once, and is called with the iterator
+/// that would be returned by `jacobians`, such as `stacked_jacobian(jacobians(&model))`. The
+/// rows are in the same order as the residuals are stored in the residual matrix, so the
+/// `k`th residual of the `i`th sample is row `i * J + k`, which is column `k` of the `i`th
+/// Jacobian.
+#[cfg(feature = "alloc")]
+pub fn stacked_jacobian<N, P, J, JS>(
+    jacobians: impl Iterator<Item = Matrix<N, P, J, JS>>,
+) -> MatrixMN<N, Dynamic, P>
+where
+    N: RealField,
+    P: Dim,
+    J: Dim,
+    JS: Storage<N, P, J>,
+    DefaultAllocator: Allocator<N, Dynamic, P>,
+{
+    let mut p = solve::initial_dim::<P>();
+    let mut rows = 0;
+    let mut entries = Vec::new();
+    for jacobian in jacobians {
+        p = jacobian.data.shape().0;
+        rows += jacobian.ncols();
+        // The storage is column-major, so each column of a Jacobian is one row of the result.
+        entries.extend(jacobian.iter().copied());
+    }
+    MatrixMN::from_row_slice_generic(Dynamic::new(rows), p, &entries)
+}
+
+/// Compares the Jacobians returned by `jacobians` against [`central_difference_jacobians`] at
+/// `model` and returns the largest absolute difference between any two entries.
+///
+/// An analytic Jacobian which disagrees with its residuals is the most common reason that a
+/// fit fails to converge, so this is worth checking at a few models before optimizing. The
+/// central differences have an error proportional to `epsilon²` and the magnitude of the
+/// second derivatives, so a correct Jacobian gives a small but nonzero difference, while a
+/// wrong sign or a missing term usually gives a difference on the order of the entries
+/// themselves. See [`central_difference_jacobians`] for how to choose `epsilon`.
+///
+/// The samples are compared in order and only as many as both iterators return. Remember that
+/// the Jacobians are of the negative residuals.
+pub fn check_jacobian<M, N, P, S, J, RS, JS, IJ>(
+    model: &M,
+    apply_delta: impl Fn(&M, VectorN<N, P>) -> M,
+    residuals: impl Fn(&M) -> Matrix<N, J, S, RS>,
+    jacobians: impl Fn(&M) -> IJ,
+    epsilon: N,
+) -> N
+where
+    N: RealField,
+    P: DimName + DimNameMul<J>,
+    S: Dim,
+    J: DimName,
+    RS: Storage<N, J, S>,
+    JS: Storage<N, P, J>,
+    IJ: Iterator<Item = Matrix<N, P, J, JS>>,
+    DefaultAllocator: Allocator<N, P>,
+    DefaultAllocator: Allocator<N, P, J>,
+    DefaultAllocator: Allocator<N, DimNameProd<P, J>, S>,
+{
+    let numerical = central_difference_jacobians(model, apply_delta, residuals, epsilon);
+    jacobians(model)
+        .zip(numerical)
+        .fold(N::zero(), |largest, (analytic, numerical)| {
+            analytic
+                .iter()
+                .zip(numerical.iter())
+                .fold(largest, |largest, (&analytic, &numerical)| {
+                    largest.max((analytic - numerical).abs())
+                })
+        })
+}
+
+/// The entry where an analytic Jacobian disagreed most with its finite differences, which is
+/// returned by [`verify_jacobians`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct JacobianMismatch<N> {
+    /// The index of the sample whose Jacobian was wrong.
+    pub sample: usize,
+    /// The index of the parameter, which is the row of the Jacobian.
+    pub parameter: usize,
+    /// The index of the residual within the sample, which is the column of the Jacobian.
+    pub residual: usize,
+    /// The entry of the analytic Jacobian.
+    pub analytic: N,
+    /// The entry of the central difference Jacobian.
+    pub numerical: N,
+    /// The relative error between the two entries.
+    pub relative_error: N,
+}
+
+impl<N> fmt::Display for JacobianMismatch<N>
+where
+    N: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "the derivative of residual {} of sample {} by parameter {} was {}, but its finite \
+             difference was {} (relative error {})",
+            self.residual,
+            self.sample,
+            self.parameter,
+            self.analytic,
+            self.numerical,
+            self.relative_error
+        )
+    }
+}
+
+/// Compares the Jacobians returned by `jacobians` against [`central_difference_jacobians`] at
+/// `model` and returns the entry with the largest relative error if it is above `tol`.
+///
+/// This is [`check_jacobian`] for finding a bug in a derivation, since the mismatch says which
+/// sample and parameter the wrong derivative belongs to. The relative error of two entries is
+/// their difference divided by the larger of their magnitudes, but never by less than `1.0`,
+/// so that the truncation error of derivatives which are nearly zero is not mistaken for a
+/// bug. With the default `epsilon` of [`central_difference_jacobians`], a `tol` around `1e-4`
+/// separates a correct Jacobian from a wrong one for most problems.
+///
+/// The samples are compared in order and only as many as both iterators return. Remember that
+/// the Jacobians are of the negative residuals.
+pub fn verify_jacobians<M, N, P, S, J, RS, JS, IJ>(
+    model: &M,
+    apply_delta: impl Fn(&M, VectorN<N, P>) -> M,
+    residuals: impl Fn(&M) -> Matrix<N, J, S, RS>,
+    jacobians: impl Fn(&M) -> IJ,
+    epsilon: N,
+    tol: N,
+) -> Result<(), JacobianMismatch<N>>
+where
+    N: RealField,
+    P: DimName + DimNameMul<J>,
+    S: Dim,
+    J: DimName,
+    RS: Storage<N, J, S>,
+    JS: Storage<N, P, J>,
+    IJ: Iterator<Item = Matrix<N, P, J, JS>>,
+    DefaultAllocator: Allocator<N, P>,
+    DefaultAllocator: Allocator<N, P, J>,
+    DefaultAllocator: Allocator<N, DimNameProd<P, J>, S>,
+{
+    let numerical = central_difference_jacobians(model, apply_delta, residuals, epsilon);
+    let mut worst: Option<JacobianMismatch<N>> = None;
+    for (sample, (analytic, numerical)) in jacobians(model).zip(numerical).enumerate() {
+        for residual in 0..analytic.ncols() {
+            for parameter in 0..analytic.nrows() {
+                let analytic = analytic[(parameter, residual)];
+                let numerical = numerical[(parameter, residual)];
+                let scale = analytic.abs().max(numerical.abs()).max(N::one());
+                let relative_error = (analytic - numerical).abs() / scale;
+                // A NaN entry is always the worst, since it can never be correct.
+                let worse = match worst {
+                    Some(worst) => {
+                        worst.relative_error.is_finite()
+                            && (!relative_error.is_finite()
+                                || relative_error > worst.relative_error)
+                    }
+                    None => true,
+                };
+                if worse {
+                    worst = Some(JacobianMismatch {
+                        sample,
+                        parameter,
+                        residual,
+                        analytic,
+                        numerical,
+                        relative_error,
+                    });
+                }
+            }
+        }
+    }
+    match worst {
+        Some(worst) if !worst.relative_error.is_finite() || worst.relative_error > tol => {
+            Err(worst)
+        }
+        _ => Ok(()),
+    }
+}
@@ -2,95 +2,947 @@
 
 #![no_std]
 
+#[cfg(feature = "alloc")]
+extern crate alloc;
+#[cfg(feature = "rayon")]
+extern crate std;
+
+#[cfg(feature = "alloc")]
+mod blocks;
+mod bounded;
+#[cfg(feature = "alloc")]
+mod broyden;
+mod builder;
+mod complex;
+mod cost;
+mod dogleg;
+mod finite_difference;
+mod fixed;
+mod history;
+mod initial_hessian;
+mod jacobian;
+#[cfg(feature = "alloc")]
+mod minibatch;
+#[cfg(feature = "rayon")]
+mod parallel;
+mod prior;
+mod problem;
+#[cfg(feature = "alloc")]
+mod ragged;
+mod robust;
+mod scaled;
+mod schedule;
+mod solve;
+mod statistics;
+mod step;
+mod weighted;
+mod workspace;
+
+#[cfg(feature = "alloc")]
+pub use blocks::{BlockId, BlockJacobians, BlockProblem};
+pub use bounded::BoundedProblem;
+pub use builder::ConfigBuilder;
+pub use complex::{ComplexJacobians, ComplexProblem};
+pub use cost::CostProblem;
+pub use finite_difference::{
+    adaptive_central_difference_jacobians, adaptive_forward_difference_jacobians,
+    central_difference_jacobians, forward_difference_jacobians,
+};
+pub use fixed::{FixedJacobians, FixedProblem};
+pub use history::HistoryRecorder;
+pub use initial_hessian::InitialHessianProblem;
+#[cfg(feature = "alloc")]
+pub use jacobian::stacked_jacobian;
+pub use jacobian::{check_jacobian, verify_jacobians, JacobianMismatch};
+#[cfg(feature = "alloc")]
+pub use minibatch::MinibatchProblem;
+#[cfg(feature = "rayon")]
+pub use parallel::ParallelClosureProblem;
+pub use prior::PriorProblem;
+pub use problem::{
+    ClosureProblem, FallibleClosureProblem, FeasibleClosureProblem, FusedClosureProblem,
+    HessianClosureProblem, LeastSquaresProblem, NormalizedClosureProblem,
+};
+#[cfg(feature = "alloc")]
+pub use ragged::RaggedProblem;
+pub use robust::{Cauchy, Huber, LossFunction, RobustProblem, SoftL1, Squared, Tukey};
+pub use scaled::{ScaledJacobians, ScaledProblem};
+pub use schedule::{InterpolatedSchedule, LambdaSchedule};
+pub use statistics::parameter_standard_errors;
+pub use step::{LevenbergMarquardt, StepOutcome};
+pub use weighted::{
+    InformationWeightedJacobians, InformationWeightedProblem, WeightedJacobians, WeightedProblem,
+};
+pub use workspace::Workspace;
+
 use nalgebra::{
     allocator::Allocator,
     constraint::{DimEq, ShapeConstraint},
     dimension::{DimMin, DimMinimum},
     storage::{ContiguousStorageMut, Storage},
-    DefaultAllocator, Dim, DimName, Matrix, MatrixMN, RealField, Vector,
+    DefaultAllocator, Dim, DimName, Matrix, MatrixMN, RealField, Scalar, Vector, VectorN,
 };
 
+#[cfg(feature = "alloc")]
+use nalgebra::{Dynamic, U1};
+
+use core::{convert::TryFrom, fmt, ops::ControlFlow};
 use num_traits::FromPrimitive;
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Config<N> {
     pub max_iterations: usize,
     pub consecutive_divergence_limit: usize,
-    pub initial_lambda: N,
+    pub initial_lambda: InitialLambda<N>,
     pub lambda_convege: N,
     pub lambda_diverge: N,
     pub threshold: N,
+    pub gradient_threshold: N,
+    pub ftol: N,
+    pub damping_strategy: DampingStrategy,
+    pub damping_mode: DampingMode,
+    pub min_lambda: N,
+    pub max_lambda: N,
+    pub solve_method: SolveMethod<N>,
+    pub geodesic_acceleration: bool,
+    pub acceleration_ratio: N,
+    pub initial_trust_radius: N,
+    pub method: Method,
+    pub lambda_candidates: usize,
+    pub line_search: bool,
+    pub line_search_min_alpha: N,
+    pub condition_warning_threshold: N,
+    pub max_function_evaluations: usize,
+    pub initial_lambda_from: Option<N>,
+    pub threshold_kind: ThresholdKind,
+    pub restart_limit: usize,
+    pub jacobian_refresh_interval: usize,
+    pub stall_iterations: usize,
+    pub relative_stall_tol: N,
+    pub compensated_accumulation: bool,
+    pub max_step_norm: Option<N>,
 }
 
-impl<N> Default for Config<N>
+/// The algorithm used to compute each step.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Method {
+    /// Damp the normal equations with lambda, which is updated according to
+    /// `damping_strategy`, and only accept steps which reduce the sum-of-squares.
+    LevenbergMarquardt,
+    /// Solve the undamped normal equations `JJᵀδ = g` and accept every step.
+    ///
+    /// Near the solution of a well-behaved problem, this converges quadratically without
+    /// wasting any evaluations on rejected steps. However, nothing keeps the steps small, so
+    /// it can diverge from a bad initial guess where the damped default would converge. If
+    /// `JJᵀ` is singular, the smallest multiple of `ε*max(diag(JJᵀ))*I` for which it can be
+    /// solved is added to it, where `ε` is the machine epsilon. Every lambda and damping
+    /// setting is ignored, and steps are only rejected if the system can't be solved, the
+    /// sum-of-squares isn't finite, or the Jacobians fail at the new guess. Since a step may
+    /// increase the sum-of-squares, `ftol` is only checked for steps which decrease it, and
+    /// the best model seen is still the one that is returned.
+    GaussNewton,
+    /// Try an undamped Gauss-Newton step, and fall back to [`Method::LevenbergMarquardt`]
+    /// once one fails.
+    ///
+    /// Unlike [`Method::GaussNewton`], a step is only accepted if it reduces the sum-of-squares
+    /// like a damped one, and a singular `JJᵀ` isn't regularized. The first step that fails
+    /// switches to damped steps with the current lambda, and Gauss-Newton is attempted again
+    /// once enough damped steps in a row have a gain ratio above `3/4`, which means that the
+    /// linearization predicts the sum-of-squares well enough for lambda to be dropped. Two
+    /// such steps are needed after the first failed attempt, and every further one doubles
+    /// that, so that a fit which is far from the solution doesn't keep wasting evaluations.
+    /// A failed attempt doesn't change lambda, but counts as a rejected step towards
+    /// `consecutive_divergence_limit`. The iterations spent in each mode are reported as
+    /// [`MinimizationReport::gauss_newton_iterations`] and
+    /// [`MinimizationReport::damped_iterations`].
+    GaussNewtonWithFallback,
+}
+
+/// How lambda is updated after each step.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DampingStrategy {
+    /// Tests `lambda_candidates` lambdas each iteration, `lambda`, `lambda * lambda_converge`,
+    /// `lambda * lambda_converge²` and so on, and keeps whichever gives the lowest sum-of-squares
+    /// if that step is accepted. By default, this tests `lambda` and `lambda * lambda_converge`.
+    /// Otherwise lambda is multiplied by `lambda_diverge`.
+    Multiplicative,
+    /// The strategy from "Methods for Non-Linear Least Squares Problems" by Madsen, Nielsen,
+    /// and Tingleff.
+    ///
+    /// Only `lambda` is tested each iteration. If the step is accepted, lambda is multiplied by
+    /// `max(1/3, 1 - (2ρ - 1)³)` where `ρ` is the gain ratio of the step. Otherwise lambda is
+    /// multiplied by a factor `ν` which starts at `2` and doubles on every consecutive
+    /// rejection. This ignores `lambda_converge` and `lambda_diverge`.
+    Nielsen,
+}
+
+/// How the lambda of the first step is chosen.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum InitialLambda<N> {
+    /// Start from exactly this lambda. This is the default, with a lambda of `50.0`.
+    Fixed(N),
+    /// Start from `tau * max(diag(JJᵀ))` of the approximate Hessian at `init`, as recommended
+    /// in "Methods for Non-Linear Least Squares Problems" by Madsen, Nielsen, and Tingleff.
+    ///
+    /// This scales with the magnitude of the Hessian, so it is far less sensitive to the
+    /// scaling of the problem than a fixed lambda. A `tau` of around `1e-3` suits an `init`
+    /// which is believed to be close to the solution, and one of around `1.0` or more suits an
+    /// `init` which isn't. This is the scale of lambda that [`DampingMode::Identity`] needs.
+    /// With the other damping modes, the damping is already scaled by the diagonal, so a `tau`
+    /// relative to the largest diagonal entry makes the problem dependent on it again. The
+    /// result is clamped to `[min_lambda, max_lambda]`, and it is computed again from the
+    /// current guess on every restart.
+    Auto { tau: N },
+}
+
+impl<N> From<N> for InitialLambda<N> {
+    fn from(lambda: N) -> Self {
+        Self::Fixed(lambda)
+    }
+}
+
+/// What the sum-of-squares is converted to before it is compared against `threshold`.
+///
+/// Each is computed from the sum-of-squares of the residuals and the number of residuals.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ThresholdKind {
+    /// The mean of the squared residuals, which doesn't change as the number of residuals
+    /// changes. This is the default.
+    MeanSquared,
+    /// The sum of the squared residuals itself, which is an absolute target for the whole fit.
+    TotalSquared,
+    /// The root-mean-square of the residuals, which is in the same units as the residuals.
+    Rmse,
+}
+
+/// The damping matrix `D` which is scaled by lambda and added to the approximate Hessian.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DampingMode {
+    /// Marquardt's damping `JJᵀ + λ*diag(JJᵀ)`.
+    ///
+    /// This is invariant to the scale of the parameters, but directions which have no
+    /// curvature are left undamped.
+    Diagonal,
+    /// Levenberg's damping `JJᵀ + λI`.
+    ///
+    /// This damps every direction, even ones where the approximate Hessian has a zero on its
+    /// diagonal. Since lambda is no longer scaled by the Hessian, `initial_lambda` should be on
+    /// the order of the diagonal of `JJᵀ`, which usually means it must be much larger.
+    Identity,
+    /// Moré's automatic scaling from MINPACK, `JJᵀ + λ*diag(d²)`.
+    ///
+    /// Each `d` is the largest norm of the corresponding row of the Jacobian that has been
+    /// seen so far, which is the square root of the largest diagonal entry of `JJᵀ`. Like
+    /// [`DampingMode::Diagonal`], this is invariant to the scale of the parameters without any
+    /// input from the user. Since the scales never decrease, a parameter whose curvature
+    /// shrinks as the fit progresses stays damped. A parameter whose row has only ever been
+    /// zero has a scale of `1`.
+    AutoScaled,
+}
+
+/// How the damped linear system is solved for each step.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SolveMethod<N> {
+    /// Form the approximate Hessian `JJᵀ` and solve `JJᵀ + λD` with a Cholesky decomposition.
+    ///
+    /// If `JJᵀ + λD` isn't positive definite, it is inverted instead. This is fast, but forming
+    /// `JJᵀ` squares the condition number of the Jacobian, which loses precision on stiff or
+    /// ill-conditioned problems.
+    NormalEquations,
+    /// Solve the damped least squares problem with the QR decomposition of the Jacobian of every
+    /// residual stacked on top of `√(λD)`.
+    ///
+    /// The approximate Hessian is never formed. Instead, each residual is rotated into the
+    /// upper-triangular factor with Givens rotations, so no allocation is required regardless
+    /// of the number of samples. This avoids squaring the condition number, at the cost of
+    /// more work per residual. A [`LeastSquaresProblem`] which
+    /// [modifies its normal equations](LeastSquaresProblem::modifies_normal_equations), such as
+    /// a [`PriorProblem`], is solved with [`NormalEquations`](Self::NormalEquations) instead,
+    /// since its system can't be formed from the Jacobians.
+    Qr,
+    /// Form the approximate Hessian `JJᵀ` and solve `JJᵀ + λD` with its truncated pseudo-inverse.
+    ///
+    /// Only the singular values of `JJᵀ + λD` above `rank_tolerance` are inverted, so the step is
+    /// confined to the directions which the residuals actually determine. This still makes
+    /// progress when the model is over-parameterized and the Hessian is singular, which would
+    /// otherwise be rejected until lambda is large enough to hide the null space. The number of
+    /// singular values above the tolerance is reported as the [`MinimizationReport::rank`].
+    Svd { rank_tolerance: N },
+}
+
+impl<N> Config<N>
 where
     N: FromPrimitive,
 {
-    fn default() -> Self {
-        Self {
+    /// Creates the same config as [`Config::default`], but returns `None` rather than panicking
+    /// if one of the default values can't be represented by `N`.
+    ///
+    /// The values are converted from `f64`, except for `min_lambda` and `max_lambda`, which are
+    /// the limits of `f32`. Converting `f64::MIN_POSITIVE` and `f64::MAX` to `f32` would
+    /// saturate them to `0.0` and infinity, so the `f32` limits are the widest that every float
+    /// type can represent. `Config::<f64>::DEFAULT` uses the limits of `f64` itself instead.
+    pub fn try_default() -> Option<Self> {
+        Some(Self {
             max_iterations: 1000,
             consecutive_divergence_limit: 5,
-            initial_lambda: N::from_f32(50.0)
-                .expect("leverberg-marquardt vector and matrix type cant store 50.0"),
-            lambda_convege: N::from_f32(0.8)
-                .expect("leverberg-marquardt vector and matrix type cant store 0.8"),
-            lambda_diverge: N::from_f32(2.0)
-                .expect("leverberg-marquardt vector and matrix type cant store 2.0"),
-            threshold: N::from_f32(0.0)
-                .expect("leverberg-marquardt vector and matrix type cant store 0.0"),
+            initial_lambda: InitialLambda::Fixed(N::from_f64(50.0)?),
+            lambda_convege: N::from_f64(0.8)?,
+            lambda_diverge: N::from_f64(2.0)?,
+            threshold: N::from_f64(0.0)?,
+            gradient_threshold: N::from_f64(0.0)?,
+            ftol: N::from_f64(0.0)?,
+            damping_strategy: DampingStrategy::Multiplicative,
+            damping_mode: DampingMode::Diagonal,
+            min_lambda: N::from_f32(f32::MIN_POSITIVE)?,
+            max_lambda: N::from_f32(f32::MAX)?,
+            solve_method: SolveMethod::NormalEquations,
+            geodesic_acceleration: false,
+            acceleration_ratio: N::from_f64(0.75)?,
+            initial_trust_radius: N::from_f64(1.0)?,
+            method: Method::LevenbergMarquardt,
+            lambda_candidates: 2,
+            line_search: false,
+            line_search_min_alpha: N::from_f64(0.0625)?,
+            condition_warning_threshold: N::from_f64(1e12)?,
+            max_function_evaluations: usize::MAX,
+            initial_lambda_from: None,
+            threshold_kind: ThresholdKind::MeanSquared,
+            restart_limit: 0,
+            jacobian_refresh_interval: 1,
+            stall_iterations: 0,
+            relative_stall_tol: N::from_f64(0.0)?,
+            compensated_accumulation: false,
+            max_step_norm: None,
+        })
+    }
+
+    /// A starting point for when a rough fit is needed quickly, such as once per frame of a
+    /// video.
+    ///
+    /// This is [`Config::default`] with an `initial_lambda` of `1.0`, a `max_iterations` of
+    /// `20`, a `consecutive_divergence_limit` of `3`, a `threshold` and a `gradient_threshold`
+    /// of `1e-6`, and an `ftol` of `1e-4`. The first steps are close to Gauss-Newton, which
+    /// assumes that `init` is already close to the solution, and it stops as soon as further
+    /// iterations would barely change the fit.
+    ///
+    /// # Panics
+    ///
+    /// Panics if one of the values can't be represented by `N`, like [`Config::default`].
+    pub fn fast() -> Self {
+        Self::preset(|default| {
+            Some(Self {
+                initial_lambda: InitialLambda::Fixed(N::from_f64(1.0)?),
+                max_iterations: 20,
+                consecutive_divergence_limit: 3,
+                threshold: N::from_f64(1e-6)?,
+                gradient_threshold: N::from_f64(1e-6)?,
+                ftol: N::from_f64(1e-4)?,
+                ..default
+            })
+        })
+    }
+
+    /// A starting point for when the fit should be as precise as the data allows and taking
+    /// longer is fine.
+    ///
+    /// This is [`Config::default`] with a `max_iterations` of `10000`, a
+    /// `consecutive_divergence_limit` of `10`, a `gradient_threshold` of `1e-12`, an `ftol` of
+    /// `1e-15`, and [`SolveMethod::Qr`], which doesn't square the condition number of the
+    /// Jacobian. The `threshold` is left at `0.0`, so it only stops once the fit stops improving.
+    ///
+    /// # Panics
+    ///
+    /// Panics if one of the values can't be represented by `N`, like [`Config::default`].
+    pub fn accurate() -> Self {
+        Self::preset(|default| {
+            Some(Self {
+                max_iterations: 10000,
+                consecutive_divergence_limit: 10,
+                gradient_threshold: N::from_f64(1e-12)?,
+                ftol: N::from_f64(1e-15)?,
+                solve_method: SolveMethod::Qr,
+                ..default
+            })
+        })
+    }
+
+    /// A starting point for when `init` is far from the solution or the residuals are badly
+    /// behaved, so that the default often diverges or gives up early.
+    ///
+    /// This is [`Config::default`] with an `initial_lambda` of `1000.0`, a `lambda_converge` of
+    /// `0.9`, a `lambda_diverge` of `4.0`, a `consecutive_divergence_limit` of `20`, a
+    /// `restart_limit` of `2`, and `line_search` enabled. The first steps are short, lambda
+    /// only decreases slowly after a success and increases quickly after a failure, and a fit
+    /// that stalls gets more chances to recover.
+    ///
+    /// # Panics
+    ///
+    /// Panics if one of the values can't be represented by `N`, like [`Config::default`].
+    pub fn robust() -> Self {
+        Self::preset(|default| {
+            Some(Self {
+                initial_lambda: InitialLambda::Fixed(N::from_f64(1000.0)?),
+                lambda_convege: N::from_f64(0.9)?,
+                lambda_diverge: N::from_f64(4.0)?,
+                consecutive_divergence_limit: 20,
+                restart_limit: 2,
+                line_search: true,
+                ..default
+            })
+        })
+    }
+
+    /// Applies `preset` to the default config, panicking if any of the values can't be
+    /// represented by `N`.
+    fn preset(preset: impl FnOnce(Self) -> Option<Self>) -> Self {
+        Self::try_default()
+            .and_then(preset)
+            .expect("leverberg-marquardt vector and matrix type cant store the preset config")
+    }
+}
+
+impl<N> Config<N>
+where
+    N: RealField,
+{
+    /// Checks the documented invariants of the config, returning the first which is violated.
+    ///
+    /// `lambda_converge` must be below `1.0`, `lambda_diverge` must be above `1.0` and above
+    /// `lambda_converge^-(lambda_candidates - 1)`, `initial_lambda` must not be `0.0`, and
+    /// `lambda_candidates` must not be `0`. A config which violates
+    /// these can still be run, but it will likely converge poorly or not at all.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        // After a rejection, the smallest new candidate must be above the largest old one.
+        let untested_powers = i32::try_from(self.lambda_candidates.max(2) - 1).unwrap_or(i32::MAX);
+        if self.lambda_convege >= N::one() {
+            Err(ConfigError::LambdaConvergeNotBelowOne)
+        } else if self.lambda_diverge <= N::one() {
+            Err(ConfigError::LambdaDivergeNotAboveOne)
+        } else if self.lambda_diverge * self.lambda_convege.powi(untested_powers) <= N::one() {
+            Err(ConfigError::LambdaDivergeRetestsLambda)
+        } else if match self.initial_lambda {
+            InitialLambda::Fixed(lambda) => lambda == N::zero(),
+            InitialLambda::Auto { tau } => tau == N::zero(),
+        } {
+            Err(ConfigError::InitialLambdaZero)
+        } else if self.lambda_candidates == 0 {
+            Err(ConfigError::NoLambdaCandidates)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Whether `sum_of_squares` of `total` residuals is below `threshold`, as measured by
+    /// `threshold_kind`.
+    pub(crate) fn below_threshold(&self, sum_of_squares: N, total: N) -> bool {
+        match self.threshold_kind {
+            ThresholdKind::MeanSquared => sum_of_squares < self.threshold * total,
+            ThresholdKind::TotalSquared => sum_of_squares < self.threshold,
+            ThresholdKind::Rmse => (sum_of_squares / total).sqrt() < self.threshold,
+        }
+    }
+}
+
+impl<N> Default for Config<N>
+where
+    N: FromPrimitive,
+{
+    fn default() -> Self {
+        Self::try_default()
+            .expect("leverberg-marquardt vector and matrix type cant store the default config")
+    }
+}
+
+/// Implements `Config::DEFAULT` for a float type with the default values written as literals.
+macro_rules! literal_default {
+    ($float:ident) => {
+        impl Config<$float> {
+            #[doc = concat!(
+                "The same config as [`Config::default`] for `", stringify!($float), "`, written ",
+                "as literals so that it is a constant and no conversion can fail or round.\n\n",
+                "`min_lambda` and `max_lambda` are the limits of `", stringify!($float),
+                "` itself."
+            )]
+            pub const DEFAULT: Self = Self {
+                max_iterations: 1000,
+                consecutive_divergence_limit: 5,
+                initial_lambda: InitialLambda::Fixed(50.0),
+                lambda_convege: 0.8,
+                lambda_diverge: 2.0,
+                threshold: 0.0,
+                gradient_threshold: 0.0,
+                ftol: 0.0,
+                damping_strategy: DampingStrategy::Multiplicative,
+                damping_mode: DampingMode::Diagonal,
+                min_lambda: <$float>::MIN_POSITIVE,
+                max_lambda: <$float>::MAX,
+                solve_method: SolveMethod::NormalEquations,
+                geodesic_acceleration: false,
+                acceleration_ratio: 0.75,
+                initial_trust_radius: 1.0,
+                method: Method::LevenbergMarquardt,
+                lambda_candidates: 2,
+                line_search: false,
+                line_search_min_alpha: 0.0625,
+                condition_warning_threshold: 1e12,
+                max_function_evaluations: usize::MAX,
+                initial_lambda_from: None,
+                threshold_kind: ThresholdKind::MeanSquared,
+                restart_limit: 0,
+                jacobian_refresh_interval: 1,
+                stall_iterations: 0,
+                relative_stall_tol: 0.0,
+                compensated_accumulation: false,
+                max_step_norm: None,
+            };
+        }
+    };
+}
+
+literal_default!(f32);
+literal_default!(f64);
+
+/// An error which prevented optimization from running.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum OptimizeError {
+    /// The number of residuals could not be represented by the scalar type.
+    ConversionFailed,
+    /// The config violated one of its invariants.
+    InvalidConfig(ConfigError),
+    /// The sum-of-squares of the residuals of `init` was infinite or NaN, so no step could
+    /// ever be accepted from it.
+    NonFiniteStart,
+    /// The supplied initial Hessian wasn't square with a row for every parameter.
+    HessianDimensionMismatch,
+}
+
+impl fmt::Display for OptimizeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ConversionFailed => write!(
+                f,
+                "there were more residuals than could be represented by the scalar type"
+            ),
+            Self::InvalidConfig(error) => write!(f, "invalid config: {}", error),
+            Self::NonFiniteStart => write!(f, "the residuals of the initial guess were not finite"),
+            Self::HessianDimensionMismatch => write!(
+                f,
+                "the initial Hessian did not have a row and a column for every parameter"
+            ),
         }
     }
 }
 
+/// An invariant of [`Config`] which was violated.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ConfigError {
+    /// `lambda_converge` was not below `1.0`, so it would not decrease lambda.
+    LambdaConvergeNotBelowOne,
+    /// `lambda_diverge` was not above `1.0`, so it would not increase lambda.
+    LambdaDivergeNotAboveOne,
+    /// `lambda_diverge` was not above `lambda_converge^-(lambda_candidates - 1)`, so a
+    /// rejected step would retest a lambda which was already tested.
+    LambdaDivergeRetestsLambda,
+    /// `initial_lambda` or its `tau` was exactly `0.0`, which multiplication can never
+    /// increase.
+    InitialLambdaZero,
+    /// `lambda_candidates` was `0`, so no step could ever be taken.
+    NoLambdaCandidates,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::LambdaConvergeNotBelowOne => write!(f, "lambda_converge must be below 1"),
+            Self::LambdaDivergeNotAboveOne => write!(f, "lambda_diverge must be above 1"),
+            Self::LambdaDivergeRetestsLambda => {
+                write!(
+                    f,
+                    "lambda_diverge must be above lambda_converge^-(lambda_candidates - 1)"
+                )
+            }
+            Self::InitialLambdaZero => write!(f, "initial_lambda must not be zero"),
+            Self::NoLambdaCandidates => write!(f, "lambda_candidates must not be zero"),
+        }
+    }
+}
+
+/// The reason that [`optimize_report`] stopped iterating.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TerminationReason {
+    /// `max_iterations` were run without any other termination condition being met.
+    MaxIterations,
+    /// The sum-of-squares failed to improve `consecutive_divergence_limit` times in a row.
+    ConsecutiveDivergence,
+    /// A step was rejected while lambda was already clamped at `max_lambda`, so not even the
+    /// most heavily damped step could reduce the sum-of-squares.
+    ///
+    /// Unlike [`ConsecutiveDivergence`](Self::ConsecutiveDivergence), this is unlikely to be
+    /// fixed by tuning the damping. The problem is probably ill-posed, the Jacobians may be
+    /// wrong, or the initial guess may be too far from any minimum.
+    LambdaSaturated,
+    /// The average-of-squares, or whatever `threshold_kind` measures instead, fell below
+    /// `threshold`.
+    BelowThreshold,
+    /// The largest absolute component of the gradient fell below `gradient_threshold`.
+    GradientTooSmall,
+    /// The relative reduction of the sum-of-squares on an accepted step fell below `ftol`.
+    ReductionTooSmall,
+    /// The relative reduction of the sum-of-squares stayed below `relative_stall_tol` for
+    /// `stall_iterations` accepted steps in a row.
+    Stalled,
+    /// The callback passed to [`optimize_with_callback`] returned [`ControlFlow::Break`].
+    Aborted,
+    /// The damped Hessian could not be inverted `consecutive_divergence_limit` times in a row,
+    /// so no step could be computed.
+    Inverted,
+    /// The Jacobians could not be computed at the initial guess or at the new guess of
+    /// `consecutive_divergence_limit` steps in a row.
+    ///
+    /// This can only happen with a [`FallibleClosureProblem`] or another [`LeastSquaresProblem`]
+    /// that implements [`LeastSquaresProblem::try_jacobians`].
+    JacobianFailed,
+    /// The `stop` flag passed to [`LevenbergMarquardt::run_cancellable`] was set.
+    Cancelled,
+    /// The residuals were evaluated `max_function_evaluations` times.
+    BudgetExhausted,
+    /// The average-of-squares of `init` was already below `threshold`, as measured by
+    /// `threshold_kind`, or its gradient was exactly zero, so no iterations were run and `init`
+    /// was returned as is.
+    AlreadyOptimal,
+    /// The `deadline` passed to [`LevenbergMarquardt::run_with_deadline`] returned `true`.
+    Deadline,
+}
+
+/// The final model along with information about how the optimization went.
+///
+/// This is returned by [`optimize_report`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct MinimizationReport<M, N, P>
+where
+    N: Scalar,
+    P: Dim,
+    DefaultAllocator: Allocator<N, P>,
+    DefaultAllocator: Allocator<N, P, P>,
+{
+    /// The model with the lowest sum-of-squares that was seen during optimization.
+    pub model: M,
+    /// Why the optimization stopped.
+    pub termination: TerminationReason,
+    /// The number of iterations that were actually run.
+    pub iterations: usize,
+    /// The sum-of-squares of the residuals of `model`.
+    pub sum_of_squares: N,
+    /// The number of times the residuals were evaluated.
+    ///
+    /// This includes the evaluation at the initial guess and every candidate step, so it can
+    /// be up to `lambda_candidates` times the number of iterations plus one with
+    /// [`DampingStrategy::Multiplicative`], which tests that many lambdas on every iteration.
+    /// A `line_search` adds an evaluation for every fraction of a step that is tried.
+    pub residual_evaluations: usize,
+    /// The number of times the Jacobians were evaluated.
+    ///
+    /// The Jacobians are evaluated at the initial guess and at every candidate step which
+    /// reduced the sum-of-squares, unless `jacobian_refresh_interval` skips some of them.
+    pub jacobian_evaluations: usize,
+    /// The number of iterations whose step was accepted.
+    ///
+    /// Together with `rejected_steps` this adds up to `iterations`. Every accepted step with
+    /// [`Method::GaussNewton`] counts, even one which increased the sum-of-squares.
+    pub accepted_steps: usize,
+    /// The number of iterations whose step was rejected, which increases lambda, or shrinks the
+    /// trust radius with [`optimize_dogleg`].
+    ///
+    /// A high ratio of rejected to accepted steps suggests that `initial_lambda` is too small
+    /// or `lambda_diverge` too gentle, while hardly any rejections suggest that lambda could
+    /// be decreased more aggressively with `lambda_convege`.
+    pub rejected_steps: usize,
+    /// The numerical rank of the damped system that was solved for the last step.
+    ///
+    /// This is only detected by [`SolveMethod::Svd`], so it is `None` with any other method or
+    /// if no step was ever solved for.
+    pub rank: Option<usize>,
+    /// An estimate of the condition number of the undamped approximate Hessian `JJᵀ` at the
+    /// final guess, or `None` if it is singular or the Jacobians couldn't be computed.
+    ///
+    /// This is exact with [`SolveMethod::Svd`]. Otherwise it is estimated from the diagonal of
+    /// the triangular factor of `JJᵀ`, which is cheap but can underestimate the true value.
+    pub condition_estimate: Option<N>,
+    /// Whether `JJᵀ` at the final guess was singular or its `condition_estimate` was above
+    /// `condition_warning_threshold`, which means that the fit is nearly rank deficient.
+    pub rank_warning: bool,
+    /// The lambda that the next step would have been taken with, which can be passed back as
+    /// `initial_lambda_from` to continue from where this fit stopped.
+    ///
+    /// This is `None` for [`optimize_dogleg`], which has no lambda.
+    pub lambda: Option<N>,
+    /// The gradients `Jr` at the last accepted guess, which are minus half the derivative of
+    /// the sum-of-squares with respect to each parameter.
+    ///
+    /// These come from the linearization computed when that guess was accepted, or at `init` if
+    /// no step was, rather than being computed again. The last accepted guess is `model`,
+    /// except with [`Method::GaussNewton`], which accepts steps that increase the
+    /// sum-of-squares. The infinity-norm is what `gradient_threshold` is compared against, and
+    /// the largest components show which parameters are still pulling the fit. This is zero if
+    /// the Jacobians couldn't be computed at `init`.
+    pub final_gradient: VectorN<N, P>,
+    /// The undamped approximate Hessian `JJᵀ` at the last accepted guess.
+    ///
+    /// Like `final_gradient`, this is the linearization computed when that guess was accepted
+    /// rather than being computed again, and it is zero if the Jacobians couldn't be computed
+    /// at `init`. Lambda is never added to it. For a model with Gaussian noise of unit variance
+    /// this is the Fisher information matrix, whose inverse is the covariance of the
+    /// parameters, so it is the starting point for identifiability analysis and experiment
+    /// design. With a [`PriorProblem`] it includes the information of the prior.
+    pub final_hessian: MatrixMN<N, P, P>,
+    /// Whether the relative reduction of the sum-of-squares on the last accepted step was above
+    /// `ftol`, or `false` if no step was accepted.
+    ///
+    /// When optimization stops for [`TerminationReason::MaxIterations`] or
+    /// [`TerminationReason::BudgetExhausted`], this says whether the fit was still making
+    /// progress, so that rerunning it with more iterations from `model` would help, or whether
+    /// it had already plateaued. With the default `ftol` of `0.0`, any reduction counts.
+    pub still_improving: bool,
+    /// The number of times that `consecutive_divergence_limit` was hit or lambda saturated at
+    /// `max_lambda` and optimization was restarted rather than terminated, which is at most
+    /// `restart_limit`.
+    pub restarts: usize,
+    /// The number of iterations that took an undamped Gauss-Newton step, which is all of them
+    /// with [`Method::GaussNewton`] and those before each fallback with
+    /// [`Method::GaussNewtonWithFallback`].
+    ///
+    /// This is `0` for [`optimize_dogleg`], whose steps are a blend of both.
+    pub gauss_newton_iterations: usize,
+    /// The number of iterations that took a damped step, which together with
+    /// `gauss_newton_iterations` adds up to `iterations`, except with [`optimize_dogleg`],
+    /// where both are `0`.
+    pub damped_iterations: usize,
+    /// The reduced chi-squared `sum_of_squares / (num_residuals - P)` of `model`, or `None` if
+    /// there weren't more residuals than parameters or the Jacobians couldn't be computed at
+    /// `init`.
+    ///
+    /// When the residuals are weighted by the inverse standard deviation of each measurement,
+    /// this should be near `1.0` for a good fit. Much larger values mean that the model
+    /// doesn't explain the data or that the errors were underestimated, while much smaller
+    /// ones suggest overfitting or overestimated errors. `num_residuals` is `J` times the
+    /// number of samples. The same convention for the degrees of freedom is used to scale the
+    /// covariance by [`parameter_standard_errors`]. With a robust loss, a prior, or a custom
+    /// cost, `sum_of_squares` is that cost rather than a chi-squared, so this is only a rough
+    /// guide.
+    pub reduced_chi_squared: Option<N>,
+}
+
 /// Note that the differentials and state vector are represented with column vectors.
 /// This is atypical from the normal way it is done in mathematics. This is done because
 /// nalgebra is column-major. A nalgebra `Vector` is a column vector.
 ///
 /// Make sure that you create your Jacobian such that it is several fixed length
 /// column vectors rather than several row vectors as per normal. If you have already
-/// computed it with row vectors, then you can take the transpose.
+/// computed it with row vectors, such as from the formulas in a textbook, pass it to
+/// [`optimize_row_jacobians`] instead, which takes the transpose for you.
 ///
 /// It is recommended to make the number of columns dynamic unless you have a small fixed
 /// number of data-points.
 ///
+/// The fields of `config` are described below. Rather than tuning them from
+/// [`Config::default`], [`Config::fast`], [`Config::accurate`] and [`Config::robust`] are
+/// reasonable starting points for some common situations.
+///
 /// `max_iterations` limits the number of times the initial guess will be updated.
 ///
+/// `max_function_evaluations` limits the number of times `residuals` is evaluated, including at
+/// the initial guess, and defaults to no limit. This is a hard ceiling on the cost when
+/// evaluating the residuals dominates, since an iteration can evaluate them several times. Once
+/// the budget is spent, optimization stops with [`TerminationReason::BudgetExhausted`], even in
+/// the middle of an iteration before the next lambda is tested. The count is reported as
+/// [`MinimizationReport::residual_evaluations`].
+///
 /// `consecutive_divergence_limit` limits the number of times that lambda can diverge
 /// consecutively from Gauss-Newton due to a failed improvement. Once the
 /// solution is as good as possible, it will begin regressing to gradient descent. This
 /// limit prevents it from wasting the remaining cycles of the algorithm.
 ///
+/// `restart_limit` is the number of times that hitting `consecutive_divergence_limit` or saturating
+/// lambda at `max_lambda` resets lambda to `initial_lambda` rather than terminating, and defaults
+/// to `0`. Lambda can end up so high that the steps are too short to make progress, and a reset can
+/// let the fit recover. With [`optimize_dogleg`], the trust radius is reset to
+/// `initial_trust_radius` instead. The number of restarts is reported as
+/// [`MinimizationReport::restarts`].
+///
+/// `jacobian_refresh_interval` is how often the Jacobians are evaluated, and defaults to `1`, which
+/// evaluates them at every accepted step, as does `0`. Above that, the Jacobians are only evaluated
+/// at `init` and then on every `jacobian_refresh_interval`th accepted step, and in between the
+/// stacked Jacobian is updated with Broyden's rank-one update from the change in the residuals that
+/// was observed along each step. The updated Jacobian is only an approximation, so this usually
+/// takes more iterations to converge, but each of them is much cheaper when evaluating the
+/// Jacobians is the bottleneck, which can make it a big win overall. Keeping the stacked Jacobian
+/// requires the `alloc` feature, and without it the Jacobians are evaluated at every accepted step
+/// regardless. The Jacobians are always evaluated rather than asking a [`LeastSquaresProblem`] for
+/// its normal equations, unless the problem
+/// [modifies them](LeastSquaresProblem::modifies_normal_equations), in which case they are asked
+/// for at every accepted step instead.
+///
+/// `compensated_accumulation` sums the contributions of every sample to the approximate Hessian
+/// and the gradients with Kahan's compensated summation, and defaults to `false`. Summing
+/// naively loses precision once there are tens of thousands of samples, especially with `f32`,
+/// since each small contribution is rounded against a large total. Compensation keeps the error
+/// of the sums from growing with the number of samples at the cost of a few more additions per
+/// entry. Like `jacobian_refresh_interval`, this always evaluates the Jacobians rather than
+/// asking a [`LeastSquaresProblem`] for its normal equations, unless the problem modifies them,
+/// in which case they are summed naively. It has no effect with [`SolveMethod::Qr`], which never
+/// forms the sums.
+///
 /// `initial_lambda` defines the initial lambda value. As lambda grows higher,
 /// Levenberg-Marquardt approaches gradient descent, which is better at converging to a distant
 /// minima. As lambda grows lower, Levenberg-Marquardt approaches Gauss-Newton, which allows faster
 /// convergence closer to the minima. A lambda of `0.0` would imply that it is purely based on
 /// Gauss-Newton approximation. Please do not set lambda to exactly `0.0` or the `lambda_scale` will be unable to
-/// increase lambda since it does so through multiplication.
+/// increase lambda since it does so through multiplication. It defaults to
+/// [`InitialLambda::Fixed`] with a lambda of `50.0`, which is wrong for many problems, so
+/// [`InitialLambda::Auto`] can scale it with the approximate Hessian at `init` instead.
+///
+/// `initial_lambda_from` overrides `initial_lambda` when it is set, after clamping it to
+/// `[min_lambda, max_lambda]`. This is meant for the [`MinimizationReport::lambda`] of a
+/// previous call, which warm-starts a series of fits to slowly changing data, such as tracking
+/// across video frames, near Gauss-Newton rather than resetting lambda every time.
 ///
 /// `lambda_converge` must be set to a value below `1.0`. On each iteration of Levenberg-Marquardt,
 /// the lambda is used as-is and multiplied by `lambda_converge`. If the original lambda or the
 /// new lambda is better, that lambda becomes the new lambda. If neither are better than the
 /// previous sum-of-squares, then lambda is multiplied by `lambda_diverge`.
 ///
+/// `lambda_candidates` is the number of lambdas that are tested on each iteration, which
+/// defaults to `2`. Each candidate is the previous one multiplied by `lambda_converge`, so
+/// `lambda * lambda_converge^k` is tested for each `k` below `lambda_candidates`, and the
+/// candidate with the lowest sum-of-squares becomes the new lambda if its step is accepted.
+/// Testing more candidates can find a good step in one iteration rather than several when
+/// lambda is far too large, at the cost of an evaluation of `residuals` per candidate. This is
+/// ignored by [`DampingStrategy::Nielsen`].
+///
 /// `lambda_diverge` must be set to a value above `1.0` and highly recommended to set it **above**
-/// `lambda_converge^-1` (it will re-test an already-used lambda otherwise). On each iteration,
-/// if the sum-of-squares regresses, then lambda is multiplied by `lambda_diverge` to move closer
-/// to gradient descent in hopes that it will cause it to converge.
+/// `lambda_converge^-(lambda_candidates - 1)` (it will re-test an already-used lambda otherwise).
+/// On each iteration, if the sum-of-squares regresses, then lambda is multiplied by
+/// `lambda_diverge` to move closer to gradient descent in hopes that it will cause it to converge.
 ///
 /// `threshold` is the point at which the average-of-squares is low enough that the algorithm can
-/// terminate. This exists so that the algorithm can short-circuit and exit early if the
+/// terminate. `threshold_kind` chooses whether it is compared against the mean, the total, or
+/// the root-mean-square of the squared residuals, and defaults to the mean. See
+/// [`ThresholdKind`]. This exists so that the algorithm can short-circuit and exit early if the
 /// solution was easy to find. Set this to `0.0` if you want it to continue for all `max_iterations`.
 /// You might do that if you always have a fixed amount of time per optimization, such as when
-/// processing live video frames.
+/// processing live video frames, in which case [`LevenbergMarquardt::run_with_deadline`] can
+/// stop once that time is up.
+///
+/// `gradient_threshold` is the point at which the gradient is small enough that the algorithm
+/// can terminate. It is compared against the infinity-norm (the max absolute component) of the
+/// gradients computed from the Jacobians and residuals each iteration. The gradient vanishes
+/// at a minima, so this is a first-order optimality test which can stop much earlier than
+/// `threshold` on problems that can't be fit exactly. Set this to `0.0` to disable it.
+///
+/// `ftol` is the point at which the relative reduction of the sum-of-squares
+/// (`(previous - new) / previous`) on an accepted step is small enough that the algorithm can
+/// terminate. Unlike `threshold`, this doesn't depend on the scale of the problem. It is only
+/// checked when a step improves the sum-of-squares, not when lambda is increased due to
+/// divergence. Either this or `threshold` can cause termination. Set this to `0.0` to disable it.
+///
+/// `stall_iterations` and `relative_stall_tol` stop a fit which keeps creeping down by tiny
+/// amounts without ever converging or diverging. Once the relative reduction of the
+/// sum-of-squares has been below `relative_stall_tol` on `stall_iterations` accepted steps in a
+/// row, optimization stops with [`TerminationReason::Stalled`]. Rejected steps neither count
+/// towards this nor reset it. Unlike `ftol`, a single small reduction isn't enough, so
+/// `relative_stall_tol` can be much larger than a useful `ftol` without stopping at the first
+/// slow step of a fit which then speeds up again. `stall_iterations` defaults to `0`, which
+/// disables it.
+///
+/// A step is only accepted if it reduces the sum-of-squares and its gain ratio `ρ` is positive.
+/// The gain ratio is the actual reduction of the sum-of-squares divided by the reduction
+/// predicted by the linearization, `δᵀ(λ*diag(JJᵀ)*δ + g)`, where `δ` is the step and `g` is the
+/// gradient. A step whose reduction wasn't predicted by the linearization only helped by luck.
+///
+/// `damping_strategy` chooses how lambda is updated after each step. See [`DampingStrategy`].
+///
+/// `damping_mode` chooses the damping matrix `D` that is scaled by lambda. Everywhere that
+/// `diag(JJᵀ)` is mentioned, `D` is used instead. See [`DampingMode`].
+///
+/// `min_lambda` and `max_lambda` bound lambda after every update. Without them, repeated
+/// divergence could increase lambda until it overflows to infinity, after which every step is
+/// zero and the remaining iterations are wasted. Likewise, lambda could decrease until it
+/// underflows to exactly `0.0`, after which it could never be increased again. These default
+/// to the smallest positive normal `f32` and the largest `f32`. If a step is rejected while
+/// lambda is already at `max_lambda`, optimization stops with
+/// [`TerminationReason::LambdaSaturated`], or restarts if `restart_limit` allows, since
+/// lambda can't grow to change the next step. Debug builds assert that lambda stays finite
+/// and positive within these bounds, and that every accepted step decreases the
+/// sum-of-squares.
+///
+/// `solve_method` chooses how the damped linear system is solved. See [`SolveMethod`].
+///
+/// `geodesic_acceleration` enables Transtrum's geodesic acceleration from "Improvements to the
+/// Levenberg-Marquardt algorithm for nonlinear least-squares minimization" by Transtrum and
+/// Sethna. After solving for each step `δ`, the second directional derivative of the
+/// residuals along `δ` is estimated with one extra evaluation of `residuals`, and the damped
+/// system is solved again for a correction `a` to follow the curvature of the residuals. The
+/// step `δ + a/2` is taken instead if `2|a|/|δ|` is at most `acceleration_ratio`, which
+/// defaults to `0.75`, otherwise the correction is too large to trust and `δ` is taken as is.
+/// This costs an extra evaluation of `residuals` and `jacobians` per tested lambda. Transtrum
+/// and Sethna report that it typically halves the number of iterations on stiff problems with
+/// narrow curved valleys, such as fitting sums of exponentials, although the benefit depends
+/// heavily on the problem and on `acceleration_ratio`. The predicted reduction that the gain
+/// ratio is computed from is still that of `δ`.
+///
+/// `line_search` enables a backtracking line search along each step `δ`. If the full step
+/// doesn't reduce the sum-of-squares, the steps `δ/2`, `δ/4` and so on are tried until one
+/// does or until the fraction of the step would fall below `line_search_min_alpha`, which
+/// defaults to `1/16`. The last step that was tried is then accepted or rejected as usual, so
+/// one solve of the damped system can salvage an iteration that would otherwise be rejected,
+/// at the cost of an evaluation of `residuals` per fraction that is tried. The predicted
+/// reduction of the step `αδ` is `α(2 - α)δᵀg + α²δᵀλDδ`.
+///
+/// `max_step_norm` caps the Euclidean norm of every step regardless of lambda, and defaults to
+/// `None`, which doesn't cap it. A step `δ` which is longer than the cap is rescaled to
+/// `max_step_norm * δ/|δ|` before `apply_delta` sees it, after any geodesic acceleration and
+/// before any line search. The residuals are evaluated at the clamped step, and its gain ratio
+/// uses the predicted reduction of the clamped step, like a fraction of a line search, so the
+/// acceptance test judges the step that was actually taken. This is a crude trust region for
+/// an `apply_delta` which breaks down on long steps, such as the chart of a manifold. Lambda is
+/// still updated as usual, so while the cap is active, accepting steps can shrink lambda
+/// towards Gauss-Newton without the steps getting any longer, and rejecting them only shortens
+/// the step once lambda has grown enough for the solved step to fall below the cap. Until then,
+/// a higher lambda only turns the step towards gradient descent. [`optimize_dogleg`] ignores
+/// this, since its trust radius already bounds the step.
+///
+/// `method` chooses the algorithm that computes each step. See [`Method`]. Everything above
+/// about lambda and the gain ratio only applies to [`Method::LevenbergMarquardt`], which is
+/// the default, and to the damped steps of [`Method::GaussNewtonWithFallback`].
+///
+/// `initial_trust_radius` is only used by [`optimize_dogleg`], which controls the step size
+/// with a trust region rather than with lambda.
+///
+/// `condition_warning_threshold` is the estimated condition number of the undamped `JJᵀ` at
+/// the final guess above which [`MinimizationReport::rank_warning`] is set, and defaults to
+/// `1e12`. A warning usually means that some combination of the parameters is barely
+/// determined by the residuals, such as a gauge freedom, and needs to be regularized or
+/// removed from the model.
 ///
 /// `init` is the initial parameter guess. Make sure to set `init` close to the actual solution.
 /// It is recommended to use a sample consensus algorithm to get a close initial approximation.
 ///
-/// `normalize` allows the parameter guess to be normalized on each iteration. It can be pushed into
+/// A `normalize` closure allows the parameter guess to be normalized on each iteration. It can be pushed into
 /// a slightly incorrect state on each iteration and this can be used to correct it. This might be something
 /// like an angle which exceeds 2 * pi. It might technically be correct, but you want to wrap it back around.
 /// This is also useful when a normal vector or unit quaternion is involved since those need to be kept
-/// normalized throughout the optimization procedure.
+/// normalized throughout the optimization procedure. This function doesn't take one, so pass
+/// `normalize` with a [`NormalizedClosureProblem`] to [`optimize_problem`]. It is applied to each
+/// new guess after `apply_delta` and before its residuals are computed, so the residuals and the
+/// guess that is kept between iterations are always normalized. It can also fail, in which case
+/// the step that it failed for is rejected and lambda is increased.
 ///
 /// `residuals` must return the difference between the expected value and the output of the
 /// function being optimized. This is returned as a matrix where the number of residuals (rows)
@@ -106,11 +958,20 @@ where
 /// You may need to caputure your observances in the closure to compute the Jacobian, but
 /// they are not arguments since they are constants to Levenberg-Marquardt.
 ///
+/// With the `log` feature, every iteration emits a `trace` record with its index, the lambda
+/// and sum-of-squares of the step that was tested, its gain ratio, and whether it was accepted,
+/// and a `debug` record is emitted when optimization terminates. The `tracing` feature emits
+/// the same information as `tracing` events with structured fields inside a `debug` span named
+/// `optimize` for each call. Neither feature requires `std`.
+///
 /// `M` is the model that is being optimized.
 ///
 /// `N` is the type parameter of the data type that is stored in the matrix (`f32`).
 ///
-/// `P` is the number of parameter variables being optimized.
+/// `P` is the number of parameter variables being optimized. This can be `Dynamic` when the
+/// number of parameters is only known at runtime, in which case it is taken from the number
+/// of rows of the Jacobians, so every Jacobian must have the same number of rows as the model
+/// has parameters.
 ///
 /// `S` is the number of samples used in optimization.
 ///
@@ -123,6 +984,11 @@ where
 /// `JS` is the nalgebra storage used for the Jacobian matrix.
 ///
 /// `IJ` is the iterator over the Jacobian matrices of each sample.
+///
+/// # Panics
+///
+/// Panics if the number of residuals can't be represented by `N`, which [`checked_optimize`]
+/// returns as an error instead.
 pub fn optimize<M, N, P, S, J, PS, RS, JS, IJ>(
     config: Config<N>,
     init: M,
@@ -132,7 +998,75 @@ pub fn optimize<M, N, P, S, J, PS, RS, JS, IJ>(
 ) -> M
 where
     N: RealField + FromPrimitive,
-    P: DimMin<P> + DimName,
+    P: DimMin<P>,
+    S: Dim,
+    J: DimName,
+    PS: ContiguousStorageMut<N, P> + Clone,
+    RS: Storage<N, J, S>,
+    JS: Storage<N, P, J>,
+    IJ: Iterator<Item = Matrix<N, P, J, JS>>,
+    DefaultAllocator: Allocator<N, J, P>,
+    DefaultAllocator: Allocator<N, P, P>,
+    DefaultAllocator: Allocator<N, P, Buffer = PS>,
+    ShapeConstraint: DimEq<DimMinimum<P, P>, P>,
+{
+    optimize_report(config, init, apply_delta, residuals, jacobians).model
+}
+
+/// Identical to [`optimize`], but returns an error rather than panicking if optimization
+/// can't be run.
+///
+/// The config is checked with [`Config::validate`] before anything is evaluated. If the
+/// sum-of-squares of `init` isn't finite, [`OptimizeError::NonFiniteStart`] is returned
+/// immediately rather than spending every iteration on steps that can't be accepted.
+pub fn checked_optimize<M, N, P, S, J, PS, RS, JS, IJ>(
+    config: Config<N>,
+    init: M,
+    apply_delta: impl Fn(&M, Vector<N, P, PS>) -> M,
+    residuals: impl Fn(&M) -> Matrix<N, J, S, RS>,
+    jacobians: impl Fn(&M) -> IJ,
+) -> Result<M, OptimizeError>
+where
+    N: RealField + FromPrimitive,
+    P: DimMin<P>,
+    S: Dim,
+    J: DimName,
+    PS: ContiguousStorageMut<N, P> + Clone,
+    RS: Storage<N, J, S>,
+    JS: Storage<N, P, J>,
+    IJ: Iterator<Item = Matrix<N, P, J, JS>>,
+    DefaultAllocator: Allocator<N, J, P>,
+    DefaultAllocator: Allocator<N, P, P>,
+    DefaultAllocator: Allocator<N, P, Buffer = PS>,
+    ShapeConstraint: DimEq<DimMinimum<P, P>, P>,
+{
+    checked_optimize_problem(
+        config,
+        init,
+        &ClosureProblem::new(apply_delta, residuals, jacobians),
+    )
+    .map(|report| report.model)
+}
+
+/// Identical to [`optimize`], but also returns the number of iterations that were run and the
+/// final sum-of-squares as `(model, iterations, sum_of_squares)`.
+///
+/// The sum-of-squares is the one tracked during optimization, so there is no need to
+/// evaluate `residuals` again on the returned model.
+///
+/// # Panics
+///
+/// Panics if the number of residuals can't be represented by `N`.
+pub fn optimize_with_stats<M, N, P, S, J, PS, RS, JS, IJ>(
+    config: Config<N>,
+    init: M,
+    apply_delta: impl Fn(&M, Vector<N, P, PS>) -> M,
+    residuals: impl Fn(&M) -> Matrix<N, J, S, RS>,
+    jacobians: impl Fn(&M) -> IJ,
+) -> (M, usize, N)
+where
+    N: RealField + FromPrimitive,
+    P: DimMin<P>,
     S: Dim,
     J: DimName,
     PS: ContiguousStorageMut<N, P> + Clone,
@@ -144,91 +1078,686 @@ where
     DefaultAllocator: Allocator<N, P, Buffer = PS>,
     ShapeConstraint: DimEq<DimMinimum<P, P>, P>,
 {
-    let mut lambda = config.initial_lambda;
-    let mut guess = init;
-    let mut res = residuals(&guess);
-    let mut sum_of_squares = res.norm_squared();
-    let mut consecutive_divergences = 0;
-    let total = N::from_usize(res.len())
+    let report = optimize_report(config, init, apply_delta, residuals, jacobians);
+    (report.model, report.iterations, report.sum_of_squares)
+}
+
+/// Estimates the covariance of the parameters at the optimized model, which is returned along
+/// with it as `(model, covariance)`.
+///
+/// The covariance is `σ²(JJᵀ)⁻¹`, the inverse of the undamped approximate Hessian at the final
+/// model scaled by the variance of the measurements. That variance is estimated from the fit by
+/// the reduced chi-square `σ² = sum_of_squares / (num_residuals - P)`. The standard error of
+/// each parameter is the square root of the corresponding diagonal element, which
+/// [`parameter_standard_errors`] computes from the unscaled `(JJᵀ)⁻¹`.
+///
+/// The covariance is `None` if there are no more residuals than parameters, so that the
+/// variance can't be estimated, or if the approximate Hessian at the final model is singular,
+/// which happens when the parameters aren't all constrained by the residuals.
+///
+/// # Panics
+///
+/// Panics if the number of residuals can't be represented by `N`.
+pub fn optimize_with_covariance<M, N, P, S, J, PS, RS, JS, IJ>(
+    config: Config<N>,
+    init: M,
+    apply_delta: impl Fn(&M, Vector<N, P, PS>) -> M,
+    residuals: impl Fn(&M) -> Matrix<N, J, S, RS>,
+    jacobians: impl Fn(&M) -> IJ,
+) -> (M, Option<MatrixMN<N, P, P>>)
+where
+    N: RealField + FromPrimitive,
+    P: DimMin<P>,
+    S: Dim,
+    J: DimName,
+    PS: ContiguousStorageMut<N, P> + Clone,
+    RS: Storage<N, J, S>,
+    JS: Storage<N, P, J>,
+    IJ: Iterator<Item = Matrix<N, P, J, JS>>,
+    DefaultAllocator: Allocator<N, J, P>,
+    DefaultAllocator: Allocator<N, P, P>,
+    DefaultAllocator: Allocator<N, P, Buffer = PS>,
+    ShapeConstraint: DimEq<DimMinimum<P, P>, P>,
+{
+    let problem = ClosureProblem::new(apply_delta, residuals, jacobians);
+    let mut lm = LevenbergMarquardt::new(config, init, &problem)
         .expect("there were more items in the vector than could be represented by the type");
+    lm.run_with(
+        &problem,
+        &mut Workspace::new(),
+        |_| None,
+        |_, _, _| ControlFlow::Continue(()),
+    );
+    // The Hessian of the last linearization is reused rather than evaluating the Jacobians
+    // again.
+    let covariance = lm
+        .best_hessian()
+        .and_then(|hessian| covariance(hessian, lm.best_sum_of_squares(), lm.residuals().len()));
+    (lm.into_best().0, covariance)
+}
 
-    for _ in 0..config.max_iterations {
-        // Next step lambda.
-        let smaller_lambda = lambda * config.lambda_convege;
-
-        // Iterate through all the Jacobians to extract the approximate Hessian and the gradients.
-        let (hessian, gradients) = jacobians(&guess).zip(res.column_iter()).fold(
-            (nalgebra::zero(), nalgebra::zero()),
-            |(hessian, gradients): (MatrixMN<N, P, P>, Vector<N, P, PS>), (jacobian, res)| {
-                (
-                    hessian + &jacobian * jacobian.transpose(),
-                    gradients + &jacobian * res,
+/// Scales the inverse of the undamped approximate Hessian by the reduced chi-square of
+/// `num_residuals` residuals, returning `None` if either can't be computed.
+fn covariance<N, P>(
+    hessian: MatrixMN<N, P, P>,
+    sum_of_squares: N,
+    num_residuals: usize,
+) -> Option<MatrixMN<N, P, P>>
+where
+    N: RealField + FromPrimitive,
+    P: DimMin<P>,
+    DefaultAllocator: Allocator<N, P, P>,
+{
+    let variance = statistics::reduced_chi_square(sum_of_squares, num_residuals, hessian.nrows())?;
+    Some(hessian.try_inverse()? * variance)
+}
+
+/// Identical to [`optimize`], but returns a [`MinimizationReport`] which also says why the
+/// optimization terminated, how many iterations it took, and the final sum-of-squares.
+///
+/// This is useful to tell a fit that converged below `threshold` apart from one that gave up
+/// after `max_iterations` or `consecutive_divergence_limit` was hit.
+///
+/// # Panics
+///
+/// Panics if the number of residuals can't be represented by `N`.
+pub fn optimize_report<M, N, P, S, J, PS, RS, JS, IJ>(
+    config: Config<N>,
+    init: M,
+    apply_delta: impl Fn(&M, Vector<N, P, PS>) -> M,
+    residuals: impl Fn(&M) -> Matrix<N, J, S, RS>,
+    jacobians: impl Fn(&M) -> IJ,
+) -> MinimizationReport<M, N, P>
+where
+    N: RealField + FromPrimitive,
+    P: DimMin<P>,
+    S: Dim,
+    J: DimName,
+    PS: ContiguousStorageMut<N, P> + Clone,
+    RS: Storage<N, J, S>,
+    JS: Storage<N, P, J>,
+    IJ: Iterator<Item = Matrix<N, P, J, JS>>,
+    DefaultAllocator: Allocator<N, J, P>,
+    DefaultAllocator: Allocator<N, P, P>,
+    DefaultAllocator: Allocator<N, P, Buffer = PS>,
+    ShapeConstraint: DimEq<DimMinimum<P, P>, P>,
+{
+    optimize_problem(
+        config,
+        init,
+        &ClosureProblem::new(apply_delta, residuals, jacobians),
+    )
+}
+
+/// Identical to [`optimize_report`], but `jacobians` produces the Jacobians in the conventional
+/// layout, with a row for every residual of a sample and a column for every parameter.
+///
+/// Every other entry point, including [`optimize`] and [`LeastSquaresProblem::jacobians`],
+/// expects the transpose of that, with a row for every parameter and a column for every
+/// residual, since nalgebra is column-major. Forgetting to transpose a Jacobian copied from a
+/// textbook doesn't always fail to compile, such as when `P` and `J` are equal, and silently
+/// produces a wrong approximate Hessian that converges to garbage. This takes the `J`x`P`
+/// Jacobians and transposes each of them before it is used, which costs a copy per sample.
+///
+/// ```
+/// use levenberg_marquardt::{optimize_row_jacobians, Config};
+/// use nalgebra::{RowVector2, RowVector3, Vector2};
+///
+/// // Fits `y = ax + b` to points on `y = 3x + 1`.
+/// let xs = [0.0, 1.0, 2.0];
+/// let report = optimize_row_jacobians(
+///     Config::default(),
+///     Vector2::new(0.0, 0.0),
+///     |model, delta: Vector2<f64>| model + delta,
+///     |model| {
+///         RowVector3::from_fn(|_, i| 3.0 * xs[i] + 1.0 - (model.x * xs[i] + model.y))
+///     },
+///     |_| xs.iter().map(|&x| RowVector2::new(x, 1.0)),
+/// );
+/// assert!((report.model - Vector2::new(3.0, 1.0)).norm() < 1e-6);
+/// ```
+///
+/// # Panics
+///
+/// Panics if the number of residuals can't be represented by `N`.
+pub fn optimize_row_jacobians<M, N, P, S, J, PS, RS, JS, IJ>(
+    config: Config<N>,
+    init: M,
+    apply_delta: impl Fn(&M, Vector<N, P, PS>) -> M,
+    residuals: impl Fn(&M) -> Matrix<N, J, S, RS>,
+    jacobians: impl Fn(&M) -> IJ,
+) -> MinimizationReport<M, N, P>
+where
+    N: RealField + FromPrimitive,
+    P: DimMin<P>,
+    S: Dim,
+    J: DimName,
+    PS: ContiguousStorageMut<N, P> + Clone,
+    RS: Storage<N, J, S>,
+    JS: Storage<N, J, P>,
+    IJ: Iterator<Item = Matrix<N, J, P, JS>>,
+    DefaultAllocator: Allocator<N, J, P>,
+    DefaultAllocator: Allocator<N, P, J>,
+    DefaultAllocator: Allocator<N, P, P>,
+    DefaultAllocator: Allocator<N, P, Buffer = PS>,
+    ShapeConstraint: DimEq<DimMinimum<P, P>, P>,
+{
+    optimize_report(config, init, apply_delta, residuals, |model| {
+        jacobians(model).map(|jacobian| jacobian.transpose())
+    })
+}
+
+/// Reports the progress of every iteration to `on_iteration`, which can also stop optimization
+/// early. Otherwise this is the same as [`optimize_report`].
+///
+/// `on_iteration` is given the index of the iteration, the best model so far, and its
+/// sum-of-squares. It is called whether the step of that iteration was accepted or rejected,
+/// so it can be used for logging, drawing the current fit, or advancing a progress bar.
+/// If it returns [`ControlFlow::Break`], optimization stops immediately with
+/// [`TerminationReason::Aborted`].
+///
+/// Since `on_iteration` runs between iterations, it can also hand the index of the next
+/// iteration to `apply_delta` through a [`Cell`](core::cell::Cell). This allows coarse-to-fine
+/// schemes, such as a damped retraction in early iterations and an exact one later. Every
+/// candidate step of an iteration sees the same index, including the retried fractions of a
+/// `line_search`.
+///
+/// ```
+/// use core::{cell::Cell, ops::ControlFlow};
+/// use levenberg_marquardt::{optimize_with_callback, Config};
+/// use nalgebra::Vector1;
+///
+/// let iteration = Cell::new(0);
+/// let report = optimize_with_callback(
+///     Config::default(),
+///     Vector1::new(0.0),
+///     |model, delta: Vector1<f64>| {
+///         // Only take half of each step in the first few iterations.
+///         if iteration.get() < 3 {
+///             model + delta * 0.5
+///         } else {
+///             model + delta
+///         }
+///     },
+///     |model| Vector1::new(1.0 - model.x),
+///     |_| core::iter::once(Vector1::new(1.0)),
+///     |index, _, _| {
+///         iteration.set(index + 1);
+///         ControlFlow::Continue(())
+///     },
+/// );
+/// assert!((report.model.x - 1.0).abs() < 1e-6);
+/// ```
+///
+/// # Panics
+///
+/// Panics if the number of residuals can't be represented by `N`.
+pub fn optimize_with_callback<M, N, P, S, J, PS, RS, JS, IJ>(
+    config: Config<N>,
+    init: M,
+    apply_delta: impl Fn(&M, Vector<N, P, PS>) -> M,
+    residuals: impl Fn(&M) -> Matrix<N, J, S, RS>,
+    jacobians: impl Fn(&M) -> IJ,
+    on_iteration: impl FnMut(usize, &M, N) -> ControlFlow<()>,
+) -> MinimizationReport<M, N, P>
+where
+    N: RealField + FromPrimitive,
+    P: DimMin<P>,
+    S: Dim,
+    J: DimName,
+    PS: ContiguousStorageMut<N, P> + Clone,
+    RS: Storage<N, J, S>,
+    JS: Storage<N, P, J>,
+    IJ: Iterator<Item = Matrix<N, P, J, JS>>,
+    DefaultAllocator: Allocator<N, J, P>,
+    DefaultAllocator: Allocator<N, P, P>,
+    DefaultAllocator: Allocator<N, P, Buffer = PS>,
+    ShapeConstraint: DimEq<DimMinimum<P, P>, P>,
+{
+    minimize(
+        config,
+        init,
+        &ClosureProblem::new(apply_delta, residuals, jacobians),
+        on_iteration,
+    )
+    .expect("there were more items in the vector than could be represented by the type")
+}
+
+/// Identical to [`optimize_report`], but records the sum-of-squares and lambda of the last `K`
+/// iterations into `recorder`.
+///
+/// This is for insight into how a fit converged on targets without an allocator, since the
+/// [`HistoryRecorder`] is a fixed-size ring buffer. Only the most recent `K` iterations are
+/// retained, as described in
+/// [`LevenbergMarquardt::run_recorded`](crate::LevenbergMarquardt::run_recorded).
+///
+/// ```
+/// use levenberg_marquardt::{optimize_recorded, Config, HistoryRecorder};
+/// use nalgebra::Vector1;
+///
+/// let mut recorder = HistoryRecorder::<f64, 4>::new();
+/// let report = optimize_recorded(
+///     Config::default(),
+///     Vector1::new(0.0),
+///     |model, delta: Vector1<f64>| model + delta,
+///     |model| Vector1::new(1.0 - model.x),
+///     |_| core::iter::once(Vector1::new(1.0)),
+///     &mut recorder,
+/// );
+/// assert_eq!(recorder.recorded(), report.iterations);
+/// assert_eq!(
+///     recorder.last().map(|(_, sum_of_squares, _)| sum_of_squares),
+///     Some(report.sum_of_squares)
+/// );
+/// ```
+///
+/// # Panics
+///
+/// Panics if the number of residuals can't be represented by `N`.
+pub fn optimize_recorded<M, N, P, S, J, PS, RS, JS, IJ, const K: usize>(
+    config: Config<N>,
+    init: M,
+    apply_delta: impl Fn(&M, Vector<N, P, PS>) -> M,
+    residuals: impl Fn(&M) -> Matrix<N, J, S, RS>,
+    jacobians: impl Fn(&M) -> IJ,
+    recorder: &mut HistoryRecorder<N, K>,
+) -> MinimizationReport<M, N, P>
+where
+    N: RealField + FromPrimitive,
+    P: DimMin<P>,
+    S: Dim,
+    J: DimName,
+    PS: ContiguousStorageMut<N, P> + Clone,
+    RS: Storage<N, J, S>,
+    JS: Storage<N, P, J>,
+    IJ: Iterator<Item = Matrix<N, P, J, JS>>,
+    DefaultAllocator: Allocator<N, J, P>,
+    DefaultAllocator: Allocator<N, P, P>,
+    DefaultAllocator: Allocator<N, P, Buffer = PS>,
+    ShapeConstraint: DimEq<DimMinimum<P, P>, P>,
+{
+    let problem = ClosureProblem::new(apply_delta, residuals, jacobians);
+    LevenbergMarquardt::new(config, init, &problem)
+        .expect("there were more items in the vector than could be represented by the type")
+        .run_recorded(&problem, recorder)
+}
+
+/// Identical to [`optimize_report`], but records the best sum-of-squares after every iteration
+/// into `history`.
+///
+/// This is the convergence curve to plot when tuning a config. `history` is filled like in
+/// [`LevenbergMarquardt::run_with_history`](crate::LevenbergMarquardt::run_with_history), so
+/// the first `report.iterations` entries are written when it has `max_iterations` entries, and
+/// no allocation is needed.
+///
+/// ```
+/// use levenberg_marquardt::{optimize_with_history, Config};
+/// use nalgebra::Vector1;
+///
+/// let mut history = [f64::NAN; 16];
+/// let report = optimize_with_history(
+///     Config {
+///         max_iterations: history.len(),
+///         ..Config::default()
+///     },
+///     Vector1::new(0.0),
+///     |model, delta: Vector1<f64>| model + delta,
+///     |model| Vector1::new(1.0 - model.x),
+///     |_| core::iter::once(Vector1::new(1.0)),
+///     &mut history,
+/// );
+/// assert_eq!(history[report.iterations - 1], report.sum_of_squares);
+/// ```
+///
+/// # Panics
+///
+/// Panics if the number of residuals can't be represented by `N`.
+pub fn optimize_with_history<M, N, P, S, J, PS, RS, JS, IJ>(
+    config: Config<N>,
+    init: M,
+    apply_delta: impl Fn(&M, Vector<N, P, PS>) -> M,
+    residuals: impl Fn(&M) -> Matrix<N, J, S, RS>,
+    jacobians: impl Fn(&M) -> IJ,
+    history: &mut [N],
+) -> MinimizationReport<M, N, P>
+where
+    N: RealField + FromPrimitive,
+    P: DimMin<P>,
+    S: Dim,
+    J: DimName,
+    PS: ContiguousStorageMut<N, P> + Clone,
+    RS: Storage<N, J, S>,
+    JS: Storage<N, P, J>,
+    IJ: Iterator<Item = Matrix<N, P, J, JS>>,
+    DefaultAllocator: Allocator<N, J, P>,
+    DefaultAllocator: Allocator<N, P, P>,
+    DefaultAllocator: Allocator<N, P, Buffer = PS>,
+    ShapeConstraint: DimEq<DimMinimum<P, P>, P>,
+{
+    let problem = ClosureProblem::new(apply_delta, residuals, jacobians);
+    LevenbergMarquardt::new(config, init, &problem)
+        .expect("there were more items in the vector than could be represented by the type")
+        .run_with_history(&problem, history)
+}
+
+/// Returns an iterator which runs one iteration of [`optimize`] every time it is advanced.
+///
+/// Each item is a snapshot of the best model so far and its sum-of-squares, like what
+/// `on_iteration` receives in [`optimize_with_callback`]. The residuals and Jacobians of `init`
+/// are evaluated immediately, but no iterations run until the iterator is advanced. Once any
+/// termination condition is met, including `max_iterations`, the iterator returns `None`
+/// forever. The last item is the model that [`optimize`] would return. This composes with
+/// iterator adapters, such as `take_while` for custom stopping criteria or `collect` to record
+/// a convergence curve.
+///
+/// # Panics
+///
+/// Panics if the number of residuals can't be represented by `N`.
+pub fn optimize_iter<M, N, P, S, J, PS, RS, JS, IJ>(
+    config: Config<N>,
+    init: M,
+    apply_delta: impl Fn(&M, Vector<N, P, PS>) -> M,
+    residuals: impl Fn(&M) -> Matrix<N, J, S, RS>,
+    jacobians: impl Fn(&M) -> IJ,
+) -> impl Iterator<Item = (M, N)>
+where
+    M: Clone,
+    N: RealField + FromPrimitive,
+    P: DimMin<P>,
+    S: Dim,
+    J: DimName,
+    PS: ContiguousStorageMut<N, P> + Clone,
+    RS: Storage<N, J, S>,
+    JS: Storage<N, P, J>,
+    IJ: Iterator<Item = Matrix<N, P, J, JS>>,
+    DefaultAllocator: Allocator<N, J, P>,
+    DefaultAllocator: Allocator<N, P, P>,
+    DefaultAllocator: Allocator<N, P, Buffer = PS>,
+    ShapeConstraint: DimEq<DimMinimum<P, P>, P>,
+{
+    let problem = ClosureProblem::new(apply_delta, residuals, jacobians);
+    let mut lm = LevenbergMarquardt::new(config, init, &problem)
+        .expect("there were more items in the vector than could be represented by the type");
+    let mut workspace = Workspace::new();
+    core::iter::from_fn(move || {
+        if lm.termination().is_some() || lm.iterations() == config.max_iterations {
+            return None;
+        }
+        lm.step_in(&problem, &mut workspace);
+        Some((lm.best_guess().clone(), lm.best_sum_of_squares()))
+    })
+}
+
+/// Fits the curve `y = f(params, x)` to the points `(xs[i], ys[i])`, which is the common case of
+/// [`optimize_report`] with one scalar residual per point.
+///
+/// This requires the `alloc` feature. The model is the parameter vector itself, and steps are
+/// added to it. `df` returns the derivative of `f` with respect to each parameter at `x`,
+/// which is the Jacobian of the negative residual `f(params, x) - y` of that point, so it is
+/// the derivative of the curve itself and no signs have to be flipped. The residual matrix and
+/// the Jacobians are built internally, so `f` and `df` only deal with scalars and the
+/// parameters.
+///
+/// # Panics
+///
+/// Panics if `xs` and `ys` have different lengths.
+#[cfg(feature = "alloc")]
+pub fn fit_curve<N, P>(
+    config: Config<N>,
+    init: VectorN<N, P>,
+    f: impl Fn(&VectorN<N, P>, N) -> N,
+    df: impl Fn(&VectorN<N, P>, N) -> VectorN<N, P>,
+    xs: &[N],
+    ys: &[N],
+) -> MinimizationReport<VectorN<N, P>, N, P>
+where
+    N: RealField + FromPrimitive,
+    P: DimMin<P>,
+    DefaultAllocator: Allocator<N, U1, P>,
+    DefaultAllocator: Allocator<N, P, P>,
+    DefaultAllocator: Allocator<N, P>,
+    ShapeConstraint: DimEq<DimMinimum<P, P>, P>,
+{
+    assert_eq!(
+        xs.len(),
+        ys.len(),
+        "there must be as many y values as x values"
+    );
+    let df = &df;
+    optimize_problem(
+        config,
+        init,
+        &ClosureProblem::new(
+            |parameters: &VectorN<N, P>, delta| parameters + delta,
+            |parameters: &VectorN<N, P>| {
+                MatrixMN::<N, U1, Dynamic>::from_iterator_generic(
+                    U1,
+                    Dynamic::new(xs.len()),
+                    xs.iter().zip(ys).map(|(&x, &y)| y - f(parameters, x)),
                 )
             },
-        );
-
-        // Get a tuple of the lambda, guess, residual, and sum-of-squares.
-        // Returns an option because it may not be possible to solve the inverse.
-        let lam_ges_res_sum = |lam| {
-            // Compute JJᵀ + λ*diag(JJᵀ).
-            let mut hessian_lambda_diag = hessian.clone();
-            let new_diag = hessian_lambda_diag.map_diagonal(|n| n * (lam + N::one()));
-            hessian_lambda_diag.set_diagonal(&new_diag);
-
-            // Invert JᵀJ + λ*diag(JᵀJ) and solve for delta.
-            let delta = hessian_lambda_diag
-                .try_inverse()
-                .map(|inv_jjl| inv_jjl * &gradients);
-            // Compute the new guess, residuals, and sum-of-squares.
-            let vars = delta.map(|delta| {
-                let ges = apply_delta(&guess, delta);
-                let res = residuals(&ges);
-                let sum = res.norm_squared();
-                (lam, ges, res, sum)
-            });
-            // If the sum-of-squares is infinite or NaN it shouldn't be allowed through.
-            vars.filter(|vars| vars.3.is_finite())
-        };
-
-        // Select the vars that minimize the sum-of-squares the most.
-        let new_vars = match (lam_ges_res_sum(smaller_lambda), lam_ges_res_sum(lambda)) {
-            (Some(s_vars), Some(o_vars)) => Some(if s_vars.3 < o_vars.3 { s_vars } else { o_vars }),
-            (Some(vars), None) | (None, Some(vars)) => Some(vars),
-            (None, None) => None,
-        };
-
-        if let Some((n_lam, n_ges, n_res, n_sum)) = new_vars {
-            // We didn't see a decrease in the new state.
-            if n_sum > sum_of_squares {
-                // Increase lambda twice and go to the next iteration.
-                // Increase twice so that the new two tested lambdas are different than current.
-                lambda *= config.lambda_diverge;
-                consecutive_divergences += 1;
-            } else {
-                // There was a decrease, so update everything.
-                lambda = n_lam;
-                guess = n_ges;
-                res = n_res;
-                sum_of_squares = n_sum;
-                consecutive_divergences = 0;
-            }
-        } else {
-            // We were unable to take the inverse, so increase lambda in hopes that it may
-            // cause the matrix to become invertible.
-            lambda *= config.lambda_diverge;
-            consecutive_divergences += 1;
-        }
+            |parameters: &VectorN<N, P>| {
+                let parameters = parameters.clone();
+                xs.iter().map(move |&x| df(&parameters, x))
+            },
+        ),
+    )
+}
 
-        // Terminate early if we hit the consecutive divergence limit.
-        if consecutive_divergences == config.consecutive_divergence_limit {
-            break;
-        }
+/// Identical to [`optimize_report`], but the residuals, Jacobians, and how to apply a step are
+/// provided by a [`LeastSquaresProblem`] rather than separate closures.
+///
+/// The problem's `normalize` is applied to every new guess after its step is applied and
+/// before its residuals are computed.
+///
+/// When `P`, `S` and `J` are all [`DimName`]s, the residuals, the Jacobians and everything the
+/// optimizer computes from them are statically-sized nalgebra matrices, so the fit never
+/// allocates on the heap unless the problem does. This is what embedded targets without an
+/// allocator need, and is meant for small problems, such as a handful of parameters fit to a few
+/// dozen samples, which fit on the stack. The one exception is a `jacobian_refresh_interval`
+/// above `1` with the `alloc` feature, since Broyden's update keeps the stacked Jacobian in a
+/// `Vec`.
+///
+/// # Panics
+///
+/// Panics if the number of residuals can't be represented by `N`, which
+/// [`checked_optimize_problem`] returns as an error instead.
+pub fn optimize_problem<N, P, S, J, LSP>(
+    config: Config<N>,
+    init: LSP::Model,
+    problem: &LSP,
+) -> MinimizationReport<LSP::Model, N, P>
+where
+    N: RealField + FromPrimitive,
+    P: DimMin<P>,
+    S: Dim,
+    J: DimName,
+    LSP: LeastSquaresProblem<N, P, S, J>,
+    DefaultAllocator: Allocator<N, J, P>,
+    DefaultAllocator: Allocator<N, P, P>,
+    DefaultAllocator: Allocator<N, P>,
+    ShapeConstraint: DimEq<DimMinimum<P, P>, P>,
+{
+    minimize(config, init, problem, |_, _, _| ControlFlow::Continue(()))
+        .expect("there were more items in the vector than could be represented by the type")
+}
 
-        // We can terminate early if the sum of squares is below the threshold.
-        if sum_of_squares < config.threshold * total {
-            break;
-        }
+/// Identical to [`optimize_problem`], but returns an error rather than panicking if
+/// optimization can't be run.
+///
+/// The config is checked with [`Config::validate`] before anything is evaluated, and
+/// [`OptimizeError::NonFiniteStart`] is returned if the sum-of-squares of `init` isn't finite.
+pub fn checked_optimize_problem<N, P, S, J, LSP>(
+    config: Config<N>,
+    init: LSP::Model,
+    problem: &LSP,
+) -> Result<MinimizationReport<LSP::Model, N, P>, OptimizeError>
+where
+    N: RealField + FromPrimitive,
+    P: DimMin<P>,
+    S: Dim,
+    J: DimName,
+    LSP: LeastSquaresProblem<N, P, S, J>,
+    DefaultAllocator: Allocator<N, J, P>,
+    DefaultAllocator: Allocator<N, P, P>,
+    DefaultAllocator: Allocator<N, P>,
+    ShapeConstraint: DimEq<DimMinimum<P, P>, P>,
+{
+    config.validate().map_err(OptimizeError::InvalidConfig)?;
+    let lm = LevenbergMarquardt::new(config, init, problem)?;
+    if !lm.sum_of_squares().is_finite() {
+        return Err(OptimizeError::NonFiniteStart);
     }
+    Ok(lm.run(problem))
+}
 
-    guess
+/// Minimizes `problem` with Powell's dogleg method instead of Levenberg-Marquardt.
+///
+/// Rather than damping the normal equations with lambda, the dogleg method limits each step to
+/// a trust radius around the current guess, starting from `initial_trust_radius`. Each step is
+/// the point within the radius along the path from the current guess to the Cauchy point, which
+/// minimizes the linearization along the gradient, and then on to the Gauss-Newton point. The
+/// radius grows when the reduction in the sum-of-squares agrees with the reduction that the
+/// linearization predicted and shrinks when it doesn't. A step that was rejected only shrinks
+/// the radius, so it doesn't need to accumulate the Hessian again or solve another system.
+///
+/// The method, lambda, damping, geodesic acceleration and `jacobian_refresh_interval` settings of
+/// `config` are unused. The other termination conditions are the same as those of [`optimize`].
+/// Closures can be optimized by wrapping them in a [`ClosureProblem`] first.
+///
+/// # Panics
+///
+/// Panics if the number of residuals can't be represented by `N`.
+pub fn optimize_dogleg<N, P, S, J, LSP>(
+    config: Config<N>,
+    init: LSP::Model,
+    problem: &LSP,
+) -> MinimizationReport<LSP::Model, N, P>
+where
+    N: RealField + FromPrimitive,
+    P: DimMin<P>,
+    S: Dim,
+    J: DimName,
+    LSP: LeastSquaresProblem<N, P, S, J>,
+    DefaultAllocator: Allocator<N, J, P>,
+    DefaultAllocator: Allocator<N, P, P>,
+    DefaultAllocator: Allocator<N, P>,
+    ShapeConstraint: DimEq<DimMinimum<P, P>, P>,
+{
+    dogleg::minimize(config, init, problem)
+        .expect("there were more items in the vector than could be represented by the type")
+}
+
+/// Runs [`optimize_problem`] from each of the initial guesses in `inits` and returns the report
+/// of the one with the lowest final sum-of-squares.
+///
+/// This is useful when the sum-of-squares has several local minima, since a single initial
+/// guess may converge to a bad one. The reported sum-of-squares can be compared against the
+/// expected noise of the residuals to judge whether the best start found the global minimum.
+/// If several starts tie for the lowest sum-of-squares, the first one is returned, and a start
+/// whose sum-of-squares isn't finite is only returned if no start has a finite one. Returns
+/// `None` if `inits` is empty.
+pub fn optimize_multistart<N, P, S, J, LSP>(
+    config: Config<N>,
+    inits: impl IntoIterator<Item = LSP::Model>,
+    problem: &LSP,
+) -> Option<MinimizationReport<LSP::Model, N, P>>
+where
+    N: RealField + FromPrimitive,
+    P: DimMin<P>,
+    S: Dim,
+    J: DimName,
+    LSP: LeastSquaresProblem<N, P, S, J>,
+    DefaultAllocator: Allocator<N, J, P>,
+    DefaultAllocator: Allocator<N, P, P>,
+    DefaultAllocator: Allocator<N, P>,
+    ShapeConstraint: DimEq<DimMinimum<P, P>, P>,
+{
+    inits
+        .into_iter()
+        .map(|init| optimize_problem(config, init, problem))
+        .reduce(better_start)
+}
+
+/// Identical to [`optimize_multistart`], but runs the starts in parallel with rayon, which
+/// requires the problem to be `Sync` and the models and reports to be `Send`.
+///
+/// The reports are still compared in the order of `inits`, so the same one is returned.
+#[cfg(feature = "rayon")]
+pub fn optimize_multistart_par<N, P, S, J, LSP>(
+    config: Config<N>,
+    inits: impl IntoIterator<Item = LSP::Model>,
+    problem: &LSP,
+) -> Option<MinimizationReport<LSP::Model, N, P>>
+where
+    N: RealField + FromPrimitive,
+    P: DimMin<P>,
+    S: Dim,
+    J: DimName,
+    LSP: LeastSquaresProblem<N, P, S, J> + Sync,
+    LSP::Model: Send,
+    MinimizationReport<LSP::Model, N, P>: Send,
+    DefaultAllocator: Allocator<N, J, P>,
+    DefaultAllocator: Allocator<N, P, P>,
+    DefaultAllocator: Allocator<N, P>,
+    ShapeConstraint: DimEq<DimMinimum<P, P>, P>,
+{
+    use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+    // Collecting the starts keeps them in order, so ties still go to the first one.
+    inits
+        .into_iter()
+        .collect::<std::vec::Vec<_>>()
+        .into_par_iter()
+        .map(|init| optimize_problem(config, init, problem))
+        .reduce_with(better_start)
+}
+
+/// Picks the better of two reports of [`optimize_multistart`], preferring `best` on a tie.
+///
+/// A report whose sum-of-squares isn't finite always loses to one whose sum-of-squares is, since
+/// no comparison with NaN is true.
+fn better_start<M, N, P>(
+    best: MinimizationReport<M, N, P>,
+    report: MinimizationReport<M, N, P>,
+) -> MinimizationReport<M, N, P>
+where
+    N: RealField,
+    P: Dim,
+    DefaultAllocator: Allocator<N, P>,
+    DefaultAllocator: Allocator<N, P, P>,
+{
+    if report.sum_of_squares.is_finite()
+        && (!best.sum_of_squares.is_finite() || report.sum_of_squares < best.sum_of_squares)
+    {
+        report
+    } else {
+        best
+    }
+}
+
+/// The implementation of Levenberg-Marquardt used by every other entry point.
+///
+/// `on_iteration` is called at the end of every iteration with the iteration index, the best
+/// model, and its sum-of-squares.
+fn minimize<N, P, S, J, LSP>(
+    config: Config<N>,
+    init: LSP::Model,
+    problem: &LSP,
+    on_iteration: impl FnMut(usize, &LSP::Model, N) -> ControlFlow<()>,
+) -> Result<MinimizationReport<LSP::Model, N, P>, OptimizeError>
+where
+    N: RealField + FromPrimitive,
+    P: DimMin<P>,
+    S: Dim,
+    J: DimName,
+    LSP: LeastSquaresProblem<N, P, S, J>,
+    DefaultAllocator: Allocator<N, J, P>,
+    DefaultAllocator: Allocator<N, P, P>,
+    DefaultAllocator: Allocator<N, P>,
+    ShapeConstraint: DimEq<DimMinimum<P, P>, P>,
+{
+    let mut lm = LevenbergMarquardt::new(config, init, problem)?;
+    let termination = lm.run_with(problem, &mut Workspace::new(), |_| None, on_iteration);
+    Ok(lm.into_report(termination))
 }
@@ -0,0 +1,87 @@
+use crate::LeastSquaresProblem;
+use alloc::vec::{self, Vec};
+use core::marker::PhantomData;
+use nalgebra::{
+    allocator::Allocator, storage::Storage, DefaultAllocator, Dim, Matrix, Scalar, VectorN,
+};
+
+/// Adapts closures into a [`LeastSquaresProblem`] over a batch of the samples, which is
+/// stochastic Levenberg-Marquardt when the batch changes between iterations.
+///
+/// This requires the `alloc` feature. It is meant for datasets where a pass over every sample on
+/// every iteration is too costly. [`select`](Self::select) replaces the indices of the samples in
+/// the batch, such as with a random subset, so that the batches eventually cover the data.
+/// `residuals` gets the model and the indices of the batch, and returns the residual matrix of
+/// just those samples in the same order. `jacobian` gets the model and a single index, and
+/// returns the Jacobian of the negative residuals of that sample. The batch starts out empty.
+///
+/// A fixed batch can be optimized like any other problem. To move on to a new batch on every
+/// iteration, pass the selection to
+/// [`LevenbergMarquardt::run_batched`](crate::LevenbergMarquardt::run_batched).
+pub struct MinibatchProblem<M, A, R, JF> {
+    apply_delta: A,
+    residuals: R,
+    jacobian: JF,
+    /// The indices of the samples in the current batch.
+    batch: Vec<usize>,
+    model: PhantomData<fn(&M) -> M>,
+}
+
+impl<M, A, R, JF> MinibatchProblem<M, A, R, JF> {
+    /// Bundles the closures with an empty batch.
+    pub fn new(apply_delta: A, residuals: R, jacobian: JF) -> Self {
+        Self {
+            apply_delta,
+            residuals,
+            jacobian,
+            batch: Vec::new(),
+            model: PhantomData,
+        }
+    }
+
+    /// Replaces the current batch with the samples at `indices`.
+    pub fn select(&mut self, indices: impl IntoIterator<Item = usize>) {
+        self.batch.clear();
+        self.batch.extend(indices);
+    }
+}
+
+impl<M, N, P, S, J, RS, JS, A, R, JF> LeastSquaresProblem<N, P, S, J>
+    for MinibatchProblem<M, A, R, JF>
+where
+    N: Scalar,
+    P: Dim,
+    S: Dim,
+    J: Dim,
+    RS: Storage<N, J, S>,
+    JS: Storage<N, P, J>,
+    A: Fn(&M, VectorN<N, P>) -> M,
+    R: Fn(&M, &[usize]) -> Matrix<N, J, S, RS>,
+    JF: Fn(&M, usize) -> Matrix<N, P, J, JS>,
+    DefaultAllocator: Allocator<N, P>,
+{
+    type Model = M;
+    type ResidualStorage = RS;
+    type JacobianStorage = JS;
+    type Jacobians<'a>
+        = vec::IntoIter<Matrix<N, P, J, JS>>
+    where
+        Self: 'a;
+
+    fn apply_delta(&self, model: &M, delta: VectorN<N, P>) -> M {
+        (self.apply_delta)(model, delta)
+    }
+
+    fn residuals(&self, model: &M) -> Matrix<N, J, S, RS> {
+        (self.residuals)(model, &self.batch)
+    }
+
+    fn jacobians(&self, model: &M) -> Self::Jacobians<'_> {
+        // The iterator can't borrow the model, so the Jacobians of the batch are collected.
+        self.batch
+            .iter()
+            .map(|&index| (self.jacobian)(model, index))
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+}
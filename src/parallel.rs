@@ -0,0 +1,111 @@
+use crate::{solve, LeastSquaresProblem};
+use core::marker::PhantomData;
+use nalgebra::{
+    allocator::Allocator, storage::Storage, DefaultAllocator, Dim, DimName, Matrix, MatrixMN,
+    RealField, VectorN, U1,
+};
+use rayon::iter::{IndexedParallelIterator, ParallelIterator};
+use std::vec::Vec;
+
+/// Adapts closures like [`ClosureProblem`](crate::ClosureProblem), but `jacobians` returns a
+/// parallel iterator, and the approximate Hessian and the gradients are summed over the samples
+/// in parallel.
+///
+/// This requires the `rayon` feature. It is only worth it when there are many samples, since
+/// the per-sample work of the sum is small and the threads must be synchronized at least once
+/// per iteration. The Jacobian of each sample is paired with the column of the residuals at
+/// the same index, so the parallel iterator must be indexed.
+///
+/// The result is the same as with a [`ClosureProblem`](crate::ClosureProblem) except that
+/// floating-point addition isn't associative, so summing the samples in a different order can
+/// change the rounding of the approximate Hessian and the gradients. This means that the steps,
+/// and therefore the returned model, may differ by a small amount and aren't guaranteed to be
+/// reproducible between runs. With [`SolveMethod::Qr`](crate::SolveMethod::Qr), the Jacobians
+/// are computed in parallel but collected and rotated in one at a time in order, since the QR
+/// decomposition can't be reduced from partial sums. `compensated_accumulation` needs the samples
+/// to be summed one at a time in order, so it gives up the parallel sums.
+pub struct ParallelClosureProblem<M, A, R, JF> {
+    apply_delta: A,
+    residuals: R,
+    jacobians: JF,
+    model: PhantomData<fn(&M) -> M>,
+}
+
+impl<M, A, R, JF> ParallelClosureProblem<M, A, R, JF> {
+    /// Bundles the closures, where `jacobians` returns an indexed parallel iterator.
+    pub fn new(apply_delta: A, residuals: R, jacobians: JF) -> Self {
+        Self {
+            apply_delta,
+            residuals,
+            jacobians,
+            model: PhantomData,
+        }
+    }
+}
+
+impl<M, N, P, S, J, RS, JS, IJ, A, R, JF> LeastSquaresProblem<N, P, S, J>
+    for ParallelClosureProblem<M, A, R, JF>
+where
+    N: RealField,
+    P: Dim,
+    S: Dim,
+    J: DimName,
+    RS: Storage<N, J, S> + Sync,
+    JS: Storage<N, P, J> + Send,
+    IJ: IndexedParallelIterator<Item = Matrix<N, P, J, JS>>,
+    A: Fn(&M, VectorN<N, P>) -> M,
+    R: Fn(&M) -> Matrix<N, J, S, RS>,
+    JF: Fn(&M) -> IJ,
+    DefaultAllocator: Allocator<N, P>,
+    DefaultAllocator: Allocator<N, P, P>,
+    DefaultAllocator: Allocator<N, J, P>,
+    MatrixMN<N, P, P>: Send,
+    VectorN<N, P>: Send,
+{
+    type Model = M;
+    type ResidualStorage = RS;
+    type JacobianStorage = JS;
+    // The QR decomposition has to rotate the rows in one at a time, so it collects them.
+    type Jacobians<'a>
+        = std::vec::IntoIter<Matrix<N, P, J, JS>>
+    where
+        Self: 'a;
+
+    fn apply_delta(&self, model: &M, delta: VectorN<N, P>) -> M {
+        (self.apply_delta)(model, delta)
+    }
+
+    fn residuals(&self, model: &M) -> Matrix<N, J, S, RS> {
+        (self.residuals)(model)
+    }
+
+    fn jacobians(&self, model: &M) -> Self::Jacobians<'_> {
+        (self.jacobians)(model).collect::<Vec<_>>().into_iter()
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn normal_equations(
+        &self,
+        model: &M,
+        residuals: &Matrix<N, J, S, RS>,
+    ) -> Option<(MatrixMN<N, P, P>, VectorN<N, P>)> {
+        // The number of parameters might only be known from the Jacobians, so the sums start
+        // from the first sample of each thread rather than from zero.
+        let sums = (self.jacobians)(model)
+            .enumerate()
+            .map(|(sample, jacobian)| {
+                (
+                    &jacobian * jacobian.transpose(),
+                    &jacobian * residuals.column(sample),
+                )
+            })
+            .reduce_with(|(h0, g0), (h1, g1)| (h0 + h1, g0 + g1));
+        Some(sums.unwrap_or_else(|| {
+            let p = solve::initial_dim::<P>();
+            (
+                MatrixMN::<N, P, P>::zeros_generic(p, p),
+                VectorN::<N, P>::zeros_generic(p, U1),
+            )
+        }))
+    }
+}
@@ -0,0 +1,145 @@
+use crate::LeastSquaresProblem;
+use nalgebra::{
+    allocator::Allocator, DefaultAllocator, Dim, DimName, Matrix, MatrixMN, RealField, VectorN,
+};
+
+/// Adapts a [`LeastSquaresProblem`] over a parameter vector so that its cost is regularized
+/// toward a prior `p0` with the weight matrix `Λ`, which is useful for maximum a posteriori
+/// estimation.
+///
+/// With `prior` set to `(p0, Λ)`, the cost that is minimized and reported as the
+/// sum-of-squares becomes `Σ rᵀr + (p - p0)ᵀΛ(p - p0)`. Every iteration adds `Λ` to the
+/// approximate Hessian and `Λ(p0 - p)` to the gradients, which keeps parameters that the
+/// residuals barely determine from wandering off, as in regularized bundle adjustment. `Λ`
+/// should be symmetric and positive semi-definite, and is usually the inverse covariance of the
+/// prior. Setting `Λ` to zero or `prior` to `None` recovers the wrapped problem.
+///
+/// The prior is added in [`normal_equations`](LeastSquaresProblem::normal_equations), which
+/// the optimizer then uses with any config, as described for
+/// [`modifies_normal_equations`](LeastSquaresProblem::modifies_normal_equations).
+#[allow(clippy::type_complexity)]
+pub struct PriorProblem<LSP, N, P>
+where
+    N: RealField,
+    P: Dim,
+    DefaultAllocator: Allocator<N, P>,
+    DefaultAllocator: Allocator<N, P, P>,
+{
+    problem: LSP,
+    prior: Option<(VectorN<N, P>, MatrixMN<N, P, P>)>,
+}
+
+impl<LSP, N, P> PriorProblem<LSP, N, P>
+where
+    N: RealField,
+    P: Dim,
+    DefaultAllocator: Allocator<N, P>,
+    DefaultAllocator: Allocator<N, P, P>,
+{
+    /// Regularizes `problem` toward the mean and weight matrix of `prior`, if any.
+    #[allow(clippy::type_complexity)]
+    pub fn new(problem: LSP, prior: Option<(VectorN<N, P>, MatrixMN<N, P, P>)>) -> Self {
+        Self { problem, prior }
+    }
+}
+
+impl<N, P, S, J, LSP> LeastSquaresProblem<N, P, S, J> for PriorProblem<LSP, N, P>
+where
+    N: RealField,
+    P: Dim,
+    S: Dim,
+    J: Dim,
+    LSP: LeastSquaresProblem<N, P, S, J, Model = VectorN<N, P>>,
+    DefaultAllocator: Allocator<N, P>,
+    DefaultAllocator: Allocator<N, P, P>,
+{
+    type Model = VectorN<N, P>;
+    type ResidualStorage = LSP::ResidualStorage;
+    type JacobianStorage = LSP::JacobianStorage;
+    type Jacobians<'a>
+        = LSP::Jacobians<'a>
+    where
+        Self: 'a;
+
+    fn apply_delta(&self, model: &Self::Model, delta: VectorN<N, P>) -> Self::Model {
+        self.problem.apply_delta(model, delta)
+    }
+
+    fn try_apply_delta(&self, model: &Self::Model, delta: VectorN<N, P>) -> Option<Self::Model> {
+        self.problem.try_apply_delta(model, delta)
+    }
+
+    fn residuals(&self, model: &Self::Model) -> Matrix<N, J, S, Self::ResidualStorage> {
+        self.problem.residuals(model)
+    }
+
+    fn jacobians(&self, model: &Self::Model) -> Self::Jacobians<'_> {
+        self.problem.jacobians(model)
+    }
+
+    fn try_jacobians(&self, model: &Self::Model) -> Option<Self::Jacobians<'_>> {
+        self.problem.try_jacobians(model)
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn residuals_and_jacobians(
+        &self,
+        model: &Self::Model,
+    ) -> (
+        Matrix<N, J, S, Self::ResidualStorage>,
+        Option<Self::Jacobians<'_>>,
+    ) {
+        self.problem.residuals_and_jacobians(model)
+    }
+
+    fn normal_equations(
+        &self,
+        model: &Self::Model,
+        residuals: &Matrix<N, J, S, Self::ResidualStorage>,
+    ) -> Option<(MatrixMN<N, P, P>, VectorN<N, P>)>
+    where
+        N: RealField,
+        P: Dim,
+        J: DimName,
+        DefaultAllocator: Allocator<N, P, P>,
+        DefaultAllocator: Allocator<N, J, P>,
+    {
+        let (mut hessian, mut gradients) = self.problem.normal_equations(model, residuals)?;
+        if let Some((mean, information)) = &self.prior {
+            // The gradients are those of the negative cost, so the prior pulls toward its mean.
+            hessian += information;
+            gradients += information * (mean - model);
+        }
+        Some((hessian, gradients))
+    }
+
+    fn modifies_normal_equations(&self) -> bool {
+        self.prior.is_some() || self.problem.modifies_normal_equations()
+    }
+
+    fn sum_of_squares(
+        &self,
+        model: &Self::Model,
+        residuals: &Matrix<N, J, S, Self::ResidualStorage>,
+    ) -> N
+    where
+        N: RealField,
+    {
+        let sum_of_squares = self.problem.sum_of_squares(model, residuals);
+        match &self.prior {
+            Some((mean, information)) => {
+                let offset = model - mean;
+                sum_of_squares + offset.dot(&(information * &offset))
+            }
+            None => sum_of_squares,
+        }
+    }
+
+    fn normalize(&self, model: Self::Model) -> Self::Model {
+        self.problem.normalize(model)
+    }
+
+    fn try_normalize(&self, model: Self::Model) -> Option<Self::Model> {
+        self.problem.try_normalize(model)
+    }
+}
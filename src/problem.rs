@@ -0,0 +1,611 @@
+use crate::solve;
+use core::{iter::Flatten, marker::PhantomData, option};
+use nalgebra::{
+    allocator::Allocator, storage::Storage, DefaultAllocator, Dim, DimName, Matrix, MatrixMN,
+    RealField, Scalar, VectorN,
+};
+
+/// A least squares problem which can be optimized with [`optimize_problem`](crate::optimize_problem).
+///
+/// This bundles everything that [`optimize`](crate::optimize) takes as separate closures so that
+/// `residuals` and `jacobians` can't accidentally disagree on the samples. Observations can be
+/// stored as fields of the implementing type rather than being captured by closures.
+///
+/// See [`optimize`](crate::optimize) for the conventions that the residuals and Jacobians
+/// must follow.
+pub trait LeastSquaresProblem<N, P, S, J>
+where
+    N: Scalar,
+    P: Dim,
+    S: Dim,
+    J: Dim,
+    DefaultAllocator: Allocator<N, P>,
+{
+    /// The model that is being optimized.
+    type Model;
+    /// The nalgebra storage used for the residual matrix.
+    type ResidualStorage: Storage<N, J, S>;
+    /// The nalgebra storage used for each Jacobian matrix.
+    type JacobianStorage: Storage<N, P, J>;
+    /// The iterator over the Jacobian matrices of each sample.
+    ///
+    /// It can borrow from the problem, so that the samples stored in the problem can be
+    /// iterated over directly rather than collected into a new iterator on every call.
+    type Jacobians<'a>: Iterator<Item = Matrix<N, P, J, Self::JacobianStorage>>
+    where
+        Self: 'a;
+
+    /// Applies a step computed by the optimizer to the model.
+    fn apply_delta(&self, model: &Self::Model, delta: VectorN<N, P>) -> Self::Model;
+
+    /// Identical to [`apply_delta`](Self::apply_delta), but returns `None` if the step leaves
+    /// the region where the model is feasible, such as a variance which would become negative.
+    ///
+    /// An infeasible step is rejected like a guess which can't be normalized, so lambda is
+    /// increased and a shorter step is tried. The optimizers only call this method, which by
+    /// default applies the step with [`apply_delta`](Self::apply_delta).
+    fn try_apply_delta(&self, model: &Self::Model, delta: VectorN<N, P>) -> Option<Self::Model> {
+        Some(self.apply_delta(model, delta))
+    }
+
+    /// Computes the residual matrix of the model.
+    fn residuals(&self, model: &Self::Model) -> Matrix<N, J, S, Self::ResidualStorage>;
+
+    /// Computes the Jacobian of the negative residuals of each sample in the model.
+    fn jacobians(&self, model: &Self::Model) -> Self::Jacobians<'_>;
+
+    /// Computes the residuals like [`residuals`](Self::residuals), along with the Jacobians if
+    /// they are cheaper to compute together with the residuals than on their own.
+    ///
+    /// This is what the optimizer calls to evaluate the model at every candidate step. The
+    /// Jacobians are only used if the step is accepted, in which case they replace the call to
+    /// [`try_jacobians`](Self::try_jacobians) or [`normal_equations`](Self::normal_equations),
+    /// unless [`modifies_normal_equations`](Self::modifies_normal_equations) is `true`.
+    /// Since most candidates are rejected, the iterator should compute the Jacobians lazily
+    /// from whatever the residuals had to compute anyway. By default no Jacobians are returned.
+    #[allow(clippy::type_complexity)]
+    fn residuals_and_jacobians(
+        &self,
+        model: &Self::Model,
+    ) -> (
+        Matrix<N, J, S, Self::ResidualStorage>,
+        Option<Self::Jacobians<'_>>,
+    ) {
+        (self.residuals(model), None)
+    }
+
+    /// Computes the Jacobians like [`jacobians`](Self::jacobians), but returns `None` if they
+    /// can't be computed at `model`, such as at a branch cut of the model.
+    ///
+    /// This is what the optimizer calls. If it fails on a step, that step is rejected just
+    /// like when the damped Hessian can't be inverted. By default it never fails.
+    fn try_jacobians(&self, model: &Self::Model) -> Option<Self::Jacobians<'_>> {
+        Some(self.jacobians(model))
+    }
+
+    /// Accumulates the approximate Hessian `JJᵀ` and the gradients `Jr` of the model from its
+    /// residuals, or returns `None` if the Jacobians can't be computed at `model`.
+    ///
+    /// This is what the optimizer calls instead of [`try_jacobians`](Self::try_jacobians) when
+    /// solving the normal equations. By default it sums the contribution of every sample from
+    /// [`try_jacobians`](Self::try_jacobians) in order. It can be overridden to accumulate the
+    /// sums some other way, such as in parallel, as long as the result is the same up to
+    /// rounding. Such overrides are skipped with [`SolveMethod::Qr`](crate::SolveMethod::Qr),
+    /// with [`Config::compensated_accumulation`](crate::Config::compensated_accumulation), or
+    /// with a [`Config::jacobian_refresh_interval`](crate::Config::jacobian_refresh_interval)
+    /// above `1`, since those need the Jacobians themselves. An override which changes the
+    /// system must also override
+    /// [`modifies_normal_equations`](Self::modifies_normal_equations), so that it is never
+    /// skipped.
+    #[allow(clippy::type_complexity)]
+    fn normal_equations(
+        &self,
+        model: &Self::Model,
+        residuals: &Matrix<N, J, S, Self::ResidualStorage>,
+    ) -> Option<(MatrixMN<N, P, P>, VectorN<N, P>)>
+    where
+        N: RealField,
+        P: Dim,
+        J: DimName,
+        DefaultAllocator: Allocator<N, P, P>,
+        DefaultAllocator: Allocator<N, J, P>,
+    {
+        let jacobians = self.try_jacobians(model)?;
+        Some(solve::normal_equations(jacobians, residuals))
+    }
+
+    /// Whether [`normal_equations`](Self::normal_equations) is overridden to change the system
+    /// rather than only how it is summed, such as by adding a prior like
+    /// [`PriorProblem`](crate::PriorProblem).
+    ///
+    /// The optimizer then always linearizes with [`normal_equations`](Self::normal_equations),
+    /// since summing the Jacobians would leave out whatever the override adds. This is even the
+    /// case when the Jacobians were already computed by
+    /// [`residuals_and_jacobians`](Self::residuals_and_jacobians). The system is solved with the
+    /// normal equations rather than [`SolveMethod::Qr`](crate::SolveMethod::Qr), it is summed
+    /// without [`Config::compensated_accumulation`](crate::Config::compensated_accumulation),
+    /// and the Jacobians are evaluated at every accepted step regardless of
+    /// [`Config::jacobian_refresh_interval`](crate::Config::jacobian_refresh_interval). By
+    /// default it is `false`, which is also right for an override that only sums the Jacobians
+    /// some other way, such as in parallel.
+    fn modifies_normal_equations(&self) -> bool {
+        false
+    }
+
+    /// Computes the cost of the model from its residuals, which is what the optimizer
+    /// minimizes and reports as the sum-of-squares.
+    ///
+    /// By default this is the sum of the squares of the residuals. It can be overridden to add a
+    /// penalty on the model itself, as long as [`normal_equations`](Self::normal_equations) is
+    /// overridden to match.
+    fn sum_of_squares(
+        &self,
+        model: &Self::Model,
+        residuals: &Matrix<N, J, S, Self::ResidualStorage>,
+    ) -> N
+    where
+        N: RealField,
+    {
+        let _ = model;
+        residuals.norm_squared()
+    }
+
+    /// Normalizes the model after a step is applied and before its residuals are computed.
+    ///
+    /// This might be something like wrapping an angle or renormalizing a unit quaternion.
+    /// By default the model is left unchanged.
+    fn normalize(&self, model: Self::Model) -> Self::Model {
+        model
+    }
+
+    /// Identical to [`normalize`](Self::normalize), but returns `None` if the model can't be
+    /// normalized, such as a quaternion which has collapsed to zero.
+    ///
+    /// A step to a guess which can't be normalized is rejected like one which increases the
+    /// sum-of-squares, so lambda is increased and a shorter step is tried. The optimizers only
+    /// call this method, which by default normalizes with [`normalize`](Self::normalize).
+    fn try_normalize(&self, model: Self::Model) -> Option<Self::Model> {
+        Some(self.normalize(model))
+    }
+}
+
+/// Adapts the closures passed to [`optimize`](crate::optimize) into a [`LeastSquaresProblem`].
+///
+/// This is useful to combine closures with adapters such as
+/// [`WeightedProblem`](crate::WeightedProblem) without writing a type for the problem.
+pub struct ClosureProblem<M, A, R, JF> {
+    apply_delta: A,
+    residuals: R,
+    jacobians: JF,
+    model: PhantomData<fn(&M) -> M>,
+}
+
+impl<M, A, R, JF> ClosureProblem<M, A, R, JF> {
+    /// Bundles the closures that would be passed to [`optimize`](crate::optimize).
+    pub fn new(apply_delta: A, residuals: R, jacobians: JF) -> Self {
+        Self {
+            apply_delta,
+            residuals,
+            jacobians,
+            model: PhantomData,
+        }
+    }
+}
+
+impl<M, N, P, S, J, RS, JS, IJ, A, R, JF> LeastSquaresProblem<N, P, S, J>
+    for ClosureProblem<M, A, R, JF>
+where
+    N: Scalar,
+    P: Dim,
+    S: Dim,
+    J: Dim,
+    RS: Storage<N, J, S>,
+    JS: Storage<N, P, J>,
+    IJ: Iterator<Item = Matrix<N, P, J, JS>>,
+    A: Fn(&M, VectorN<N, P>) -> M,
+    R: Fn(&M) -> Matrix<N, J, S, RS>,
+    JF: Fn(&M) -> IJ,
+    DefaultAllocator: Allocator<N, P>,
+{
+    type Model = M;
+    type ResidualStorage = RS;
+    type JacobianStorage = JS;
+    type Jacobians<'a>
+        = IJ
+    where
+        Self: 'a;
+
+    fn apply_delta(&self, model: &M, delta: VectorN<N, P>) -> M {
+        (self.apply_delta)(model, delta)
+    }
+
+    fn residuals(&self, model: &M) -> Matrix<N, J, S, RS> {
+        (self.residuals)(model)
+    }
+
+    fn jacobians(&self, model: &M) -> IJ {
+        (self.jacobians)(model)
+    }
+}
+
+/// Adapts closures like [`ClosureProblem`], but the residuals and the Jacobians of each guess
+/// come from `residuals_and_jacobians`, which computes both at once.
+///
+/// Most models compute both from the same intermediate values, such as a reprojection which
+/// gives both the error and its derivatives, so computing them together avoids doing that work
+/// twice. The closure is called once for every guess that is evaluated, and the Jacobians it
+/// returns are kept for the guesses that are accepted rather than computing them again. Since
+/// most candidate steps are rejected, the Jacobians should be computed lazily by the iterator
+/// from the intermediate values, so that they are never computed for rejected steps.
+pub struct FusedClosureProblem<M, A, F> {
+    apply_delta: A,
+    residuals_and_jacobians: F,
+    model: PhantomData<fn(&M) -> M>,
+}
+
+impl<M, A, F> FusedClosureProblem<M, A, F> {
+    /// Bundles the closures, where `residuals_and_jacobians` returns the residuals of a model
+    /// along with its Jacobians.
+    pub fn new(apply_delta: A, residuals_and_jacobians: F) -> Self {
+        Self {
+            apply_delta,
+            residuals_and_jacobians,
+            model: PhantomData,
+        }
+    }
+}
+
+impl<M, N, P, S, J, RS, JS, IJ, A, F> LeastSquaresProblem<N, P, S, J>
+    for FusedClosureProblem<M, A, F>
+where
+    N: Scalar,
+    P: Dim,
+    S: Dim,
+    J: Dim,
+    RS: Storage<N, J, S>,
+    JS: Storage<N, P, J>,
+    IJ: Iterator<Item = Matrix<N, P, J, JS>>,
+    A: Fn(&M, VectorN<N, P>) -> M,
+    F: Fn(&M) -> (Matrix<N, J, S, RS>, IJ),
+    DefaultAllocator: Allocator<N, P>,
+{
+    type Model = M;
+    type ResidualStorage = RS;
+    type JacobianStorage = JS;
+    type Jacobians<'a>
+        = IJ
+    where
+        Self: 'a;
+
+    fn apply_delta(&self, model: &M, delta: VectorN<N, P>) -> M {
+        (self.apply_delta)(model, delta)
+    }
+
+    fn residuals(&self, model: &M) -> Matrix<N, J, S, RS> {
+        (self.residuals_and_jacobians)(model).0
+    }
+
+    fn jacobians(&self, model: &M) -> IJ {
+        (self.residuals_and_jacobians)(model).1
+    }
+
+    fn residuals_and_jacobians(&self, model: &M) -> (Matrix<N, J, S, RS>, Option<IJ>) {
+        let (residuals, jacobians) = (self.residuals_and_jacobians)(model);
+        (residuals, Some(jacobians))
+    }
+}
+
+/// Adapts closures like [`ClosureProblem`], but the approximate Hessian `JJᵀ` of each guess is
+/// computed by `hessian` rather than being accumulated from the Jacobians.
+///
+/// This is useful when the approximate Hessian has a known sparse or block structure that is
+/// much cheaper to compute directly than to sum from the Jacobian of every sample. The
+/// gradients are still accumulated from `jacobians` and `residuals`, so `hessian` only needs
+/// to be an approximation for the steps to head towards a minima, but convergence will suffer
+/// the further it is from `JJᵀ`. The gain ratio is computed from the overridden Hessian too.
+/// The dimensions of the Hessian are part of its type, so a Hessian of the wrong size can't
+/// be passed in.
+///
+/// `hessian` is only called when solving the normal equations. With
+/// [`SolveMethod::Qr`](crate::SolveMethod::Qr), the approximate Hessian is never formed, so
+/// `hessian` is ignored and the Jacobians are used as usual. A `jacobian_refresh_interval` above
+/// `1` and `compensated_accumulation` also sum the approximate Hessian from the Jacobians instead
+/// of calling `hessian`, so they should be left at their defaults.
+pub struct HessianClosureProblem<M, A, R, JF, H> {
+    apply_delta: A,
+    residuals: R,
+    jacobians: JF,
+    hessian: H,
+    model: PhantomData<fn(&M) -> M>,
+}
+
+impl<M, A, R, JF, H> HessianClosureProblem<M, A, R, JF, H> {
+    /// Bundles the closures, where `hessian` computes the approximate Hessian of a model.
+    pub fn new(apply_delta: A, residuals: R, jacobians: JF, hessian: H) -> Self {
+        Self {
+            apply_delta,
+            residuals,
+            jacobians,
+            hessian,
+            model: PhantomData,
+        }
+    }
+}
+
+impl<M, N, P, S, J, RS, JS, IJ, A, R, JF, H> LeastSquaresProblem<N, P, S, J>
+    for HessianClosureProblem<M, A, R, JF, H>
+where
+    N: Scalar,
+    P: Dim,
+    S: Dim,
+    J: Dim,
+    RS: Storage<N, J, S>,
+    JS: Storage<N, P, J>,
+    IJ: Iterator<Item = Matrix<N, P, J, JS>>,
+    A: Fn(&M, VectorN<N, P>) -> M,
+    R: Fn(&M) -> Matrix<N, J, S, RS>,
+    JF: Fn(&M) -> IJ,
+    H: Fn(&M) -> MatrixMN<N, P, P>,
+    DefaultAllocator: Allocator<N, P>,
+    DefaultAllocator: Allocator<N, P, P>,
+{
+    type Model = M;
+    type ResidualStorage = RS;
+    type JacobianStorage = JS;
+    type Jacobians<'a>
+        = IJ
+    where
+        Self: 'a;
+
+    fn apply_delta(&self, model: &M, delta: VectorN<N, P>) -> M {
+        (self.apply_delta)(model, delta)
+    }
+
+    fn residuals(&self, model: &M) -> Matrix<N, J, S, RS> {
+        (self.residuals)(model)
+    }
+
+    fn jacobians(&self, model: &M) -> IJ {
+        (self.jacobians)(model)
+    }
+
+    fn normal_equations(
+        &self,
+        model: &M,
+        residuals: &Matrix<N, J, S, RS>,
+    ) -> Option<(MatrixMN<N, P, P>, VectorN<N, P>)>
+    where
+        N: RealField,
+        P: Dim,
+        J: DimName,
+        DefaultAllocator: Allocator<N, J, P>,
+    {
+        let gradients = solve::gradients(self.try_jacobians(model)?, residuals);
+        Some(((self.hessian)(model), gradients))
+    }
+
+    fn modifies_normal_equations(&self) -> bool {
+        true
+    }
+}
+
+/// Adapts closures like [`ClosureProblem`], but `jacobians` returns a `Result` so that it can
+/// fail, such as at a branch cut or singularity where the Jacobian would otherwise be NaN.
+///
+/// If the Jacobians fail at the new guess of a step, that step is rejected and lambda is
+/// increased just like when the damped Hessian can't be inverted, so that the next step lands
+/// somewhere else. The error itself is discarded. If they fail at the initial guess or for
+/// `consecutive_divergence_limit` steps in a row, optimization terminates with
+/// [`TerminationReason::JacobianFailed`](crate::TerminationReason::JacobianFailed).
+///
+/// Since [`LeastSquaresProblem::jacobians`] can't fail, it yields no Jacobians at all where
+/// `jacobians` fails. Use [`LeastSquaresProblem::try_jacobians`] to tell the two apart.
+pub struct FallibleClosureProblem<M, A, R, JF> {
+    apply_delta: A,
+    residuals: R,
+    jacobians: JF,
+    model: PhantomData<fn(&M) -> M>,
+}
+
+impl<M, A, R, JF> FallibleClosureProblem<M, A, R, JF> {
+    /// Bundles the closures, where `jacobians` returns an error if it fails.
+    pub fn new(apply_delta: A, residuals: R, jacobians: JF) -> Self {
+        Self {
+            apply_delta,
+            residuals,
+            jacobians,
+            model: PhantomData,
+        }
+    }
+}
+
+impl<M, N, P, S, J, RS, JS, IJ, E, A, R, JF> LeastSquaresProblem<N, P, S, J>
+    for FallibleClosureProblem<M, A, R, JF>
+where
+    N: Scalar,
+    P: Dim,
+    S: Dim,
+    J: Dim,
+    RS: Storage<N, J, S>,
+    JS: Storage<N, P, J>,
+    IJ: Iterator<Item = Matrix<N, P, J, JS>>,
+    A: Fn(&M, VectorN<N, P>) -> M,
+    R: Fn(&M) -> Matrix<N, J, S, RS>,
+    JF: Fn(&M) -> Result<IJ, E>,
+    DefaultAllocator: Allocator<N, P>,
+{
+    type Model = M;
+    type ResidualStorage = RS;
+    type JacobianStorage = JS;
+    type Jacobians<'a>
+        = Flatten<option::IntoIter<IJ>>
+    where
+        Self: 'a;
+
+    fn apply_delta(&self, model: &M, delta: VectorN<N, P>) -> M {
+        (self.apply_delta)(model, delta)
+    }
+
+    fn residuals(&self, model: &M) -> Matrix<N, J, S, RS> {
+        (self.residuals)(model)
+    }
+
+    fn jacobians(&self, model: &M) -> Self::Jacobians<'_> {
+        (self.jacobians)(model).ok().into_iter().flatten()
+    }
+
+    fn try_jacobians(&self, model: &M) -> Option<Self::Jacobians<'_>> {
+        let jacobians = (self.jacobians)(model).ok()?;
+        Some(Some(jacobians).into_iter().flatten())
+    }
+}
+
+/// Adapts closures like [`ClosureProblem`], but every new guess is passed through `normalize`
+/// after its step is applied and before its residuals are computed.
+///
+/// This keeps models such as angles, unit vectors or unit quaternions in their canonical form,
+/// as described for `normalize` in [`optimize`](crate::optimize). If `normalize` returns `None`
+/// for the new guess of a step, such as a quaternion which has collapsed to zero, that step is
+/// rejected without computing its residuals and lambda is increased just like when the step
+/// didn't reduce the sum-of-squares. A `normalize` which can't fail just returns `Some`. The
+/// initial guess is never normalized, so it must already be valid.
+pub struct NormalizedClosureProblem<M, A, NF, R, JF> {
+    apply_delta: A,
+    normalize: NF,
+    residuals: R,
+    jacobians: JF,
+    model: PhantomData<fn(&M) -> M>,
+}
+
+impl<M, A, NF, R, JF> NormalizedClosureProblem<M, A, NF, R, JF> {
+    /// Bundles the closures, where `normalize` returns `None` if a model can't be normalized.
+    pub fn new(apply_delta: A, normalize: NF, residuals: R, jacobians: JF) -> Self {
+        Self {
+            apply_delta,
+            normalize,
+            residuals,
+            jacobians,
+            model: PhantomData,
+        }
+    }
+}
+
+impl<M, N, P, S, J, RS, JS, IJ, A, NF, R, JF> LeastSquaresProblem<N, P, S, J>
+    for NormalizedClosureProblem<M, A, NF, R, JF>
+where
+    N: Scalar,
+    P: Dim,
+    S: Dim,
+    J: Dim,
+    RS: Storage<N, J, S>,
+    JS: Storage<N, P, J>,
+    IJ: Iterator<Item = Matrix<N, P, J, JS>>,
+    A: Fn(&M, VectorN<N, P>) -> M,
+    NF: Fn(&M) -> Option<M>,
+    R: Fn(&M) -> Matrix<N, J, S, RS>,
+    JF: Fn(&M) -> IJ,
+    DefaultAllocator: Allocator<N, P>,
+{
+    type Model = M;
+    type ResidualStorage = RS;
+    type JacobianStorage = JS;
+    type Jacobians<'a>
+        = IJ
+    where
+        Self: 'a;
+
+    fn apply_delta(&self, model: &M, delta: VectorN<N, P>) -> M {
+        (self.apply_delta)(model, delta)
+    }
+
+    fn residuals(&self, model: &M) -> Matrix<N, J, S, RS> {
+        (self.residuals)(model)
+    }
+
+    fn jacobians(&self, model: &M) -> IJ {
+        (self.jacobians)(model)
+    }
+
+    fn normalize(&self, model: M) -> M {
+        (self.normalize)(&model).unwrap_or(model)
+    }
+
+    fn try_normalize(&self, model: M) -> Option<M> {
+        (self.normalize)(&model)
+    }
+}
+
+/// Adapts closures like [`ClosureProblem`], but `apply_delta` returns an `Option` so that it can
+/// reject a step, such as one which would flip the chirality of a rotation or make a rate
+/// constant negative.
+///
+/// If `apply_delta` returns `None`, the step would leave the region where the model is feasible.
+/// That step is rejected without computing its residuals and lambda is increased just like when
+/// the step didn't reduce the sum-of-squares, so that a shorter step is tried. A step is only
+/// rejected once every one of the `lambda_candidates` lambdas gave an infeasible or worse step,
+/// and if that happens `consecutive_divergence_limit` times in a row, optimization terminates with
+/// [`TerminationReason::ConsecutiveDivergence`](crate::TerminationReason::ConsecutiveDivergence).
+/// The initial guess must be feasible.
+///
+/// Since [`LeastSquaresProblem::apply_delta`] can't fail, it leaves the model unchanged where
+/// `apply_delta` rejects the step, which needs `M: Clone`. Use
+/// [`LeastSquaresProblem::try_apply_delta`] to tell the two apart.
+pub struct FeasibleClosureProblem<M, A, R, JF> {
+    apply_delta: A,
+    residuals: R,
+    jacobians: JF,
+    model: PhantomData<fn(&M) -> M>,
+}
+
+impl<M, A, R, JF> FeasibleClosureProblem<M, A, R, JF> {
+    /// Bundles the closures, where `apply_delta` returns `None` for an infeasible step.
+    pub fn new(apply_delta: A, residuals: R, jacobians: JF) -> Self {
+        Self {
+            apply_delta,
+            residuals,
+            jacobians,
+            model: PhantomData,
+        }
+    }
+}
+
+impl<M, N, P, S, J, RS, JS, IJ, A, R, JF> LeastSquaresProblem<N, P, S, J>
+    for FeasibleClosureProblem<M, A, R, JF>
+where
+    M: Clone,
+    N: Scalar,
+    P: Dim,
+    S: Dim,
+    J: Dim,
+    RS: Storage<N, J, S>,
+    JS: Storage<N, P, J>,
+    IJ: Iterator<Item = Matrix<N, P, J, JS>>,
+    A: Fn(&M, VectorN<N, P>) -> Option<M>,
+    R: Fn(&M) -> Matrix<N, J, S, RS>,
+    JF: Fn(&M) -> IJ,
+    DefaultAllocator: Allocator<N, P>,
+{
+    type Model = M;
+    type ResidualStorage = RS;
+    type JacobianStorage = JS;
+    type Jacobians<'a>
+        = IJ
+    where
+        Self: 'a;
+
+    fn apply_delta(&self, model: &M, delta: VectorN<N, P>) -> M {
+        (self.apply_delta)(model, delta).unwrap_or_else(|| model.clone())
+    }
+
+    fn try_apply_delta(&self, model: &M, delta: VectorN<N, P>) -> Option<M> {
+        (self.apply_delta)(model, delta)
+    }
+
+    fn residuals(&self, model: &M) -> Matrix<N, J, S, RS> {
+        (self.residuals)(model)
+    }
+
+    fn jacobians(&self, model: &M) -> IJ {
+        (self.jacobians)(model)
+    }
+}
@@ -0,0 +1,132 @@
+use crate::LeastSquaresProblem;
+use alloc::vec::{self, Vec};
+use core::marker::PhantomData;
+use nalgebra::{
+    allocator::Allocator, storage::Storage, DefaultAllocator, Dim, Dynamic, Matrix, Scalar,
+    VecStorage, Vector, VectorN, U1,
+};
+
+/// Adapts closures into a [`LeastSquaresProblem`] whose samples can have different numbers of
+/// residuals, with a sample for every residual of every block.
+///
+/// This requires the `alloc` feature. Rather than a residual matrix and an iterator of Jacobians,
+/// `blocks` returns an iterator over every block of residuals along with the Jacobian of their
+/// negatives, such as the observations of every landmark when some are seen more often than
+/// others. Each block has its own number of rows `B`, so `B` is usually
+/// [`Dynamic`](nalgebra::Dynamic), and the Jacobian of a block with `b` residuals has a column for
+/// each of them, just like the Jacobian of a sample in [`optimize`](crate::optimize).
+///
+/// The Hessian and gradients are the sum of the contributions of every block. Since each block
+/// gives its Jacobian with its residuals, `blocks` is evaluated once for every guess and the
+/// Jacobians of accepted guesses are kept rather than computed again.
+///
+/// # Panics
+///
+/// Evaluating the problem panics if a block has a different number of residuals than columns in
+/// its Jacobian.
+pub struct RaggedProblem<M, A, F> {
+    apply_delta: A,
+    blocks: F,
+    model: PhantomData<fn(&M) -> M>,
+}
+
+impl<M, A, F> RaggedProblem<M, A, F> {
+    /// Bundles the closures into a problem.
+    pub fn new(apply_delta: A, blocks: F) -> Self {
+        Self {
+            apply_delta,
+            blocks,
+            model: PhantomData,
+        }
+    }
+}
+
+impl<M, A, F> RaggedProblem<M, A, F> {
+    /// Flattens the blocks of `model` into a residual for every row and a Jacobian for every
+    /// column.
+    #[allow(clippy::type_complexity)]
+    fn flatten<N, P, B, RS, JS, IB>(
+        &self,
+        model: &M,
+    ) -> (
+        Matrix<N, U1, Dynamic, VecStorage<N, U1, Dynamic>>,
+        Vec<VectorN<N, P>>,
+    )
+    where
+        N: Scalar,
+        P: Dim,
+        B: Dim,
+        RS: Storage<N, B>,
+        JS: Storage<N, P, B>,
+        F: Fn(&M) -> IB,
+        IB: Iterator<Item = (Vector<N, B, RS>, Matrix<N, P, B, JS>)>,
+        DefaultAllocator: Allocator<N, P>,
+    {
+        let mut residuals = Vec::new();
+        let mut jacobians = Vec::new();
+        for (block_residuals, block_jacobian) in (self.blocks)(model) {
+            assert_eq!(
+                block_residuals.nrows(),
+                block_jacobian.ncols(),
+                "a block had a different number of residuals than columns in its Jacobian"
+            );
+            residuals.extend(block_residuals.iter().cloned());
+            jacobians.extend(
+                block_jacobian
+                    .column_iter()
+                    .map(|jacobian| jacobian.into_owned()),
+            );
+        }
+        let count = Dynamic::new(residuals.len());
+        (
+            Matrix::from_data(VecStorage::new(U1, count, residuals)),
+            jacobians,
+        )
+    }
+}
+
+impl<M, N, P, B, RS, JS, A, F, IB> LeastSquaresProblem<N, P, Dynamic, U1> for RaggedProblem<M, A, F>
+where
+    N: Scalar,
+    P: Dim,
+    B: Dim,
+    RS: Storage<N, B>,
+    JS: Storage<N, P, B>,
+    A: Fn(&M, VectorN<N, P>) -> M,
+    F: Fn(&M) -> IB,
+    IB: Iterator<Item = (Vector<N, B, RS>, Matrix<N, P, B, JS>)>,
+    DefaultAllocator: Allocator<N, P>,
+{
+    type Model = M;
+    type ResidualStorage = VecStorage<N, U1, Dynamic>;
+    type JacobianStorage = <DefaultAllocator as Allocator<N, P>>::Buffer;
+    type Jacobians<'a>
+        = vec::IntoIter<VectorN<N, P>>
+    where
+        Self: 'a;
+
+    fn apply_delta(&self, model: &M, delta: VectorN<N, P>) -> M {
+        (self.apply_delta)(model, delta)
+    }
+
+    fn residuals(&self, model: &M) -> Matrix<N, U1, Dynamic, Self::ResidualStorage> {
+        self.flatten(model).0
+    }
+
+    fn jacobians(&self, model: &M) -> Self::Jacobians<'_> {
+        self.flatten(model).1.into_iter()
+    }
+
+    fn residuals_and_jacobians(
+        &self,
+        model: &M,
+    ) -> (
+        Matrix<N, U1, Dynamic, Self::ResidualStorage>,
+        Option<Self::Jacobians<'_>>,
+    ) {
+        // The blocks always come with their Jacobians, so keep them rather than evaluating the
+        // blocks again if the step is accepted.
+        let (residuals, jacobians) = self.flatten(model);
+        (residuals, Some(jacobians.into_iter()))
+    }
+}
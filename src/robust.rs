@@ -0,0 +1,312 @@
+use crate::{weighted::WeightedJacobians, LeastSquaresProblem};
+use nalgebra::{
+    allocator::Allocator,
+    storage::{Owned, Storage},
+    DefaultAllocator, Dim, Matrix, MatrixMN, RealField, VectorN,
+};
+
+/// A robust loss (also known as an M-estimator) which reduces the influence of residuals that
+/// are too large to be explained by the noise, such as outliers.
+///
+/// Losses are applied with iteratively reweighted least squares by [`RobustProblem`]. On every
+/// evaluation, each residual `r` is replaced by `sign(r)·sqrt(ρ(r²))`, which has the same sign
+/// and whose square is its cost `ρ` ([`LossFunction::cost`]). Its column of the Jacobian is
+/// multiplied by the derivative of that in respect to `r`, which is `w(r²)·|r| / sqrt(ρ(r²))`,
+/// where `w` is [`LossFunction::weight`]. This makes the gradient `w(r²)·r·J`, which is zero at
+/// the M-estimate.
+///
+/// Both methods take the square of the residual. The weight must be the derivative of the
+/// cost in respect to the squared residual, otherwise the Jacobian doesn't match the cost and
+/// the fit converges somewhere else. A loss should have a cost of about `r²` and a weight of
+/// about `1` for small residuals so that inliers are fit like they would be by plain least
+/// squares.
+pub trait LossFunction<N> {
+    /// The derivative of the cost in respect to the squared residual.
+    fn weight(&self, squared_residual: N) -> N;
+
+    /// The contribution of a residual to the sum-of-squares, which replaces `r²`.
+    fn cost(&self, squared_residual: N) -> N;
+}
+
+/// The plain squared loss, which leaves every residual unchanged.
+///
+/// Optimizing with this loss is identical to ordinary least squares.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Squared;
+
+impl<N> LossFunction<N> for Squared
+where
+    N: RealField,
+{
+    fn weight(&self, _: N) -> N {
+        N::one()
+    }
+
+    fn cost(&self, squared_residual: N) -> N {
+        squared_residual
+    }
+}
+
+/// The Huber loss, which is quadratic for residuals with a magnitude below `delta` and linear
+/// above it.
+///
+/// Unlike a squared loss, a single outlier can only pull the fit with a constant force.
+/// `delta` should be a few times the standard deviation of the inlier residuals.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Huber<N> {
+    pub delta: N,
+}
+
+impl<N> LossFunction<N> for Huber<N>
+where
+    N: RealField,
+{
+    fn weight(&self, squared_residual: N) -> N {
+        if squared_residual <= self.delta * self.delta {
+            N::one()
+        } else {
+            self.delta / squared_residual.sqrt()
+        }
+    }
+
+    fn cost(&self, squared_residual: N) -> N {
+        if squared_residual <= self.delta * self.delta {
+            squared_residual
+        } else {
+            let two = N::one() + N::one();
+            self.delta * (two * squared_residual.sqrt() - self.delta)
+        }
+    }
+}
+
+/// The soft L1 (or pseudo-Huber) loss, whose cost is `2δ²(sqrt(1 + (r/δ)²) - 1)`.
+///
+/// This behaves like [`Huber`] with the same `delta`, quadratic for small residuals and linear
+/// for large ones, but the transition between them is smooth. The weight
+/// `1 / sqrt(1 + (r/δ)²)` has no kink at `delta`, so the reweighted Jacobian doesn't jump when a
+/// residual crosses it, which keeps the fit from chattering between iterations when residuals
+/// lie near `delta`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SoftL1<N> {
+    pub delta: N,
+}
+
+impl<N> LossFunction<N> for SoftL1<N>
+where
+    N: RealField,
+{
+    fn weight(&self, squared_residual: N) -> N {
+        N::one() / (N::one() + squared_residual / (self.delta * self.delta)).sqrt()
+    }
+
+    fn cost(&self, squared_residual: N) -> N {
+        let two = N::one() + N::one();
+        let squared_delta = self.delta * self.delta;
+        two * squared_delta * ((N::one() + squared_residual / squared_delta).sqrt() - N::one())
+    }
+}
+
+/// The Cauchy (or Lorentzian) loss, whose weight is `1 / (1 + (r/scale)²)`.
+///
+/// This suppresses large residuals much more strongly than [`Huber`], which makes it suitable
+/// for heavy-tailed noise. However, the loss isn't convex, so it can converge to a bad local
+/// minima unless the initial guess is already close to the inliers. `scale` should be around
+/// the standard deviation of the inlier residuals.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Cauchy<N> {
+    pub scale: N,
+}
+
+impl<N> LossFunction<N> for Cauchy<N>
+where
+    N: RealField,
+{
+    fn weight(&self, squared_residual: N) -> N {
+        N::one() / (N::one() + squared_residual / (self.scale * self.scale))
+    }
+
+    fn cost(&self, squared_residual: N) -> N {
+        let squared_scale = self.scale * self.scale;
+        squared_scale * (squared_residual / squared_scale).ln_1p()
+    }
+}
+
+/// Tukey's biweight loss, whose weight is `(1 - (r/c)²)²` for residuals with a magnitude below
+/// `c` and zero above it.
+///
+/// Residuals above `c` are rejected entirely. Their cost is the constant `c²/3` and their
+/// Jacobian is zeroed, so they contribute nothing to the gradient or the approximate Hessian.
+/// Since the set of rejected residuals can change whenever the model does, the effective
+/// number of samples changes between iterations. Like [`Cauchy`], the loss isn't convex and
+/// needs a good initial guess. `c` is commonly set to `4.685` times the standard deviation of
+/// the inlier residuals.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Tukey<N> {
+    pub c: N,
+}
+
+impl<N> LossFunction<N> for Tukey<N>
+where
+    N: RealField,
+{
+    fn weight(&self, squared_residual: N) -> N {
+        if squared_residual <= self.c * self.c {
+            let inlier = N::one() - squared_residual / (self.c * self.c);
+            inlier * inlier
+        } else {
+            N::zero()
+        }
+    }
+
+    fn cost(&self, squared_residual: N) -> N {
+        let three = N::one() + N::one() + N::one();
+        let saturated = self.c * self.c / three;
+        if squared_residual <= self.c * self.c {
+            let inlier = N::one() - squared_residual / (self.c * self.c);
+            saturated * (N::one() - inlier * inlier * inlier)
+        } else {
+            saturated
+        }
+    }
+}
+
+/// Adapts a [`LeastSquaresProblem`] so that its residuals are reweighted by a [`LossFunction`],
+/// which reduces the influence of outliers on the fit.
+///
+/// The wrapped problem should be left unweighted. The weights are computed from the residuals
+/// of the wrapped problem, so the optimizer asks it for its Jacobians along with the residuals
+/// of every guess it evaluates. They are only iterated if the step is accepted, so
+/// [`LeastSquaresProblem::try_jacobians`] of the wrapped problem should be cheap until then,
+/// like it is for closures that return a lazy iterator.
+///
+/// The sum-of-squares which is minimized and reported is the sum of [`LossFunction::cost`] over
+/// every residual, which is the squared norm of the reweighted residuals. If the wrapped problem
+/// overrides [`LeastSquaresProblem::sum_of_squares`], such as a
+/// [`CostProblem`](crate::CostProblem), that override isn't used, since it would be given the
+/// reweighted residuals rather than its own. Wrap the `RobustProblem` in the
+/// [`CostProblem`](crate::CostProblem) instead, so that the cost sees the reweighted residuals.
+/// Likewise, if the wrapped problem overrides [`LeastSquaresProblem::normal_equations`], such as to
+/// sum them in parallel, that override isn't used, since it would sum the unweighted Jacobians.
+///
+/// Closures can be made robust by wrapping them in a [`ClosureProblem`](crate::ClosureProblem)
+/// first, and the result is optimized with [`optimize_problem`](crate::optimize_problem).
+pub struct RobustProblem<LSP, L> {
+    problem: LSP,
+    loss: L,
+}
+
+impl<LSP, L> RobustProblem<LSP, L> {
+    /// Reweights the residuals of `problem` by `loss`.
+    pub fn new(problem: LSP, loss: L) -> Self {
+        Self { problem, loss }
+    }
+
+    /// Replaces each residual with the one of the same sign whose square is its cost.
+    fn reweight<N, J, S, RS>(&self, residuals: &Matrix<N, J, S, RS>) -> MatrixMN<N, J, S>
+    where
+        N: RealField,
+        J: Dim,
+        S: Dim,
+        RS: Storage<N, J, S>,
+        L: LossFunction<N>,
+        DefaultAllocator: Allocator<N, J, S>,
+    {
+        residuals.map(|residual| {
+            let reweighted = self.loss.cost(residual * residual).sqrt();
+            if residual < N::zero() {
+                -reweighted
+            } else {
+                reweighted
+            }
+        })
+    }
+
+    /// The derivative of every reweighted residual in respect to the unweighted one, which its
+    /// column of the Jacobian is multiplied by.
+    fn weights<N, J, S, RS>(&self, residuals: &Matrix<N, J, S, RS>) -> MatrixMN<N, J, S>
+    where
+        N: RealField,
+        J: Dim,
+        S: Dim,
+        RS: Storage<N, J, S>,
+        L: LossFunction<N>,
+        DefaultAllocator: Allocator<N, J, S>,
+    {
+        residuals.map(|residual| {
+            let squared_residual = residual * residual;
+            let weight = self.loss.weight(squared_residual);
+            let cost = self.loss.cost(squared_residual);
+            if cost > N::zero() {
+                weight * residual.abs() / cost.sqrt()
+            } else {
+                // The limit as the residual goes to zero, where the cost is about `w(0)·r²`.
+                weight.sqrt()
+            }
+        })
+    }
+}
+
+impl<N, P, S, J, LSP, L> LeastSquaresProblem<N, P, S, J> for RobustProblem<LSP, L>
+where
+    N: RealField,
+    P: Dim,
+    S: Dim,
+    J: Dim,
+    LSP: LeastSquaresProblem<N, P, S, J>,
+    L: LossFunction<N>,
+    DefaultAllocator: Allocator<N, P>,
+    DefaultAllocator: Allocator<N, J, S>,
+    DefaultAllocator: Allocator<N, P, J>,
+{
+    type Model = LSP::Model;
+    type ResidualStorage = Owned<N, J, S>;
+    type JacobianStorage = Owned<N, P, J>;
+    type Jacobians<'a>
+        = WeightedJacobians<LSP::Jacobians<'a>, N, J, S>
+    where
+        Self: 'a;
+
+    fn apply_delta(&self, model: &Self::Model, delta: VectorN<N, P>) -> Self::Model {
+        self.problem.apply_delta(model, delta)
+    }
+
+    fn try_apply_delta(&self, model: &Self::Model, delta: VectorN<N, P>) -> Option<Self::Model> {
+        self.problem.try_apply_delta(model, delta)
+    }
+
+    fn residuals(&self, model: &Self::Model) -> MatrixMN<N, J, S> {
+        self.reweight(&self.problem.residuals(model))
+    }
+
+    fn jacobians(&self, model: &Self::Model) -> Self::Jacobians<'_> {
+        // The weights depend on the unweighted residuals, so those must be computed again.
+        let weights = self.weights(&self.problem.residuals(model));
+        WeightedJacobians::new(self.problem.jacobians(model), weights)
+    }
+
+    fn try_jacobians(&self, model: &Self::Model) -> Option<Self::Jacobians<'_>> {
+        let jacobians = self.problem.try_jacobians(model)?;
+        let weights = self.weights(&self.problem.residuals(model));
+        Some(WeightedJacobians::new(jacobians, weights))
+    }
+
+    fn residuals_and_jacobians(
+        &self,
+        model: &Self::Model,
+    ) -> (MatrixMN<N, J, S>, Option<Self::Jacobians<'_>>) {
+        // Both the reweighted residuals and the weights come from the same unweighted ones.
+        let (residuals, jacobians) = self.problem.residuals_and_jacobians(model);
+        let jacobians = jacobians
+            .or_else(|| self.problem.try_jacobians(model))
+            .map(|jacobians| WeightedJacobians::new(jacobians, self.weights(&residuals)));
+        (self.reweight(&residuals), jacobians)
+    }
+
+    fn normalize(&self, model: Self::Model) -> Self::Model {
+        self.problem.normalize(model)
+    }
+
+    fn try_normalize(&self, model: Self::Model) -> Option<Self::Model> {
+        self.problem.try_normalize(model)
+    }
+}
@@ -0,0 +1,189 @@
+use crate::LeastSquaresProblem;
+use nalgebra::{
+    allocator::Allocator,
+    storage::{Owned, Storage},
+    DefaultAllocator, Dim, DimName, Matrix, MatrixMN, RealField, VectorN,
+};
+
+/// Adapts a [`LeastSquaresProblem`] so that the steps are solved for in a parameter space where
+/// each parameter is divided by its scale.
+///
+/// When parameters differ by many orders of magnitude, such as a rate constant around `1e-9`
+/// next to an amplitude around `1e3`, the approximate Hessian is badly conditioned and steps
+/// are dominated by the largest parameters. Each scale should be roughly the expected
+/// magnitude of its parameter, or the distance over which it must move, so that every scaled
+/// parameter is on the order of `1`. Every scale must be nonzero.
+///
+/// A step `δ` in the scaled space is the step `diag(scale)δ` in the original space, so the
+/// wrapped problem receives the unscaled step and stays in the original parameter space, while
+/// the Jacobian with respect to the scaled parameters has each row multiplied by its scale.
+/// Likewise, the approximate Hessian `H` and the gradients `g` from the
+/// [`normal_equations`](LeastSquaresProblem::normal_equations) of the wrapped problem become
+/// `DHD` and `Dg` with `D = diag(scale)`, so an override of them, such as the prior of a
+/// [`PriorProblem`](crate::PriorProblem), is solved for in the scaled space too. Its
+/// [`sum_of_squares`](LeastSquaresProblem::sum_of_squares) is used as is, since the cost doesn't
+/// depend on how the parameters are scaled.
+///
+/// With [`DampingMode::Diagonal`](crate::DampingMode::Diagonal), the damping is already
+/// invariant to the scale of the parameters, but scaling still improves the precision of the
+/// solve. With [`DampingMode::Identity`](crate::DampingMode::Identity), scaling changes which
+/// directions are damped the most, and is usually necessary for the steps to make progress.
+pub struct ScaledProblem<LSP, N, P>
+where
+    N: RealField,
+    P: Dim,
+    DefaultAllocator: Allocator<N, P>,
+{
+    problem: LSP,
+    scale: VectorN<N, P>,
+}
+
+impl<LSP, N, P> ScaledProblem<LSP, N, P>
+where
+    N: RealField,
+    P: Dim,
+    DefaultAllocator: Allocator<N, P>,
+{
+    /// Scales the parameters of `problem` by `scale`.
+    pub fn new(problem: LSP, scale: VectorN<N, P>) -> Self {
+        Self { problem, scale }
+    }
+}
+
+impl<N, P, S, J, LSP> LeastSquaresProblem<N, P, S, J> for ScaledProblem<LSP, N, P>
+where
+    N: RealField,
+    P: Dim,
+    S: Dim,
+    J: Dim,
+    LSP: LeastSquaresProblem<N, P, S, J>,
+    DefaultAllocator: Allocator<N, P>,
+    DefaultAllocator: Allocator<N, P, J>,
+{
+    type Model = LSP::Model;
+    type ResidualStorage = LSP::ResidualStorage;
+    type JacobianStorage = Owned<N, P, J>;
+    type Jacobians<'a>
+        = ScaledJacobians<LSP::Jacobians<'a>, N, P>
+    where
+        Self: 'a;
+
+    fn apply_delta(&self, model: &Self::Model, delta: VectorN<N, P>) -> Self::Model {
+        self.problem
+            .apply_delta(model, delta.component_mul(&self.scale))
+    }
+
+    fn try_apply_delta(&self, model: &Self::Model, delta: VectorN<N, P>) -> Option<Self::Model> {
+        self.problem
+            .try_apply_delta(model, delta.component_mul(&self.scale))
+    }
+
+    fn residuals(&self, model: &Self::Model) -> Matrix<N, J, S, Self::ResidualStorage> {
+        self.problem.residuals(model)
+    }
+
+    fn jacobians(&self, model: &Self::Model) -> Self::Jacobians<'_> {
+        ScaledJacobians {
+            jacobians: self.problem.jacobians(model),
+            scale: self.scale.clone(),
+        }
+    }
+
+    fn try_jacobians(&self, model: &Self::Model) -> Option<Self::Jacobians<'_>> {
+        Some(ScaledJacobians {
+            jacobians: self.problem.try_jacobians(model)?,
+            scale: self.scale.clone(),
+        })
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn residuals_and_jacobians(
+        &self,
+        model: &Self::Model,
+    ) -> (
+        Matrix<N, J, S, Self::ResidualStorage>,
+        Option<Self::Jacobians<'_>>,
+    ) {
+        let (residuals, jacobians) = self.problem.residuals_and_jacobians(model);
+        let jacobians = jacobians.map(|jacobians| ScaledJacobians {
+            jacobians,
+            scale: self.scale.clone(),
+        });
+        (residuals, jacobians)
+    }
+
+    fn normal_equations(
+        &self,
+        model: &Self::Model,
+        residuals: &Matrix<N, J, S, Self::ResidualStorage>,
+    ) -> Option<(MatrixMN<N, P, P>, VectorN<N, P>)>
+    where
+        J: DimName,
+        DefaultAllocator: Allocator<N, P, P>,
+        DefaultAllocator: Allocator<N, J, P>,
+    {
+        let (mut hessian, gradients) = self.problem.normal_equations(model, residuals)?;
+        for (mut row, &scale) in hessian.row_iter_mut().zip(self.scale.iter()) {
+            row *= scale;
+        }
+        for (mut column, &scale) in hessian.column_iter_mut().zip(self.scale.iter()) {
+            column *= scale;
+        }
+        Some((hessian, gradients.component_mul(&self.scale)))
+    }
+
+    fn modifies_normal_equations(&self) -> bool {
+        self.problem.modifies_normal_equations()
+    }
+
+    fn sum_of_squares(
+        &self,
+        model: &Self::Model,
+        residuals: &Matrix<N, J, S, Self::ResidualStorage>,
+    ) -> N
+    where
+        N: RealField,
+    {
+        self.problem.sum_of_squares(model, residuals)
+    }
+
+    fn normalize(&self, model: Self::Model) -> Self::Model {
+        self.problem.normalize(model)
+    }
+
+    fn try_normalize(&self, model: Self::Model) -> Option<Self::Model> {
+        self.problem.try_normalize(model)
+    }
+}
+
+/// Multiplies each row of the Jacobian of every sample by the scale of its parameter.
+pub struct ScaledJacobians<I, N, P>
+where
+    N: RealField,
+    P: Dim,
+    DefaultAllocator: Allocator<N, P>,
+{
+    jacobians: I,
+    scale: VectorN<N, P>,
+}
+
+impl<I, N, P, J, JS> Iterator for ScaledJacobians<I, N, P>
+where
+    I: Iterator<Item = Matrix<N, P, J, JS>>,
+    N: RealField,
+    P: Dim,
+    J: Dim,
+    JS: Storage<N, P, J>,
+    DefaultAllocator: Allocator<N, P>,
+    DefaultAllocator: Allocator<N, P, J>,
+{
+    type Item = MatrixMN<N, P, J>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut jacobian = self.jacobians.next()?.into_owned();
+        for (mut row, &scale) in jacobian.row_iter_mut().zip(self.scale.iter()) {
+            row *= scale;
+        }
+        Some(jacobian)
+    }
+}
@@ -0,0 +1,85 @@
+use crate::Config;
+use core::cell::Cell;
+use nalgebra::RealField;
+
+/// Chooses `lambda_converge` and `lambda_diverge` before every iteration of
+/// [`LevenbergMarquardt::run_with_schedule`](crate::LevenbergMarquardt::run_with_schedule).
+///
+/// A fixed pair of factors is a compromise on long runs, so a schedule can, for instance,
+/// make lambda change less aggressively as the fit converges. The factors are only used by
+/// [`DampingStrategy::Multiplicative`](crate::DampingStrategy::Multiplicative), and they
+/// should satisfy the same invariants as the config, which are checked by
+/// [`Config::validate`](crate::Config::validate).
+pub trait LambdaSchedule<N> {
+    /// The `(lambda_converge, lambda_diverge)` to use for the iteration with index
+    /// `iteration`, given the `sum_of_squares` of the current guess.
+    fn factors(&self, iteration: usize, sum_of_squares: N) -> (N, N);
+}
+
+/// A config is the schedule which always uses its own `lambda_converge` and `lambda_diverge`,
+/// which is the behavior of every other `optimize` function.
+impl<N> LambdaSchedule<N> for Config<N>
+where
+    N: Copy,
+{
+    fn factors(&self, _: usize, _: N) -> (N, N) {
+        (self.lambda_convege, self.lambda_diverge)
+    }
+}
+
+/// A schedule which interpolates from `aggressive` factors to `conservative` ones as the
+/// sum-of-squares decreases.
+///
+/// The interpolation is linear in the relative reduction `1 - sum_of_squares / initial`, where
+/// `initial` is the sum-of-squares that the schedule was given for iteration `0`. So the first
+/// iterations use `aggressive` to get close quickly, and once most of the sum-of-squares has
+/// been removed, the factors approach `conservative` so that lambda is changed more carefully
+/// near the solution. Each pair is `(lambda_converge, lambda_diverge)`.
+#[derive(Clone, Debug)]
+pub struct InterpolatedSchedule<N>
+where
+    N: Copy,
+{
+    aggressive: (N, N),
+    conservative: (N, N),
+    initial: Cell<Option<N>>,
+}
+
+impl<N> InterpolatedSchedule<N>
+where
+    N: Copy,
+{
+    pub fn new(aggressive: (N, N), conservative: (N, N)) -> Self {
+        Self {
+            aggressive,
+            conservative,
+            initial: Cell::new(None),
+        }
+    }
+}
+
+impl<N> LambdaSchedule<N> for InterpolatedSchedule<N>
+where
+    N: RealField,
+{
+    fn factors(&self, iteration: usize, sum_of_squares: N) -> (N, N) {
+        let initial = match self.initial.get() {
+            Some(initial) if iteration != 0 => initial,
+            _ => sum_of_squares,
+        };
+        self.initial.set(Some(initial));
+        let reduction = if initial > N::zero() {
+            (N::one() - sum_of_squares / initial)
+                .max(N::zero())
+                .min(N::one())
+        } else {
+            N::zero()
+        };
+        let (aggressive_converge, aggressive_diverge) = self.aggressive;
+        let (conservative_converge, conservative_diverge) = self.conservative;
+        (
+            aggressive_converge + (conservative_converge - aggressive_converge) * reduction,
+            aggressive_diverge + (conservative_diverge - aggressive_diverge) * reduction,
+        )
+    }
+}
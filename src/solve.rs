@@ -0,0 +1,555 @@
+use crate::{SolveMethod, Workspace};
+use nalgebra::{
+    allocator::Allocator,
+    constraint::{DimEq, ShapeConstraint},
+    dimension::{DimMin, DimMinimum},
+    storage::Storage,
+    Cholesky, DefaultAllocator, Dim, DimName, Matrix, MatrixMN, RealField, VectorN, U1,
+};
+
+/// The number of parameters `P` if it is known at compile time, or zero if it is only known at
+/// runtime.
+///
+/// Everything sized by `P` starts out with this dimension until the first Jacobian is seen,
+/// which is when a runtime number of parameters becomes known.
+pub(crate) fn initial_dim<P: Dim>() -> P {
+    P::from_usize(P::try_to_usize().unwrap_or(0))
+}
+
+/// The linearization of the residuals around the current guess, which is solved for a step
+/// each iteration.
+#[derive(Clone)]
+pub(crate) enum LinearSystem<N, P>
+where
+    N: RealField,
+    P: Dim,
+    DefaultAllocator: Allocator<N, P, P>,
+    DefaultAllocator: Allocator<N, P>,
+{
+    /// The approximate Hessian `JJᵀ` of the normal equations.
+    Normal(MatrixMN<N, P, P>),
+    /// The upper-triangular `R` of the QR decomposition of the stacked Jacobian and `Qᵀr`.
+    Qr(MatrixMN<N, P, P>, VectorN<N, P>),
+    /// The approximate Hessian `JJᵀ` and the tolerance below which the singular values of the
+    /// damped system are treated as zero.
+    Svd(MatrixMN<N, P, P>, N),
+}
+
+impl<N, P> LinearSystem<N, P>
+where
+    N: RealField,
+    P: DimMin<P>,
+    DefaultAllocator: Allocator<N, P, P>,
+    DefaultAllocator: Allocator<N, P>,
+    ShapeConstraint: DimEq<DimMinimum<P, P>, P>,
+{
+    /// An empty system of `p` parameters which will be solved with `method`.
+    pub(crate) fn zeros(method: SolveMethod<N>, p: P) -> Self {
+        match method {
+            SolveMethod::NormalEquations => Self::Normal(MatrixMN::<N, P, P>::zeros_generic(p, p)),
+            SolveMethod::Qr => Self::Qr(
+                MatrixMN::<N, P, P>::zeros_generic(p, p),
+                VectorN::<N, P>::zeros_generic(p, U1),
+            ),
+            SolveMethod::Svd { rank_tolerance } => {
+                Self::Svd(MatrixMN::<N, P, P>::zeros_generic(p, p), rank_tolerance)
+            }
+        }
+    }
+
+    /// Replaces the system with the approximate Hessian `JJᵀ` of the normal equations.
+    pub(crate) fn set_normal(&mut self, new_hessian: &MatrixMN<N, P, P>) {
+        match self {
+            Self::Normal(hessian) if hessian.shape() == new_hessian.shape() => {
+                hessian.copy_from(new_hessian)
+            }
+            _ => *self = Self::Normal(new_hessian.clone()),
+        }
+    }
+
+    /// Replaces the system with `new_hessian` for `method`. The Hessian is never formed with
+    /// [`SolveMethod::Qr`], so it falls back to the normal equations.
+    pub(crate) fn set_hessian(&mut self, method: SolveMethod<N>, new_hessian: &MatrixMN<N, P, P>) {
+        match method {
+            SolveMethod::Svd { rank_tolerance } => self.set_svd(new_hessian, rank_tolerance),
+            _ => self.set_normal(new_hessian),
+        }
+    }
+
+    /// Replaces the system with the approximate Hessian `JJᵀ`, which will be solved with its
+    /// pseudo-inverse truncated at `rank_tolerance`.
+    pub(crate) fn set_svd(&mut self, new_hessian: &MatrixMN<N, P, P>, rank_tolerance: N) {
+        match self {
+            Self::Svd(hessian, tolerance) if hessian.shape() == new_hessian.shape() => {
+                hessian.copy_from(new_hessian);
+                *tolerance = rank_tolerance;
+            }
+            _ => *self = Self::Svd(new_hessian.clone(), rank_tolerance),
+        }
+    }
+
+    /// Replaces the system and `gradients` with the QR decomposition of the Jacobian with
+    /// every residual stacked as a row.
+    ///
+    /// Each row is rotated into `R` one at a time with Givens rotations, so neither the stacked
+    /// Jacobian nor the approximate Hessian is ever formed. This avoids squaring the condition
+    /// number of the Jacobian like the normal equations do. The existing storage is overwritten
+    /// in place unless the system was built for the normal equations.
+    pub(crate) fn set_qr<J, S, JS, RS>(
+        &mut self,
+        gradients: &mut VectorN<N, P>,
+        jacobians: impl Iterator<Item = Matrix<N, P, J, JS>>,
+        residuals: &Matrix<N, J, S, RS>,
+    ) where
+        J: DimName,
+        S: Dim,
+        JS: Storage<N, P, J>,
+        RS: Storage<N, J, S>,
+    {
+        let mut jacobians = jacobians.peekable();
+        let p = jacobians
+            .peek()
+            .map_or_else(|| self.dim(), |jacobian| jacobian.data.shape().0);
+        match self {
+            Self::Qr(r, qtr) if r.data.shape().0 == p => {
+                r.fill(N::zero());
+                qtr.fill(N::zero());
+            }
+            _ => *self = Self::zeros(SolveMethod::Qr, p),
+        }
+        if let Self::Qr(r, qtr) = self {
+            for (jacobian, res) in jacobians.zip(residuals.column_iter()) {
+                for (row, &rhs) in jacobian.column_iter().zip(res.iter()) {
+                    rotate_into(r, qtr, row.into_owned(), rhs);
+                }
+            }
+            // The gradients are Jr = RᵀQᵀr.
+            *gradients = r.tr_mul(qtr);
+        }
+    }
+
+    /// The number of parameters in the system.
+    pub(crate) fn dim(&self) -> P {
+        match self {
+            Self::Normal(hessian) | Self::Svd(hessian, _) => hessian.data.shape().0,
+            Self::Qr(r, _) => r.data.shape().0,
+        }
+    }
+
+    /// The undamped approximate Hessian `JJᵀ`.
+    pub(crate) fn hessian(&self) -> MatrixMN<N, P, P> {
+        match self {
+            Self::Normal(hessian) | Self::Svd(hessian, _) => hessian.clone(),
+            // JJᵀ = RᵀQᵀQR = RᵀR.
+            Self::Qr(r, _) => r.tr_mul(r),
+        }
+    }
+
+    /// The diagonal of the approximate Hessian `JJᵀ`.
+    pub(crate) fn hessian_diagonal(&self) -> VectorN<N, P> {
+        match self {
+            Self::Normal(hessian) | Self::Svd(hessian, _) => hessian.diagonal(),
+            // The diagonal of RᵀR is the squared norm of each column of R.
+            Self::Qr(r, _) => VectorN::<N, P>::from_fn_generic(r.data.shape().0, U1, |i, _| {
+                r.column(i).norm_squared()
+            }),
+        }
+    }
+
+    /// Estimates the condition number of the undamped approximate Hessian `JJᵀ`.
+    ///
+    /// With [`SolveMethod::Svd`], this is the exact ratio of its largest to smallest eigenvalue.
+    /// Otherwise it is the squared ratio of the largest to smallest diagonal entry of its
+    /// triangular factor, which is cheap but can underestimate the true condition number.
+    /// Returns `None` if `JJᵀ` is singular or its eigenvalues couldn't be found.
+    pub(crate) fn condition_estimate(&self) -> Option<N> {
+        // The eigenvalues of JJᵀ, or the squares of the diagonal of its triangular factor.
+        let eigenvalues = match self {
+            Self::Normal(hessian) => Cholesky::new(hessian.clone())?
+                .unpack_dirty()
+                .diagonal()
+                .map(|l| l * l),
+            Self::Qr(r, _) => r.diagonal().map(|r| r * r),
+            Self::Svd(hessian, _) => {
+                let mut eigenvalues = hessian.clone();
+                symmetric_eigen(&mut eigenvalues)?;
+                eigenvalues.diagonal()
+            }
+        };
+        let estimate = eigenvalues.max() / eigenvalues.min();
+        if eigenvalues.min() > N::zero() && estimate.is_finite() {
+            Some(estimate)
+        } else {
+            None
+        }
+    }
+
+    /// Computes `vᵀJJᵀv`, which is the squared norm of the change in the residuals that the
+    /// linearization predicts for the step `v`.
+    pub(crate) fn quadratic_form(&self, v: &VectorN<N, P>) -> N {
+        match self {
+            Self::Normal(hessian) | Self::Svd(hessian, _) => v.dot(&(hessian * v)),
+            Self::Qr(r, _) => (r * v).norm_squared(),
+        }
+    }
+
+    /// Solves `(JJᵀ + λD)δ = g` for the step `δ`, where `damping` is the diagonal of `D`.
+    ///
+    /// The damped system is built in the scratch space of `workspace` so that the undamped
+    /// system can be reused for other values of lambda. Returns `None` if the damped system is
+    /// singular, unless it is solved with its pseudo-inverse, in which case the rank of the
+    /// damped system is recorded in `workspace`, or `None` is returned if its eigenvalues
+    /// couldn't be found.
+    pub(crate) fn solve(
+        &self,
+        gradients: &VectorN<N, P>,
+        damping: &VectorN<N, P>,
+        lambda: N,
+        workspace: &mut Workspace<N, P>,
+    ) -> Option<VectorN<N, P>> {
+        let p = self.dim();
+        if workspace.damped.data.shape().0 != p {
+            workspace.damped = MatrixMN::zeros_generic(p, p);
+            workspace.rhs = VectorN::zeros_generic(p, U1);
+        }
+        workspace.rank = None;
+        let damped = &mut workspace.damped;
+        match self {
+            Self::Normal(hessian) => {
+                // Compute JJᵀ + λD.
+                damped.copy_from(hessian);
+                for (i, &damping) in damping.iter().enumerate() {
+                    damped[(i, i)] += damping * lambda;
+                }
+
+                // JJᵀ + λD is symmetric positive definite unless it is degenerate, so try to
+                // solve it with a Cholesky decomposition before falling back to an LU
+                // decomposition. Only the step is needed, so the inverse is never formed.
+                match Cholesky::new(damped.clone()) {
+                    Some(cholesky) => Some(cholesky.solve(gradients)),
+                    None => {
+                        let rhs = &mut workspace.rhs;
+                        rhs.copy_from(gradients);
+                        if lu_solve(damped, rhs) {
+                            Some(rhs.clone())
+                        } else {
+                            None
+                        }
+                    }
+                }
+            }
+            Self::Qr(r, qtr) => {
+                // The damping is equivalent to stacking the rows of √(λD) under the Jacobian
+                // with residuals of zero, so rotate those into a copy of R too.
+                let rhs = &mut workspace.rhs;
+                damped.copy_from(r);
+                rhs.copy_from(qtr);
+                for (i, &damping) in damping.iter().enumerate() {
+                    let mut row = VectorN::<N, P>::zeros_generic(p, U1);
+                    row[i] = (lambda * damping).sqrt();
+                    rotate_into(damped, rhs, row, N::zero());
+                }
+                damped.solve_upper_triangular(rhs)
+            }
+            Self::Svd(hessian, rank_tolerance) => {
+                damped.copy_from(hessian);
+                for (i, &damping) in damping.iter().enumerate() {
+                    damped[(i, i)] += damping * lambda;
+                }
+                let (delta, rank) = truncated_solve(damped, gradients, *rank_tolerance)?;
+                workspace.rank = Some(rank);
+                Some(delta)
+            }
+        }
+    }
+}
+
+/// Accumulates the approximate Hessian `JJᵀ` and the gradients `Jr` from every sample.
+pub(crate) fn normal_equations<N, P, S, J, JS, RS>(
+    jacobians: impl Iterator<Item = Matrix<N, P, J, JS>>,
+    residuals: &Matrix<N, J, S, RS>,
+) -> (MatrixMN<N, P, P>, VectorN<N, P>)
+where
+    N: RealField,
+    P: Dim,
+    S: Dim,
+    J: DimName,
+    JS: Storage<N, P, J>,
+    RS: Storage<N, J, S>,
+    DefaultAllocator: Allocator<N, P, P>,
+    DefaultAllocator: Allocator<N, P>,
+    DefaultAllocator: Allocator<N, J, P>,
+{
+    let mut samples = jacobians.zip(residuals.column_iter()).peekable();
+    let p = samples
+        .peek()
+        .map_or_else(initial_dim, |(jacobian, _)| jacobian.data.shape().0);
+    samples.fold(
+        (MatrixMN::zeros_generic(p, p), VectorN::zeros_generic(p, U1)),
+        |(hessian, gradients): (MatrixMN<N, P, P>, VectorN<N, P>), (jacobian, res)| {
+            (
+                hessian + &jacobian * jacobian.transpose(),
+                gradients + &jacobian * res,
+            )
+        },
+    )
+}
+
+/// Identical to [`normal_equations`], but every entry is accumulated with Kahan's compensated
+/// summation, which keeps the rounding error of the sums from growing with the number of
+/// samples.
+pub(crate) fn compensated_normal_equations<N, P, S, J, JS, RS>(
+    jacobians: impl Iterator<Item = Matrix<N, P, J, JS>>,
+    residuals: &Matrix<N, J, S, RS>,
+) -> (MatrixMN<N, P, P>, VectorN<N, P>)
+where
+    N: RealField,
+    P: Dim,
+    S: Dim,
+    J: DimName,
+    JS: Storage<N, P, J>,
+    RS: Storage<N, J, S>,
+    DefaultAllocator: Allocator<N, P, P>,
+    DefaultAllocator: Allocator<N, P>,
+    DefaultAllocator: Allocator<N, J, P>,
+{
+    let mut samples = jacobians.zip(residuals.column_iter()).peekable();
+    let p = samples
+        .peek()
+        .map_or_else(initial_dim, |(jacobian, _)| jacobian.data.shape().0);
+    let mut hessian = MatrixMN::<N, P, P>::zeros_generic(p, p);
+    let mut hessian_error = hessian.clone();
+    let mut gradients = VectorN::<N, P>::zeros_generic(p, U1);
+    let mut gradients_error = gradients.clone();
+    for (jacobian, res) in samples {
+        compensated_add(
+            &mut hessian,
+            &mut hessian_error,
+            &(&jacobian * jacobian.transpose()),
+        );
+        compensated_add(&mut gradients, &mut gradients_error, &(&jacobian * res));
+    }
+    (hessian, gradients)
+}
+
+/// Adds `term` to `sum`, where `error` holds the low-order bits that were lost from `sum` by the
+/// previous additions, and replaces `error` with the bits lost by this one.
+fn compensated_add<N, R, C>(
+    sum: &mut MatrixMN<N, R, C>,
+    error: &mut MatrixMN<N, R, C>,
+    term: &MatrixMN<N, R, C>,
+) where
+    N: RealField,
+    R: Dim,
+    C: Dim,
+    DefaultAllocator: Allocator<N, R, C>,
+{
+    for ((sum, error), &term) in sum.iter_mut().zip(error.iter_mut()).zip(term.iter()) {
+        let corrected = term - *error;
+        let next = *sum + corrected;
+        *error = (next - *sum) - corrected;
+        *sum = next;
+    }
+}
+
+/// Accumulates only the gradients `Jr` from every sample.
+pub(crate) fn gradients<N, P, S, J, JS, RS>(
+    jacobians: impl Iterator<Item = Matrix<N, P, J, JS>>,
+    residuals: &Matrix<N, J, S, RS>,
+) -> VectorN<N, P>
+where
+    N: RealField,
+    P: Dim,
+    S: Dim,
+    J: DimName,
+    JS: Storage<N, P, J>,
+    RS: Storage<N, J, S>,
+    DefaultAllocator: Allocator<N, P>,
+{
+    let mut samples = jacobians.zip(residuals.column_iter()).peekable();
+    let p = samples
+        .peek()
+        .map_or_else(initial_dim, |(jacobian, _)| jacobian.data.shape().0);
+    samples.fold(
+        VectorN::zeros_generic(p, U1),
+        |gradients: VectorN<N, P>, (jacobian, res)| gradients + &jacobian * res,
+    )
+}
+
+/// The most sweeps of Jacobi rotations that [`symmetric_eigen`] runs before giving up on the
+/// off-diagonal entries converging to zero.
+const JACOBI_SWEEPS: usize = 64;
+
+/// Diagonalizes the symmetric `a` in place with cyclic Jacobi rotations, leaving its
+/// eigenvalues on the diagonal, and returns the eigenvectors as columns in the same order.
+///
+/// Jacobi rotations determine even the smallest eigenvalues of a positive semi-definite matrix
+/// to high relative accuracy, which is what decides its rank and conditioning. Returns `None` if
+/// the off-diagonal entries haven't converged to zero after [`JACOBI_SWEEPS`] sweeps, such as
+/// when `a` isn't finite, since the diagonal isn't made of eigenvalues then.
+fn symmetric_eigen<N, P>(a: &mut MatrixMN<N, P, P>) -> Option<MatrixMN<N, P, P>>
+where
+    N: RealField,
+    P: Dim,
+    DefaultAllocator: Allocator<N, P, P>,
+{
+    let (rows, cols) = a.data.shape();
+    let p = rows.value();
+    let mut eigenvectors = MatrixMN::<N, P, P>::identity_generic(rows, cols);
+    let two = N::one() + N::one();
+    let epsilon = N::default_epsilon();
+    let converged = |a: &MatrixMN<N, P, P>| {
+        let off_diagonal = (0..p)
+            .flat_map(|i| (i + 1..p).map(move |j| (i, j)))
+            .fold(N::zero(), |sum, (i, j)| sum + a[(i, j)] * a[(i, j)]);
+        off_diagonal <= epsilon * epsilon * a.norm_squared()
+    };
+    for _ in 0..JACOBI_SWEEPS {
+        if converged(a) {
+            return Some(eigenvectors);
+        }
+        for i in 0..p {
+            for j in i + 1..p {
+                if a[(i, j)] == N::zero() {
+                    continue;
+                }
+                // Choose the smaller of the rotations that zero a[(i, j)].
+                let theta = (a[(j, j)] - a[(i, i)]) / (two * a[(i, j)]);
+                let tan = if theta >= N::zero() {
+                    N::one() / (theta + theta.hypot(N::one()))
+                } else {
+                    -N::one() / (theta.hypot(N::one()) - theta)
+                };
+                let cos = N::one() / tan.hypot(N::one());
+                let sin = tan * cos;
+                for k in 0..p {
+                    let (x, y) = (a[(k, i)], a[(k, j)]);
+                    a[(k, i)] = cos * x - sin * y;
+                    a[(k, j)] = sin * x + cos * y;
+                }
+                for k in 0..p {
+                    let (x, y) = (a[(i, k)], a[(j, k)]);
+                    a[(i, k)] = cos * x - sin * y;
+                    a[(j, k)] = sin * x + cos * y;
+                }
+                for k in 0..p {
+                    let (x, y) = (eigenvectors[(k, i)], eigenvectors[(k, j)]);
+                    eigenvectors[(k, i)] = cos * x - sin * y;
+                    eigenvectors[(k, j)] = sin * x + cos * y;
+                }
+            }
+        }
+    }
+    if converged(a) {
+        Some(eigenvectors)
+    } else {
+        None
+    }
+}
+
+/// Solves the symmetric positive semi-definite `a` for `b` with the pseudo-inverse of `a`,
+/// ignoring every singular value which isn't above `rank_tolerance`.
+///
+/// Returns the solution along with the number of singular values that were inverted, or `None`
+/// if the eigendecomposition didn't converge. The singular values of a symmetric positive
+/// semi-definite matrix are its eigenvalues, so `a` is overwritten by its eigendecomposition.
+fn truncated_solve<N, P>(
+    a: &mut MatrixMN<N, P, P>,
+    b: &VectorN<N, P>,
+    rank_tolerance: N,
+) -> Option<(VectorN<N, P>, usize)>
+where
+    N: RealField,
+    P: Dim,
+    DefaultAllocator: Allocator<N, P, P>,
+    DefaultAllocator: Allocator<N, P>,
+{
+    let eigenvectors = symmetric_eigen(a)?;
+
+    // a = VΛVᵀ, so its pseudo-inverse applied to b is the sum of vᵀb / λ * v over each
+    // eigenvalue λ above the tolerance and its eigenvector v.
+    let mut solution = VectorN::<N, P>::zeros_generic(b.data.shape().0, U1);
+    let mut rank = 0;
+    for (k, eigenvector) in eigenvectors.column_iter().enumerate() {
+        let eigenvalue = a[(k, k)];
+        if eigenvalue > rank_tolerance {
+            solution.axpy(eigenvector.dot(b) / eigenvalue, &eigenvector, N::one());
+            rank += 1;
+        }
+    }
+    Some((solution, rank))
+}
+
+/// Solves `a x = b` in place with an LU decomposition with partial pivoting, which leaves `x`
+/// in `b` and `U` in `a`. Returns `false` if `a` is singular.
+///
+/// This only needs a single right-hand side, so it is cheaper than forming the inverse of `a`,
+/// and it doesn't allocate, unlike [`LU`](nalgebra::linalg::LU), which also needs bounds on
+/// `DimMinimum<P, P>` that every caller would have to repeat.
+fn lu_solve<N, P>(a: &mut MatrixMN<N, P, P>, b: &mut VectorN<N, P>) -> bool
+where
+    N: RealField,
+    P: Dim,
+    DefaultAllocator: Allocator<N, P, P>,
+    DefaultAllocator: Allocator<N, P>,
+{
+    let p = b.len();
+    for i in 0..p {
+        let pivot = (i..p)
+            .max_by(|&j, &k| {
+                a[(j, i)]
+                    .abs()
+                    .partial_cmp(&a[(k, i)].abs())
+                    .unwrap_or(core::cmp::Ordering::Equal)
+            })
+            .unwrap_or(i);
+        if a[(pivot, i)] == N::zero() {
+            return false;
+        }
+        a.swap_rows(i, pivot);
+        b.swap_rows(i, pivot);
+        for k in i + 1..p {
+            let factor = a[(k, i)] / a[(i, i)];
+            for j in i..p {
+                let above = a[(i, j)];
+                a[(k, j)] -= factor * above;
+            }
+            let above = b[i];
+            b[k] -= factor * above;
+        }
+    }
+    a.solve_upper_triangular_mut(b)
+}
+
+/// Uses Givens rotations to add a row and its right-hand side to the upper-triangular `r` and
+/// the rotated right-hand side `qtr` of a QR decomposition.
+fn rotate_into<N, P>(
+    r: &mut MatrixMN<N, P, P>,
+    qtr: &mut VectorN<N, P>,
+    mut row: VectorN<N, P>,
+    mut rhs: N,
+) where
+    N: RealField,
+    P: Dim,
+    DefaultAllocator: Allocator<N, P, P>,
+    DefaultAllocator: Allocator<N, P>,
+{
+    let p = row.len();
+    for i in 0..p {
+        if row[i] == N::zero() {
+            continue;
+        }
+        // Rotate row i of R and the new row so that the new row is zero in column i.
+        let hypot = r[(i, i)].hypot(row[i]);
+        let cos = r[(i, i)] / hypot;
+        let sin = row[i] / hypot;
+        for k in i..p {
+            let (a, b) = (r[(i, k)], row[k]);
+            r[(i, k)] = cos * a + sin * b;
+            row[k] = cos * b - sin * a;
+        }
+        let (a, b) = (qtr[i], rhs);
+        qtr[i] = cos * a + sin * b;
+        rhs = cos * b - sin * a;
+    }
+}
@@ -0,0 +1,104 @@
+use nalgebra::{
+    allocator::Allocator, storage::Storage, DefaultAllocator, Dim, Matrix, RealField, VectorN,
+};
+use num_traits::FromPrimitive;
+
+/// Computes the one-sigma standard error of each parameter from the inverse of the undamped
+/// approximate Hessian `(JJᵀ)⁻¹` at the optimized model.
+///
+/// The inverse Hessian is scaled by the reduced chi-square `sum_of_squares / (num_residuals -
+/// P)`, which estimates the variance of the measurements, so the error of each parameter is
+/// `sqrt(diag((JJᵀ)⁻¹) * sum_of_squares / (num_residuals - P))`. `num_residuals` is the total
+/// number of residuals, which is `J` times the number of samples. The covariance returned by
+/// [`optimize_with_covariance`](crate::optimize_with_covariance) is already scaled, so the
+/// errors are just the square roots of its diagonal.
+///
+/// The errors are returned along with whether any of them were clamped. A near-singular inverse
+/// Hessian can have slightly negative diagonal elements due to numerical error. Those are
+/// clamped to zero, so a clamped error of `0.0` means that the parameter was poorly constrained
+/// rather than perfectly known.
+///
+/// Returns `None` if there aren't more residuals than parameters, since the variance of the
+/// measurements can't be estimated then, or if `num_residuals` can't be represented by `N`.
+///
+/// ```
+/// use levenberg_marquardt::{optimize_with_covariance, parameter_standard_errors, Config};
+/// use nalgebra::{dimension::U1, Dynamic, Matrix, Matrix2, VecStorage, Vector2};
+///
+/// // Samples of `y = 3x + 1` with noise which has a standard deviation of `0.1`.
+/// let samples: Vec<(f64, f64)> = (0..100)
+///     .map(|i| {
+///         let x = f64::from(i) * 0.1;
+///         let noise = if i % 2 == 0 { 0.1 } else { -0.1 };
+///         (x, 3.0 * x + 1.0 + noise)
+///     })
+///     .collect();
+///
+/// let residuals = |model: &Vector2<f64>| {
+///     Matrix::<f64, U1, Dynamic, VecStorage<f64, U1, Dynamic>>::from_iterator(
+///         samples.len(),
+///         samples.iter().map(|&(x, y)| y - (model.x * x + model.y)),
+///     )
+/// };
+/// let (model, covariance) = optimize_with_covariance(
+///     Config::default(),
+///     Vector2::zeros(),
+///     |model, delta: Vector2<f64>| model + delta,
+///     residuals,
+///     |_| samples.iter().map(|&(x, _)| Vector2::new(x, 1.0)),
+/// );
+///
+/// // The approximate Hessian of a line is the same everywhere.
+/// let hessian = samples.iter().fold(Matrix2::zeros(), |hessian, &(x, _)| {
+///     hessian + Vector2::new(x, 1.0) * Vector2::new(x, 1.0).transpose()
+/// });
+/// let (errors, clamped) = parameter_standard_errors(
+///     &hessian.try_inverse().unwrap(),
+///     residuals(&model).norm_squared(),
+///     samples.len(),
+/// )
+/// .unwrap();
+/// assert!(!clamped);
+///
+/// // The error of the slope of a line is `σ / sqrt(Σ(x - x̄)²)`.
+/// let mean = samples.iter().map(|&(x, _)| x).sum::<f64>() / 100.0;
+/// let spread = samples.iter().map(|&(x, _)| (x - mean).powi(2)).sum::<f64>();
+/// assert!((errors.x - 0.1 / spread.sqrt()).abs() < 1e-3);
+/// assert!((errors.x - covariance.unwrap()[(0, 0)].sqrt()).abs() < 1e-12);
+/// ```
+pub fn parameter_standard_errors<N, P, S>(
+    inverse_hessian: &Matrix<N, P, P, S>,
+    sum_of_squares: N,
+    num_residuals: usize,
+) -> Option<(VectorN<N, P>, bool)>
+where
+    N: RealField + FromPrimitive,
+    P: Dim,
+    S: Storage<N, P, P>,
+    DefaultAllocator: Allocator<N, P>,
+{
+    let reduced_chi_square =
+        reduced_chi_square(sum_of_squares, num_residuals, inverse_hessian.nrows())?;
+    let mut clamped = false;
+    let errors = inverse_hessian.diagonal().map(|variance| {
+        clamped |= variance < N::zero();
+        (variance.max(N::zero()) * reduced_chi_square).sqrt()
+    });
+    Some((errors, clamped))
+}
+
+/// The reduced chi-square `sum_of_squares / (num_residuals - parameters)`, or `None` if there
+/// aren't more residuals than parameters or the degrees of freedom can't be represented by `N`.
+pub(crate) fn reduced_chi_square<N>(
+    sum_of_squares: N,
+    num_residuals: usize,
+    parameters: usize,
+) -> Option<N>
+where
+    N: RealField + FromPrimitive,
+{
+    let degrees_of_freedom = num_residuals
+        .checked_sub(parameters)
+        .filter(|&dof| dof > 0)?;
+    Some(sum_of_squares / N::from_usize(degrees_of_freedom)?)
+}
@@ -0,0 +1,1726 @@
+#[cfg(feature = "alloc")]
+use crate::broyden::Broyden;
+use crate::{
+    solve::{self, LinearSystem},
+    statistics, Config, DampingMode, DampingStrategy, HistoryRecorder, InitialLambda,
+    LambdaSchedule, LeastSquaresProblem, Method, MinimizationReport, OptimizeError, SolveMethod,
+    TerminationReason, Workspace,
+};
+use core::{
+    cell::Cell,
+    convert::{Infallible, TryFrom},
+    mem,
+    ops::ControlFlow,
+    sync::atomic::{AtomicBool, Ordering},
+};
+use nalgebra::{
+    allocator::Allocator,
+    constraint::{DimEq, ShapeConstraint},
+    dimension::{DimMin, DimMinimum},
+    storage::Storage,
+    DefaultAllocator, Dim, DimName, Matrix, MatrixMN, RealField, VectorN, U1,
+};
+use num_traits::FromPrimitive;
+
+/// What happened during a call to [`LevenbergMarquardt::step`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum StepOutcome {
+    /// The step was accepted.
+    ///
+    /// This means that the step reduced the sum-of-squares, unless [`Method::GaussNewton`] is
+    /// being used, which accepts every step that it can take.
+    Improved,
+    /// The step was rejected and lambda was increased.
+    Rejected,
+    /// Optimization has terminated, either due to this step or an earlier one.
+    ///
+    /// Once this is returned, every later call to [`LevenbergMarquardt::step`] returns it again
+    /// without doing anything.
+    Terminated(TerminationReason),
+}
+
+/// A step taken from the current guess with a particular lambda.
+struct Step<M, N, P, R, IJ>
+where
+    N: RealField,
+    P: Dim,
+    DefaultAllocator: Allocator<N, P>,
+{
+    lambda: N,
+    /// The step that was applied to the current guess to get `guess`.
+    #[cfg_attr(not(feature = "alloc"), allow(dead_code))]
+    delta: VectorN<N, P>,
+    guess: M,
+    residuals: R,
+    /// The Jacobians at `guess`, if they were computed along with the residuals.
+    jacobians: Option<IJ>,
+    sum_of_squares: N,
+    /// The ratio of the actual reduction in the sum-of-squares to the predicted reduction.
+    gain_ratio: N,
+    /// The numerical rank of the damped system that was solved for the step.
+    rank: Option<usize>,
+}
+
+/// Why a step was rejected.
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Rejection {
+    /// The step didn't reduce the sum-of-squares as predicted.
+    Diverged,
+    /// The damped Hessian couldn't be inverted or the sum-of-squares wasn't finite.
+    Singular,
+    /// The Jacobians couldn't be computed at the new guess.
+    JacobianFailed,
+    /// The step was infeasible or the new guess couldn't be normalized.
+    Unnormalized,
+}
+
+/// The state of Levenberg-Marquardt between iterations, which lets the caller run one
+/// iteration at a time with [`LevenbergMarquardt::step`].
+///
+/// This is what every `optimize` function uses internally. It is useful when iterations must
+/// be interleaved with other work, such as running one iteration per tick of a control loop,
+/// or when the caller wants to save and restore the state or decide when to stop. Unlike the
+/// `optimize` functions, `max_iterations` is not enforced by [`step`](Self::step), since the
+/// caller decides how many times to step. [`run`](Self::run) steps until termination like the
+/// `optimize` functions do.
+///
+/// The same problem must be passed to every call, although the data it contains may change
+/// between calls as long as the residuals and Jacobians stay consistent with each other.
+pub struct LevenbergMarquardt<N, P, S, J, LSP>
+where
+    N: RealField,
+    P: Dim,
+    S: Dim,
+    J: Dim,
+    LSP: LeastSquaresProblem<N, P, S, J>,
+    DefaultAllocator: Allocator<N, P, P>,
+    DefaultAllocator: Allocator<N, P>,
+{
+    config: Config<N>,
+    guess: LSP::Model,
+    residuals: Matrix<N, J, S, LSP::ResidualStorage>,
+    sum_of_squares: N,
+    lambda: N,
+    /// The factor that lambda is increased by on divergence when using Nielsen's strategy.
+    nu: N,
+    /// The linear system and the gradients at the current guess.
+    linearization: Option<(LinearSystem<N, P>, VectorN<N, P>)>,
+    /// The stacked Jacobian at the current guess when it is updated with Broyden's method.
+    #[cfg(feature = "alloc")]
+    broyden: Option<Broyden<N>>,
+    /// The largest diagonal of the approximate Hessian seen so far, for
+    /// [`DampingMode::AutoScaled`].
+    auto_scale: VectorN<N, P>,
+    // The best guess ever seen is tracked separately from the current guess so that the
+    // returned model can never be worse than one that was already evaluated. It is `None`
+    // while the current guess is the best, so that no model ever needs to be cloned.
+    best_guess: Option<LSP::Model>,
+    best_sum: N,
+    consecutive_divergences: usize,
+    consecutive_failed_inversions: usize,
+    consecutive_failed_jacobians: usize,
+    /// The number of accepted steps in a row whose relative reduction was below
+    /// `relative_stall_tol`.
+    consecutive_stalls: usize,
+    /// The relative reduction of the sum-of-squares on the last accepted step.
+    last_reduction: Option<N>,
+    /// Whether the next step of [`Method::GaussNewtonWithFallback`] is an undamped one.
+    gauss_newton: bool,
+    /// The number of damped steps in a row whose gain ratio was above `3/4`.
+    well_predicted_steps: usize,
+    /// How many well-predicted steps are needed before Gauss-Newton is attempted again.
+    fallback_patience: usize,
+    iterations: usize,
+    gauss_newton_iterations: usize,
+    damped_iterations: usize,
+    residual_evaluations: usize,
+    jacobian_evaluations: usize,
+    accepted_steps: usize,
+    rejected_steps: usize,
+    /// The numerical rank of the damped system of the last step that was taken.
+    rank: Option<usize>,
+    restarts: usize,
+    /// The number of residuals.
+    total: N,
+    termination: Option<TerminationReason>,
+}
+
+impl<N, P, S, J, LSP> Clone for LevenbergMarquardt<N, P, S, J, LSP>
+where
+    N: RealField,
+    P: Dim,
+    S: Dim,
+    J: Dim,
+    LSP: LeastSquaresProblem<N, P, S, J>,
+    LSP::Model: Clone,
+    Matrix<N, J, S, LSP::ResidualStorage>: Clone,
+    DefaultAllocator: Allocator<N, P, P>,
+    DefaultAllocator: Allocator<N, P>,
+{
+    fn clone(&self) -> Self {
+        Self {
+            config: self.config,
+            guess: self.guess.clone(),
+            residuals: self.residuals.clone(),
+            sum_of_squares: self.sum_of_squares,
+            lambda: self.lambda,
+            nu: self.nu,
+            linearization: self.linearization.clone(),
+            #[cfg(feature = "alloc")]
+            broyden: self.broyden.clone(),
+            auto_scale: self.auto_scale.clone(),
+            best_guess: self.best_guess.clone(),
+            best_sum: self.best_sum,
+            consecutive_divergences: self.consecutive_divergences,
+            consecutive_failed_inversions: self.consecutive_failed_inversions,
+            consecutive_failed_jacobians: self.consecutive_failed_jacobians,
+            consecutive_stalls: self.consecutive_stalls,
+            last_reduction: self.last_reduction,
+            gauss_newton: self.gauss_newton,
+            well_predicted_steps: self.well_predicted_steps,
+            fallback_patience: self.fallback_patience,
+            iterations: self.iterations,
+            gauss_newton_iterations: self.gauss_newton_iterations,
+            damped_iterations: self.damped_iterations,
+            residual_evaluations: self.residual_evaluations,
+            jacobian_evaluations: self.jacobian_evaluations,
+            accepted_steps: self.accepted_steps,
+            rejected_steps: self.rejected_steps,
+            rank: self.rank,
+            restarts: self.restarts,
+            total: self.total,
+            termination: self.termination,
+        }
+    }
+}
+
+impl<N, P, S, J, LSP> LevenbergMarquardt<N, P, S, J, LSP>
+where
+    N: RealField + FromPrimitive,
+    P: DimMin<P>,
+    S: Dim,
+    J: DimName,
+    LSP: LeastSquaresProblem<N, P, S, J>,
+    DefaultAllocator: Allocator<N, J, P>,
+    DefaultAllocator: Allocator<N, P, P>,
+    DefaultAllocator: Allocator<N, P>,
+    ShapeConstraint: DimEq<DimMinimum<P, P>, P>,
+{
+    /// Evaluates the residuals and the Jacobians at `init` to prepare for the first step.
+    ///
+    /// If `init` is already below `threshold` or its gradient is zero, the Jacobians can't be
+    /// computed at `init`, the gradient is already below `gradient_threshold`, or this
+    /// evaluation spends the whole `max_function_evaluations`, the first step immediately
+    /// returns [`StepOutcome::Terminated`].
+    pub fn new(config: Config<N>, init: LSP::Model, problem: &LSP) -> Result<Self, OptimizeError> {
+        let (residuals, jacobians) = problem.residuals_and_jacobians(&init);
+        let mut lm = Self::linearized(config, init, residuals, jacobians, problem)?;
+        lm.residual_evaluations += 1;
+        if lm.termination.is_none() && lm.residual_evaluations >= config.max_function_evaluations {
+            lm.termination = Some(TerminationReason::BudgetExhausted);
+        }
+        Ok(lm)
+    }
+
+    /// Prepares for the first step like [`new`](Self::new), but uses `residuals` as the
+    /// residuals of `init` rather than evaluating them.
+    ///
+    /// This saves an evaluation when the caller already has the residuals, such as when
+    /// warm-starting from the result of an earlier optimization. `residuals` must be exactly
+    /// what the problem would return for `init`. Nothing checks this, and stale residuals will
+    /// make the first step use the wrong gradient and compare candidate steps against the wrong
+    /// sum-of-squares.
+    pub fn with_residuals(
+        config: Config<N>,
+        init: LSP::Model,
+        residuals: Matrix<N, J, S, LSP::ResidualStorage>,
+        problem: &LSP,
+    ) -> Result<Self, OptimizeError> {
+        Self::linearized(config, init, residuals, None, problem)
+    }
+
+    /// Identical to [`with_residuals`](Self::with_residuals), but uses `jacobians` as the
+    /// Jacobians of `init` if they were computed along with `residuals`.
+    fn linearized<'p>(
+        config: Config<N>,
+        init: LSP::Model,
+        residuals: Matrix<N, J, S, LSP::ResidualStorage>,
+        jacobians: Option<LSP::Jacobians<'p>>,
+        problem: &'p LSP,
+    ) -> Result<Self, OptimizeError> {
+        let sum_of_squares = problem.sum_of_squares(&init, &residuals);
+        let total = N::from_usize(residuals.len()).ok_or(OptimizeError::ConversionFailed)?;
+        let p = solve::initial_dim::<P>();
+        let mut system = LinearSystem::zeros(config.solve_method, p);
+        let mut gradients = VectorN::<N, P>::zeros_generic(p, U1);
+        #[cfg(feature = "alloc")]
+        let mut broyden = None;
+        let linearized = linearize_guess(
+            &config,
+            problem,
+            &init,
+            &residuals,
+            jacobians,
+            #[cfg(feature = "alloc")]
+            &mut broyden,
+            &mut system,
+            &mut gradients,
+        );
+        let linearization = if linearized {
+            Some((system, gradients))
+        } else {
+            None
+        };
+        // Columns that have never been seen to have curvature get a scale of one.
+        let auto_scale = match &linearization {
+            Some((system, _)) => system.hessian_diagonal().map(|diagonal| {
+                if diagonal == N::zero() {
+                    N::one()
+                } else {
+                    diagonal
+                }
+            }),
+            None => VectorN::<N, P>::from_element_generic(p, U1, N::one()),
+        };
+        let mut lm = Self {
+            config,
+            guess: init,
+            residuals,
+            sum_of_squares,
+            lambda: N::zero(),
+            nu: N::one() + N::one(),
+            linearization,
+            #[cfg(feature = "alloc")]
+            broyden,
+            best_guess: None,
+            auto_scale,
+            best_sum: sum_of_squares,
+            consecutive_divergences: 0,
+            consecutive_failed_inversions: 0,
+            consecutive_failed_jacobians: 0,
+            consecutive_stalls: 0,
+            last_reduction: None,
+            gauss_newton: true,
+            well_predicted_steps: 0,
+            fallback_patience: 1,
+            iterations: 0,
+            gauss_newton_iterations: 0,
+            damped_iterations: 0,
+            residual_evaluations: 0,
+            jacobian_evaluations: 1,
+            accepted_steps: 0,
+            rejected_steps: 0,
+            rank: None,
+            restarts: 0,
+            total,
+            termination: None,
+        };
+        lm.lambda = match config.initial_lambda_from {
+            Some(lambda) => lambda.max(config.min_lambda).min(config.max_lambda),
+            None => lm.initial_lambda(),
+        };
+        // There is nothing to do if `init` is already below the threshold or is a stationary
+        // point, which would otherwise only be noticed after taking a step of zero.
+        lm.termination = match &lm.linearization {
+            _ if config.below_threshold(lm.sum_of_squares, lm.total) => {
+                Some(TerminationReason::AlreadyOptimal)
+            }
+            Some((_, gradients)) if gradients.iter().all(|&gradient| gradient == N::zero()) => {
+                Some(TerminationReason::AlreadyOptimal)
+            }
+            Some(_) if lm.gradient_too_small() => Some(TerminationReason::GradientTooSmall),
+            Some(_) => None,
+            None => Some(TerminationReason::JacobianFailed),
+        };
+        Ok(lm)
+    }
+
+    /// Evaluates the residuals and the Jacobians at the current guess again, for when the data
+    /// in the problem has changed between steps, such as when it holds a new mini-batch of
+    /// samples.
+    ///
+    /// The sum-of-squares of earlier guesses were computed from different data, so the best
+    /// guess is reset to the current guess. Lambda and the counters carry over. If the
+    /// Jacobians can't be computed at the current guess, the next step immediately returns
+    /// [`StepOutcome::Terminated`]. Returns an error if the new number of residuals can't be
+    /// represented by `N`.
+    pub fn reevaluate(&mut self, problem: &LSP) -> Result<(), OptimizeError> {
+        let (residuals, jacobians) = problem.residuals_and_jacobians(&self.guess);
+        self.residual_evaluations += 1;
+        self.total = N::from_usize(residuals.len()).ok_or(OptimizeError::ConversionFailed)?;
+        self.sum_of_squares = problem.sum_of_squares(&self.guess, &residuals);
+        let p = solve::initial_dim::<P>();
+        let (mut system, mut gradients) = self.linearization.take().unwrap_or_else(|| {
+            (
+                LinearSystem::zeros(self.config.solve_method, p),
+                VectorN::<N, P>::zeros_generic(p, U1),
+            )
+        });
+        let linearized = linearize_guess(
+            &self.config,
+            problem,
+            &self.guess,
+            &residuals,
+            jacobians,
+            #[cfg(feature = "alloc")]
+            &mut self.broyden,
+            &mut system,
+            &mut gradients,
+        );
+        self.jacobian_evaluations += 1;
+        self.linearization = if linearized {
+            Some((system, gradients))
+        } else {
+            self.termination = Some(TerminationReason::JacobianFailed);
+            None
+        };
+        self.residuals = residuals;
+        self.best_guess = None;
+        self.best_sum = self.sum_of_squares;
+        Ok(())
+    }
+
+    /// Runs a single iteration of Levenberg-Marquardt.
+    ///
+    /// This takes a step from the current guess and accepts or rejects it, updates lambda,
+    /// and then checks every termination condition except `max_iterations`.
+    pub fn step(&mut self, problem: &LSP) -> StepOutcome {
+        self.step_in(problem, &mut Workspace::new())
+    }
+
+    /// Runs a single iteration like [`step`](Self::step), but uses `workspace` as scratch space
+    /// rather than creating it again.
+    pub fn step_in(&mut self, problem: &LSP, workspace: &mut Workspace<N, P>) -> StepOutcome {
+        if let Some(termination) = self.termination {
+            return StepOutcome::Terminated(termination);
+        }
+        let (system, gradients) = match &self.linearization {
+            Some(linearization) => linearization,
+            None => unreachable!("the optimization would have terminated"),
+        };
+        let config = self.config;
+        let two = N::one() + N::one();
+        let three = two + N::one();
+        self.iterations += 1;
+        // Whether this step is an undamped attempt of `GaussNewtonWithFallback`, which is only
+        // accepted if it reduces the sum-of-squares.
+        let attempt = config.method == Method::GaussNewtonWithFallback && self.gauss_newton;
+        if config.method == Method::GaussNewton || attempt {
+            self.gauss_newton_iterations += 1;
+        } else {
+            self.damped_iterations += 1;
+        }
+
+        // The diagonal of the damping matrix D. Gauss-Newton only damps to regularize a
+        // singular system, so it uses the identity.
+        let damping = match (config.method, config.damping_mode) {
+            (Method::GaussNewton, _) | (_, DampingMode::Identity) => {
+                VectorN::<N, P>::from_element_generic(system.dim(), U1, N::one())
+            }
+            (_, DampingMode::Diagonal) => system.hessian_diagonal(),
+            (_, DampingMode::AutoScaled) => self.auto_scale.clone(),
+        };
+
+        let guess = &self.guess;
+        let residuals = &self.residuals;
+        let sum_of_squares = self.sum_of_squares;
+        let mut residual_evaluations = 0;
+        let mut jacobian_evaluations = 0;
+        // The fraction of the step that the guess is nudged by to estimate the curvature.
+        let acceleration_step = N::from_f64(0.1).filter(|_| config.geodesic_acceleration);
+        // The residual evaluations left in the budget, which no candidate may exceed.
+        let budget = config
+            .max_function_evaluations
+            .saturating_sub(self.residual_evaluations);
+        let mut exhausted = false;
+        let mut unnormalized = false;
+        // Take a step with the given lambda.
+        // Returns an option because it may not be possible to solve the inverse.
+        let mut take_step = |lam: N| {
+            // Solve JJᵀ + λD for delta.
+            let (lam, delta) = match config.method {
+                Method::LevenbergMarquardt | Method::GaussNewtonWithFallback => {
+                    (lam, system.solve(gradients, &damping, lam, workspace)?)
+                }
+                Method::GaussNewton => {
+                    Self::regularized_solve(&config, system, gradients, workspace)?
+                }
+            };
+            let rank = workspace.rank;
+            // The linearization predicts that the sum-of-squares reduces by δᵀ(λDδ + g). For
+            // the step αδ of a line search, that becomes α(2 - α)δᵀg + α²δᵀλDδ.
+            let descent = delta.dot(gradients);
+            let damped = delta.dot(&damping.component_mul(&delta)) * lam;
+            let predicted = |alpha: N| alpha * (two - alpha) * descent + alpha * alpha * damped;
+            // The acceleration is only worth an evaluation if another is left for the step.
+            let acceleration_step = acceleration_step.filter(|_| residual_evaluations + 1 < budget);
+            let delta = if let Some(h) = acceleration_step {
+                let acceleration = Self::acceleration(
+                    problem, system, guess, residuals, &delta, h, &damping, lam, workspace,
+                );
+                // Both the residuals of the nudged guess and the Jacobians have been evaluated.
+                residual_evaluations += 1;
+                jacobian_evaluations += 1;
+                match acceleration {
+                    // Only accept the correction when it is small compared to the step, which
+                    // means that the second-order expansion is trustworthy.
+                    Some(acceleration)
+                        if (acceleration.norm() * two) / delta.norm()
+                            <= config.acceleration_ratio =>
+                    {
+                        delta + acceleration / two
+                    }
+                    _ => delta,
+                }
+            } else {
+                delta
+            };
+            // Rescale a step which is longer than `max_step_norm` onto the cap. The clamped step
+            // is the fraction `scale` of the solved one, so it is predicted like a line search.
+            let norm = delta.norm();
+            let (delta, scale) = match config.max_step_norm {
+                Some(max_step_norm) if norm > max_step_norm => {
+                    let scale = max_step_norm / norm;
+                    (delta * scale, scale)
+                }
+                _ => (delta, N::one()),
+            };
+            // Compute the new guess, residuals, and sum-of-squares. With a line search, the
+            // step is halved until it reduces the sum-of-squares or gets too short.
+            let mut alpha = N::one();
+            loop {
+                if residual_evaluations == budget {
+                    exhausted = true;
+                    return None;
+                }
+                let ges = match problem
+                    .try_apply_delta(guess, &delta * alpha)
+                    .and_then(|ges| problem.try_normalize(ges))
+                {
+                    Some(ges) => ges,
+                    None => {
+                        unnormalized = true;
+                        return None;
+                    }
+                };
+                let (res, jac) = problem.residuals_and_jacobians(&ges);
+                residual_evaluations += 1;
+                let sum = problem.sum_of_squares(&ges, &res);
+                let reduced = sum.is_finite() && sum < sum_of_squares;
+                if config.line_search
+                    && !reduced
+                    && alpha / two >= config.line_search_min_alpha
+                    && residual_evaluations < budget
+                {
+                    alpha /= two;
+                    continue;
+                }
+                // If the sum-of-squares is infinite or NaN it shouldn't be allowed through.
+                if !sum.is_finite() {
+                    return None;
+                }
+                return Some(Step {
+                    lambda: lam,
+                    delta: &delta * alpha,
+                    guess: ges,
+                    residuals: res,
+                    jacobians: jac,
+                    sum_of_squares: sum,
+                    gain_ratio: (sum_of_squares - sum) / predicted(alpha * scale),
+                    rank,
+                });
+            }
+        };
+
+        let step = match (config.method, config.damping_strategy) {
+            (Method::GaussNewton, _) => take_step(N::zero()),
+            _ if attempt => take_step(N::zero()),
+            // Select the step that minimizes the sum-of-squares the most. The candidates are
+            // tested from the smallest lambda up, and ties go to the larger lambda.
+            (_, DampingStrategy::Multiplicative) => {
+                let mut best: Option<Step<_, _, _, _, _>> = None;
+                for power in (0..config.lambda_candidates).rev() {
+                    let power = i32::try_from(power).unwrap_or(i32::MAX);
+                    let candidate = take_step(self.lambda * config.lambda_convege.powi(power));
+                    match (candidate, &best) {
+                        (Some(step), Some(best_step))
+                            if step.sum_of_squares > best_step.sum_of_squares => {}
+                        (Some(step), _) => best = Some(step),
+                        (None, _) => {}
+                    }
+                }
+                best
+            }
+            (_, DampingStrategy::Nielsen) => take_step(self.lambda),
+        };
+        self.residual_evaluations += residual_evaluations;
+        self.jacobian_evaluations += jacobian_evaluations;
+        if let Some(step) = &step {
+            self.rank = step.rank;
+        }
+        #[cfg(any(feature = "log", feature = "tracing"))]
+        let tested = step
+            .as_ref()
+            .map(|step| (step.lambda, step.sum_of_squares, step.gain_ratio));
+
+        // The step must actually reduce the sum-of-squares and the linearization must have
+        // predicted that reduction, otherwise it only helped by luck. The Jacobians must also
+        // be computable at the new guess so that the next step can be taken from it. The new
+        // linearization is accumulated into the spare system of the workspace so that the
+        // current one is left intact if the step is rejected. Gauss-Newton takes every step.
+        let accepted = match step {
+            Some(mut step)
+                if config.method == Method::GaussNewton
+                    || (step.sum_of_squares < sum_of_squares && step.gain_ratio > N::zero()) =>
+            {
+                if self.linearize_step(problem, &mut step, workspace) {
+                    Ok(step)
+                } else {
+                    Err(Rejection::JacobianFailed)
+                }
+            }
+            Some(_) => Err(Rejection::Diverged),
+            None if unnormalized => Err(Rejection::Unnormalized),
+            None => Err(Rejection::Singular),
+        };
+
+        let mut reduction_too_small = false;
+        let mut saturated = false;
+        let outcome = match accepted {
+            Ok(step) => {
+                // The step was accepted, so update everything.
+                let reduction = sum_of_squares - step.sum_of_squares;
+                reduction_too_small =
+                    reduction >= N::zero() && reduction / sum_of_squares < config.ftol;
+                if reduction / sum_of_squares < config.relative_stall_tol {
+                    self.consecutive_stalls += 1;
+                } else {
+                    self.consecutive_stalls = 0;
+                }
+                self.last_reduction = Some(reduction / sum_of_squares);
+                self.lambda = match (config.method, config.damping_strategy) {
+                    (Method::GaussNewton, _) => self.lambda,
+                    _ if attempt => self.lambda,
+                    (_, DampingStrategy::Multiplicative) => step.lambda,
+                    (_, DampingStrategy::Nielsen) => {
+                        // λ *= max(1/3, 1 - (2ρ - 1)³)
+                        self.nu = two;
+                        let ratio = two * step.gain_ratio - N::one();
+                        self.lambda * (N::one() / three).max(N::one() - ratio * ratio * ratio)
+                    }
+                };
+                // Every accepted step must decrease the sum-of-squares, except with Gauss-Newton,
+                // which takes every step.
+                debug_assert!(
+                    config.method == Method::GaussNewton || step.sum_of_squares < sum_of_squares,
+                    "an accepted step increased the sum-of-squares from {} to {}",
+                    sum_of_squares,
+                    step.sum_of_squares,
+                );
+                // Once enough damped steps in a row are predicted well by the linearization, the
+                // fit is close enough to the solution for Gauss-Newton to be tried again.
+                if config.method == Method::GaussNewtonWithFallback && !attempt {
+                    if step.gain_ratio > three / (two + two) {
+                        self.well_predicted_steps += 1;
+                    } else {
+                        self.well_predicted_steps = 0;
+                    }
+                    if self.well_predicted_steps >= self.fallback_patience {
+                        self.gauss_newton = true;
+                        self.well_predicted_steps = 0;
+                    }
+                }
+                if step.sum_of_squares < self.best_sum {
+                    self.best_guess = None;
+                    self.best_sum = step.sum_of_squares;
+                    self.guess = step.guess;
+                } else if self.best_guess.is_none() {
+                    self.best_guess = Some(mem::replace(&mut self.guess, step.guess));
+                } else {
+                    self.guess = step.guess;
+                }
+                self.residuals = step.residuals;
+                self.sum_of_squares = step.sum_of_squares;
+                // The old linearization becomes the spare for the next accepted step.
+                if let Some((system, gradients)) = &mut self.linearization {
+                    mem::swap(system, &mut workspace.system);
+                    mem::swap(gradients, &mut workspace.gradients);
+                    self.auto_scale = self.auto_scale.sup(&system.hessian_diagonal());
+                }
+                self.consecutive_divergences = 0;
+                self.consecutive_failed_inversions = 0;
+                self.consecutive_failed_jacobians = 0;
+                self.accepted_steps += 1;
+                StepOutcome::Improved
+            }
+            Err(rejection) => {
+                // We didn't see a decrease in the new state or were unable to take the inverse,
+                // so increase lambda to move towards gradient descent. This may also cause the
+                // matrix to become invertible or the step to land somewhere that the Jacobians
+                // can be computed. If lambda was already clamped at `max_lambda`, the next
+                // iteration would only test the same candidates again.
+                saturated = config.method != Method::GaussNewton
+                    && !attempt
+                    && self.lambda >= config.max_lambda
+                    && rejection != Rejection::Singular
+                    && rejection != Rejection::JacobianFailed;
+                // A failed Gauss-Newton attempt falls back to damping with the current lambda,
+                // and the next attempt has to wait for twice as many well-predicted steps.
+                self.well_predicted_steps = 0;
+                match (config.method, config.damping_strategy) {
+                    (Method::GaussNewton, _) => {}
+                    _ if attempt => {
+                        self.gauss_newton = false;
+                        self.fallback_patience = self.fallback_patience.saturating_mul(2);
+                    }
+                    (_, DampingStrategy::Multiplicative) => self.lambda *= config.lambda_diverge,
+                    (_, DampingStrategy::Nielsen) => {
+                        self.lambda *= self.nu;
+                        self.nu *= two;
+                    }
+                }
+                self.consecutive_divergences += 1;
+                if rejection == Rejection::Singular {
+                    self.consecutive_failed_inversions += 1;
+                } else {
+                    self.consecutive_failed_inversions = 0;
+                }
+                if rejection == Rejection::JacobianFailed {
+                    self.consecutive_failed_jacobians += 1;
+                } else {
+                    self.consecutive_failed_jacobians = 0;
+                }
+                self.rejected_steps += 1;
+                StepOutcome::Rejected
+            }
+        };
+
+        // Lambda may have been driven too high to make progress, so start over from
+        // `initial_lambda` rather than giving up while there are restarts left.
+        if (saturated || self.consecutive_divergences == config.consecutive_divergence_limit)
+            && self.restarts < config.restart_limit
+        {
+            saturated = false;
+            self.restarts += 1;
+            self.lambda = self.initial_lambda();
+            self.nu = two;
+            self.consecutive_divergences = 0;
+            self.consecutive_failed_inversions = 0;
+            self.consecutive_failed_jacobians = 0;
+        }
+
+        // Keep lambda within bounds so that it can't underflow to zero or overflow to infinity.
+        self.lambda = self.lambda.max(config.min_lambda).min(config.max_lambda);
+        debug_assert!(
+            !config.max_lambda.is_finite() || self.lambda.is_finite(),
+            "lambda overflowed to {}",
+            self.lambda,
+        );
+        debug_assert!(
+            config.min_lambda <= N::zero() || self.lambda > N::zero(),
+            "lambda underflowed to {}",
+            self.lambda,
+        );
+
+        self.termination = if exhausted {
+            // A candidate couldn't be tested, so this rejection says nothing about the problem.
+            Some(TerminationReason::BudgetExhausted)
+        } else if saturated {
+            // No step reduced the sum-of-squares even with the most damping allowed.
+            Some(TerminationReason::LambdaSaturated)
+        } else if self.consecutive_divergences == config.consecutive_divergence_limit {
+            // Terminate early if we hit the consecutive divergence limit. If every one of the
+            // divergences was a failure to invert or to compute the Jacobians, then say so.
+            Some(
+                if self.consecutive_failed_inversions == self.consecutive_divergences {
+                    TerminationReason::Inverted
+                } else if self.consecutive_failed_jacobians == self.consecutive_divergences {
+                    TerminationReason::JacobianFailed
+                } else {
+                    TerminationReason::ConsecutiveDivergence
+                },
+            )
+        } else if config.below_threshold(self.sum_of_squares, self.total) {
+            // We can terminate early if the sum of squares is below the threshold.
+            Some(TerminationReason::BelowThreshold)
+        } else if reduction_too_small {
+            // We can also terminate early if the last accepted step barely reduced the sum of
+            // squares.
+            Some(TerminationReason::ReductionTooSmall)
+        } else if config.stall_iterations != 0 && self.consecutive_stalls >= config.stall_iterations
+        {
+            // The last few accepted steps all barely reduced the sum of squares.
+            Some(TerminationReason::Stalled)
+        } else if outcome == StepOutcome::Improved && self.gradient_too_small() {
+            // We can terminate early if the gradient is small enough that we are at a minima.
+            Some(TerminationReason::GradientTooSmall)
+        } else if self.residual_evaluations >= config.max_function_evaluations {
+            // The next step couldn't be evaluated without exceeding the budget.
+            Some(TerminationReason::BudgetExhausted)
+        } else {
+            None
+        };
+
+        #[cfg(any(feature = "log", feature = "tracing"))]
+        self.record_iteration(outcome, tested);
+        self.termination.map_or(outcome, StepOutcome::Terminated)
+    }
+
+    /// Steps until optimization terminates or `max_iterations` is reached and reports the best
+    /// model, just like [`optimize_report`](crate::optimize_report).
+    pub fn run(self, problem: &LSP) -> MinimizationReport<LSP::Model, N, P> {
+        self.run_in(problem, &mut Workspace::new())
+    }
+
+    /// Steps until termination like [`run`](Self::run), but uses `workspace` as scratch space
+    /// rather than creating it again.
+    ///
+    /// This is meant for optimizing in a hot loop, such as once per frame in a real-time
+    /// system. The same workspace can be reused for any config and any problem with the same
+    /// number of parameters, and its contents never affect the result.
+    pub fn run_in(
+        mut self,
+        problem: &LSP,
+        workspace: &mut Workspace<N, P>,
+    ) -> MinimizationReport<LSP::Model, N, P> {
+        let termination = self.run_with(
+            problem,
+            workspace,
+            |_| None,
+            |_, _, _| ControlFlow::Continue(()),
+        );
+        self.into_report(termination)
+    }
+
+    /// Steps until termination like [`run`](Self::run), but stops with
+    /// [`TerminationReason::Cancelled`] once `stop` is set.
+    ///
+    /// The flag is checked at the start of every iteration, so optimization stops within one
+    /// iteration of it being set from another thread and the best model so far is returned. If it
+    /// is already set, no iterations are run and the initial guess is returned. The flag is never
+    /// cleared, so the same flag can be shared between runs which should all be cancelled
+    /// together.
+    pub fn run_cancellable(
+        mut self,
+        problem: &LSP,
+        stop: &AtomicBool,
+    ) -> MinimizationReport<LSP::Model, N, P> {
+        let termination = self.run_with(
+            problem,
+            &mut Workspace::new(),
+            |_| {
+                if stop.load(Ordering::Relaxed) {
+                    Some(TerminationReason::Cancelled)
+                } else {
+                    None
+                }
+            },
+            |_, _, _| ControlFlow::Continue(()),
+        );
+        self.into_report(termination)
+    }
+
+    /// Steps until termination like [`run`](Self::run), but stops with
+    /// [`TerminationReason::Deadline`] once `deadline` returns `true`.
+    ///
+    /// `deadline` is called at the start of every iteration and should return whether the time
+    /// for the optimization is up, so that it can be run within a fixed time budget, such as once
+    /// per frame of a video. The crate has no clock of its own, so `deadline` should wrap
+    /// whichever one is available, such as a cycle counter on an embedded target or an `Instant`
+    /// with `std`. An iteration that has already started runs to completion, so the deadline
+    /// should leave room for one more iteration. The best model so far is returned, which is the
+    /// initial guess if `deadline` returns `true` the first time that it is called.
+    pub fn run_with_deadline(
+        mut self,
+        problem: &LSP,
+        mut deadline: impl FnMut() -> bool,
+    ) -> MinimizationReport<LSP::Model, N, P> {
+        let termination = self.run_with(
+            problem,
+            &mut Workspace::new(),
+            |_| {
+                if deadline() {
+                    Some(TerminationReason::Deadline)
+                } else {
+                    None
+                }
+            },
+            |_, _, _| ControlFlow::Continue(()),
+        );
+        self.into_report(termination)
+    }
+
+    /// Steps until termination like [`run`](Self::run), but records the best sum-of-squares
+    /// after every iteration into `history`.
+    ///
+    /// Entry `i` of `history` is the sum-of-squares of the best model after iteration `i`, which
+    /// is unchanged from the previous entry when the step of that iteration was rejected. This is
+    /// the trajectory to plot when tuning a config. The caller sizes `history`, so no allocation is
+    /// required, and the first `min(report.iterations, history.len())` entries are written. A
+    /// buffer of `max_iterations` entries is always long enough, and iterations run past the end
+    /// of a shorter buffer without being recorded. The rest of `history` is left untouched.
+    pub fn run_with_history(
+        mut self,
+        problem: &LSP,
+        history: &mut [N],
+    ) -> MinimizationReport<LSP::Model, N, P> {
+        let termination = self.run_with(
+            problem,
+            &mut Workspace::new(),
+            |_| None,
+            |iteration, _, sum_of_squares| {
+                if let Some(entry) = history.get_mut(iteration) {
+                    *entry = sum_of_squares;
+                }
+                ControlFlow::Continue(())
+            },
+        );
+        self.into_report(termination)
+    }
+
+    /// Steps until termination like [`run`](Self::run), but records the lambda that every
+    /// iteration started from into `lambdas`.
+    ///
+    /// Entry `i` of `lambdas` is the lambda of the first step tested in iteration `i`, so entry `0`
+    /// is the initial lambda and each entry after it shows how the previous iteration changed it,
+    /// including the jumps back to `initial_lambda` on a restart. A lambda which keeps bouncing up
+    /// and down rather than settling usually means that `lambda_diverge` undoes what
+    /// `lambda_converge` did, as in the footgun described for `lambda_diverge` in
+    /// [`optimize`](crate::optimize). The lambda after the last iteration is the
+    /// [`MinimizationReport::lambda`]. `lambdas` is filled like the `history` of
+    /// [`run_with_history`](Self::run_with_history).
+    pub fn run_with_lambda_history(
+        mut self,
+        problem: &LSP,
+        lambdas: &mut [N],
+    ) -> MinimizationReport<LSP::Model, N, P> {
+        let termination = self.run_with(
+            problem,
+            &mut Workspace::new(),
+            |lm| {
+                if let Some(entry) = lambdas.get_mut(lm.iterations) {
+                    *entry = lm.lambda;
+                }
+                None
+            },
+            |_, _, _| ControlFlow::Continue(()),
+        );
+        self.into_report(termination)
+    }
+
+    /// Steps until termination like [`run`](Self::run), but records the best sum-of-squares
+    /// after every iteration and the lambda that it started from into `recorder`.
+    ///
+    /// `recorder` is cleared first, so afterwards it holds the last `K` iterations of this run
+    /// and [`HistoryRecorder::recorded`] is the number of iterations. Unlike
+    /// [`run_with_history`](Self::run_with_history), which keeps the first iterations that fit,
+    /// this keeps the tail of the convergence without needing a buffer of `max_iterations`
+    /// entries.
+    pub fn run_recorded<const K: usize>(
+        mut self,
+        problem: &LSP,
+        recorder: &mut HistoryRecorder<N, K>,
+    ) -> MinimizationReport<LSP::Model, N, P> {
+        recorder.clear();
+        let lambda = Cell::new(self.lambda);
+        let termination = self.run_with(
+            problem,
+            &mut Workspace::new(),
+            |lm| {
+                lambda.set(lm.lambda);
+                None
+            },
+            |_, _, sum_of_squares| {
+                recorder.record(sum_of_squares, lambda.get());
+                ControlFlow::Continue(())
+            },
+        );
+        self.into_report(termination)
+    }
+
+    /// Steps until termination like [`run`](Self::run), but `lambda_converge` and
+    /// `lambda_diverge` are chosen by `schedule` before every iteration rather than being fixed
+    /// by the config.
+    ///
+    /// The `lambda_converge` and `lambda_diverge` of the config are ignored. Passing the config
+    /// itself as the schedule is identical to [`run`](Self::run), and
+    /// [`InterpolatedSchedule`](crate::InterpolatedSchedule) makes lambda change less
+    /// aggressively as the sum-of-squares decreases. The factors only matter with
+    /// [`DampingStrategy::Multiplicative`].
+    pub fn run_with_schedule(
+        mut self,
+        problem: &LSP,
+        schedule: &impl LambdaSchedule<N>,
+    ) -> MinimizationReport<LSP::Model, N, P> {
+        let termination = self.run_with(
+            problem,
+            &mut Workspace::new(),
+            |lm| {
+                let (converge, diverge) = schedule.factors(lm.iterations, lm.sum_of_squares);
+                lm.set_lambda_factors(converge, diverge);
+                None
+            },
+            |_, _, _| ControlFlow::Continue(()),
+        );
+        self.into_report(termination)
+    }
+
+    /// Steps until termination like [`run`](Self::run), but if the final sum-of-squares is above
+    /// `target`, the best model is perturbed by `perturb` and optimized again with the same config,
+    /// up to `attempts` times.
+    ///
+    /// This is a simple form of basin hopping for escaping shallow local minima. `perturb` is
+    /// called with the best model found so far and a seed, which is `0` for the first restart and
+    /// increases by one for every restart after it, and returns the initial guess of the restart.
+    /// The crate has no random number generator of its own, so `perturb` should use the seed to
+    /// drive one, which also makes the restarts reproducible. Restarts stop as soon as the best
+    /// sum-of-squares is at or below `target`, or if the number of residuals at a restart can't be
+    /// represented by `N`.
+    ///
+    /// The report of the best attempt is returned, preferring the earlier one on a tie, so its
+    /// counts such as `iterations` only cover that attempt.
+    pub fn run_with_perturbation(
+        self,
+        problem: &LSP,
+        target: N,
+        attempts: usize,
+        mut perturb: impl FnMut(&LSP::Model, u64) -> LSP::Model,
+    ) -> MinimizationReport<LSP::Model, N, P> {
+        let config = self.config;
+        let mut workspace = Workspace::new();
+        let mut best = self.run_in(problem, &mut workspace);
+        for seed in 0..attempts as u64 {
+            if best.sum_of_squares <= target {
+                break;
+            }
+            let restart = match Self::new(config, perturb(&best.model, seed), problem) {
+                Ok(restart) => restart,
+                Err(_) => break,
+            };
+            let report = restart.run_in(problem, &mut workspace);
+            if report.sum_of_squares < best.sum_of_squares {
+                best = report;
+            }
+        }
+        best
+    }
+
+    /// Steps until termination like [`run`](Self::run), but calls `next_batch` after every
+    /// iteration to change the data in `problem`, which is then [reevaluated](Self::reevaluate)
+    /// at the current guess.
+    ///
+    /// This is meant for stochastic Levenberg-Marquardt with a `MinibatchProblem`, which requires
+    /// the `alloc` feature, where `next_batch` selects the samples of the next batch. A step is
+    /// still only accepted if it reduces the sum-of-squares of the batch it was taken on, but
+    /// this breaks the guarantee that the sum-of-squares over all of the samples decreases
+    /// monotonically. For the same reason, the returned model is only the best guess on the last
+    /// batch rather than the best one seen, and the reported sum-of-squares is only that of the
+    /// last batch. The termination criteria are also measured on a single batch, so `threshold`,
+    /// `gradient_threshold` and `ftol` are noisy and are best left at `0.0` so that
+    /// `max_iterations` decides when to stop. The acceptance test can be disabled entirely with
+    /// [`Method::GaussNewton`](crate::Method::GaussNewton), which takes every step.
+    ///
+    /// `next_batch` isn't called after the last iteration. Returns an error if the number of
+    /// residuals of a batch can't be represented by `N`.
+    pub fn run_batched(
+        mut self,
+        problem: &mut LSP,
+        mut next_batch: impl FnMut(&mut LSP),
+    ) -> Result<MinimizationReport<LSP::Model, N, P>, OptimizeError> {
+        let termination = self.run_loop(&mut Workspace::new(), |lm, workspace| {
+            lm.step_in(problem, workspace);
+
+            // Move on to the next batch unless this was the last iteration.
+            if lm.termination.is_none() && lm.iterations < lm.config.max_iterations {
+                next_batch(problem);
+                lm.reevaluate(problem)?;
+            }
+            Ok(None)
+        })?;
+        Ok(self.into_report(termination))
+    }
+
+    /// Steps until optimization terminates, `max_iterations` is reached, or `interrupt` returns
+    /// why it should stop, which it is asked before every iteration, returning why it stopped.
+    /// `interrupt` can also adjust the state before the iteration is run.
+    ///
+    /// `on_iteration` is called after every step with the iteration index, the best model, and
+    /// its sum-of-squares.
+    pub(crate) fn run_with(
+        &mut self,
+        problem: &LSP,
+        workspace: &mut Workspace<N, P>,
+        mut interrupt: impl FnMut(&mut Self) -> Option<TerminationReason>,
+        mut on_iteration: impl FnMut(usize, &LSP::Model, N) -> ControlFlow<()>,
+    ) -> TerminationReason {
+        let termination: Result<_, Infallible> = self.run_loop(workspace, |lm, workspace| {
+            if let Some(termination) = interrupt(lm) {
+                return Ok(Some(termination));
+            }
+            lm.step_in(problem, workspace);
+
+            // Let the caller observe the iteration and abort if they want to.
+            if on_iteration(lm.iterations - 1, lm.best_guess(), lm.best_sum).is_break() {
+                return Ok(Some(TerminationReason::Aborted));
+            }
+            Ok(None)
+        });
+        match termination {
+            Ok(termination) => termination,
+            Err(never) => match never {},
+        }
+    }
+
+    /// The loop shared by every way of running to termination, which calls `iteration` until
+    /// optimization terminates, `max_iterations` is reached, or `iteration` returns why it should
+    /// stop, and records the termination with the `log` and `tracing` features.
+    ///
+    /// `iteration` is responsible for stepping. An error it returns stops the loop immediately.
+    fn run_loop<E>(
+        &mut self,
+        workspace: &mut Workspace<N, P>,
+        mut iteration: impl FnMut(
+            &mut Self,
+            &mut Workspace<N, P>,
+        ) -> Result<Option<TerminationReason>, E>,
+    ) -> Result<TerminationReason, E> {
+        #[cfg(feature = "tracing")]
+        let _span =
+            tracing::debug_span!("optimize", max_iterations = self.config.max_iterations).entered();
+        let termination = loop {
+            if let Some(termination) = self.termination {
+                break termination;
+            }
+            if self.iterations == self.config.max_iterations {
+                break TerminationReason::MaxIterations;
+            }
+            if let Some(termination) = iteration(self, workspace)? {
+                break termination;
+            }
+        };
+        #[cfg(feature = "log")]
+        log::debug!(
+            "terminated after {} iterations with a sum of squares of {}: {:?}",
+            self.iterations,
+            self.best_sum,
+            termination,
+        );
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            iterations = self.iterations,
+            sum_of_squares = %self.best_sum,
+            termination = ?termination,
+            "terminated",
+        );
+        Ok(termination)
+    }
+
+    /// Consumes the state and reports the best model along with why optimization terminated.
+    pub(crate) fn into_report(
+        self,
+        termination: TerminationReason,
+    ) -> MinimizationReport<LSP::Model, N, P> {
+        let iterations = self.iterations;
+        let residual_evaluations = self.residual_evaluations;
+        let jacobian_evaluations = self.jacobian_evaluations;
+        let accepted_steps = self.accepted_steps;
+        let rejected_steps = self.rejected_steps;
+        let rank = self.rank;
+        let condition_estimate = self.condition_estimate();
+        let rank_warning = self.rank_warning(condition_estimate);
+        let lambda = self.lambda;
+        let restarts = self.restarts;
+        let still_improving = self.still_improving();
+        let gauss_newton_iterations = self.gauss_newton_iterations;
+        let damped_iterations = self.damped_iterations;
+        let reduced_chi_squared = self.reduced_chi_squared();
+        let final_gradient = self.gradients().cloned().unwrap_or_else(|| {
+            let p = solve::initial_dim::<P>();
+            VectorN::<N, P>::zeros_generic(p, U1)
+        });
+        let final_hessian = self.hessian().unwrap_or_else(|| {
+            let p = final_gradient.data.shape().0;
+            MatrixMN::<N, P, P>::zeros_generic(p, p)
+        });
+        let (model, sum_of_squares) = self.into_best();
+        MinimizationReport {
+            model,
+            termination,
+            iterations,
+            sum_of_squares,
+            residual_evaluations,
+            jacobian_evaluations,
+            accepted_steps,
+            rejected_steps,
+            rank,
+            condition_estimate,
+            rank_warning,
+            lambda: Some(lambda),
+            final_gradient,
+            final_hessian,
+            still_improving,
+            restarts,
+            gauss_newton_iterations,
+            damped_iterations,
+            reduced_chi_squared,
+        }
+    }
+
+    /// Emits a trace event for the iteration that was just run, including the lambda,
+    /// sum-of-squares, and gain ratio of the step that was tested, if any.
+    #[cfg(any(feature = "log", feature = "tracing"))]
+    fn record_iteration(&self, outcome: StepOutcome, tested: Option<(N, N, N)>) {
+        let accepted = outcome == StepOutcome::Improved;
+        #[cfg(feature = "log")]
+        match tested {
+            Some((lambda, sum_of_squares, gain_ratio)) => log::trace!(
+                "iteration {}: lambda {}, sum of squares {}, gain ratio {}, accepted {}",
+                self.iterations,
+                lambda,
+                sum_of_squares,
+                gain_ratio,
+                accepted,
+            ),
+            None => log::trace!(
+                "iteration {}: lambda {}, no step could be taken",
+                self.iterations,
+                self.lambda,
+            ),
+        }
+        #[cfg(feature = "tracing")]
+        match tested {
+            Some((lambda, sum_of_squares, gain_ratio)) => tracing::trace!(
+                iteration = self.iterations,
+                lambda = %lambda,
+                sum_of_squares = %sum_of_squares,
+                gain_ratio = %gain_ratio,
+                accepted,
+            ),
+            None => tracing::trace!(
+                iteration = self.iterations,
+                lambda = %self.lambda,
+                accepted,
+                "no step could be taken",
+            ),
+        }
+    }
+
+    /// Linearizes the problem at the new guess of the accepted `step` into the spare system of
+    /// `workspace`.
+    ///
+    /// With a `jacobian_refresh_interval` above one, the stacked Jacobian of the current guess is
+    /// updated along the step with Broyden's method, and the Jacobians are only evaluated on
+    /// every `jacobian_refresh_interval`th linearization.
+    #[allow(clippy::type_complexity)]
+    fn linearize_step<'p>(
+        &mut self,
+        problem: &'p LSP,
+        step: &mut Step<
+            LSP::Model,
+            N,
+            P,
+            Matrix<N, J, S, LSP::ResidualStorage>,
+            LSP::Jacobians<'p>,
+        >,
+        workspace: &mut Workspace<N, P>,
+    ) -> bool {
+        let jacobians = step.jacobians.take();
+        #[cfg(feature = "alloc")]
+        {
+            if self.config.jacobian_refresh_interval > 1 && !problem.modifies_normal_equations() {
+                match &mut self.broyden {
+                    Some(broyden)
+                        if broyden.updates + 1 < self.config.jacobian_refresh_interval =>
+                    {
+                        broyden.update(&step.delta, &self.residuals, &step.residuals);
+                        linearize_jacobians(
+                            &self.config,
+                            broyden.jacobians(),
+                            &step.residuals,
+                            &mut workspace.system,
+                            &mut workspace.gradients,
+                        );
+                        return true;
+                    }
+                    _ => {
+                        let linearized = linearize_broyden(
+                            &self.config,
+                            problem,
+                            &step.guess,
+                            &step.residuals,
+                            jacobians,
+                            &mut self.broyden,
+                            &mut workspace.system,
+                            &mut workspace.gradients,
+                        );
+                        self.jacobian_evaluations += 1;
+                        return linearized;
+                    }
+                }
+            }
+        }
+        let linearized = linearize(
+            &self.config,
+            problem,
+            &step.guess,
+            &step.residuals,
+            jacobians,
+            &mut workspace.system,
+            &mut workspace.gradients,
+        );
+        self.jacobian_evaluations += 1;
+        linearized
+    }
+
+    /// Replaces `lambda_converge` and `lambda_diverge` for the following steps.
+    pub fn set_lambda_factors(&mut self, lambda_converge: N, lambda_diverge: N) {
+        self.config.lambda_convege = lambda_converge;
+        self.config.lambda_diverge = lambda_diverge;
+    }
+
+    /// The lambda that `initial_lambda` starts from at the current guess.
+    fn initial_lambda(&self) -> N {
+        match self.config.initial_lambda {
+            InitialLambda::Fixed(lambda) => lambda,
+            InitialLambda::Auto { tau } => {
+                let largest = self
+                    .linearization
+                    .as_ref()
+                    .map_or(N::zero(), |(system, _)| system.hessian_diagonal().amax());
+                (tau * largest)
+                    .max(self.config.min_lambda)
+                    .min(self.config.max_lambda)
+            }
+        }
+    }
+
+    /// Solves the undamped system `JJᵀδ = g` for Gauss-Newton, or if it is singular, the system
+    /// regularized by the smallest power of ten times `ε*max(diag(JJᵀ))*I` that is solvable.
+    ///
+    /// Returns the regularizer along with the step, or `None` if the system still can't be
+    /// solved once the regularizer exceeds `max_lambda`.
+    fn regularized_solve(
+        config: &Config<N>,
+        system: &LinearSystem<N, P>,
+        gradients: &VectorN<N, P>,
+        workspace: &mut Workspace<N, P>,
+    ) -> Option<(N, VectorN<N, P>)> {
+        let identity = VectorN::<N, P>::from_element_generic(system.dim(), U1, N::one());
+        if let Some(delta) = system.solve(gradients, &identity, N::zero(), workspace) {
+            return Some((N::zero(), delta));
+        }
+        let ten = N::from_f64(10.0)?;
+        let scale = system.hessian_diagonal().max();
+        let mut regularizer =
+            N::default_epsilon() * if scale > N::zero() { scale } else { N::one() };
+        while regularizer <= config.max_lambda {
+            if let Some(delta) = system.solve(gradients, &identity, regularizer, workspace) {
+                return Some((regularizer, delta));
+            }
+            regularizer *= ten;
+        }
+        None
+    }
+
+    /// Computes Transtrum's geodesic acceleration `a` for the step `delta`, so that the step
+    /// `delta + a/2` follows the curvature of the residuals.
+    ///
+    /// The second directional derivative of the residuals along the step is estimated with a
+    /// finite difference as `r_vv = (2/h)((r(x + hδ) - r(x))/h - J_r δ)`, where `J_r` is the
+    /// Jacobian of the residuals, and then the damped system is solved again with `-J_rᵀr_vv`
+    /// as the right-hand side. The residuals and the Jacobians are always evaluated once each.
+    /// Returns `None` if the nudged guess can't be normalized, the Jacobians can't be computed
+    /// at the guess or the damped system is singular.
+    #[allow(clippy::too_many_arguments)]
+    fn acceleration(
+        problem: &LSP,
+        system: &LinearSystem<N, P>,
+        guess: &LSP::Model,
+        residuals: &Matrix<N, J, S, LSP::ResidualStorage>,
+        delta: &VectorN<N, P>,
+        h: N,
+        damping: &VectorN<N, P>,
+        lambda: N,
+        workspace: &mut Workspace<N, P>,
+    ) -> Option<VectorN<N, P>> {
+        let two = N::one() + N::one();
+        let nudged = problem.try_normalize(problem.try_apply_delta(guess, delta * h)?)?;
+        let nudged_residuals = problem.residuals(&nudged);
+        let jacobians = problem.try_jacobians(guess)?;
+
+        // The Jacobians are of the negative residuals, so `J_r δ` is `-Jᵀδ` and `-J_rᵀr_vv` is
+        // `J r_vv`.
+        let mut rhs = VectorN::<N, P>::zeros_generic(system.dim(), U1);
+        for (jacobian, (nudged, current)) in
+            jacobians.zip(nudged_residuals.column_iter().zip(residuals.column_iter()))
+        {
+            for (k, row) in jacobian.column_iter().enumerate() {
+                let directional = (nudged[k] - current[k]) / h + row.dot(delta);
+                rhs.axpy(directional * two / h, &row, N::one());
+            }
+        }
+        system.solve(&rhs, damping, lambda, workspace)
+    }
+
+    /// An estimate of the condition number of the undamped approximate Hessian at the current
+    /// guess, or `None` if it is singular or the Jacobians couldn't be computed.
+    pub fn condition_estimate(&self) -> Option<N> {
+        self.linearization
+            .as_ref()
+            .and_then(|(system, _)| system.condition_estimate())
+    }
+
+    /// The undamped approximate Hessian `JJᵀ` at the current guess, or `None` if the Jacobians
+    /// couldn't be computed at the initial guess.
+    ///
+    /// This is the linearization that the next step would be solved from, before lambda is
+    /// added to its diagonal.
+    pub fn hessian(&self) -> Option<MatrixMN<N, P, P>> {
+        self.linearization
+            .as_ref()
+            .map(|(system, _)| system.hessian())
+    }
+
+    /// Whether the approximate Hessian at the current guess is singular or `condition_estimate`
+    /// is above `condition_warning_threshold`.
+    pub(crate) fn rank_warning(&self, condition_estimate: Option<N>) -> bool {
+        self.linearization.is_some()
+            && condition_estimate.map_or(true, |estimate| {
+                estimate > self.config.condition_warning_threshold
+            })
+    }
+
+    /// Whether the infinity-norm of the gradient at the current guess is below
+    /// `gradient_threshold`.
+    fn gradient_too_small(&self) -> bool {
+        self.linearization.as_ref().map_or(false, |(_, gradients)| {
+            let gradient_norm = gradients
+                .iter()
+                .fold(N::zero(), |norm, gradient| norm.max(gradient.abs()));
+            gradient_norm < self.config.gradient_threshold
+        })
+    }
+
+    /// The undamped approximate Hessian `JJᵀ` at [`best_guess`](Self::best_guess).
+    ///
+    /// Returns `None` if the Jacobians couldn't be computed there or if the best guess isn't
+    /// the current guess, since only the current guess is linearized.
+    pub(crate) fn best_hessian(&self) -> Option<MatrixMN<N, P, P>> {
+        if self.best_guess.is_some() {
+            return None;
+        }
+        self.linearization
+            .as_ref()
+            .map(|(system, _)| system.hessian())
+    }
+}
+
+impl<N, P, S, J, LSP> LevenbergMarquardt<N, P, S, J, LSP>
+where
+    N: RealField,
+    P: Dim,
+    S: Dim,
+    J: Dim,
+    LSP: LeastSquaresProblem<N, P, S, J>,
+    DefaultAllocator: Allocator<N, P, P>,
+    DefaultAllocator: Allocator<N, P>,
+{
+    /// The current guess, which is the last one that was accepted.
+    pub fn guess(&self) -> &LSP::Model {
+        &self.guess
+    }
+
+    /// The residuals of the current guess.
+    pub fn residuals(&self) -> &Matrix<N, J, S, LSP::ResidualStorage> {
+        &self.residuals
+    }
+
+    /// The sum-of-squares of the residuals of the current guess.
+    pub fn sum_of_squares(&self) -> N {
+        self.sum_of_squares
+    }
+
+    /// The model with the lowest sum-of-squares that was seen so far.
+    pub fn best_guess(&self) -> &LSP::Model {
+        self.best_guess.as_ref().unwrap_or(&self.guess)
+    }
+
+    /// The sum-of-squares of [`best_guess`](Self::best_guess).
+    pub fn best_sum_of_squares(&self) -> N {
+        self.best_sum
+    }
+
+    /// The gradients `Jr` at the current guess, or `None` if the Jacobians couldn't be computed
+    /// at the initial guess.
+    pub fn gradients(&self) -> Option<&VectorN<N, P>> {
+        self.linearization.as_ref().map(|(_, gradients)| gradients)
+    }
+
+    /// The lambda that the next step will be taken with.
+    pub fn lambda(&self) -> N {
+        self.lambda
+    }
+
+    /// The number of steps that were rejected in a row.
+    pub fn consecutive_divergences(&self) -> usize {
+        self.consecutive_divergences
+    }
+
+    /// The number of times lambda was reset to `initial_lambda` after
+    /// `consecutive_divergence_limit` steps were rejected in a row.
+    pub fn restarts(&self) -> usize {
+        self.restarts
+    }
+
+    /// The number of iterations that have actually been run.
+    pub fn iterations(&self) -> usize {
+        self.iterations
+    }
+
+    /// The number of iterations that took an undamped Gauss-Newton step, which is every
+    /// iteration with [`Method::GaussNewton`] and none with [`Method::LevenbergMarquardt`].
+    pub fn gauss_newton_iterations(&self) -> usize {
+        self.gauss_newton_iterations
+    }
+
+    /// The number of iterations that took a damped step, which together with
+    /// [`gauss_newton_iterations`](Self::gauss_newton_iterations) adds up to
+    /// [`iterations`](Self::iterations).
+    pub fn damped_iterations(&self) -> usize {
+        self.damped_iterations
+    }
+
+    /// The number of times the residuals of the problem were evaluated, including at the
+    /// initial guess.
+    pub fn residual_evaluations(&self) -> usize {
+        self.residual_evaluations
+    }
+
+    /// The number of times the Jacobians of the problem were evaluated, including at the
+    /// initial guess and including evaluations which failed.
+    pub fn jacobian_evaluations(&self) -> usize {
+        self.jacobian_evaluations
+    }
+
+    /// The number of iterations whose step was accepted.
+    pub fn accepted_steps(&self) -> usize {
+        self.accepted_steps
+    }
+
+    /// The number of iterations whose step was rejected, which increased lambda.
+    pub fn rejected_steps(&self) -> usize {
+        self.rejected_steps
+    }
+
+    /// The numerical rank of the damped system that was solved for the last step, which is
+    /// only detected by [`SolveMethod::Svd`].
+    pub fn rank(&self) -> Option<usize> {
+        self.rank
+    }
+
+    /// Whether the relative reduction of the sum-of-squares on the last accepted step was above
+    /// `ftol`, which means that more iterations would likely keep improving the fit.
+    ///
+    /// This is `false` if no step has been accepted yet.
+    pub fn still_improving(&self) -> bool {
+        self.last_reduction
+            .map_or(false, |reduction| reduction > self.config.ftol)
+    }
+
+    /// The reduced chi-squared of the best guess, which is its sum-of-squares divided by the
+    /// number of residuals minus the number of parameters.
+    ///
+    /// This is `None` if there aren't more residuals than parameters, or if the Jacobians
+    /// couldn't be computed at the current guess, since the number of parameters of a dynamic
+    /// `P` is only known from them.
+    pub fn reduced_chi_squared(&self) -> Option<N> {
+        let (_, gradients) = self.linearization.as_ref()?;
+        statistics::reduced_chi_square(self.best_sum, self.residuals.len(), gradients.len())
+    }
+
+    /// Why optimization terminated, or `None` if steps can still be taken.
+    pub fn termination(&self) -> Option<TerminationReason> {
+        self.termination
+    }
+
+    /// Consumes the state and returns the best model and its sum-of-squares.
+    pub fn into_best(self) -> (LSP::Model, N) {
+        (self.best_guess.unwrap_or(self.guess), self.best_sum)
+    }
+}
+
+/// Iterates through all the Jacobians to extract the linear system and the gradients into
+/// `system` and `gradients`.
+///
+/// If `jacobians` were already computed along with `residuals`, they are used rather than
+/// asking the problem for them again. If the problem modifies its normal equations, those are
+/// used instead with any config, and the system falls back from [`SolveMethod::Qr`] to the
+/// normal equations. Returns `false` and leaves both untouched if the Jacobians can't be
+/// computed at the guess.
+pub(crate) fn linearize<'p, N, P, S, J, LSP>(
+    config: &Config<N>,
+    problem: &'p LSP,
+    guess: &LSP::Model,
+    residuals: &Matrix<N, J, S, LSP::ResidualStorage>,
+    jacobians: Option<LSP::Jacobians<'p>>,
+    system: &mut LinearSystem<N, P>,
+    gradients: &mut VectorN<N, P>,
+) -> bool
+where
+    N: RealField,
+    P: DimMin<P>,
+    S: Dim,
+    J: DimName,
+    LSP: LeastSquaresProblem<N, P, S, J>,
+    DefaultAllocator: Allocator<N, J, P>,
+    DefaultAllocator: Allocator<N, P, P>,
+    DefaultAllocator: Allocator<N, P>,
+    ShapeConstraint: DimEq<DimMinimum<P, P>, P>,
+{
+    // Summing the Jacobians would leave out whatever the problem adds to its normal equations,
+    // so they are only used if it doesn't.
+    let modifies = problem.modifies_normal_equations();
+    match (config.solve_method, jacobians) {
+        (SolveMethod::Qr, jacobians) if !modifies => {
+            match jacobians.or_else(|| problem.try_jacobians(guess)) {
+                Some(jacobians) => {
+                    linearize_jacobians(config, jacobians, residuals, system, gradients);
+                    true
+                }
+                None => false,
+            }
+        }
+        (_, Some(jacobians)) if !modifies => {
+            linearize_jacobians(config, jacobians, residuals, system, gradients);
+            true
+        }
+        // The problem's normal equations are summed naively, so only the Jacobians can be
+        // accumulated with compensation.
+        (_, None) if config.compensated_accumulation && !modifies => {
+            match problem.try_jacobians(guess) {
+                Some(jacobians) => {
+                    linearize_jacobians(config, jacobians, residuals, system, gradients);
+                    true
+                }
+                None => false,
+            }
+        }
+        (method, _) => match problem.normal_equations(guess, residuals) {
+            Some((hessian, new_gradients)) => {
+                system.set_hessian(method, &hessian);
+                *gradients = new_gradients;
+                true
+            }
+            None => false,
+        },
+    }
+}
+
+/// Linearizes the problem at a guess that no step was taken to, which stacks the Jacobians
+/// into `broyden` if they will be updated with Broyden's method.
+#[allow(clippy::too_many_arguments)]
+fn linearize_guess<'p, N, P, S, J, LSP>(
+    config: &Config<N>,
+    problem: &'p LSP,
+    guess: &LSP::Model,
+    residuals: &Matrix<N, J, S, LSP::ResidualStorage>,
+    jacobians: Option<LSP::Jacobians<'p>>,
+    #[cfg(feature = "alloc")] broyden: &mut Option<Broyden<N>>,
+    system: &mut LinearSystem<N, P>,
+    gradients: &mut VectorN<N, P>,
+) -> bool
+where
+    N: RealField,
+    P: DimMin<P>,
+    S: Dim,
+    J: DimName,
+    LSP: LeastSquaresProblem<N, P, S, J>,
+    DefaultAllocator: Allocator<N, J, P>,
+    DefaultAllocator: Allocator<N, P, P>,
+    DefaultAllocator: Allocator<N, P>,
+    ShapeConstraint: DimEq<DimMinimum<P, P>, P>,
+{
+    #[cfg(feature = "alloc")]
+    {
+        if config.jacobian_refresh_interval > 1 && !problem.modifies_normal_equations() {
+            return linearize_broyden(
+                config, problem, guess, residuals, jacobians, broyden, system, gradients,
+            );
+        }
+    }
+    linearize(
+        config, problem, guess, residuals, jacobians, system, gradients,
+    )
+}
+
+/// Identical to [`linearize`], but also stacks the Jacobians into `broyden` so that they can be
+/// updated with Broyden's method rather than evaluated at the next guess.
+///
+/// The Jacobians are always evaluated rather than asking the problem for its normal equations,
+/// since they are needed for the update.
+#[cfg(feature = "alloc")]
+#[allow(clippy::too_many_arguments)]
+fn linearize_broyden<'p, N, P, S, J, LSP>(
+    config: &Config<N>,
+    problem: &'p LSP,
+    guess: &LSP::Model,
+    residuals: &Matrix<N, J, S, LSP::ResidualStorage>,
+    jacobians: Option<LSP::Jacobians<'p>>,
+    broyden: &mut Option<Broyden<N>>,
+    system: &mut LinearSystem<N, P>,
+    gradients: &mut VectorN<N, P>,
+) -> bool
+where
+    N: RealField,
+    P: DimMin<P>,
+    S: Dim,
+    J: DimName,
+    LSP: LeastSquaresProblem<N, P, S, J>,
+    DefaultAllocator: Allocator<N, J, P>,
+    DefaultAllocator: Allocator<N, P, P>,
+    DefaultAllocator: Allocator<N, P>,
+    ShapeConstraint: DimEq<DimMinimum<P, P>, P>,
+{
+    match jacobians.or_else(|| problem.try_jacobians(guess)) {
+        Some(jacobians) => {
+            let stacked = Broyden::new(jacobians);
+            linearize_jacobians(config, stacked.jacobians(), residuals, system, gradients);
+            *broyden = Some(stacked);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Extracts the linear system and the gradients of `jacobians` into `system` and `gradients`.
+fn linearize_jacobians<N, P, S, J, JS, RS>(
+    config: &Config<N>,
+    jacobians: impl Iterator<Item = Matrix<N, P, J, JS>>,
+    residuals: &Matrix<N, J, S, RS>,
+    system: &mut LinearSystem<N, P>,
+    gradients: &mut VectorN<N, P>,
+) where
+    N: RealField,
+    P: DimMin<P>,
+    S: Dim,
+    J: DimName,
+    JS: Storage<N, P, J>,
+    RS: Storage<N, J, S>,
+    DefaultAllocator: Allocator<N, J, P>,
+    DefaultAllocator: Allocator<N, P, P>,
+    DefaultAllocator: Allocator<N, P>,
+    ShapeConstraint: DimEq<DimMinimum<P, P>, P>,
+{
+    match config.solve_method {
+        SolveMethod::Qr => system.set_qr(gradients, jacobians, residuals),
+        method => {
+            let (hessian, new_gradients) = if config.compensated_accumulation {
+                solve::compensated_normal_equations(jacobians, residuals)
+            } else {
+                solve::normal_equations(jacobians, residuals)
+            };
+            system.set_hessian(method, &hessian);
+            *gradients = new_gradients;
+        }
+    }
+}
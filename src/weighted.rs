@@ -0,0 +1,305 @@
+use crate::LeastSquaresProblem;
+use nalgebra::{
+    allocator::Allocator,
+    storage::{Owned, Storage},
+    DefaultAllocator, Dim, Matrix, MatrixMN, RealField, VectorN,
+};
+
+/// Adapts a [`LeastSquaresProblem`] so that each residual and its column of the Jacobian are
+/// multiplied by a weight, which is known as weighted least squares.
+///
+/// `weights` must return a matrix with the same shape as the residuals of the problem. Each
+/// weight multiplies its residual and the corresponding column of the Jacobian of its sample,
+/// so the wrapped problem should be left unweighted. Since the weights are multipliers on the
+/// residuals rather than on the squared residuals, the weight of a measurement with a standard
+/// deviation of `σ` is `1/σ`, not `1/σ²`. The sum-of-squares which is minimized and reported is
+/// of the weighted residuals. If the wrapped problem overrides
+/// [`LeastSquaresProblem::normal_equations`], such as to sum them in parallel, that override
+/// isn't used, since it would sum the unweighted Jacobians.
+///
+/// Closures can be weighted by wrapping them in a [`ClosureProblem`](crate::ClosureProblem)
+/// first, and the result is optimized with [`optimize_problem`](crate::optimize_problem).
+pub struct WeightedProblem<LSP, W> {
+    problem: LSP,
+    weights: W,
+}
+
+impl<LSP, W> WeightedProblem<LSP, W> {
+    /// Weights the residuals of `problem` by the matrix returned from `weights`.
+    pub fn new(problem: LSP, weights: W) -> Self {
+        Self { problem, weights }
+    }
+}
+
+impl<N, P, S, J, LSP, W, WS> LeastSquaresProblem<N, P, S, J> for WeightedProblem<LSP, W>
+where
+    N: RealField,
+    P: Dim,
+    S: Dim,
+    J: Dim,
+    LSP: LeastSquaresProblem<N, P, S, J>,
+    W: Fn(&LSP::Model) -> Matrix<N, J, S, WS>,
+    WS: Storage<N, J, S>,
+    DefaultAllocator: Allocator<N, P>,
+    DefaultAllocator: Allocator<N, J, S>,
+    DefaultAllocator: Allocator<N, P, J>,
+{
+    type Model = LSP::Model;
+    type ResidualStorage = Owned<N, J, S>;
+    type JacobianStorage = Owned<N, P, J>;
+    type Jacobians<'a>
+        = WeightedJacobians<LSP::Jacobians<'a>, N, J, S>
+    where
+        Self: 'a;
+
+    fn apply_delta(&self, model: &Self::Model, delta: VectorN<N, P>) -> Self::Model {
+        self.problem.apply_delta(model, delta)
+    }
+
+    fn try_apply_delta(&self, model: &Self::Model, delta: VectorN<N, P>) -> Option<Self::Model> {
+        self.problem.try_apply_delta(model, delta)
+    }
+
+    fn residuals(&self, model: &Self::Model) -> MatrixMN<N, J, S> {
+        self.problem
+            .residuals(model)
+            .component_mul(&(self.weights)(model))
+    }
+
+    fn jacobians(&self, model: &Self::Model) -> Self::Jacobians<'_> {
+        WeightedJacobians::new(
+            self.problem.jacobians(model),
+            (self.weights)(model).into_owned(),
+        )
+    }
+
+    fn try_jacobians(&self, model: &Self::Model) -> Option<Self::Jacobians<'_>> {
+        let jacobians = self.problem.try_jacobians(model)?;
+        Some(WeightedJacobians::new(
+            jacobians,
+            (self.weights)(model).into_owned(),
+        ))
+    }
+
+    fn residuals_and_jacobians(
+        &self,
+        model: &Self::Model,
+    ) -> (MatrixMN<N, J, S>, Option<Self::Jacobians<'_>>) {
+        let (residuals, jacobians) = self.problem.residuals_and_jacobians(model);
+        let weights = (self.weights)(model).into_owned();
+        let residuals = residuals.component_mul(&weights);
+        (
+            residuals,
+            jacobians.map(|jacobians| WeightedJacobians::new(jacobians, weights)),
+        )
+    }
+
+    fn normalize(&self, model: Self::Model) -> Self::Model {
+        self.problem.normalize(model)
+    }
+
+    fn try_normalize(&self, model: Self::Model) -> Option<Self::Model> {
+        self.problem.try_normalize(model)
+    }
+}
+
+/// Multiplies each column of the Jacobian of every sample by the weight of its residual.
+pub struct WeightedJacobians<I, N, J, S>
+where
+    N: RealField,
+    J: Dim,
+    S: Dim,
+    DefaultAllocator: Allocator<N, J, S>,
+{
+    jacobians: I,
+    weights: MatrixMN<N, J, S>,
+    sample: usize,
+}
+
+impl<I, N, J, S> WeightedJacobians<I, N, J, S>
+where
+    N: RealField,
+    J: Dim,
+    S: Dim,
+    DefaultAllocator: Allocator<N, J, S>,
+{
+    /// Weights the Jacobian of each sample with the corresponding column of `weights`.
+    pub(crate) fn new(jacobians: I, weights: MatrixMN<N, J, S>) -> Self {
+        Self {
+            jacobians,
+            weights,
+            sample: 0,
+        }
+    }
+}
+
+impl<I, N, P, S, J, JS> Iterator for WeightedJacobians<I, N, J, S>
+where
+    I: Iterator<Item = Matrix<N, P, J, JS>>,
+    N: RealField,
+    P: Dim,
+    S: Dim,
+    J: Dim,
+    JS: Storage<N, P, J>,
+    DefaultAllocator: Allocator<N, J, S>,
+    DefaultAllocator: Allocator<N, P, J>,
+{
+    type Item = MatrixMN<N, P, J>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut jacobian = self.jacobians.next()?.into_owned();
+        let weights = self.weights.column(self.sample);
+        for (mut column, &weight) in jacobian.column_iter_mut().zip(weights.iter()) {
+            column *= weight;
+        }
+        self.sample += 1;
+        Some(jacobian)
+    }
+}
+
+/// Adapts a [`LeastSquaresProblem`] so that the residuals of each sample are weighted by the
+/// Cholesky factor `L` of its information matrix `W = LLᵀ`.
+///
+/// This is the Mahalanobis distance formulation for samples whose residuals have a covariance
+/// `Σ` with correlated components, such as a measurement of a position from a sensor with a
+/// full covariance. `factors` must return one `J`×`J` factor `L` for each sample, in the same
+/// order as the columns of the residuals and the Jacobians, such that `W = LLᵀ`. The lower
+/// triangular factor of the Cholesky decomposition of `W` is one such factor, and a diagonal
+/// matrix of the weights that [`WeightedProblem`] would take is another. The weighted residuals
+/// of a sample are `Lᵀr`, and since the Jacobians are stored with one column per residual, the
+/// weighted Jacobian of a sample is `JL`. The sum-of-squares which is minimized and reported is
+/// then the sum of the squared Mahalanobis distances `rᵀWr` over the samples, and the wrapped
+/// problem should be left unweighted. Like with [`WeightedProblem`], an override of
+/// [`LeastSquaresProblem::normal_equations`] by the wrapped problem isn't used.
+pub struct InformationWeightedProblem<LSP, F> {
+    problem: LSP,
+    factors: F,
+}
+
+impl<LSP, F> InformationWeightedProblem<LSP, F> {
+    /// Weights the residuals of each sample of `problem` by the factor returned from `factors`.
+    pub fn new(problem: LSP, factors: F) -> Self {
+        Self { problem, factors }
+    }
+}
+
+impl<N, P, S, J, LSP, F, IL, LS> LeastSquaresProblem<N, P, S, J>
+    for InformationWeightedProblem<LSP, F>
+where
+    N: RealField,
+    P: Dim,
+    S: Dim,
+    J: Dim,
+    LSP: LeastSquaresProblem<N, P, S, J>,
+    F: Fn(&LSP::Model) -> IL,
+    IL: Iterator<Item = Matrix<N, J, J, LS>>,
+    LS: Storage<N, J, J>,
+    DefaultAllocator: Allocator<N, P>,
+    DefaultAllocator: Allocator<N, J>,
+    DefaultAllocator: Allocator<N, J, S>,
+    DefaultAllocator: Allocator<N, P, J>,
+{
+    type Model = LSP::Model;
+    type ResidualStorage = Owned<N, J, S>;
+    type JacobianStorage = Owned<N, P, J>;
+    type Jacobians<'a>
+        = InformationWeightedJacobians<LSP::Jacobians<'a>, IL>
+    where
+        Self: 'a;
+
+    fn apply_delta(&self, model: &Self::Model, delta: VectorN<N, P>) -> Self::Model {
+        self.problem.apply_delta(model, delta)
+    }
+
+    fn try_apply_delta(&self, model: &Self::Model, delta: VectorN<N, P>) -> Option<Self::Model> {
+        self.problem.try_apply_delta(model, delta)
+    }
+
+    fn residuals(&self, model: &Self::Model) -> MatrixMN<N, J, S> {
+        weigh_samples(self.problem.residuals(model), (self.factors)(model))
+    }
+
+    fn jacobians(&self, model: &Self::Model) -> Self::Jacobians<'_> {
+        InformationWeightedJacobians {
+            jacobians: self.problem.jacobians(model),
+            factors: (self.factors)(model),
+        }
+    }
+
+    fn try_jacobians(&self, model: &Self::Model) -> Option<Self::Jacobians<'_>> {
+        Some(InformationWeightedJacobians {
+            jacobians: self.problem.try_jacobians(model)?,
+            factors: (self.factors)(model),
+        })
+    }
+
+    fn residuals_and_jacobians(
+        &self,
+        model: &Self::Model,
+    ) -> (MatrixMN<N, J, S>, Option<Self::Jacobians<'_>>) {
+        let (residuals, jacobians) = self.problem.residuals_and_jacobians(model);
+        let residuals = weigh_samples(residuals, (self.factors)(model));
+        let jacobians = jacobians.map(|jacobians| InformationWeightedJacobians {
+            jacobians,
+            factors: (self.factors)(model),
+        });
+        (residuals, jacobians)
+    }
+
+    fn normalize(&self, model: Self::Model) -> Self::Model {
+        self.problem.normalize(model)
+    }
+
+    fn try_normalize(&self, model: Self::Model) -> Option<Self::Model> {
+        self.problem.try_normalize(model)
+    }
+}
+
+/// Replaces the residuals `r` of every sample with `Lᵀr` for its factor `L`.
+fn weigh_samples<N, S, J, RS, IL, LS>(
+    residuals: Matrix<N, J, S, RS>,
+    factors: IL,
+) -> MatrixMN<N, J, S>
+where
+    N: RealField,
+    S: Dim,
+    J: Dim,
+    RS: Storage<N, J, S>,
+    IL: Iterator<Item = Matrix<N, J, J, LS>>,
+    LS: Storage<N, J, J>,
+    DefaultAllocator: Allocator<N, J>,
+    DefaultAllocator: Allocator<N, J, S>,
+{
+    let mut residuals = residuals.into_owned();
+    for (mut column, factor) in residuals.column_iter_mut().zip(factors) {
+        let weighted = factor.tr_mul(&column);
+        column.copy_from(&weighted);
+    }
+    residuals
+}
+
+/// Multiplies the Jacobian of every sample by the Cholesky factor of its information matrix.
+pub struct InformationWeightedJacobians<I, IL> {
+    jacobians: I,
+    factors: IL,
+}
+
+impl<I, IL, N, P, J, JS, LS> Iterator for InformationWeightedJacobians<I, IL>
+where
+    I: Iterator<Item = Matrix<N, P, J, JS>>,
+    IL: Iterator<Item = Matrix<N, J, J, LS>>,
+    N: RealField,
+    P: Dim,
+    J: Dim,
+    JS: Storage<N, P, J>,
+    LS: Storage<N, J, J>,
+    DefaultAllocator: Allocator<N, P, J>,
+{
+    type Item = MatrixMN<N, P, J>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let jacobian = self.jacobians.next()?;
+        let factor = self.factors.next()?;
+        Some(jacobian * factor)
+    }
+}
@@ -0,0 +1,72 @@
+use crate::{
+    solve::{self, LinearSystem},
+    SolveMethod,
+};
+use nalgebra::{
+    allocator::Allocator,
+    constraint::{DimEq, ShapeConstraint},
+    dimension::{DimMin, DimMinimum},
+    DefaultAllocator, Dim, MatrixMN, RealField, VectorN, U1,
+};
+
+/// Scratch space for the linear algebra of each iteration, which can be reused between calls
+/// to [`LevenbergMarquardt::run_in`](crate::LevenbergMarquardt::run_in) and
+/// [`LevenbergMarquardt::step_in`](crate::LevenbergMarquardt::step_in).
+///
+/// This holds a spare approximate Hessian and gradient vector that the linearization at each
+/// accepted step is accumulated into, as well as the damped copy of the Hessian which is
+/// solved for each candidate step. Reusing a workspace means that these are only created
+/// once rather than on every call. The residuals and Jacobians are still owned by the caller,
+/// so they must avoid allocating too for optimization to run without any allocation.
+///
+/// If the number of parameters is only known at runtime, the workspace starts out empty and is
+/// resized the first time it is used, or whenever it is used for a different number of
+/// parameters.
+pub struct Workspace<N, P>
+where
+    N: RealField,
+    P: Dim,
+    DefaultAllocator: Allocator<N, P, P>,
+    DefaultAllocator: Allocator<N, P>,
+{
+    pub(crate) system: LinearSystem<N, P>,
+    pub(crate) gradients: VectorN<N, P>,
+    pub(crate) damped: MatrixMN<N, P, P>,
+    pub(crate) rhs: VectorN<N, P>,
+    /// The numerical rank detected by the last solve, if its method detects one.
+    pub(crate) rank: Option<usize>,
+}
+
+impl<N, P> Workspace<N, P>
+where
+    N: RealField,
+    P: DimMin<P>,
+    DefaultAllocator: Allocator<N, P, P>,
+    DefaultAllocator: Allocator<N, P>,
+    ShapeConstraint: DimEq<DimMinimum<P, P>, P>,
+{
+    /// Creates the scratch space for `P` parameters.
+    pub fn new() -> Self {
+        let p = solve::initial_dim::<P>();
+        Self {
+            system: LinearSystem::zeros(SolveMethod::NormalEquations, p),
+            gradients: VectorN::<N, P>::zeros_generic(p, U1),
+            damped: MatrixMN::<N, P, P>::zeros_generic(p, p),
+            rhs: VectorN::<N, P>::zeros_generic(p, U1),
+            rank: None,
+        }
+    }
+}
+
+impl<N, P> Default for Workspace<N, P>
+where
+    N: RealField,
+    P: DimMin<P>,
+    DefaultAllocator: Allocator<N, P, P>,
+    DefaultAllocator: Allocator<N, P>,
+    ShapeConstraint: DimEq<DimMinimum<P, P>, P>,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
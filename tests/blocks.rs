@@ -0,0 +1,109 @@
+#![cfg(feature = "alloc")]
+
+use levenberg_marquardt::{optimize_problem, BlockProblem, Config, LevenbergMarquardt};
+use nalgebra::{Vector1, Vector2};
+use std::cell::Cell;
+
+/// A sample of `y = 2exp(-0.5x)` at `x`.
+fn sample(x: f64) -> (f64, f64) {
+    (x, 2.0 * (-0.5 * x).exp())
+}
+
+#[test]
+fn sliding_window_only_linearizes_new_blocks() {
+    let evaluations = Cell::new(0);
+    let mut problem = BlockProblem::new(
+        |model: &Vector2<f64>, delta| model + delta,
+        |model: &Vector2<f64>, &(x, y): &(f64, f64)| {
+            Vector1::new(y - model.x * (-model.y * x).exp())
+        },
+        |model: &Vector2<f64>, &(x, _): &(f64, f64)| {
+            evaluations.set(evaluations.get() + 1);
+            let exp = (-model.y * x).exp();
+            Vector2::new(exp, -model.x * x * exp)
+        },
+    );
+    let mut window: Vec<_> = (0..10)
+        .map(|i| problem.add_block(sample(f64::from(i) * 0.25)))
+        .collect();
+    let config = Config::default();
+    let first = optimize_problem(config, Vector2::new(1.0, 1.0), &problem);
+    assert!((first.model - Vector2::new(2.0, 0.5)).norm() < 1e-6);
+
+    // Slide the window forward by two samples.
+    for _ in 0..2 {
+        assert!(problem.remove_block(window.remove(0)).is_some());
+    }
+    for i in 10..12 {
+        window.push(problem.add_block(sample(f64::from(i) * 0.25)));
+    }
+    assert_eq!(problem.len(), 10);
+    assert!(problem.block_ids().eq(window.iter().copied()));
+
+    evaluations.set(0);
+    let mut lm = LevenbergMarquardt::new(config, first.model, &problem).unwrap();
+    assert_eq!(evaluations.get(), 2);
+
+    // The cached linearization is the same as the one that would have been computed.
+    let mut fresh = BlockProblem::new(
+        |model: &Vector2<f64>, delta| model + delta,
+        |model: &Vector2<f64>, &(x, y): &(f64, f64)| {
+            Vector1::new(y - model.x * (-model.y * x).exp())
+        },
+        |model: &Vector2<f64>, &(x, _): &(f64, f64)| {
+            let exp = (-model.y * x).exp();
+            Vector2::new(exp, -model.x * x * exp)
+        },
+    );
+    for &id in &window {
+        fresh.add_block(*problem.block(id).unwrap());
+    }
+    let expected = LevenbergMarquardt::new(config, first.model, &fresh).unwrap();
+    assert_eq!(lm.gradients(), expected.gradients());
+    assert_eq!(lm.hessian(), expected.hessian());
+    while lm.termination().is_none() && lm.iterations() < config.max_iterations {
+        lm.step(&problem);
+    }
+    assert!((lm.guess() - Vector2::new(2.0, 0.5)).norm() < 1e-6);
+}
+
+#[test]
+fn modified_blocks_are_linearized_again() {
+    let evaluations = Cell::new(0);
+    let mut problem = BlockProblem::new(
+        |model: &Vector1<f64>, delta| model + delta,
+        |model: &Vector1<f64>, &y: &f64| Vector1::new(y - model.x),
+        |_: &Vector1<f64>, _: &f64| {
+            evaluations.set(evaluations.get() + 1);
+            Vector1::new(1.0)
+        },
+    );
+    let ids: Vec<_> = [1.0, 2.0, 3.0]
+        .iter()
+        .map(|&y| problem.add_block(y))
+        .collect();
+    let config = Config::default();
+    let init = Vector1::new(0.0);
+    LevenbergMarquardt::new(config, init, &problem).unwrap();
+    assert_eq!(evaluations.get(), 3);
+
+    // Nothing changed, so every block is reused.
+    LevenbergMarquardt::new(config, init, &problem).unwrap();
+    assert_eq!(evaluations.get(), 3);
+
+    *problem.block_mut(ids[1]).unwrap() = 5.0;
+    LevenbergMarquardt::new(config, init, &problem).unwrap();
+    assert_eq!(evaluations.get(), 4);
+
+    // A different model can't use any of the caches.
+    LevenbergMarquardt::new(config, Vector1::new(1.0), &problem).unwrap();
+    assert_eq!(evaluations.get(), 7);
+
+    problem.invalidate();
+    LevenbergMarquardt::new(config, Vector1::new(1.0), &problem).unwrap();
+    assert_eq!(evaluations.get(), 10);
+
+    assert_eq!(problem.remove_block(ids[0]), Some(1.0));
+    assert_eq!(problem.remove_block(ids[0]), None);
+    assert_eq!(problem.block(ids[1]), Some(&5.0));
+}
@@ -0,0 +1,68 @@
+use levenberg_marquardt::{optimize_problem, BoundedProblem, Config, MinimizationReport};
+use nalgebra::{Vector2, U2};
+
+mod common;
+
+use common::line::{problem, residuals, samples};
+
+fn fit(
+    config: Config<f64>,
+    samples: &[(f64, f64)],
+    init: Vector2<f64>,
+    lower: Option<Vector2<f64>>,
+    upper: Option<Vector2<f64>>,
+) -> MinimizationReport<Vector2<f64>, f64, U2> {
+    let problem = BoundedProblem::new(problem(samples), lower, upper);
+    let init = problem.clamp(init);
+    optimize_problem(config, init, &problem)
+}
+
+#[test]
+fn interior_optimum_is_unaffected() {
+    let samples = samples();
+    let bounded = fit(
+        Config::default(),
+        &samples,
+        Vector2::zeros(),
+        Some(Vector2::new(-10.0, -10.0)),
+        Some(Vector2::new(10.0, 10.0)),
+    );
+    let unbounded = optimize_problem(Config::default(), Vector2::zeros(), &problem(&samples));
+    assert_eq!(bounded, unbounded);
+}
+
+#[test]
+fn active_bound_is_respected() {
+    let samples = samples();
+    let report = fit(
+        Config::default(),
+        &samples,
+        Vector2::zeros(),
+        None,
+        Some(Vector2::new(2.0, f64::INFINITY)),
+    );
+    assert_eq!(report.model.x, 2.0);
+    // With the slope fixed at the bound, the best intercept is the mean of `y - 2x`.
+    let intercept = samples.iter().map(|&(x, y)| y - 2.0 * x).sum::<f64>() / 10.0;
+    assert!((report.model.y - intercept).abs() < 1e-3);
+    assert!(
+        (report.sum_of_squares - residuals(&samples, &report.model).norm_squared()).abs() < 1e-12
+    );
+}
+
+#[test]
+fn init_is_clamped() {
+    let samples = samples();
+    let config = Config {
+        max_iterations: 0,
+        ..Config::default()
+    };
+    let report = fit(
+        config,
+        &samples,
+        Vector2::new(-5.0, 0.0),
+        Some(Vector2::new(0.0, 0.0)),
+        None,
+    );
+    assert_eq!(report.model, Vector2::new(0.0, 0.0));
+}
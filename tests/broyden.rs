@@ -0,0 +1,60 @@
+#![cfg(feature = "alloc")]
+
+use levenberg_marquardt::{
+    optimize_report, Config, MinimizationReport, SolveMethod, TerminationReason,
+};
+use nalgebra::{dimension::U3, Vector3};
+
+mod common;
+
+use common::exponential::{jacobian, residuals, samples};
+
+fn fit(config: Config<f64>) -> MinimizationReport<Vector3<f64>, f64, U3> {
+    let samples = samples();
+    optimize_report(
+        Config {
+            threshold: 1e-16,
+            max_iterations: 1000,
+            ..config
+        },
+        Vector3::new(1.0, 1.0, 0.0),
+        |model, delta| model + delta,
+        |model| residuals(&samples, model),
+        |&model| samples.iter().map(move |&(x, _)| jacobian(&model, x)),
+    )
+}
+
+#[test]
+fn broyden_updates_save_jacobian_evaluations() {
+    for &solve_method in &[
+        SolveMethod::NormalEquations,
+        SolveMethod::Qr,
+        SolveMethod::Svd {
+            rank_tolerance: 1e-12,
+        },
+    ] {
+        let full = fit(Config {
+            solve_method,
+            ..Config::default()
+        });
+        let broyden = fit(Config {
+            solve_method,
+            jacobian_refresh_interval: 5,
+            ..Config::default()
+        });
+
+        assert_eq!(full.termination, TerminationReason::BelowThreshold);
+        assert_eq!(broyden.termination, TerminationReason::BelowThreshold);
+        assert!((broyden.model - Vector3::new(2.0, 0.5, 1.0)).norm() < 1e-6);
+        assert!(broyden.jacobian_evaluations * 2 < full.jacobian_evaluations);
+    }
+}
+
+#[test]
+fn zero_refresh_interval_matches_default() {
+    let config = Config {
+        jacobian_refresh_interval: 0,
+        ..Config::default()
+    };
+    assert_eq!(fit(config), fit(Config::default()));
+}
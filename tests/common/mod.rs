@@ -0,0 +1,106 @@
+//! Fixtures shared by the integration tests.
+//!
+//! Every test crate declares this module but only uses some of it.
+#![allow(dead_code)]
+
+use nalgebra::{dimension::U1, Dynamic, Matrix, VecStorage};
+
+pub type Residuals = Matrix<f64, U1, Dynamic, VecStorage<f64, U1, Dynamic>>;
+
+/// Fits `y = a·exp(-bx) + c` as the model `(a, b, c)`.
+pub mod exponential {
+    use super::Residuals;
+    use nalgebra::Vector3;
+
+    /// Samples of `y = 2exp(-0.5x) + 1`.
+    pub fn samples() -> Vec<(f64, f64)> {
+        (0..20)
+            .map(|x| {
+                let x = f64::from(x) * 0.25;
+                (x, 2.0 * (-0.5 * x).exp() + 1.0)
+            })
+            .collect()
+    }
+
+    pub fn residuals(samples: &[(f64, f64)], model: &Vector3<f64>) -> Residuals {
+        Residuals::from_iterator(
+            samples.len(),
+            samples
+                .iter()
+                .map(|&(x, y)| y - (model.x * (-model.y * x).exp() + model.z)),
+        )
+    }
+
+    pub fn jacobian(model: &Vector3<f64>, x: f64) -> Vector3<f64> {
+        let exp = (-model.y * x).exp();
+        Vector3::new(exp, -model.x * x * exp, 1.0)
+    }
+}
+
+/// Fits `y = ax + b` as the model `(a, b)`.
+pub mod line {
+    use super::Residuals;
+    use levenberg_marquardt::{ClosureProblem, LeastSquaresProblem};
+    use nalgebra::{Dynamic, Vector2, U1, U2};
+
+    /// Samples of `y = 3x + 1`.
+    pub fn samples() -> Vec<(f64, f64)> {
+        (0..10)
+            .map(|x| {
+                let x = f64::from(x) * 0.5;
+                (x, 3.0 * x + 1.0)
+            })
+            .collect()
+    }
+
+    pub fn residuals(samples: &[(f64, f64)], model: &Vector2<f64>) -> Residuals {
+        Residuals::from_iterator(
+            samples.len(),
+            samples.iter().map(|&(x, y)| y - (model.x * x + model.y)),
+        )
+    }
+
+    pub fn jacobian(x: f64) -> Vector2<f64> {
+        Vector2::new(x, 1.0)
+    }
+
+    /// The line fit to `samples` as a [`LeastSquaresProblem`].
+    pub fn problem(
+        samples: &[(f64, f64)],
+    ) -> impl LeastSquaresProblem<f64, U2, Dynamic, U1, Model = Vector2<f64>> + '_ {
+        ClosureProblem::new(
+            |model: &Vector2<f64>, delta| model + delta,
+            move |model: &Vector2<f64>| residuals(samples, model),
+            move |_: &Vector2<f64>| samples.iter().map(|&(x, _)| jacobian(x)),
+        )
+    }
+}
+
+/// Fits `y = ax² + bx + c` as the model `(a, b, c)`.
+pub mod parabola {
+    use super::Residuals;
+    use nalgebra::Vector3;
+
+    /// Samples of `y = 2x² - 3x + 1`.
+    pub fn samples() -> Vec<(f64, f64)> {
+        (-10..=10)
+            .map(|x| {
+                let x = f64::from(x) * 0.5;
+                (x, 2.0 * x * x - 3.0 * x + 1.0)
+            })
+            .collect()
+    }
+
+    pub fn residuals(samples: &[(f64, f64)], model: &Vector3<f64>) -> Residuals {
+        Residuals::from_iterator(
+            samples.len(),
+            samples
+                .iter()
+                .map(|&(x, y)| y - (model.x * x * x + model.y * x + model.z)),
+        )
+    }
+
+    pub fn jacobian(x: f64) -> Vector3<f64> {
+        Vector3::new(x * x, x, 1.0)
+    }
+}
@@ -0,0 +1,58 @@
+use levenberg_marquardt::{optimize_problem, ComplexProblem, Config, TerminationReason};
+use nalgebra::{dimension::U1, Complex, Dynamic, Matrix, VecStorage, Vector2};
+
+type Residuals = Matrix<Complex<f64>, U1, Dynamic, VecStorage<Complex<f64>, U1, Dynamic>>;
+
+/// The impedance `R / (1 + iωRC)` of a resistor and capacitor in parallel.
+fn impedance(model: &Vector2<f64>, omega: f64) -> Complex<f64> {
+    Complex::new(model.x, 0.0) / Complex::new(1.0, omega * model.x * model.y)
+}
+
+/// The derivatives of the impedance with respect to `R` and `C`.
+fn impedance_derivatives(model: &Vector2<f64>, omega: f64) -> Vector2<Complex<f64>> {
+    let denominator = Complex::new(1.0, omega * model.x * model.y);
+    let squared = denominator * denominator;
+    Vector2::new(
+        Complex::new(1.0, 0.0) / squared,
+        Complex::new(0.0, -omega * model.x * model.x) / squared,
+    )
+}
+
+#[test]
+fn fits_impedance_spectrum() {
+    let truth = Vector2::new(2.0, 0.5);
+    let samples: Vec<(f64, Complex<f64>)> = (0..30)
+        .map(|i| {
+            let omega = 10f64.powf(f64::from(i) / 10.0 - 1.0);
+            (omega, impedance(&truth, omega))
+        })
+        .collect();
+
+    let problem = ComplexProblem::new(
+        |model: &Vector2<f64>, delta| model + delta,
+        |model: &Vector2<f64>| {
+            Residuals::from_iterator(
+                samples.len(),
+                samples
+                    .iter()
+                    .map(|&(omega, z)| z - impedance(model, omega)),
+            )
+        },
+        |&model: &Vector2<f64>| {
+            samples
+                .iter()
+                .map(move |&(omega, _)| impedance_derivatives(&model, omega))
+        },
+    );
+    let report = optimize_problem(
+        Config {
+            threshold: 1e-20,
+            ..Config::default()
+        },
+        Vector2::new(1.0, 1.0),
+        &problem,
+    );
+
+    assert_eq!(report.termination, TerminationReason::BelowThreshold);
+    assert!((report.model - truth).amax() < 1e-8);
+}
@@ -0,0 +1,161 @@
+use levenberg_marquardt::{
+    checked_optimize, Config, ConfigError, DampingStrategy, InitialLambda, OptimizeError,
+};
+use nalgebra::Vector1;
+
+#[test]
+fn try_default_matches_default() {
+    assert_eq!(Config::<f64>::try_default(), Some(Config::default()));
+    assert_eq!(Config::<f32>::try_default(), Some(Config::default()));
+}
+
+#[test]
+fn builder_starts_from_default() {
+    assert_eq!(Config::<f64>::builder().build(), Ok(Config::default()));
+}
+
+#[test]
+fn builder_sets_fields() {
+    let config = Config::<f64>::builder()
+        .max_iterations(200)
+        .initial_lambda(1.0)
+        .lambda_converge(0.5)
+        .lambda_diverge(3.0)
+        .damping_strategy(DampingStrategy::Nielsen)
+        .build()
+        .unwrap();
+    assert_eq!(
+        config,
+        Config {
+            max_iterations: 200,
+            initial_lambda: InitialLambda::Fixed(1.0),
+            lambda_convege: 0.5,
+            lambda_diverge: 3.0,
+            damping_strategy: DampingStrategy::Nielsen,
+            ..Config::default()
+        }
+    );
+}
+
+#[test]
+fn builder_rejects_invalid_lambda_scaling() {
+    let builder = Config::<f64>::builder();
+    assert_eq!(
+        builder.lambda_converge(1.0).build(),
+        Err(ConfigError::LambdaConvergeNotBelowOne)
+    );
+    assert_eq!(
+        builder.lambda_diverge(0.9).build(),
+        Err(ConfigError::LambdaDivergeNotAboveOne)
+    );
+    // `1.2` is below `0.8^-1`, so lambda would decrease after a rejection and a converging step.
+    assert_eq!(
+        builder.lambda_converge(0.8).lambda_diverge(1.2).build(),
+        Err(ConfigError::LambdaDivergeRetestsLambda)
+    );
+}
+
+#[test]
+fn validate_rejects_zero_initial_lambda() {
+    let config = Config::<f64> {
+        initial_lambda: InitialLambda::Fixed(0.0),
+        ..Config::default()
+    };
+    assert_eq!(config.validate(), Err(ConfigError::InitialLambdaZero));
+    assert_eq!(Config::<f64>::default().validate(), Ok(()));
+}
+
+#[test]
+fn validate_checks_lambda_candidates() {
+    let config = Config::<f64> {
+        lambda_candidates: 0,
+        ..Config::default()
+    };
+    assert_eq!(config.validate(), Err(ConfigError::NoLambdaCandidates));
+
+    // `1.5` is above `0.8^-1` but not above `0.8^-3`, so a rejection after testing four
+    // candidates would retest the smallest of them.
+    let builder = Config::<f64>::builder()
+        .lambda_converge(0.8)
+        .lambda_diverge(1.5);
+    assert!(builder.lambda_candidates(2).build().is_ok());
+    assert_eq!(
+        builder.lambda_candidates(4).build(),
+        Err(ConfigError::LambdaDivergeRetestsLambda)
+    );
+    assert!(builder
+        .lambda_candidates(4)
+        .lambda_diverge(2.5)
+        .build()
+        .is_ok());
+}
+
+#[test]
+fn checked_optimize_rejects_invalid_config() {
+    let config = Config::<f64> {
+        lambda_diverge: 1.0,
+        ..Config::default()
+    };
+    let result = checked_optimize(
+        config,
+        Vector1::new(0.0),
+        |model, delta: Vector1<f64>| model + delta,
+        |model| Vector1::new(1.0 - model.x),
+        |_| core::iter::once(Vector1::new(1.0)),
+    );
+    assert_eq!(
+        result,
+        Err(OptimizeError::InvalidConfig(
+            ConfigError::LambdaDivergeNotAboveOne
+        ))
+    );
+}
+
+#[test]
+fn checked_optimize_rejects_non_finite_start() {
+    let evaluations = core::cell::Cell::new(0);
+    // The residual is only defined for positive guesses.
+    let result = checked_optimize(
+        Config::default(),
+        Vector1::new(-1.0),
+        |model, delta: Vector1<f64>| model + delta,
+        |model: &Vector1<f64>| {
+            evaluations.set(evaluations.get() + 1);
+            Vector1::new(model.x.sqrt() - 1.0)
+        },
+        |model| core::iter::once(Vector1::new(-0.5 / model.x.sqrt())),
+    );
+    assert_eq!(result, Err(OptimizeError::NonFiniteStart));
+    assert_eq!(evaluations.get(), 1);
+}
+
+#[test]
+fn presets_are_valid_and_converge() {
+    for config in [Config::<f64>::fast(), Config::accurate(), Config::robust()].iter() {
+        assert_eq!(config.validate(), Ok(()));
+        let model = checked_optimize(
+            *config,
+            Vector1::new(0.0),
+            |model, delta: Vector1<f64>| model + delta,
+            |model| Vector1::new(1.0 - model.x),
+            |_| core::iter::once(Vector1::new(1.0)),
+        )
+        .unwrap();
+        assert!((model.x - 1.0).abs() < 1e-3);
+    }
+}
+
+#[test]
+fn literal_defaults_match_default() {
+    assert_eq!(Config::<f32>::DEFAULT, Config::default());
+    assert_eq!(
+        Config::<f64>::DEFAULT,
+        Config {
+            min_lambda: f64::MIN_POSITIVE,
+            max_lambda: f64::MAX,
+            ..Config::default()
+        }
+    );
+    // The default is exact rather than rounded through `f32`.
+    assert_eq!(Config::<f64>::default().lambda_convege, 0.8);
+}
@@ -0,0 +1,86 @@
+use levenberg_marquardt::{
+    optimize_problem, ClosureProblem, Config, CostProblem, LeastSquaresProblem, TerminationReason,
+};
+use nalgebra::{dimension::U1, Dynamic, VecStorage, Vector2, U2};
+
+mod common;
+
+use common::{line::jacobian, Residuals};
+
+/// Samples of `y = 3x + 1` with a small amount of noise.
+fn samples() -> Vec<(f64, f64)> {
+    (0..10)
+        .map(|i| {
+            let x = f64::from(i) * 0.5;
+            let noise = if i % 2 == 0 { 0.05 } else { -0.05 };
+            (x, 3.0 * x + 1.0 + noise)
+        })
+        .collect()
+}
+
+fn residuals(samples: &[(f64, f64)], model: &Vector2<f64>) -> Residuals {
+    Residuals::from_iterator(
+        samples.len(),
+        samples.iter().map(|&(x, y)| y - (model.x * x + model.y)),
+    )
+}
+
+fn problem(
+    samples: &[(f64, f64)],
+) -> impl LeastSquaresProblem<
+    f64,
+    U2,
+    Dynamic,
+    U1,
+    Model = Vector2<f64>,
+    ResidualStorage = VecStorage<f64, U1, Dynamic>,
+> + '_ {
+    ClosureProblem::new(
+        |model: &Vector2<f64>, delta| model + delta,
+        move |model: &Vector2<f64>| residuals(samples, model),
+        move |_: &Vector2<f64>| samples.iter().map(|&(x, _)| jacobian(x)),
+    )
+}
+
+#[test]
+fn reports_custom_cost() {
+    let samples = samples();
+    let plain = optimize_problem(Config::default(), Vector2::zeros(), &problem(&samples));
+    let scaled = optimize_problem(
+        Config::default(),
+        Vector2::zeros(),
+        &CostProblem::new(problem(&samples), |residuals: &Residuals| {
+            4.0 * residuals.norm_squared()
+        }),
+    );
+
+    assert!((scaled.model - plain.model).norm() < 1e-9);
+    let expected = 4.0 * residuals(&samples, &scaled.model).norm_squared();
+    assert!((scaled.sum_of_squares - expected).abs() < 1e-12);
+}
+
+#[test]
+fn threshold_is_compared_against_custom_cost() {
+    // Residuals within a tolerance band cost nothing, so the fit stops as soon as every sample
+    // is inside it, rather than continuing to the least squares solution.
+    let samples = samples();
+    let band = 0.5;
+    let report = optimize_problem(
+        Config {
+            threshold: 1e-12,
+            ..Config::default()
+        },
+        Vector2::zeros(),
+        &CostProblem::new(problem(&samples), |residuals: &Residuals| {
+            residuals
+                .iter()
+                .map(|&r| (r.abs() - band).max(0.0).powi(2))
+                .sum()
+        }),
+    );
+
+    assert_eq!(report.termination, TerminationReason::BelowThreshold);
+    assert_eq!(report.sum_of_squares, 0.0);
+    assert!(residuals(&samples, &report.model).amax() <= band);
+    assert!(residuals(&samples, &report.model).amax() > 0.05 + 1e-3);
+}
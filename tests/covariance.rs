@@ -0,0 +1,153 @@
+use levenberg_marquardt::{
+    optimize_report, optimize_with_covariance, parameter_standard_errors, Config, DampingMode,
+    SolveMethod,
+};
+use nalgebra::{Matrix2, Vector2};
+
+mod common;
+
+use common::{
+    line::{jacobian, residuals, samples},
+    Residuals,
+};
+
+/// Samples of the line with every other sample moved up or down by `0.1`.
+fn noisy_samples() -> Vec<(f64, f64)> {
+    samples()
+        .into_iter()
+        .enumerate()
+        .map(|(i, (x, y))| (x, if i % 2 == 0 { y + 0.1 } else { y - 0.1 }))
+        .collect()
+}
+
+#[test]
+fn line_covariance() {
+    let samples = noisy_samples();
+    let (model, covariance) = optimize_with_covariance(
+        Config::default(),
+        Vector2::zeros(),
+        |model, delta: Vector2<f64>| model + delta,
+        |model| residuals(&samples, model),
+        |_| samples.iter().map(|&(x, _)| jacobian(x)),
+    );
+
+    assert!((model - Vector2::new(3.0, 1.0)).norm() < 0.1);
+    // The Hessian of a linear fit is the same everywhere.
+    let hessian = samples.iter().fold(Matrix2::zeros(), |hessian, &(x, _)| {
+        hessian + jacobian(x) * jacobian(x).transpose()
+    });
+    let variance = residuals(&samples, &model).norm_squared() / (samples.len() - 2) as f64;
+    let expected = hessian.try_inverse().unwrap() * variance;
+    assert!((covariance.unwrap() - expected).amax() < 1e-12);
+}
+
+#[test]
+fn unconstrained_parameter_has_no_covariance() {
+    let samples = noisy_samples();
+    // Only the sum of the parameters affects the residuals.
+    let (_, covariance) = optimize_with_covariance(
+        Config {
+            damping_mode: DampingMode::Identity,
+            ..Config::default()
+        },
+        Vector2::zeros(),
+        |model, delta: Vector2<f64>| model + delta,
+        |model| {
+            Residuals::from_iterator(
+                samples.len(),
+                samples.iter().map(|&(_, y)| y - (model.x + model.y)),
+            )
+        },
+        |_| samples.iter().map(|_| Vector2::new(1.0, 1.0)),
+    );
+
+    assert_eq!(covariance, None);
+}
+
+#[test]
+fn no_covariance_without_degrees_of_freedom() {
+    // A line through two points fits them exactly, so there is nothing to estimate the
+    // variance from.
+    let samples = [(0.0, 1.0), (1.0, 4.0)];
+    let (model, covariance) = optimize_with_covariance(
+        Config::default(),
+        Vector2::zeros(),
+        |model, delta: Vector2<f64>| model + delta,
+        |model| residuals(&samples, model),
+        |_| samples.iter().map(|&(x, _)| jacobian(x)),
+    );
+
+    assert!((model - Vector2::new(3.0, 1.0)).norm() < 1e-6);
+    assert_eq!(covariance, None);
+}
+
+#[test]
+fn standard_errors_clamp_negative_variances() {
+    let inverse_hessian = Matrix2::new(4.0, 0.0, 0.0, -1e-12);
+    let (errors, clamped) = parameter_standard_errors(&inverse_hessian, 8.0, 10).unwrap();
+
+    assert_eq!(errors, Vector2::new(2.0, 0.0));
+    assert!(clamped);
+}
+
+#[test]
+fn no_standard_errors_without_degrees_of_freedom() {
+    let inverse_hessian = Matrix2::<f64>::identity();
+
+    assert_eq!(parameter_standard_errors(&inverse_hessian, 1.0, 2), None);
+    assert_eq!(parameter_standard_errors(&inverse_hessian, 1.0, 1), None);
+}
+
+#[test]
+fn report_contains_undamped_hessian() {
+    let samples = samples();
+    let hessian = samples.iter().fold(Matrix2::zeros(), |hessian, &(x, _)| {
+        hessian + jacobian(x) * jacobian(x).transpose()
+    });
+    for &solve_method in &[SolveMethod::NormalEquations, SolveMethod::Qr] {
+        let report = optimize_report(
+            Config {
+                solve_method,
+                ..Config::default()
+            },
+            Vector2::zeros(),
+            |model, delta: Vector2<f64>| model + delta,
+            |model| residuals(&samples, model),
+            |_| samples.iter().map(|&(x, _)| jacobian(x)),
+        );
+
+        assert!((report.final_hessian - hessian).amax() < 1e-9);
+    }
+}
+
+#[test]
+fn reduced_chi_squared_is_near_one_for_correct_errors() {
+    // The noise is `±0.1` on every sample, and each residual is divided by its standard deviation.
+    let sigma = 0.1;
+    let samples = noisy_samples();
+    let report = optimize_report(
+        Config::default(),
+        Vector2::zeros(),
+        |model, delta: Vector2<f64>| model + delta,
+        |model| residuals(&samples, model) / sigma,
+        |_| samples.iter().map(|&(x, _)| jacobian(x) / sigma),
+    );
+
+    let reduced_chi_squared = report.reduced_chi_squared.unwrap();
+    assert!((reduced_chi_squared - report.sum_of_squares / 8.0).abs() < 1e-12);
+    assert!((reduced_chi_squared - 1.0).abs() < 0.25);
+}
+
+#[test]
+fn reduced_chi_squared_needs_more_residuals_than_parameters() {
+    let samples = &samples()[..2];
+    let report = optimize_report(
+        Config::default(),
+        Vector2::zeros(),
+        |model, delta: Vector2<f64>| model + delta,
+        |model| residuals(samples, model),
+        |_| samples.iter().map(|&(x, _)| jacobian(x)),
+    );
+
+    assert_eq!(report.reduced_chi_squared, None);
+}
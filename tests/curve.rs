@@ -0,0 +1,42 @@
+#![cfg(feature = "alloc")]
+
+use levenberg_marquardt::{fit_curve, Config, TerminationReason};
+use nalgebra::Vector3;
+
+#[test]
+fn fits_scalar_curve() {
+    // y = 2exp(-0.5x) + 1
+    let xs: Vec<f64> = (0..20).map(|x| f64::from(x) * 0.25).collect();
+    let ys: Vec<f64> = xs.iter().map(|x| 2.0 * (-0.5 * x).exp() + 1.0).collect();
+
+    let report = fit_curve(
+        Config {
+            threshold: 1e-20,
+            ..Config::default()
+        },
+        Vector3::new(1.0, 1.0, 0.0),
+        |p, x| p.x * (-p.y * x).exp() + p.z,
+        |p, x| {
+            let exp = (-p.y * x).exp();
+            Vector3::new(exp, -p.x * x * exp, 1.0)
+        },
+        &xs,
+        &ys,
+    );
+
+    assert_eq!(report.termination, TerminationReason::BelowThreshold);
+    assert!((report.model - Vector3::new(2.0, 0.5, 1.0)).amax() < 1e-6);
+}
+
+#[test]
+#[should_panic(expected = "there must be as many y values as x values")]
+fn mismatched_lengths_panic() {
+    fit_curve(
+        Config::default(),
+        Vector3::zeros(),
+        |p, x| p.x * x,
+        |_, x| Vector3::new(x, 0.0, 0.0),
+        &[0.0, 1.0],
+        &[0.0],
+    );
+}
@@ -0,0 +1,183 @@
+use levenberg_marquardt::{
+    optimize_report, Config, DampingMode, DampingStrategy, InitialLambda, TerminationReason,
+};
+use nalgebra::{Vector3, U3};
+
+mod common;
+
+use common::exponential::{jacobian, residuals, samples};
+
+fn fit(
+    config: Config<f64>,
+    init: Vector3<f64>,
+) -> levenberg_marquardt::MinimizationReport<Vector3<f64>, f64, U3> {
+    let samples = samples();
+    optimize_report(
+        config,
+        init,
+        |model, delta| model + delta,
+        |model| residuals(&samples, model),
+        |&model| samples.iter().map(move |&(x, _)| jacobian(&model, x)),
+    )
+}
+
+#[test]
+fn nielsen() {
+    let report = fit(
+        Config {
+            threshold: 1e-12,
+            damping_strategy: DampingStrategy::Nielsen,
+            ..Config::default()
+        },
+        Vector3::new(1.0, 1.0, 0.0),
+    );
+
+    assert_eq!(report.termination, TerminationReason::BelowThreshold);
+    assert!((report.model - Vector3::new(2.0, 0.5, 1.0)).norm() < 1e-4);
+}
+
+#[test]
+fn rejects_steps_without_gain() {
+    // Starting at the solution, no step can reduce the sum-of-squares, so every step should be
+    // rejected rather than accepted until `max_iterations` is hit.
+    let samples = samples();
+    let report = optimize_report(
+        Config::default(),
+        Vector3::new(2.0, 0.5, 1.0),
+        |model, delta| model + delta,
+        |model| residuals(&samples, model),
+        |&model| samples.iter().map(move |&(x, _)| jacobian(&model, x)),
+    );
+
+    assert_ne!(report.termination, TerminationReason::MaxIterations);
+    assert!(report.iterations <= Config::<f64>::default().consecutive_divergence_limit);
+}
+
+#[test]
+fn identity_damping_handles_zero_curvature() {
+    // With an amplitude of zero, the decay rate has no effect on the residuals, so its
+    // diagonal entry in the approximate Hessian is zero.
+    let init = Vector3::new(0.0, 1.0, 0.0);
+    let config = Config {
+        threshold: 1e-12,
+        ..Config::default()
+    };
+
+    let diagonal = fit(config, init);
+    assert_eq!(diagonal.termination, TerminationReason::Inverted);
+
+    let identity = fit(
+        Config {
+            damping_mode: DampingMode::Identity,
+            ..config
+        },
+        init,
+    );
+    assert_eq!(identity.termination, TerminationReason::BelowThreshold);
+    assert!((identity.model - Vector3::new(2.0, 0.5, 1.0)).norm() < 1e-4);
+}
+
+#[test]
+fn lambda_stays_finite() {
+    let samples = samples();
+    let deltas = std::cell::RefCell::new(Vec::new());
+    // The Jacobian has the wrong sign, so every step diverges and lambda keeps increasing.
+    let report = optimize_report(
+        Config {
+            max_iterations: 1000,
+            consecutive_divergence_limit: usize::MAX,
+            lambda_diverge: 10.0,
+            ..Config::default()
+        },
+        Vector3::new(1.0, 1.0, 0.0),
+        |model, delta| {
+            deltas.borrow_mut().push(delta);
+            model + delta
+        },
+        |model| residuals(&samples, model),
+        |&model| samples.iter().map(move |&(x, _)| -jacobian(&model, x)),
+    );
+
+    // Lambda reaches `max_lambda` rather than overflowing, after which optimization stops.
+    assert_eq!(report.termination, TerminationReason::LambdaSaturated);
+    assert_eq!(report.lambda, Some(f64::from(f32::MAX)));
+    assert!(deltas
+        .borrow()
+        .iter()
+        .all(|delta| delta.iter().all(|d| d.is_finite())));
+}
+
+#[test]
+fn auto_scaled_damping_is_invariant_to_parameter_scale() {
+    // The same fit, but with the decay rate in units which make it a million times smaller.
+    let samples: Vec<(f64, f64)> = samples().into_iter().map(|(x, y)| (x * 1e6, y)).collect();
+    let fit_rescaled = |damping_mode| {
+        optimize_report(
+            Config {
+                threshold: 1e-12,
+                damping_mode,
+                ..Config::default()
+            },
+            Vector3::new(1.0, 1e-6, 0.0),
+            |model, delta| model + delta,
+            |model| residuals(&samples, model),
+            |&model| samples.iter().map(move |&(x, _)| jacobian(&model, x)),
+        )
+    };
+    let config = Config {
+        threshold: 1e-12,
+        damping_mode: DampingMode::AutoScaled,
+        ..Config::default()
+    };
+
+    let original = fit(config, Vector3::new(1.0, 1.0, 0.0));
+    let rescaled = fit_rescaled(DampingMode::AutoScaled);
+    assert_eq!(original.termination, TerminationReason::BelowThreshold);
+    assert_eq!(rescaled.termination, TerminationReason::BelowThreshold);
+    assert_eq!(rescaled.iterations, original.iterations);
+    assert!((rescaled.model - Vector3::new(2.0, 0.5e-6, 1.0)).norm() < 1e-4);
+
+    // Marquardt's damping is also invariant and takes about as many iterations, but Levenberg's
+    // damping can't make any progress on the rescaled problem.
+    let diagonal = fit_rescaled(DampingMode::Diagonal);
+    assert_eq!(diagonal.termination, TerminationReason::BelowThreshold);
+    assert!((rescaled.iterations as isize - diagonal.iterations as isize).abs() <= 5);
+    let identity = fit_rescaled(DampingMode::Identity);
+    assert_eq!(
+        identity.termination,
+        TerminationReason::ConsecutiveDivergence
+    );
+}
+
+#[test]
+fn auto_initial_lambda_is_invariant_to_residual_scale() {
+    let samples = samples();
+    let config = Config {
+        initial_lambda: InitialLambda::Auto { tau: 1.0 },
+        damping_mode: DampingMode::Identity,
+        ftol: 1e-15,
+        ..Config::default()
+    };
+    let fit_scaled = |scale: f64| {
+        optimize_report(
+            config,
+            Vector3::new(1.0, 1.0, 0.0),
+            |model, delta| model + delta,
+            |model| residuals(&samples, model) * scale,
+            |&model| {
+                samples
+                    .iter()
+                    .map(move |&(x, _)| jacobian(&model, x) * scale)
+            },
+        )
+    };
+    let unscaled = fit_scaled(1.0);
+    let scaled = fit_scaled(1e4);
+
+    assert!((unscaled.model - Vector3::new(2.0, 0.5, 1.0)).norm() < 1e-6);
+    assert!((scaled.model - unscaled.model).norm() < 1e-6);
+    // Lambda scaled with the Hessian, so the same steps were taken.
+    assert_eq!(scaled.iterations, unscaled.iterations);
+    let ratio = scaled.lambda.unwrap() / unscaled.lambda.unwrap();
+    assert!((ratio / 1e8 - 1.0).abs() < 1e-6);
+}
@@ -0,0 +1,65 @@
+use levenberg_marquardt::{
+    optimize_dogleg, optimize_problem, ClosureProblem, Config, TerminationReason,
+};
+use nalgebra::{Matrix2, Vector2, Vector3};
+
+mod common;
+
+use common::exponential::{jacobian, residuals, samples};
+
+#[test]
+fn fits_exponential() {
+    let samples = samples();
+    let config = Config {
+        threshold: 1e-12,
+        ..Config::default()
+    };
+    let init = Vector3::new(1.0, 1.0, 0.0);
+    let problem = ClosureProblem::new(
+        |model: &Vector3<f64>, delta| model + delta,
+        |model: &Vector3<f64>| residuals(&samples, model),
+        |&model: &Vector3<f64>| samples.iter().map(move |&(x, _)| jacobian(&model, x)),
+    );
+    let dogleg = optimize_dogleg(config, init, &problem);
+    let lm = optimize_problem(config, init, &problem);
+
+    assert_eq!(dogleg.termination, TerminationReason::BelowThreshold);
+    assert!((dogleg.model - Vector3::new(2.0, 0.5, 1.0)).norm() < 1e-4);
+    assert!((dogleg.model - lm.model).norm() < 1e-4);
+    // Every rejected step costs a residual evaluation but no Jacobian evaluation.
+    assert_eq!(dogleg.residual_evaluations, dogleg.iterations + 1);
+    assert!(dogleg.jacobian_evaluations <= dogleg.residual_evaluations);
+}
+
+#[test]
+fn steps_stay_within_trust_radius() {
+    // The Rosenbrock function, whose minimum at (1, 1) lies at the end of a curved valley.
+    let deltas = std::cell::RefCell::new(Vec::new());
+    let report = optimize_dogleg(
+        Config {
+            threshold: 1e-20,
+            initial_trust_radius: 0.1,
+            max_iterations: 1000,
+            ..Config::default()
+        },
+        Vector2::new(-1.2, 1.0),
+        &ClosureProblem::new(
+            |model: &Vector2<f64>, delta| {
+                deltas.borrow_mut().push(delta);
+                model + delta
+            },
+            |model: &Vector2<f64>| {
+                Vector2::new(10.0 * (model.y - model.x * model.x), 1.0 - model.x)
+            },
+            |model: &Vector2<f64>| core::iter::once(Matrix2::new(20.0 * model.x, 1.0, -10.0, 0.0)),
+        ),
+    );
+
+    assert_eq!(report.termination, TerminationReason::BelowThreshold);
+    assert!((report.model - Vector2::new(1.0, 1.0)).norm() < 1e-8);
+    // The radius can at most double after each step, so the first step is within the initial
+    // radius and the second is within twice it.
+    let deltas = deltas.borrow();
+    assert!(deltas[0].norm() <= 0.1 + 1e-12);
+    assert!(deltas[1].norm() <= 0.2 + 1e-12);
+}
@@ -0,0 +1,104 @@
+mod common;
+
+use common::Residuals;
+use levenberg_marquardt::{
+    optimize_report, ClosureProblem, Config, LevenbergMarquardt, SolveMethod, TerminationReason,
+    Workspace,
+};
+use nalgebra::{DVector, Dynamic};
+
+/// Samples of the polynomial with the given coefficients, in increasing order of degree.
+fn samples(coefficients: &[f64]) -> Vec<(f64, f64)> {
+    (0..20)
+        .map(|x| {
+            let x = f64::from(x) * 0.1 - 1.0;
+            (x, polynomial(coefficients, x))
+        })
+        .collect()
+}
+
+fn polynomial(coefficients: &[f64], x: f64) -> f64 {
+    coefficients.iter().rev().fold(0.0, |sum, c| sum * x + c)
+}
+
+/// Fits a polynomial whose degree is only known at runtime from the number of coefficients.
+fn fit(
+    config: Config<f64>,
+    coefficients: &[f64],
+) -> levenberg_marquardt::MinimizationReport<DVector<f64>, f64, Dynamic> {
+    let samples = samples(coefficients);
+    let degree = coefficients.len();
+    optimize_report(
+        config,
+        DVector::zeros(degree),
+        |model, delta| model + delta,
+        |model| {
+            Residuals::from_iterator(
+                samples.len(),
+                samples
+                    .iter()
+                    .map(|&(x, y)| y - polynomial(model.as_slice(), x)),
+            )
+        },
+        |_| {
+            samples
+                .iter()
+                .map(move |&(x, _)| DVector::from_fn(degree, |k, _| x.powi(k as i32)))
+        },
+    )
+}
+
+#[test]
+fn fits_runtime_sized_models() {
+    for method in [SolveMethod::NormalEquations, SolveMethod::Qr] {
+        for coefficients in [&[1.0, -2.0][..], &[0.5, 1.0, -1.5, 2.0, 0.25]] {
+            let report = fit(
+                Config {
+                    threshold: 1e-20,
+                    solve_method: method,
+                    ..Config::default()
+                },
+                coefficients,
+            );
+
+            assert_eq!(report.termination, TerminationReason::BelowThreshold);
+            assert_eq!(report.model.len(), coefficients.len());
+            assert!((report.model - DVector::from_column_slice(coefficients)).norm() < 1e-6);
+        }
+    }
+}
+
+#[test]
+fn workspace_resizes_between_models() {
+    let mut workspace = Workspace::<f64, Dynamic>::new();
+    for coefficients in [&[0.5, 1.0, -1.5][..], &[1.0, -2.0], &[3.0, 0.0, 0.0, 1.0]] {
+        let samples = samples(coefficients);
+        let degree = coefficients.len();
+        let problem = ClosureProblem::new(
+            |model: &DVector<f64>, delta| model + delta,
+            |model: &DVector<f64>| {
+                Residuals::from_iterator(
+                    samples.len(),
+                    samples
+                        .iter()
+                        .map(|&(x, y)| y - polynomial(model.as_slice(), x)),
+                )
+            },
+            |_: &DVector<f64>| {
+                samples
+                    .iter()
+                    .map(move |&(x, _)| DVector::from_fn(degree, |k, _| x.powi(k as i32)))
+            },
+        );
+        let config = Config {
+            threshold: 1e-20,
+            ..Config::default()
+        };
+        let report = LevenbergMarquardt::new(config, DVector::zeros(degree), &problem)
+            .unwrap()
+            .run_in(&problem, &mut workspace);
+
+        assert_eq!(report.termination, TerminationReason::BelowThreshold);
+        assert!((report.model - DVector::from_column_slice(coefficients)).norm() < 1e-6);
+    }
+}
@@ -0,0 +1,105 @@
+use core::cell::Cell;
+use levenberg_marquardt::{
+    optimize_problem, Config, FallibleClosureProblem, LeastSquaresProblem, MinimizationReport,
+    TerminationReason,
+};
+use nalgebra::{Dynamic, Vector2, U1, U2};
+
+mod common;
+
+use common::line::{jacobian, problem, residuals, samples};
+
+fn fit<IJ, E>(
+    config: Config<f64>,
+    samples: &[(f64, f64)],
+    jacobians: impl Fn(&Vector2<f64>) -> Result<IJ, E>,
+) -> MinimizationReport<Vector2<f64>, f64, U2>
+where
+    IJ: Iterator<Item = Vector2<f64>>,
+{
+    let problem = FallibleClosureProblem::new(
+        |model: &Vector2<f64>, delta| model + delta,
+        |model: &Vector2<f64>| residuals(samples, model),
+        jacobians,
+    );
+    optimize_problem(config, Vector2::zeros(), &problem)
+}
+
+#[test]
+fn succeeding_jacobians_match_infallible_problem() {
+    let samples = samples();
+    let fallible = fit(Config::default(), &samples, |_| {
+        Ok::<_, ()>(samples.iter().map(|&(x, _)| jacobian(x)))
+    });
+    let infallible = optimize_problem(Config::default(), Vector2::zeros(), &problem(&samples));
+    assert_eq!(fallible, infallible);
+}
+
+#[test]
+fn failure_at_init() {
+    let samples = samples();
+    let report = fit(Config::default(), &samples, |_| {
+        Err::<core::iter::Empty<Vector2<f64>>, _>("undefined")
+    });
+    assert_eq!(report.termination, TerminationReason::JacobianFailed);
+    assert_eq!(report.iterations, 0);
+    assert_eq!(report.model, Vector2::zeros());
+}
+
+#[test]
+fn persistent_failure_terminates() {
+    let samples = samples();
+    let evaluations = Cell::new(0usize);
+    // The Jacobians can only be computed at the initial guess.
+    let report = fit(Config::default(), &samples, |model| {
+        evaluations.set(evaluations.get() + 1);
+        if *model == Vector2::zeros() {
+            Ok(samples.iter().map(|&(x, _)| jacobian(x)))
+        } else {
+            Err("undefined")
+        }
+    });
+    let config = Config::<f64>::default();
+    assert_eq!(report.termination, TerminationReason::JacobianFailed);
+    assert_eq!(report.iterations, config.consecutive_divergence_limit);
+    assert_eq!(evaluations.get(), config.consecutive_divergence_limit + 1);
+    // Rejected steps never replace the current guess.
+    assert_eq!(report.model, Vector2::zeros());
+}
+
+#[test]
+fn failure_is_retried_elsewhere() {
+    let samples = samples();
+    let failures = Cell::new(0usize);
+    let config = Config {
+        threshold: 1e-12,
+        ..Config::default()
+    };
+    // The first step from the initial guess lands where the Jacobians can't be computed.
+    let report = fit(config, &samples, |model| {
+        if *model != Vector2::zeros() && failures.get() == 0 {
+            failures.set(1);
+            Err("undefined")
+        } else {
+            Ok(samples.iter().map(|&(x, _)| jacobian(x)))
+        }
+    });
+    assert_eq!(failures.get(), 1);
+    assert_eq!(report.termination, TerminationReason::BelowThreshold);
+    assert!((report.model - Vector2::new(3.0, 1.0)).norm() < 1e-5);
+}
+
+#[test]
+fn failed_jacobians_are_empty() {
+    let problem = FallibleClosureProblem::new(
+        |model: &Vector2<f64>, delta| model + delta,
+        |model: &Vector2<f64>| residuals(&[], model),
+        |_: &Vector2<f64>| Err::<core::iter::Once<Vector2<f64>>, _>("undefined"),
+    );
+    let model = Vector2::zeros();
+    let try_jacobians =
+        LeastSquaresProblem::<f64, U2, Dynamic, U1>::try_jacobians(&problem, &model);
+    let jacobians = LeastSquaresProblem::<f64, U2, Dynamic, U1>::jacobians(&problem, &model);
+    assert!(try_jacobians.is_none());
+    assert_eq!(jacobians.count(), 0);
+}
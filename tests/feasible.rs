@@ -0,0 +1,60 @@
+use core::cell::Cell;
+use levenberg_marquardt::{
+    optimize_problem, Config, FeasibleClosureProblem, InitialLambda, TerminationReason,
+};
+use nalgebra::Vector1;
+
+mod common;
+
+use common::Residuals;
+
+#[test]
+fn infeasible_step_is_rejected() {
+    // Fit a variance to a standard deviation of 0.5. The Gauss-Newton step from a variance of
+    // 4.0 lands at -2.0, which `apply_delta` refuses until lambda has grown enough.
+    let infeasible = Cell::new(0);
+    let problem = FeasibleClosureProblem::new(
+        |&variance: &f64, delta: Vector1<f64>| {
+            let variance = variance + delta.x;
+            if variance > 0.0 {
+                Some(variance)
+            } else {
+                infeasible.set(infeasible.get() + 1);
+                None
+            }
+        },
+        |&variance: &f64| {
+            assert!(
+                variance > 0.0,
+                "the residuals saw the variance {}",
+                variance
+            );
+            Residuals::from_iterator(1, vec![0.5 - variance.sqrt()])
+        },
+        |&variance: &f64| vec![Vector1::new(0.5 / variance.sqrt())].into_iter(),
+    );
+    let report = optimize_problem(
+        Config {
+            initial_lambda: InitialLambda::Fixed(0.1),
+            ..Config::default()
+        },
+        4.0f64,
+        &problem,
+    );
+
+    assert!(infeasible.get() > 0);
+    assert!((report.model - 0.25).abs() < 1e-8);
+}
+
+#[test]
+fn persistently_infeasible_steps_diverge() {
+    let problem = FeasibleClosureProblem::new(
+        |_: &f64, _: Vector1<f64>| None,
+        |&x: &f64| Residuals::from_iterator(1, vec![2.0 - x]),
+        |_: &f64| vec![Vector1::new(1.0)].into_iter(),
+    );
+    let report = optimize_problem(Config::default(), 1.0f64, &problem);
+
+    assert_eq!(report.termination, TerminationReason::ConsecutiveDivergence);
+    assert_eq!(report.model, 1.0);
+}
@@ -0,0 +1,229 @@
+use levenberg_marquardt::{
+    adaptive_central_difference_jacobians, adaptive_forward_difference_jacobians,
+    central_difference_jacobians, check_jacobian, forward_difference_jacobians, optimize_report,
+    verify_jacobians, Config, TerminationReason,
+};
+use nalgebra::{Vector2, Vector3};
+
+mod common;
+
+use common::{
+    exponential::{jacobian as analytic_jacobian, residuals, samples},
+    Residuals,
+};
+
+#[test]
+fn forward_difference_matches_analytic() {
+    let samples = samples();
+    let model = Vector3::new(1.5, 0.3, 0.5);
+    let numerical = forward_difference_jacobians(
+        &model,
+        |model, delta: Vector3<f64>| model + delta,
+        |model| residuals(&samples, model),
+        1e-7,
+    );
+    for (numerical, &(x, _)) in numerical.zip(&samples) {
+        assert!((numerical - analytic_jacobian(&model, x)).amax() < 1e-5);
+    }
+}
+
+#[test]
+fn forward_difference_optimizes() {
+    let samples = samples();
+    let apply_delta = |model: &Vector3<f64>, delta: Vector3<f64>| model + delta;
+    let residuals = |model: &Vector3<f64>| residuals(&samples, model);
+    let report = optimize_report(
+        Config {
+            threshold: 1e-12,
+            ..Config::default()
+        },
+        Vector3::new(1.5, 0.3, 0.5),
+        apply_delta,
+        residuals,
+        |model| forward_difference_jacobians(model, apply_delta, residuals, 1e-7),
+    );
+
+    assert_eq!(report.termination, TerminationReason::BelowThreshold);
+    assert!((report.model - Vector3::new(2.0, 0.5, 1.0)).norm() < 1e-4);
+}
+
+fn gaussian(model: &Vector3<f64>, x: f64) -> f64 {
+    model.x * (-(x - model.y).powi(2) / (2.0 * model.z * model.z)).exp()
+}
+
+fn gaussian_residuals(samples: &[(f64, f64)], model: &Vector3<f64>) -> Residuals {
+    Residuals::from_iterator(
+        samples.len(),
+        samples.iter().map(|&(x, y)| y - gaussian(model, x)),
+    )
+}
+
+fn gaussian_samples() -> Vec<(f64, f64)> {
+    let truth = Vector3::new(3.0, 1.0, 0.5);
+    (0..40)
+        .map(|x| {
+            let x = f64::from(x) * 0.1 - 1.0;
+            (x, gaussian(&truth, x))
+        })
+        .collect()
+}
+
+#[test]
+fn central_difference_is_more_accurate() {
+    let samples = gaussian_samples();
+    let model = Vector3::new(2.0, 0.7, 0.8);
+    let apply_delta = |model: &Vector3<f64>, delta: Vector3<f64>| model + delta;
+    let residuals = |model: &Vector3<f64>| gaussian_residuals(&samples, model);
+    let exact = central_difference_jacobians(&model, apply_delta, residuals, 1e-6);
+    let forward = forward_difference_jacobians(&model, apply_delta, residuals, 1e-2);
+    let central = central_difference_jacobians(&model, apply_delta, residuals, 1e-2);
+
+    let (forward_error, central_error) = exact.zip(forward.zip(central)).fold(
+        (0.0f64, 0.0f64),
+        |(forward_error, central_error), (exact, (forward, central))| {
+            (
+                forward_error.max((forward - exact).amax()),
+                central_error.max((central - exact).amax()),
+            )
+        },
+    );
+    assert!(central_error * 10.0 < forward_error);
+}
+
+#[test]
+fn central_difference_fits_gaussian() {
+    let samples = gaussian_samples();
+    let apply_delta = |model: &Vector3<f64>, delta: Vector3<f64>| model + delta;
+    let residuals = |model: &Vector3<f64>| gaussian_residuals(&samples, model);
+    let report = optimize_report(
+        Config {
+            threshold: 1e-12,
+            ..Config::default()
+        },
+        Vector3::new(2.0, 0.7, 0.8),
+        apply_delta,
+        residuals,
+        |model| central_difference_jacobians(model, apply_delta, residuals, 1e-2),
+    );
+
+    assert_eq!(report.termination, TerminationReason::BelowThreshold);
+    assert!((report.model - Vector3::new(3.0, 1.0, 0.5)).norm() < 1e-4);
+}
+
+#[test]
+fn check_jacobian_catches_wrong_sign() {
+    let samples = samples();
+    let model = Vector3::new(1.5, 0.3, 0.5);
+    let check = |jacobian: fn(&Vector3<f64>, f64) -> Vector3<f64>| {
+        check_jacobian(
+            &model,
+            |model, delta: Vector3<f64>| model + delta,
+            |model| residuals(&samples, model),
+            |&model| samples.iter().map(move |&(x, _)| jacobian(&model, x)),
+            1e-5,
+        )
+    };
+    assert!(check(analytic_jacobian) < 1e-8);
+    // The derivative of the decay rate has the wrong sign.
+    let wrong = |model: &Vector3<f64>, x: f64| {
+        let exp = (-model.y * x).exp();
+        Vector3::new(exp, model.x * x * exp, 1.0)
+    };
+    assert!(check(wrong) > 0.1);
+}
+
+#[test]
+fn verify_jacobians_locates_wrong_derivative() {
+    let samples = samples();
+    let model = Vector3::new(1.5, 0.3, 0.5);
+    let verify = |jacobian: fn(&Vector3<f64>, f64) -> Vector3<f64>| {
+        verify_jacobians(
+            &model,
+            |model, delta: Vector3<f64>| model + delta,
+            |model| residuals(&samples, model),
+            |&model| samples.iter().map(move |&(x, _)| jacobian(&model, x)),
+            1e-5,
+            1e-6,
+        )
+    };
+    assert_eq!(verify(analytic_jacobian), Ok(()));
+    // The derivative of the decay rate is only wrong from the sample at `x = 1.0` onward.
+    let wrong = |model: &Vector3<f64>, x: f64| {
+        let exp = (-model.y * x).exp();
+        let rate = if x < 1.0 { -model.x * x * exp } else { 0.0 };
+        Vector3::new(exp, rate, 1.0)
+    };
+    let mismatch = verify(wrong).unwrap_err();
+    assert_eq!(mismatch.parameter, 1);
+    assert_eq!(mismatch.residual, 0);
+    assert!(mismatch.sample >= 4);
+    assert_eq!(mismatch.analytic, 0.0);
+    assert!(mismatch.relative_error > 0.1);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn stacked_jacobian_has_a_row_per_residual() {
+    let samples = samples();
+    let model = Vector3::new(1.5, 0.3, 0.5);
+    let stacked = levenberg_marquardt::stacked_jacobian(
+        samples.iter().map(|&(x, _)| analytic_jacobian(&model, x)),
+    );
+    assert_eq!(stacked.shape(), (samples.len(), 3));
+    for (row, &(x, _)) in stacked.row_iter().zip(&samples) {
+        assert_eq!(row.transpose(), analytic_jacobian(&model, x));
+    }
+}
+
+#[test]
+fn adaptive_step_handles_parameters_of_different_scales() {
+    // Samples of `y = sqrt(a)x + sqrt(b)` for `a = 1e-3` and `b = 1e3`.
+    let samples: Vec<(f64, f64)> = (0..20)
+        .map(|x| {
+            let x = f64::from(x) * 0.25;
+            (x, 1e-3f64.sqrt() * x + 1e3f64.sqrt())
+        })
+        .collect();
+    let residuals = |model: &Vector2<f64>| {
+        Residuals::from_iterator(
+            samples.len(),
+            samples
+                .iter()
+                .map(|&(x, y)| y - (model.x.sqrt() * x + model.y.sqrt())),
+        )
+    };
+    let config = Config {
+        threshold: 1e-20,
+        ..Config::default()
+    };
+    let init = Vector2::new(1.5e-3, 1.01e3);
+    let apply_delta = |model: &Vector2<f64>, delta: Vector2<f64>| model + delta;
+
+    // A step which suits `b` steps `a` past zero, where its square root isn't defined.
+    let fixed = optimize_report(config, init, apply_delta, residuals, |model| {
+        central_difference_jacobians(model, apply_delta, residuals, 1e-2)
+    });
+    assert_ne!(fixed.termination, TerminationReason::BelowThreshold);
+
+    let adaptive = optimize_report(config, init, apply_delta, residuals, |model| {
+        adaptive_central_difference_jacobians(model, residuals, 1e-5, 1e-12)
+    });
+    assert_eq!(adaptive.termination, TerminationReason::BelowThreshold);
+    assert!((adaptive.model.x - 1e-3).abs() < 1e-9);
+    assert!((adaptive.model.y - 1e3).abs() < 1e-6);
+}
+
+#[test]
+fn adaptive_forward_difference_matches_analytic() {
+    let samples = samples();
+    let model = Vector3::new(2.0, 0.5, 1.0);
+    let numerical = adaptive_forward_difference_jacobians(
+        &model,
+        |model| residuals(&samples, model),
+        1e-7,
+        1e-7,
+    );
+    for (numerical, &(x, _)) in numerical.zip(&samples) {
+        assert!((numerical - analytic_jacobian(&model, x)).amax() < 1e-5);
+    }
+}
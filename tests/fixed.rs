@@ -0,0 +1,96 @@
+use levenberg_marquardt::{
+    optimize_problem, optimize_report, ClosureProblem, Config, DampingMode, FixedProblem,
+    SolveMethod,
+};
+use nalgebra::{Vector2, Vector3};
+
+mod common;
+
+use common::parabola::{jacobian, residuals, samples};
+
+#[test]
+fn fixed_parameter_is_never_modified() {
+    let samples = samples();
+    // The constant term is frozen at the wrong value, so the others fit around it.
+    let init = Vector3::new(0.0, 0.0, 5.0);
+    let fixed = Vector3::new(false, false, true);
+    let problem = FixedProblem::new(
+        ClosureProblem::new(
+            |model: &Vector3<f64>, delta: Vector3<f64>| {
+                assert_eq!(delta.z, 0.0);
+                model + delta
+            },
+            |model: &Vector3<f64>| residuals(&samples, model),
+            |_: &Vector3<f64>| samples.iter().map(|&(x, _)| jacobian(x)),
+        ),
+        fixed,
+    );
+
+    // The same fit with only the free parameters in the model.
+    let free = optimize_report(
+        Config::default(),
+        Vector2::zeros(),
+        |model, delta| model + delta,
+        |model| residuals(&samples, &Vector3::new(model.x, model.y, 5.0)),
+        |_| samples.iter().map(|&(x, _)| Vector2::new(x * x, x)),
+    );
+
+    for config in [
+        Config::default(),
+        Config {
+            geodesic_acceleration: true,
+            ..Config::default()
+        },
+    ]
+    .iter()
+    {
+        let report = optimize_problem(*config, init, &problem);
+        assert_eq!(report.model.z, 5.0);
+        assert!((report.model.xy() - free.model).norm() < 1e-6);
+    }
+}
+
+#[test]
+fn mask_applies_to_every_solve_method() {
+    let samples = samples();
+    let init = Vector3::new(0.0, 0.0, 5.0);
+    let problem = FixedProblem::new(
+        ClosureProblem::new(
+            |model: &Vector3<f64>, delta: Vector3<f64>| model + delta,
+            |model: &Vector3<f64>| residuals(&samples, model),
+            |_: &Vector3<f64>| samples.iter().map(|&(x, _)| jacobian(x)),
+        ),
+        Vector3::new(false, false, true),
+    );
+    let config = Config {
+        damping_mode: DampingMode::AutoScaled,
+        ..Config::default()
+    };
+    let free = optimize_report(
+        config,
+        Vector2::zeros(),
+        |model, delta| model + delta,
+        |model| residuals(&samples, &Vector3::new(model.x, model.y, 5.0)),
+        |_| samples.iter().map(|&(x, _)| Vector2::new(x * x, x)),
+    );
+
+    // These would sum the Jacobians rather than call `normal_equations`, if the fixed
+    // parameter didn't modify them.
+    for config in [
+        Config {
+            solve_method: SolveMethod::Qr,
+            ..config
+        },
+        Config {
+            compensated_accumulation: true,
+            ..config
+        },
+    ]
+    .iter()
+    {
+        let report = optimize_problem(*config, init, &problem);
+        assert_eq!(report.model.z, 5.0);
+        assert!((report.model.xy() - free.model).norm() < 1e-6);
+        assert_eq!(report.final_gradient.z, 0.0);
+    }
+}
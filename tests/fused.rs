@@ -0,0 +1,133 @@
+use levenberg_marquardt::{
+    optimize_problem, BoundedProblem, ClosureProblem, Config, FixedProblem, FusedClosureProblem,
+    InformationWeightedProblem, LeastSquaresProblem, PriorProblem, ScaledProblem,
+    TerminationReason, WeightedProblem,
+};
+use nalgebra::{Dynamic, Matrix1, Matrix3, Vector3, U1, U3};
+use std::cell::Cell;
+
+mod common;
+
+use common::{
+    exponential::{jacobian, residuals, samples},
+    Residuals,
+};
+
+/// The exponential fit with its residuals and Jacobians computed together, counting the calls.
+fn fused<'a>(
+    samples: &'a [(f64, f64)],
+    calls: &'a Cell<usize>,
+) -> impl LeastSquaresProblem<f64, U3, Dynamic, U1, Model = Vector3<f64>> + 'a {
+    FusedClosureProblem::new(
+        |model: &Vector3<f64>, delta| model + delta,
+        move |model: &Vector3<f64>| {
+            calls.set(calls.get() + 1);
+            // The exponential of each sample is shared by its residual and its Jacobian.
+            let exps: Vec<f64> = samples.iter().map(|&(x, _)| (-model.y * x).exp()).collect();
+            let residuals = Residuals::from_iterator(
+                samples.len(),
+                samples
+                    .iter()
+                    .zip(&exps)
+                    .map(|(&(_, y), &exp)| y - (model.x * exp + model.z)),
+            );
+            let model = *model;
+            let jacobians = samples
+                .iter()
+                .zip(exps)
+                .map(move |(&(x, _), exp)| Vector3::new(exp, -model.x * x * exp, 1.0));
+            (residuals, jacobians)
+        },
+    )
+}
+
+/// The same fit as [`fused`] with its residuals and Jacobians computed separately.
+fn separate(
+    samples: &[(f64, f64)],
+) -> impl LeastSquaresProblem<f64, U3, Dynamic, U1, Model = Vector3<f64>> + '_ {
+    ClosureProblem::new(
+        |model: &Vector3<f64>, delta| model + delta,
+        move |model: &Vector3<f64>| residuals(samples, model),
+        move |&model: &Vector3<f64>| samples.iter().map(move |&(x, _)| jacobian(&model, x)),
+    )
+}
+
+#[test]
+fn matches_separate_closures() {
+    let samples = samples();
+    let config = Config {
+        threshold: 1e-12,
+        ..Config::default()
+    };
+    let init = Vector3::new(1.0, 1.0, 0.0);
+    let calls = Cell::new(0);
+    let fused = optimize_problem(config, init, &fused(&samples, &calls));
+    let separate = optimize_problem(config, init, &separate(&samples));
+
+    assert_eq!(fused.termination, TerminationReason::BelowThreshold);
+    assert_eq!(fused, separate);
+    // The Jacobians of every accepted step were computed along with its residuals.
+    assert_eq!(calls.get(), fused.residual_evaluations);
+}
+
+#[test]
+fn adapters_keep_the_fused_jacobians() {
+    let samples = samples();
+    let init = Vector3::new(1.0, 1.0, 0.0);
+    let calls = Cell::new(0);
+    let check = |residual_evaluations| {
+        assert_eq!(calls.get(), residual_evaluations);
+        calls.set(0);
+    };
+
+    let bounded = BoundedProblem::new(fused(&samples, &calls), Some(Vector3::repeat(-10.0)), None);
+    check(optimize_problem(Config::default(), init, &bounded).residual_evaluations);
+    let scaled = ScaledProblem::new(fused(&samples, &calls), Vector3::new(1.0, 0.5, 2.0));
+    check(optimize_problem(Config::default(), init, &scaled).residual_evaluations);
+    let weighted = WeightedProblem::new(fused(&samples, &calls), |_: &Vector3<f64>| {
+        Residuals::repeat(samples.len(), 2.0)
+    });
+    check(optimize_problem(Config::default(), init, &weighted).residual_evaluations);
+    let information =
+        InformationWeightedProblem::new(fused(&samples, &calls), |_: &Vector3<f64>| {
+            samples.iter().map(|_| Matrix1::new(2.0))
+        });
+    check(optimize_problem(Config::default(), init, &information).residual_evaluations);
+}
+
+#[test]
+fn adapters_with_their_own_normal_equations_match_separate_closures() {
+    let samples = samples();
+    let init = Vector3::new(1.0, 1.0, 0.0);
+    let calls = Cell::new(0);
+    let prior = Some((Vector3::new(1.5, 0.25, 0.5), Matrix3::identity()));
+    let fixed = Vector3::new(false, false, true);
+
+    // The fused Jacobians would leave out the prior and the decoupled fixed parameter.
+    let fused_prior = optimize_problem(
+        Config::default(),
+        init,
+        &PriorProblem::new(fused(&samples, &calls), prior),
+    );
+    let separate_prior = optimize_problem(
+        Config::default(),
+        init,
+        &PriorProblem::new(separate(&samples), prior),
+    );
+    assert_eq!(fused_prior.model, separate_prior.model);
+    assert_eq!(fused_prior.sum_of_squares, separate_prior.sum_of_squares);
+
+    let fused_fixed = optimize_problem(
+        Config::default(),
+        init,
+        &FixedProblem::new(fused(&samples, &calls), fixed),
+    );
+    let separate_fixed = optimize_problem(
+        Config::default(),
+        init,
+        &FixedProblem::new(separate(&samples), fixed),
+    );
+    assert_ne!(fused_fixed.termination, TerminationReason::Inverted);
+    assert_eq!(fused_fixed.model, separate_fixed.model);
+    assert_eq!(fused_fixed.sum_of_squares, separate_fixed.sum_of_squares);
+}
@@ -0,0 +1,125 @@
+use levenberg_marquardt::{optimize_report, Config, Method, TerminationReason};
+use nalgebra::{Vector3, U3};
+
+mod common;
+
+use common::exponential::{jacobian, residuals, samples};
+
+fn fit(
+    config: Config<f64>,
+    init: Vector3<f64>,
+) -> levenberg_marquardt::MinimizationReport<Vector3<f64>, f64, U3> {
+    let samples = samples();
+    optimize_report(
+        config,
+        init,
+        |model, delta| model + delta,
+        |model| residuals(&samples, model),
+        |&model| samples.iter().map(move |&(x, _)| jacobian(&model, x)),
+    )
+}
+
+#[test]
+fn converges_faster_near_solution() {
+    let config = Config {
+        threshold: 1e-20,
+        ..Config::default()
+    };
+    let init = Vector3::new(1.8, 0.6, 1.1);
+    let damped = fit(config, init);
+    let gauss_newton = fit(
+        Config {
+            method: Method::GaussNewton,
+            ..config
+        },
+        init,
+    );
+
+    assert_eq!(gauss_newton.termination, TerminationReason::BelowThreshold);
+    assert!((gauss_newton.model - Vector3::new(2.0, 0.5, 1.0)).norm() < 1e-8);
+    assert!(gauss_newton.iterations < damped.iterations);
+    // No step is ever rejected, so each one costs exactly one evaluation of each.
+    assert_eq!(
+        gauss_newton.residual_evaluations,
+        gauss_newton.iterations + 1
+    );
+    assert_eq!(
+        gauss_newton.jacobian_evaluations,
+        gauss_newton.iterations + 1
+    );
+}
+
+#[test]
+fn regularizes_singular_hessian() {
+    // With an amplitude of zero, the decay rate has no effect on the residuals, so the
+    // approximate Hessian is singular at the initial guess.
+    let report = fit(
+        Config {
+            threshold: 1e-20,
+            method: Method::GaussNewton,
+            ..Config::default()
+        },
+        Vector3::new(0.0, 0.5, 0.0),
+    );
+
+    assert_eq!(report.termination, TerminationReason::BelowThreshold);
+    assert!((report.model - Vector3::new(2.0, 0.5, 1.0)).norm() < 1e-8);
+}
+
+#[test]
+fn fallback_stays_undamped_near_solution() {
+    let report = fit(
+        Config {
+            threshold: 1e-20,
+            method: Method::GaussNewtonWithFallback,
+            ..Config::default()
+        },
+        Vector3::new(1.8, 0.6, 1.1),
+    );
+
+    assert_eq!(report.termination, TerminationReason::BelowThreshold);
+    assert!((report.model - Vector3::new(2.0, 0.5, 1.0)).norm() < 1e-8);
+    assert_eq!(report.gauss_newton_iterations, report.iterations);
+    assert_eq!(report.damped_iterations, 0);
+    assert_eq!(report.rejected_steps, 0);
+}
+
+#[test]
+fn fallback_recovers_where_gauss_newton_fails() {
+    // The steps of plain Gauss-Newton leave the region where the decay can be fit, while the
+    // damped steps take over from each failed attempt until the fit is close enough.
+    let config = Config {
+        threshold: 1e-20,
+        ..Config::default()
+    };
+    let init = Vector3::new(0.1, 4.0, 0.0);
+    let report = fit(
+        Config {
+            method: Method::GaussNewtonWithFallback,
+            ..config
+        },
+        init,
+    );
+    let gauss_newton = fit(
+        Config {
+            method: Method::GaussNewton,
+            ..config
+        },
+        init,
+    );
+    let damped = fit(config, init);
+
+    assert_ne!(gauss_newton.termination, TerminationReason::BelowThreshold);
+    assert_eq!(report.termination, TerminationReason::BelowThreshold);
+    assert!((report.model - Vector3::new(2.0, 0.5, 1.0)).norm() < 1e-8);
+    assert!(report.damped_iterations > 0);
+    // The fit switched back to Gauss-Newton after failed attempts.
+    assert!(report.gauss_newton_iterations > report.rejected_steps);
+    assert_eq!(
+        report.gauss_newton_iterations + report.damped_iterations,
+        report.iterations
+    );
+    assert!(report.iterations < damped.iterations);
+    assert_eq!(damped.damped_iterations, damped.iterations);
+    assert_eq!(damped.gauss_newton_iterations, 0);
+}
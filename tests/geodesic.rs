@@ -0,0 +1,58 @@
+use levenberg_marquardt::{optimize_report, Config, DampingStrategy, TerminationReason};
+use nalgebra::{Matrix2, Vector2, U2};
+
+/// The Rosenbrock function as a least squares problem, whose minimum at `(1, 1)` lies at the
+/// end of a narrow curved valley.
+fn rosenbrock(
+    config: Config<f64>,
+) -> levenberg_marquardt::MinimizationReport<Vector2<f64>, f64, U2> {
+    optimize_report(
+        Config {
+            threshold: 1e-20,
+            ..config
+        },
+        Vector2::new(-1.2, 1.0),
+        |model, delta| model + delta,
+        |model| Vector2::new(10.0 * (model.y - model.x * model.x), 1.0 - model.x),
+        |model| core::iter::once(Matrix2::new(20.0 * model.x, 1.0, -10.0, 0.0)),
+    )
+}
+
+#[test]
+fn acceleration_follows_curved_valley() {
+    for &damping_strategy in &[DampingStrategy::Multiplicative, DampingStrategy::Nielsen] {
+        let config = Config {
+            damping_strategy,
+            ..Config::default()
+        };
+        let plain = rosenbrock(config);
+        let accelerated = rosenbrock(Config {
+            geodesic_acceleration: true,
+            ..config
+        });
+
+        assert_eq!(accelerated.termination, TerminationReason::BelowThreshold);
+        assert!((accelerated.model - Vector2::new(1.0, 1.0)).norm() < 1e-9);
+        assert!(accelerated.iterations < plain.iterations);
+        // Every tested lambda costs another evaluation of the residuals and the Jacobians.
+        assert!(accelerated.residual_evaluations > accelerated.iterations * 2);
+    }
+}
+
+#[test]
+fn large_ratio_accepts_every_correction() {
+    // Always taking the correction is much faster here, but isn't safe in general.
+    let config = Config {
+        damping_strategy: DampingStrategy::Nielsen,
+        ..Config::default()
+    };
+    let plain = rosenbrock(config);
+    let accelerated = rosenbrock(Config {
+        geodesic_acceleration: true,
+        acceleration_ratio: f64::INFINITY,
+        ..config
+    });
+
+    assert_eq!(accelerated.termination, TerminationReason::BelowThreshold);
+    assert!(accelerated.iterations * 3 < plain.iterations * 2);
+}
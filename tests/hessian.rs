@@ -0,0 +1,200 @@
+use levenberg_marquardt::{
+    optimize_problem, ClosureProblem, Config, HessianClosureProblem, InitialHessianProblem, Method,
+    OptimizeError, TerminationReason,
+};
+use nalgebra::{DMatrix, DVector, Matrix2, Matrix3, Vector2, Vector3};
+use std::cell::Cell;
+
+mod common;
+
+use common::{
+    exponential::{jacobian, residuals, samples},
+    Residuals,
+};
+
+fn hessian(samples: &[(f64, f64)], model: &Vector3<f64>) -> Matrix3<f64> {
+    samples
+        .iter()
+        .map(|&(x, _)| {
+            let jacobian = jacobian(model, x);
+            jacobian * jacobian.transpose()
+        })
+        .sum()
+}
+
+#[test]
+fn exact_hessian_matches_accumulated() {
+    let samples = samples();
+    let config = Config {
+        threshold: 1e-12,
+        ..Config::default()
+    };
+    let init = Vector3::new(1.0, 1.0, 0.0);
+    let calls = Cell::new(0);
+    let overridden = optimize_problem(
+        config,
+        init,
+        &HessianClosureProblem::new(
+            |model: &Vector3<f64>, delta| model + delta,
+            |model: &Vector3<f64>| residuals(&samples, model),
+            |&model: &Vector3<f64>| samples.iter().map(move |&(x, _)| jacobian(&model, x)),
+            |model: &Vector3<f64>| {
+                calls.set(calls.get() + 1);
+                hessian(&samples, model)
+            },
+        ),
+    );
+    let accumulated = optimize_problem(
+        config,
+        init,
+        &ClosureProblem::new(
+            |model: &Vector3<f64>, delta| model + delta,
+            |model: &Vector3<f64>| residuals(&samples, model),
+            |&model: &Vector3<f64>| samples.iter().map(move |&(x, _)| jacobian(&model, x)),
+        ),
+    );
+
+    assert_eq!(overridden.termination, TerminationReason::BelowThreshold);
+    assert_eq!(overridden.iterations, accumulated.iterations);
+    assert!((overridden.model - accumulated.model).norm() < 1e-9);
+    // The Hessian is only needed once for each linearization.
+    assert_eq!(calls.get(), overridden.jacobian_evaluations);
+}
+
+#[test]
+fn approximate_hessian_still_converges() {
+    // Only keep the diagonal of JJᵀ, which is enough to descend since the gradient is exact,
+    // although many more steps are rejected while lambda adjusts.
+    let samples = samples();
+    let report = optimize_problem(
+        Config {
+            threshold: 1e-12,
+            max_iterations: 10_000,
+            consecutive_divergence_limit: 100,
+            ..Config::default()
+        },
+        Vector3::new(1.0, 1.0, 0.0),
+        &HessianClosureProblem::new(
+            |model: &Vector3<f64>, delta| model + delta,
+            |model: &Vector3<f64>| residuals(&samples, model),
+            |&model: &Vector3<f64>| samples.iter().map(move |&(x, _)| jacobian(&model, x)),
+            |model: &Vector3<f64>| Matrix3::from_diagonal(&hessian(&samples, model).diagonal()),
+        ),
+    );
+
+    assert_eq!(report.termination, TerminationReason::BelowThreshold);
+    assert!((report.model - Vector3::new(2.0, 0.5, 1.0)).norm() < 1e-4);
+}
+
+#[test]
+fn indefinite_hessian_is_solved_with_pivoting() {
+    // The Hessian has a zero in its first pivot, so it has no Cholesky decomposition, but it
+    // swaps the components of the gradient of `r = (3, 3) - p`, which leaves the step exact.
+    let report = optimize_problem(
+        Config {
+            method: Method::GaussNewton,
+            threshold: 1e-20,
+            ..Config::default()
+        },
+        Vector2::new(1.0, 1.0),
+        &HessianClosureProblem::new(
+            |model: &Vector2<f64>, delta| model + delta,
+            |model: &Vector2<f64>| {
+                Residuals::from_iterator(2, (Vector2::repeat(3.0) - model).iter().copied())
+            },
+            |_: &Vector2<f64>| vec![Vector2::x(), Vector2::y()].into_iter(),
+            |_: &Vector2<f64>| Matrix2::new(0.0, 1.0, 1.0, 0.0),
+        ),
+    );
+
+    assert_eq!(report.termination, TerminationReason::BelowThreshold);
+    assert_eq!(report.iterations, 1);
+    assert_eq!(report.model, Vector2::repeat(3.0));
+}
+
+#[test]
+fn exact_initial_hessian_matches_accumulated() {
+    let samples = samples();
+    let config = Config {
+        threshold: 1e-12,
+        ..Config::default()
+    };
+    let init = Vector3::new(1.0, 1.0, 0.0);
+    let problem = || {
+        ClosureProblem::new(
+            |model: &Vector3<f64>, delta| model + delta,
+            |model: &Vector3<f64>| residuals(&samples, model),
+            |&model: &Vector3<f64>| samples.iter().map(move |&(x, _)| jacobian(&model, x)),
+        )
+    };
+    let seeded = InitialHessianProblem::new(problem(), &init, hessian(&samples, &init)).unwrap();
+    let seeded = optimize_problem(config, init, &seeded);
+    let accumulated = optimize_problem(config, init, &problem());
+
+    assert_eq!(seeded.termination, TerminationReason::BelowThreshold);
+    assert_eq!(seeded.iterations, accumulated.iterations);
+    assert!((seeded.model - accumulated.model).norm() < 1e-9);
+}
+
+#[test]
+fn initial_hessian_only_seeds_first_step() {
+    // Twice the true Hessian halves the first step of `r = (3, 3) - p`, and the accumulated
+    // Hessian makes the second step exact.
+    let init = Vector2::new(1.0, 1.0);
+    let problem = InitialHessianProblem::new(
+        ClosureProblem::new(
+            |model: &Vector2<f64>, delta| model + delta,
+            |model: &Vector2<f64>| {
+                Residuals::from_iterator(2, (Vector2::repeat(3.0) - model).iter().copied())
+            },
+            |_: &Vector2<f64>| vec![Vector2::x(), Vector2::y()].into_iter(),
+        ),
+        &init,
+        Matrix2::identity() * 2.0,
+    )
+    .unwrap();
+    let report = optimize_problem(
+        Config {
+            method: Method::GaussNewton,
+            threshold: 1e-20,
+            ..Config::default()
+        },
+        init,
+        &problem,
+    );
+
+    assert_eq!(report.termination, TerminationReason::BelowThreshold);
+    assert_eq!(report.iterations, 2);
+    assert_eq!(report.model, Vector2::repeat(3.0));
+}
+
+#[test]
+fn dynamic_initial_hessian_must_match_parameters() {
+    let init = DVector::from_element(2, 1.0);
+    let fit = |hessian: DMatrix<f64>| {
+        let problem = ClosureProblem::new(
+            |model: &DVector<f64>, delta| model + delta,
+            |model: &DVector<f64>| Residuals::from_iterator(2, model.iter().map(|p| 3.0 - p)),
+            |_: &DVector<f64>| {
+                vec![
+                    DVector::from_column_slice(&[1.0, 0.0]),
+                    DVector::from_column_slice(&[0.0, 1.0]),
+                ]
+                .into_iter()
+            },
+        );
+        InitialHessianProblem::new(problem, &init, hessian)
+            .map(|problem| optimize_problem(Config::default(), init.clone(), &problem).model)
+    };
+
+    assert_eq!(
+        fit(DMatrix::identity(3, 3)).err(),
+        Some(OptimizeError::HessianDimensionMismatch)
+    );
+    assert_eq!(
+        fit(DMatrix::identity(2, 3)).err(),
+        Some(OptimizeError::HessianDimensionMismatch)
+    );
+    let model = fit(DMatrix::identity(2, 2)).unwrap();
+    assert!((model - DVector::from_element(2, 3.0)).norm() < 1e-6);
+}
@@ -0,0 +1,79 @@
+use levenberg_marquardt::{optimize_iter, optimize_report, Config, TerminationReason};
+use nalgebra::Vector3;
+
+mod common;
+
+use common::exponential::{jacobian, residuals, samples};
+
+fn config() -> Config<f64> {
+    Config {
+        threshold: 1e-20,
+        ..Config::default()
+    }
+}
+
+fn init() -> Vector3<f64> {
+    Vector3::new(1.0, 1.0, 0.0)
+}
+
+#[test]
+fn last_item_matches_report() {
+    let samples = samples();
+    let curve: Vec<(Vector3<f64>, f64)> = optimize_iter(
+        config(),
+        init(),
+        |model, delta: Vector3<f64>| model + delta,
+        |model| residuals(&samples, model),
+        |&model| samples.iter().map(move |&(x, _)| jacobian(&model, x)),
+    )
+    .collect();
+    let report = optimize_report(
+        config(),
+        init(),
+        |model, delta: Vector3<f64>| model + delta,
+        |model| residuals(&samples, model),
+        |&model| samples.iter().map(move |&(x, _)| jacobian(&model, x)),
+    );
+
+    assert_eq!(report.termination, TerminationReason::BelowThreshold);
+    assert_eq!(curve.len(), report.iterations);
+    assert_eq!(
+        curve.last().copied(),
+        Some((report.model, report.sum_of_squares))
+    );
+    // The best sum-of-squares can never increase.
+    assert!(curve.windows(2).all(|pair| pair[1].1 <= pair[0].1));
+}
+
+#[test]
+fn stops_at_max_iterations_and_fuses() {
+    let samples = samples();
+    let mut iter = optimize_iter(
+        Config {
+            max_iterations: 3,
+            ..config()
+        },
+        init(),
+        |model, delta: Vector3<f64>| model + delta,
+        |model| residuals(&samples, model),
+        |&model| samples.iter().map(move |&(x, _)| jacobian(&model, x)),
+    );
+    assert_eq!(iter.by_ref().count(), 3);
+    assert!(iter.next().is_none());
+    assert!(iter.next().is_none());
+}
+
+#[test]
+fn custom_stopping_criterion() {
+    let samples = samples();
+    let (_, sum_of_squares) = optimize_iter(
+        config(),
+        init(),
+        |model, delta: Vector3<f64>| model + delta,
+        |model| residuals(&samples, model),
+        |&model| samples.iter().map(move |&(x, _)| jacobian(&model, x)),
+    )
+    .find(|&(_, sum_of_squares)| sum_of_squares < 1e-3)
+    .unwrap();
+    assert!(sum_of_squares < 1e-3);
+}
@@ -0,0 +1,78 @@
+use levenberg_marquardt::{
+    optimize_report, Config, DampingStrategy, InitialLambda, TerminationReason,
+};
+use nalgebra::{Vector3, U3};
+use std::cell::RefCell;
+
+mod common;
+
+use common::exponential::{jacobian, residuals, samples};
+
+fn fit(
+    config: Config<f64>,
+    deltas: &RefCell<Vec<Vector3<f64>>>,
+) -> levenberg_marquardt::MinimizationReport<Vector3<f64>, f64, U3> {
+    let samples = samples();
+    optimize_report(
+        config,
+        Vector3::new(1.0, 1.0, 0.0),
+        |model, delta| {
+            deltas.borrow_mut().push(delta);
+            model + delta
+        },
+        |model| residuals(&samples, model),
+        |&model| samples.iter().map(move |&(x, _)| jacobian(&model, x)),
+    )
+}
+
+#[test]
+fn salvages_overshooting_steps() {
+    // With a tiny lambda, the early steps are nearly Gauss-Newton steps which overshoot, and
+    // lambda can't grow fast enough before the divergence limit is hit.
+    let config = Config {
+        threshold: 1e-12,
+        initial_lambda: InitialLambda::Fixed(1e-3),
+        damping_strategy: DampingStrategy::Nielsen,
+        ..Config::default()
+    };
+    let deltas = RefCell::new(Vec::new());
+    let plain = fit(config, &deltas);
+    assert_eq!(plain.termination, TerminationReason::ConsecutiveDivergence);
+
+    deltas.borrow_mut().clear();
+    let searched = fit(
+        Config {
+            line_search: true,
+            ..config
+        },
+        &deltas,
+    );
+    assert_eq!(searched.termination, TerminationReason::BelowThreshold);
+    assert!((searched.model - Vector3::new(2.0, 0.5, 1.0)).norm() < 1e-4);
+    assert!(searched.residual_evaluations > searched.iterations + 1);
+
+    // Every backtracked step is passed to `apply_delta` as exactly half of the previous one.
+    let deltas = deltas.borrow();
+    assert!(deltas.windows(2).any(|pair| pair[1] == pair[0] * 0.5));
+}
+
+#[test]
+fn min_alpha_limits_backtracking() {
+    // With a minimum fraction of one, the line search never tries a shorter step.
+    let config = Config {
+        threshold: 1e-12,
+        initial_lambda: InitialLambda::Fixed(1e-3),
+        damping_strategy: DampingStrategy::Nielsen,
+        ..Config::default()
+    };
+    let plain = fit(config, &RefCell::new(Vec::new()));
+    let limited = fit(
+        Config {
+            line_search: true,
+            line_search_min_alpha: 1.0,
+            ..config
+        },
+        &RefCell::new(Vec::new()),
+    );
+    assert_eq!(limited, plain);
+}
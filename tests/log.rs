@@ -0,0 +1,64 @@
+#![cfg(feature = "log")]
+
+use levenberg_marquardt::{optimize_report, Config};
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use nalgebra::Vector3;
+use std::sync::Mutex;
+
+mod common;
+
+use common::exponential::{jacobian, residuals, samples};
+
+/// Records the level and message of every record.
+#[derive(Default)]
+struct Recorder {
+    records: Mutex<Vec<(Level, String)>>,
+}
+
+impl Log for Recorder {
+    fn enabled(&self, _: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        self.records
+            .lock()
+            .unwrap()
+            .push((record.level(), record.args().to_string()));
+    }
+
+    fn flush(&self) {}
+}
+
+#[test]
+fn records_every_iteration() {
+    let recorder: &'static Recorder = Box::leak(Box::default());
+    log::set_logger(recorder).unwrap();
+    log::set_max_level(LevelFilter::Trace);
+
+    let samples = samples();
+    let report = optimize_report(
+        Config {
+            threshold: 1e-20,
+            ..Config::default()
+        },
+        Vector3::new(1.0, 1.0, 0.0),
+        |model, delta| model + delta,
+        |model| residuals(&samples, model),
+        |&model| samples.iter().map(move |&(x, _)| jacobian(&model, x)),
+    );
+
+    let records = recorder.records.lock().unwrap();
+    let iterations: Vec<_> = records
+        .iter()
+        .filter(|(level, _)| *level == Level::Trace)
+        .collect();
+    assert_eq!(iterations.len(), report.iterations);
+    assert!(iterations[0].1.starts_with("iteration 1: lambda "));
+    assert!(iterations
+        .iter()
+        .any(|(_, record)| record.ends_with("accepted true")));
+    let last = records.last().unwrap();
+    assert_eq!(last.0, Level::Debug);
+    assert!(last.1.contains(&format!("{:?}", report.termination)));
+}
@@ -0,0 +1,93 @@
+#![cfg(feature = "alloc")]
+
+use levenberg_marquardt::{
+    optimize_problem, optimize_report, Config, LevenbergMarquardt, MinibatchProblem,
+};
+use nalgebra::Vector2;
+use std::cell::Cell;
+
+mod common;
+
+use common::Residuals;
+
+/// Samples of `y = 2exp(-0.5x) + 1`.
+fn samples() -> Vec<(f64, f64)> {
+    (0..1000)
+        .map(|x| {
+            let x = f64::from(x) * 0.01;
+            (x, 2.0 * (-0.5 * x).exp() + 1.0)
+        })
+        .collect()
+}
+
+fn residual(&(x, y): &(f64, f64), model: &Vector2<f64>) -> f64 {
+    y - (model.x * (-0.5 * x).exp() + model.y)
+}
+
+fn jacobian(&(x, _): &(f64, f64)) -> Vector2<f64> {
+    Vector2::new((-0.5 * x).exp(), 1.0)
+}
+
+#[test]
+fn batches_converge_to_full_fit() {
+    let samples = samples();
+    let config = Config {
+        max_iterations: 30,
+        ..Config::default()
+    };
+    let evaluated = Cell::new(0);
+    let mut problem = MinibatchProblem::new(
+        |model: &Vector2<f64>, delta| model + delta,
+        |model: &Vector2<f64>, batch: &[usize]| {
+            evaluated.set(evaluated.get() + batch.len());
+            Residuals::from_iterator(
+                batch.len(),
+                batch.iter().map(|&i| residual(&samples[i], model)),
+            )
+        },
+        |_: &Vector2<f64>, i| jacobian(&samples[i]),
+    );
+    // Stride through the data so that every batch spans the whole range of `x`.
+    let mut start = 0;
+    problem.select((0..50).map(|i| 20 * i));
+    let report = LevenbergMarquardt::new(config, Vector2::zeros(), &problem)
+        .unwrap()
+        .run_batched(&mut problem, |problem| {
+            start = (start + 1) % 20;
+            problem.select((0..50).map(|i| start + 20 * i));
+        })
+        .unwrap();
+    assert!((report.model - Vector2::new(2.0, 1.0)).norm() < 1e-5);
+    // Only the samples in each batch were ever evaluated.
+    assert_eq!(evaluated.get(), 50 * report.residual_evaluations);
+}
+
+#[test]
+fn every_sample_matches_full_fit() {
+    let samples = samples();
+    let mut problem = MinibatchProblem::new(
+        |model: &Vector2<f64>, delta| model + delta,
+        |model: &Vector2<f64>, batch: &[usize]| {
+            Residuals::from_iterator(
+                batch.len(),
+                batch.iter().map(|&i| residual(&samples[i], model)),
+            )
+        },
+        |_: &Vector2<f64>, i| jacobian(&samples[i]),
+    );
+    problem.select(0..samples.len());
+    let minibatch = optimize_problem(Config::default(), Vector2::zeros(), &problem);
+    let full = optimize_report(
+        Config::default(),
+        Vector2::zeros(),
+        |model, delta: Vector2<f64>| model + delta,
+        |model| {
+            Residuals::from_iterator(
+                samples.len(),
+                samples.iter().map(|sample| residual(sample, model)),
+            )
+        },
+        |_| samples.iter().map(jacobian),
+    );
+    assert_eq!(minibatch, full);
+}
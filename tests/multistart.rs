@@ -0,0 +1,152 @@
+use levenberg_marquardt::{
+    optimize_multistart, optimize_problem, ClosureProblem, Config, LeastSquaresProblem,
+    LevenbergMarquardt,
+};
+use nalgebra::{dimension::U1, Dynamic, VecStorage, Vector1};
+
+mod common;
+
+use common::Residuals;
+
+/// Samples of `y = sin(2x)`, whose frequency has a local minimum near every other frequency.
+fn samples() -> Vec<(f64, f64)> {
+    (0..50)
+        .map(|x| {
+            let x = f64::from(x) * 0.1;
+            (x, (2.0 * x).sin())
+        })
+        .collect()
+}
+
+fn problem(
+    samples: &[(f64, f64)],
+) -> impl LeastSquaresProblem<
+    f64,
+    U1,
+    Dynamic,
+    U1,
+    Model = Vector1<f64>,
+    ResidualStorage = VecStorage<f64, U1, Dynamic>,
+> + '_ {
+    ClosureProblem::new(
+        |model: &Vector1<f64>, delta| model + delta,
+        move |model: &Vector1<f64>| {
+            Residuals::from_iterator(
+                samples.len(),
+                samples.iter().map(|&(x, y)| y - (model.x * x).sin()),
+            )
+        },
+        move |&model: &Vector1<f64>| {
+            samples
+                .iter()
+                .map(move |&(x, _)| Vector1::new(x * (model.x * x).cos()))
+        },
+    )
+}
+
+fn inits() -> Vec<Vector1<f64>> {
+    [0.5, 1.0, 1.7, 3.0, 4.0]
+        .iter()
+        .map(|&w| Vector1::new(w))
+        .collect()
+}
+
+#[test]
+fn keeps_best_start() {
+    let samples = samples();
+    let problem = problem(&samples);
+    let config = Config {
+        threshold: 1e-20,
+        ..Config::default()
+    };
+    let best = optimize_multistart(config, inits(), &problem).unwrap();
+    assert!((best.model.x - 2.0).abs() < 1e-6);
+
+    // Some of the starts get stuck in other minima, and none of them does better.
+    let reports: Vec<_> = inits()
+        .into_iter()
+        .map(|init| optimize_problem(config, init, &problem))
+        .collect();
+    assert!(reports.iter().any(|report| report.sum_of_squares > 1.0));
+    assert!(reports
+        .iter()
+        .all(|report| report.sum_of_squares >= best.sum_of_squares));
+    assert!(reports.contains(&best));
+}
+
+#[test]
+fn no_starts() {
+    let samples = samples();
+    let best = optimize_multistart(Config::default(), Vec::new(), &problem(&samples));
+    assert_eq!(best, None);
+}
+
+#[test]
+fn perturbation_escapes_local_minimum() {
+    let samples = samples();
+    let problem = problem(&samples);
+    let config = Config {
+        threshold: 1e-20,
+        ..Config::default()
+    };
+    let stuck = optimize_problem(config, Vector1::new(4.0), &problem);
+    assert!((stuck.model.x - 2.0).abs() > 0.1);
+
+    // Hop by a pseudorandom offset in [-2.5, 2.5) derived from the seed.
+    let mut seeds = Vec::new();
+    let report = LevenbergMarquardt::new(config, Vector1::new(4.0), &problem)
+        .unwrap()
+        .run_with_perturbation(&problem, 1e-12, 20, |model, seed| {
+            seeds.push(seed);
+            let hash = (seed + 1).wrapping_mul(0x9e37_79b9_7f4a_7c15) >> 11;
+            let offset = hash as f64 / (1u64 << 53) as f64 * 5.0 - 2.5;
+            model + Vector1::new(offset)
+        });
+
+    assert!((report.model.x - 2.0).abs() < 1e-6);
+    // The restarts stopped once the target was reached.
+    assert!(!seeds.is_empty() && seeds.len() < 20);
+    assert!(seeds.iter().copied().eq(0..seeds.len() as u64));
+}
+
+#[test]
+fn non_finite_start_never_wins() {
+    let samples = samples();
+    let problem = problem(&samples);
+    let config = Config {
+        threshold: 1e-20,
+        ..Config::default()
+    };
+    let nan = optimize_problem(config, Vector1::new(f64::NAN), &problem);
+    assert!(nan.sum_of_squares.is_nan());
+
+    let best = optimize_multistart(
+        config,
+        vec![Vector1::new(f64::NAN), Vector1::new(1.7)],
+        &problem,
+    )
+    .unwrap();
+    assert!((best.model.x - 2.0).abs() < 1e-6);
+
+    // With only non-finite starts, the first one is still returned.
+    let only = optimize_multistart(config, vec![Vector1::new(f64::NAN)], &problem).unwrap();
+    assert!(only.sum_of_squares.is_nan());
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn parallel_matches_sequential() {
+    use levenberg_marquardt::optimize_multistart_par;
+
+    let samples = samples();
+    let problem = problem(&samples);
+    let config = Config {
+        threshold: 1e-20,
+        ..Config::default()
+    };
+    let mut inits = inits();
+    inits.insert(0, Vector1::new(f64::NAN));
+    let sequential = optimize_multistart(config, inits.clone(), &problem);
+    assert_eq!(optimize_multistart_par(config, inits, &problem), sequential);
+    assert_eq!(optimize_multistart_par(config, Vec::new(), &problem), None);
+}
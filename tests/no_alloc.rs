@@ -0,0 +1,70 @@
+//! This runs without the test harness, which allocates, so that a global allocator can count
+//! every allocation made while optimizing.
+
+use levenberg_marquardt::{optimize_problem, ClosureProblem, Config, SolveMethod};
+use nalgebra::{dimension::U8, MatrixMN, Vector2, U1};
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+};
+
+struct CountingAllocator;
+
+static COUNTING: AtomicBool = AtomicBool::new(false);
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        if COUNTING.load(Ordering::SeqCst) {
+            ALLOCATIONS.fetch_add(1, Ordering::SeqCst);
+        }
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Fits `y = 3x + 1` to eight samples, counting the allocations made while optimizing.
+fn count_allocations(config: Config<f64>) -> (Vector2<f64>, usize) {
+    let xs = [0.0, 0.5, 1.0, 1.5, 2.0, 2.5, 3.0, 3.5];
+    ALLOCATIONS.store(0, Ordering::SeqCst);
+    COUNTING.store(true, Ordering::SeqCst);
+    let report = optimize_problem(
+        config,
+        Vector2::zeros(),
+        &ClosureProblem::new(
+            |model: &Vector2<f64>, delta| model + delta,
+            |model: &Vector2<f64>| {
+                MatrixMN::<f64, U1, U8>::from_fn(|_, i| {
+                    3.0 * xs[i] + 1.0 - (model.x * xs[i] + model.y)
+                })
+            },
+            |_: &Vector2<f64>| xs.iter().map(|&x| Vector2::new(x, 1.0)),
+        ),
+    );
+    COUNTING.store(false, Ordering::SeqCst);
+    (report.model, ALLOCATIONS.load(Ordering::SeqCst))
+}
+
+fn main() {
+    for &solve_method in &[
+        SolveMethod::NormalEquations,
+        SolveMethod::Qr,
+        SolveMethod::Svd {
+            rank_tolerance: 1e-12,
+        },
+    ] {
+        let (model, allocations) = count_allocations(Config {
+            solve_method,
+            threshold: 1e-20,
+            ..Config::default()
+        });
+        assert_eq!(allocations, 0, "{:?} allocated", solve_method);
+        assert!((model - Vector2::new(3.0, 1.0)).norm() < 1e-8);
+    }
+}
@@ -0,0 +1,94 @@
+use core::{cell::Cell, f64::consts::PI};
+use levenberg_marquardt::{optimize_problem, Config, NormalizedClosureProblem, TerminationReason};
+use nalgebra::{Vector1, Vector2};
+
+mod common;
+
+use common::{
+    line::{jacobian, residuals, samples},
+    Residuals,
+};
+
+#[test]
+fn wraps_angle_across_iterations() {
+    // Fit the angle of a direction on the unit circle. The closest solution to the initial
+    // guess is reached by increasing the angle past 2π.
+    let target = 0.1f64;
+    let wrapped = |angle: f64| (0.0..2.0 * PI).contains(&angle);
+    let evaluated = Cell::new(0);
+    let report = optimize_problem(
+        Config {
+            threshold: 1e-20,
+            ..Config::default()
+        },
+        5.5,
+        &NormalizedClosureProblem::new(
+            |&angle: &f64, delta: Vector1<f64>| angle + delta.x,
+            |&angle: &f64| Some(angle.rem_euclid(2.0 * PI)),
+            |&angle: &f64| {
+                assert!(wrapped(angle), "the residuals saw the angle {}", angle);
+                evaluated.set(evaluated.get() + 1);
+                Residuals::from_iterator(
+                    2,
+                    vec![target.cos() - angle.cos(), target.sin() - angle.sin()],
+                )
+            },
+            |&angle: &f64| vec![Vector1::new(-angle.sin()), Vector1::new(angle.cos())].into_iter(),
+        ),
+    );
+
+    assert!(evaluated.get() > 2);
+    assert!(wrapped(report.model));
+    assert!((report.model - target).abs() < 1e-8);
+}
+
+#[test]
+fn keeps_unit_vector_normalized() {
+    // The closest unit vector to the target is the target scaled to unit length.
+    let target = Vector2::new(1.2, 1.6);
+    let report = optimize_problem(
+        Config::default(),
+        Vector2::new(1.0, 0.0),
+        &NormalizedClosureProblem::new(
+            |model: &Vector2<f64>, delta| model + delta,
+            |model: &Vector2<f64>| Some(model.normalize()),
+            |model: &Vector2<f64>| Residuals::from_iterator(2, (target - model).iter().copied()),
+            |_: &Vector2<f64>| vec![Vector2::x(), Vector2::y()].into_iter(),
+        ),
+    );
+
+    assert!((report.model.norm() - 1.0).abs() < 1e-12);
+    assert!((report.model - Vector2::new(0.6, 0.8)).norm() < 1e-6);
+}
+
+#[test]
+fn failed_normalization_rejects_step() {
+    // Slopes of 2.5 or more are refused by `normalize`.
+    let samples = samples();
+    let normalized = Cell::new(0);
+    let failed = Cell::new(0);
+    let report = optimize_problem(
+        Config::default(),
+        Vector2::zeros(),
+        &NormalizedClosureProblem::new(
+            |model: &Vector2<f64>, delta| model + delta,
+            |model: &Vector2<f64>| {
+                if model.x < 2.5 {
+                    normalized.set(normalized.get() + 1);
+                    Some(*model)
+                } else {
+                    failed.set(failed.get() + 1);
+                    None
+                }
+            },
+            |model: &Vector2<f64>| residuals(&samples, model),
+            |_: &Vector2<f64>| samples.iter().map(|&(x, _)| jacobian(x)),
+        ),
+    );
+
+    assert!(failed.get() > 0);
+    assert!(report.model.x < 2.5);
+    assert_ne!(report.termination, TerminationReason::Inverted);
+    // The residuals are only computed at the initial guess and at normalized guesses.
+    assert_eq!(report.residual_evaluations, normalized.get() + 1);
+}
@@ -0,0 +1,72 @@
+#![cfg(feature = "rayon")]
+
+use levenberg_marquardt::{
+    optimize_problem, ClosureProblem, Config, ParallelClosureProblem, SolveMethod,
+    TerminationReason,
+};
+use nalgebra::Vector2;
+use rayon::prelude::*;
+
+mod common;
+
+use common::Residuals;
+
+/// Many samples of `y = 2exp(-1.5x)`.
+fn samples() -> Vec<(f64, f64)> {
+    (0..10_000)
+        .map(|x| {
+            let x = f64::from(x) * 1e-4;
+            (x, 2.0 * (-1.5 * x).exp())
+        })
+        .collect()
+}
+
+fn residuals(samples: &[(f64, f64)], model: &Vector2<f64>) -> Residuals {
+    Residuals::from_iterator(
+        samples.len(),
+        samples
+            .iter()
+            .map(|&(x, y)| y - model.x * (model.y * x).exp()),
+    )
+}
+
+fn jacobian(model: &Vector2<f64>, x: f64) -> Vector2<f64> {
+    let exp = (model.y * x).exp();
+    Vector2::new(exp, model.x * x * exp)
+}
+
+#[test]
+fn parallel_matches_sequential() {
+    let samples = samples();
+    for &solve_method in &[SolveMethod::NormalEquations, SolveMethod::Qr] {
+        let config = Config {
+            solve_method,
+            threshold: 1e-20,
+            ..Config::default()
+        };
+        let init = Vector2::new(1.0, -1.0);
+        let sequential = optimize_problem(
+            config,
+            init,
+            &ClosureProblem::new(
+                |model: &Vector2<f64>, delta| model + delta,
+                |model: &Vector2<f64>| residuals(&samples, model),
+                |&model: &Vector2<f64>| samples.iter().map(move |&(x, _)| jacobian(&model, x)),
+            ),
+        );
+        let parallel = optimize_problem(
+            config,
+            init,
+            &ParallelClosureProblem::new(
+                |model: &Vector2<f64>, delta| model + delta,
+                |model: &Vector2<f64>| residuals(&samples, model),
+                |&model: &Vector2<f64>| samples.par_iter().map(move |&(x, _)| jacobian(&model, x)),
+            ),
+        );
+
+        assert_eq!(parallel.termination, TerminationReason::BelowThreshold);
+        assert_eq!(parallel.termination, sequential.termination);
+        assert!((parallel.model - sequential.model).norm() < 1e-9);
+        assert!((parallel.model - Vector2::new(2.0, -1.5)).norm() < 1e-6);
+    }
+}
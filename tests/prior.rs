@@ -0,0 +1,177 @@
+use levenberg_marquardt::{
+    optimize_problem, optimize_report, BoundedProblem, ClosureProblem, Config, PriorProblem,
+    ScaledProblem, SolveMethod,
+};
+use nalgebra::{Matrix2, Vector2};
+
+mod common;
+
+use common::{line::samples, Residuals};
+
+/// The residuals of fitting `y = ax + b/100` to the samples, so that `b` is only weakly observed.
+fn residuals(samples: &[(f64, f64)], model: &Vector2<f64>) -> Residuals {
+    Residuals::from_iterator(
+        samples.len(),
+        samples
+            .iter()
+            .map(|&(x, y)| y - (model.x * x + model.y / 100.0)),
+    )
+}
+
+#[test]
+fn weakly_observed_parameter_stays_near_prior() {
+    let samples = samples();
+    let fit = |prior| {
+        let problem = ClosureProblem::new(
+            |model: &Vector2<f64>, delta| model + delta,
+            |model: &Vector2<f64>| residuals(&samples, model),
+            |_: &Vector2<f64>| samples.iter().map(|&(x, _)| Vector2::new(x, 0.01)),
+        );
+        optimize_problem(
+            Config::default(),
+            Vector2::zeros(),
+            &PriorProblem::new(problem, prior),
+        )
+    };
+
+    let unregularized = fit(None);
+    assert!((unregularized.model.y - 100.0).abs() < 1e-3);
+
+    let regularized = fit(Some((Vector2::zeros(), Matrix2::new(0.0, 0.0, 0.0, 1.0))));
+    assert!(regularized.model.y.abs() < 1.0);
+    // The slope absorbs the intercept that the prior won't let `b` explain.
+    assert!(regularized.model.x > 3.0);
+    let prior_cost = regularized.model.y * regularized.model.y;
+    let residual_cost = residuals(&samples, &regularized.model).norm_squared();
+    assert!((regularized.sum_of_squares - (residual_cost + prior_cost)).abs() < 1e-12);
+}
+
+#[test]
+fn zero_weight_recovers_plain_levenberg_marquardt() {
+    let samples = samples();
+    let jacobians = |_: &Vector2<f64>| samples.iter().map(|&(x, _)| Vector2::new(x, 0.01));
+    let problem = ClosureProblem::new(
+        |model: &Vector2<f64>, delta| model + delta,
+        |model: &Vector2<f64>| residuals(&samples, model),
+        jacobians,
+    );
+    let regularized = optimize_problem(
+        Config::default(),
+        Vector2::zeros(),
+        &PriorProblem::new(problem, Some((Vector2::new(5.0, 5.0), Matrix2::zeros()))),
+    );
+    let plain = optimize_report(
+        Config::default(),
+        Vector2::zeros(),
+        |model, delta: Vector2<f64>| model + delta,
+        |model| residuals(&samples, model),
+        jacobians,
+    );
+    assert_eq!(regularized, plain);
+}
+
+#[test]
+fn bounds_keep_the_prior_in_the_cost() {
+    let samples = samples();
+    let prior = Some((Vector2::zeros(), Matrix2::new(0.0, 0.0, 0.0, 1.0)));
+    let problem = || {
+        ClosureProblem::new(
+            |model: &Vector2<f64>, delta| model + delta,
+            |model: &Vector2<f64>| residuals(&samples, model),
+            |_: &Vector2<f64>| samples.iter().map(|&(x, _)| Vector2::new(x, 0.01)),
+        )
+    };
+    // The bounds are far from the solution, so they never clamp a guess.
+    let bounded = optimize_problem(
+        Config::default(),
+        Vector2::zeros(),
+        &BoundedProblem::new(
+            PriorProblem::new(problem(), prior),
+            Some(Vector2::new(-1e3, -1e3)),
+            Some(Vector2::new(1e3, 1e3)),
+        ),
+    );
+    let unbounded = optimize_problem(
+        Config::default(),
+        Vector2::zeros(),
+        &PriorProblem::new(problem(), prior),
+    );
+
+    assert_eq!(bounded, unbounded);
+    let prior_cost = bounded.model.y * bounded.model.y;
+    let residual_cost = residuals(&samples, &bounded.model).norm_squared();
+    assert!((bounded.sum_of_squares - (residual_cost + prior_cost)).abs() < 1e-12);
+}
+
+#[test]
+fn scaling_keeps_the_prior_in_the_steps() {
+    let samples = samples();
+    let prior = Some((Vector2::zeros(), Matrix2::new(0.0, 0.0, 0.0, 1.0)));
+    let problem = || {
+        ClosureProblem::new(
+            |model: &Vector2<f64>, delta| model + delta,
+            |model: &Vector2<f64>| residuals(&samples, model),
+            |_: &Vector2<f64>| samples.iter().map(|&(x, _)| Vector2::new(x, 0.01)),
+        )
+    };
+    let config = Config {
+        threshold: 1e-14,
+        ..Config::default()
+    };
+    let scaled = optimize_problem(
+        config,
+        Vector2::zeros(),
+        &ScaledProblem::new(
+            PriorProblem::new(problem(), prior),
+            Vector2::new(1.0, 100.0),
+        ),
+    );
+    let unscaled = optimize_problem(
+        config,
+        Vector2::zeros(),
+        &PriorProblem::new(problem(), prior),
+    );
+
+    assert!((scaled.model - unscaled.model).norm() < 1e-6);
+    assert!((scaled.sum_of_squares - unscaled.sum_of_squares).abs() < 1e-9);
+}
+
+#[test]
+fn prior_applies_with_every_config() {
+    let samples = samples();
+    let problem = PriorProblem::new(
+        ClosureProblem::new(
+            |model: &Vector2<f64>, delta| model + delta,
+            |model: &Vector2<f64>| residuals(&samples, model),
+            |_: &Vector2<f64>| samples.iter().map(|&(x, _)| Vector2::new(x, 0.01)),
+        ),
+        Some((Vector2::zeros(), Matrix2::new(0.0, 0.0, 0.0, 1.0))),
+    );
+    let config = Config {
+        threshold: 1e-14,
+        ..Config::default()
+    };
+    let expected = optimize_problem(config, Vector2::zeros(), &problem);
+
+    // These sum or stack the Jacobians themselves, which would leave out the prior.
+    for config in [
+        Config {
+            solve_method: SolveMethod::Qr,
+            ..config
+        },
+        Config {
+            compensated_accumulation: true,
+            ..config
+        },
+        Config {
+            jacobian_refresh_interval: 3,
+            ..config
+        },
+    ]
+    .iter()
+    {
+        let report = optimize_problem(*config, Vector2::zeros(), &problem);
+        assert!((report.model - expected.model).norm() < 1e-6);
+        assert!((report.sum_of_squares - expected.sum_of_squares).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,67 @@
+use levenberg_marquardt::{optimize_problem, Config, LeastSquaresProblem, TerminationReason};
+use nalgebra::{
+    dimension::{U1, U3},
+    storage::Owned,
+    Dynamic, Matrix, VecStorage, Vector3,
+};
+
+mod common;
+
+use common::parabola::{jacobian, residuals, samples};
+
+/// Fits `y = ax² + bx + c` to the samples.
+struct Parabola {
+    samples: Vec<(f64, f64)>,
+}
+
+/// The Jacobians of the samples of a [`Parabola`], which are computed from the samples as they
+/// are borrowed rather than collected.
+struct ParabolaJacobians<'a> {
+    samples: std::slice::Iter<'a, (f64, f64)>,
+}
+
+impl Iterator for ParabolaJacobians<'_> {
+    type Item = Vector3<f64>;
+
+    fn next(&mut self) -> Option<Vector3<f64>> {
+        let &(x, _) = self.samples.next()?;
+        Some(jacobian(x))
+    }
+}
+
+impl LeastSquaresProblem<f64, U3, Dynamic, U1> for Parabola {
+    type Model = Vector3<f64>;
+    type ResidualStorage = VecStorage<f64, U1, Dynamic>;
+    type JacobianStorage = Owned<f64, U3, U1>;
+    type Jacobians<'a> = ParabolaJacobians<'a>;
+
+    fn apply_delta(&self, model: &Vector3<f64>, delta: Vector3<f64>) -> Vector3<f64> {
+        model + delta
+    }
+
+    fn residuals(&self, model: &Vector3<f64>) -> Matrix<f64, U1, Dynamic, Self::ResidualStorage> {
+        residuals(&self.samples, model)
+    }
+
+    fn jacobians(&self, _model: &Vector3<f64>) -> ParabolaJacobians<'_> {
+        ParabolaJacobians {
+            samples: self.samples.iter(),
+        }
+    }
+}
+
+#[test]
+fn optimizes_problem() {
+    let problem = Parabola { samples: samples() };
+    let report = optimize_problem(
+        Config {
+            threshold: 1e-12,
+            ..Config::default()
+        },
+        Vector3::zeros(),
+        &problem,
+    );
+
+    assert_eq!(report.termination, TerminationReason::BelowThreshold);
+    assert!((report.model - Vector3::new(2.0, -3.0, 1.0)).norm() < 1e-4);
+}
@@ -0,0 +1,68 @@
+#![cfg(feature = "alloc")]
+
+use levenberg_marquardt::{optimize_problem, Config, RaggedProblem, TerminationReason};
+use nalgebra::{DMatrix, DVector, Dynamic, MatrixMN, Vector2, VectorN, U2};
+
+type Block = (VectorN<f64, Dynamic>, MatrixMN<f64, U2, Dynamic>);
+
+fn point() -> Vector2<f64> {
+    Vector2::new(1.0, 2.0)
+}
+
+/// The distance to each anchor gives one residual and the position observation gives two.
+fn blocks(model: &Vector2<f64>) -> impl Iterator<Item = Block> {
+    let model = *model;
+    let anchors = [
+        Vector2::new(0.0, 0.0),
+        Vector2::new(3.0, 0.0),
+        Vector2::new(0.0, 4.0),
+    ];
+    let ranges = IntoIterator::into_iter(anchors).map(move |anchor| {
+        let offset = model - anchor;
+        let residual = (point() - anchor).norm() - offset.norm();
+        let jacobian = offset / offset.norm();
+        (
+            DVector::from_element(1, residual),
+            MatrixMN::from_column_slice_generic(U2, Dynamic::new(1), jacobian.as_slice()),
+        )
+    });
+    let position = core::iter::once((
+        DVector::from_column_slice((point() - model).as_slice()),
+        MatrixMN::from_column_slice_generic(
+            U2,
+            Dynamic::new(2),
+            DMatrix::<f64>::identity(2, 2).as_slice(),
+        ),
+    ));
+    ranges.chain(position)
+}
+
+#[test]
+fn blocks_of_different_sizes_converge() {
+    let report = optimize_problem(
+        Config {
+            threshold: 1e-14,
+            ..Config::default()
+        },
+        Vector2::new(2.0, 3.0),
+        &RaggedProblem::new(|model: &Vector2<f64>, delta| model + delta, blocks),
+    );
+    assert_ne!(report.termination, TerminationReason::MaxIterations);
+    assert!((report.model - point()).amax() < 1e-6);
+    assert!(report.sum_of_squares < 1e-12);
+}
+
+#[test]
+#[should_panic(expected = "a block had a different number of residuals")]
+fn mismatched_block_panics() {
+    let problem = RaggedProblem::new(
+        |model: &Vector2<f64>, delta| model + delta,
+        |model: &Vector2<f64>| {
+            core::iter::once((
+                DVector::from_element(2, model.x),
+                MatrixMN::<f64, U2, Dynamic>::zeros(1),
+            ))
+        },
+    );
+    optimize_problem(Config::default(), Vector2::new(2.0, 3.0), &problem);
+}
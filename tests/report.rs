@@ -0,0 +1,810 @@
+use levenberg_marquardt::{
+    optimize_recorded, optimize_report, optimize_with_callback, optimize_with_history,
+    optimize_with_stats, ClosureProblem, Config, DampingStrategy, HistoryRecorder, InitialLambda,
+    LevenbergMarquardt, TerminationReason, ThresholdKind,
+};
+use nalgebra::Vector3;
+use std::{
+    cell::Cell,
+    ops::ControlFlow,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+mod common;
+
+use common::parabola::{jacobian, residuals, samples as parabola_samples};
+
+#[test]
+fn converges_below_threshold() {
+    let samples = parabola_samples();
+    let report = optimize_report(
+        Config {
+            threshold: 1e-12,
+            ..Config::default()
+        },
+        Vector3::zeros(),
+        |model, delta| model + delta,
+        |model| residuals(&samples, model),
+        |_| samples.iter().map(|&(x, _)| jacobian(x)),
+    );
+
+    assert_eq!(report.termination, TerminationReason::BelowThreshold);
+    assert!(report.iterations < Config::<f64>::default().max_iterations);
+    assert!(report.sum_of_squares < 1e-12 * samples.len() as f64);
+    assert!((report.model - Vector3::new(2.0, -3.0, 1.0)).norm() < 1e-4);
+}
+
+#[test]
+fn reports_divergence() {
+    let samples = parabola_samples();
+    // The Jacobian has the wrong sign, so every step makes the fit worse.
+    let report = optimize_report(
+        Config::default(),
+        Vector3::zeros(),
+        |model, delta| model + delta,
+        |model| residuals(&samples, model),
+        |_| samples.iter().map(|&(x, _)| -jacobian(x)),
+    );
+
+    assert_eq!(report.termination, TerminationReason::ConsecutiveDivergence);
+    assert_eq!(
+        report.iterations,
+        Config::<f64>::default().consecutive_divergence_limit
+    );
+    assert_eq!(report.model, Vector3::zeros());
+}
+
+#[test]
+fn returns_best_model_seen() {
+    use std::cell::{Cell, RefCell};
+
+    let samples = parabola_samples();
+    let calls = Cell::new(0usize);
+    let evaluated = RefCell::new(Vec::new());
+    let report = optimize_report(
+        Config::default(),
+        Vector3::zeros(),
+        |model, delta| model + delta,
+        |model| {
+            // After a few evaluations every model gets progressively worse, so the last
+            // iterations are all worse than an earlier one.
+            calls.set(calls.get() + 1);
+            let penalty = calls.get().saturating_sub(5) as f64;
+            let res = residuals(&samples, model).add_scalar(penalty);
+            evaluated.borrow_mut().push((*model, res.norm_squared()));
+            res
+        },
+        |_| samples.iter().map(|&(x, _)| jacobian(x)),
+    );
+
+    let evaluated = evaluated.into_inner();
+    let &(best_model, best_sum) = evaluated
+        .iter()
+        .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+        .unwrap();
+    assert!(calls.get() > 5);
+    assert_eq!(report.sum_of_squares, best_sum);
+    assert_eq!(report.model, best_model);
+}
+
+#[test]
+fn stats_are_the_tracked_best() {
+    use std::cell::{Cell, RefCell};
+
+    let samples = parabola_samples();
+    let calls = Cell::new(0usize);
+    let evaluated = RefCell::new(Vec::new());
+    // Every evaluation is worse than the one before it after the fifth, so evaluating the
+    // residuals of the returned model again would give a larger sum than the best one seen.
+    let worsening = |model: &Vector3<f64>| {
+        calls.set(calls.get() + 1);
+        let penalty = calls.get().saturating_sub(5) as f64;
+        let res = residuals(&samples, model).add_scalar(penalty);
+        evaluated.borrow_mut().push(res.norm_squared());
+        res
+    };
+    let (model, iterations, sum_of_squares) = optimize_with_stats(
+        Config::default(),
+        Vector3::zeros(),
+        |model, delta| model + delta,
+        worsening,
+        |_| samples.iter().map(|&(x, _)| jacobian(x)),
+    );
+
+    let best_sum = evaluated
+        .take()
+        .into_iter()
+        .min_by(|a, b| a.partial_cmp(b).unwrap())
+        .unwrap();
+    assert_eq!(sum_of_squares, best_sum);
+    assert!(worsening(&model).norm_squared() > sum_of_squares);
+
+    calls.set(0);
+    let report = optimize_report(
+        Config::default(),
+        Vector3::zeros(),
+        |model, delta| model + delta,
+        worsening,
+        |_| samples.iter().map(|&(x, _)| jacobian(x)),
+    );
+    assert_eq!(iterations, report.iterations);
+    assert_eq!(model, report.model);
+    assert_eq!(sum_of_squares, report.sum_of_squares);
+}
+
+#[test]
+fn stops_on_small_gradient() {
+    let mut samples = parabola_samples();
+    // Move one sample off of the parabola so the residuals can never reach zero.
+    samples[3].1 += 1.0;
+    let report = optimize_report(
+        Config {
+            gradient_threshold: 1e-6,
+            ..Config::default()
+        },
+        Vector3::zeros(),
+        |model, delta| model + delta,
+        |model| residuals(&samples, model),
+        |_| samples.iter().map(|&(x, _)| jacobian(x)),
+    );
+
+    assert_eq!(report.termination, TerminationReason::GradientTooSmall);
+    assert!(report.iterations < Config::<f64>::default().max_iterations);
+}
+
+#[test]
+fn stops_on_small_relative_reduction() {
+    let mut samples = parabola_samples();
+    samples[3].1 += 1.0;
+    let report = optimize_report(
+        Config {
+            ftol: 1e-8,
+            ..Config::default()
+        },
+        Vector3::zeros(),
+        |model, delta| model + delta,
+        |model| residuals(&samples, model),
+        |_| samples.iter().map(|&(x, _)| jacobian(x)),
+    );
+
+    assert_eq!(report.termination, TerminationReason::ReductionTooSmall);
+    assert!(report.iterations < Config::<f64>::default().max_iterations);
+}
+
+#[test]
+fn stops_when_reduction_stalls() {
+    let samples = parabola_samples();
+    // Lambda is so large and decreases so slowly that every step barely helps.
+    let config = Config {
+        initial_lambda: InitialLambda::Fixed(1e9),
+        lambda_convege: 0.999_999,
+        max_iterations: 100,
+        ..Config::default()
+    };
+    let run = |config| {
+        optimize_report(
+            config,
+            Vector3::zeros(),
+            |model, delta| model + delta,
+            |model| residuals(&samples, model),
+            |_| samples.iter().map(|&(x, _)| jacobian(x)),
+        )
+    };
+    let report = run(Config {
+        stall_iterations: 10,
+        relative_stall_tol: 1e-6,
+        ..config
+    });
+    assert_eq!(report.termination, TerminationReason::Stalled);
+    assert_eq!(report.iterations, 10);
+    assert_eq!(run(config).termination, TerminationReason::MaxIterations);
+}
+
+#[test]
+fn callback_sees_rejected_iterations() {
+    let samples = parabola_samples();
+    let mut seen = Vec::new();
+    let report = optimize_with_callback(
+        Config::default(),
+        Vector3::zeros(),
+        |model, delta| model + delta,
+        |model| residuals(&samples, model),
+        |_| samples.iter().map(|&(x, _)| -jacobian(x)),
+        |iteration, _, _| {
+            seen.push(iteration);
+            ControlFlow::Continue(())
+        },
+    );
+
+    assert_eq!(report.termination, TerminationReason::ConsecutiveDivergence);
+    assert_eq!(seen, (0..report.iterations).collect::<Vec<_>>());
+}
+
+#[test]
+fn history_records_best_sum_after_each_iteration() {
+    let samples = parabola_samples();
+    let problem = ClosureProblem::new(
+        |model: &Vector3<f64>, delta| model + delta,
+        |model: &Vector3<f64>| residuals(&samples, model),
+        |_: &Vector3<f64>| samples.iter().map(|&(x, _)| jacobian(x)),
+    );
+    let config = Config {
+        threshold: 1e-12,
+        ..Config::default()
+    };
+    let mut history = vec![f64::NAN; config.max_iterations];
+    let report = LevenbergMarquardt::new(config, Vector3::zeros(), &problem)
+        .unwrap()
+        .run_with_history(&problem, &mut history);
+
+    let (recorded, rest) = history.split_at(report.iterations);
+    assert!(recorded.windows(2).all(|pair| pair[1] <= pair[0]));
+    assert_eq!(*recorded.last().unwrap(), report.sum_of_squares);
+    assert!(rest.iter().all(|sum| sum.is_nan()));
+
+    // A buffer which is too short only records the first iterations.
+    let mut short = [f64::NAN; 2];
+    let again = LevenbergMarquardt::new(config, Vector3::zeros(), &problem)
+        .unwrap()
+        .run_with_history(&problem, &mut short);
+    assert_eq!(again, report);
+    assert_eq!(short, recorded[..2]);
+
+    let mut closures = vec![f64::NAN; config.max_iterations];
+    let from_closures = optimize_with_history(
+        config,
+        Vector3::zeros(),
+        |model, delta| model + delta,
+        |model| residuals(&samples, model),
+        |_| samples.iter().map(|&(x, _)| jacobian(x)),
+        &mut closures,
+    );
+    assert_eq!(from_closures, report);
+    assert_eq!(closures[..report.iterations], *recorded);
+}
+
+#[test]
+fn records_lambda_trajectory() {
+    let samples = parabola_samples();
+    let problem = ClosureProblem::new(
+        |model: &Vector3<f64>, delta| model + delta,
+        |model: &Vector3<f64>| residuals(&samples, model),
+        |_: &Vector3<f64>| samples.iter().map(|&(x, _)| jacobian(x)),
+    );
+    let config = Config {
+        threshold: 1e-12,
+        ..Config::default()
+    };
+    let mut lambdas = vec![f64::NAN; config.max_iterations];
+    let report = LevenbergMarquardt::new(config, Vector3::zeros(), &problem)
+        .unwrap()
+        .run_with_lambda_history(&problem, &mut lambdas);
+
+    let (recorded, rest) = lambdas.split_at(report.iterations);
+    assert_eq!(recorded[0], 50.0);
+    // Each iteration either keeps lambda, accepts the smaller candidate, or diverges.
+    for pair in recorded.windows(2) {
+        let ratio = pair[1] / pair[0];
+        assert!([1.0, 0.8, 2.0]
+            .iter()
+            .any(|&factor| (ratio - factor).abs() < 1e-12));
+    }
+    assert!(rest.iter().all(|lambda| lambda.is_nan()));
+}
+
+#[test]
+fn recorder_keeps_the_last_iterations() {
+    let samples = parabola_samples();
+    let problem = ClosureProblem::new(
+        |model: &Vector3<f64>, delta| model + delta,
+        |model: &Vector3<f64>| residuals(&samples, model),
+        |_: &Vector3<f64>| samples.iter().map(|&(x, _)| jacobian(x)),
+    );
+    let config = Config {
+        threshold: 1e-12,
+        ..Config::default()
+    };
+    let mut history = vec![f64::NAN; config.max_iterations];
+    let report = LevenbergMarquardt::new(config, Vector3::zeros(), &problem)
+        .unwrap()
+        .run_with_history(&problem, &mut history);
+    let mut lambdas = vec![f64::NAN; config.max_iterations];
+    LevenbergMarquardt::new(config, Vector3::zeros(), &problem)
+        .unwrap()
+        .run_with_lambda_history(&problem, &mut lambdas);
+    assert!(report.iterations > 3);
+
+    let mut recorder = HistoryRecorder::<f64, 3>::new();
+    let recorded = optimize_recorded(
+        config,
+        Vector3::zeros(),
+        |model, delta| model + delta,
+        |model| residuals(&samples, model),
+        |_| samples.iter().map(|&(x, _)| jacobian(x)),
+        &mut recorder,
+    );
+    assert_eq!(recorded, report);
+    assert_eq!(recorder.recorded(), report.iterations);
+    assert_eq!(recorder.len(), 3);
+    let expected: Vec<_> = (report.iterations - 3..report.iterations)
+        .map(|i| (i, history[i], lambdas[i]))
+        .collect();
+    assert!(recorder.iter().eq(expected));
+
+    // The recorder is cleared before every run.
+    LevenbergMarquardt::new(
+        Config {
+            max_iterations: 2,
+            ..config
+        },
+        Vector3::zeros(),
+        &problem,
+    )
+    .unwrap()
+    .run_recorded(&problem, &mut recorder);
+    assert_eq!(recorder.recorded(), 2);
+    assert!(recorder.iter().eq(vec![
+        (0, history[0], lambdas[0]),
+        (1, history[1], lambdas[1])
+    ]));
+}
+
+#[test]
+fn callback_aborts() {
+    let samples = parabola_samples();
+    let report = optimize_with_callback(
+        Config::default(),
+        Vector3::zeros(),
+        |model, delta| model + delta,
+        |model| residuals(&samples, model),
+        |_| samples.iter().map(|&(x, _)| jacobian(x)),
+        |iteration, _, _| {
+            if iteration == 2 {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        },
+    );
+
+    assert_eq!(report.termination, TerminationReason::Aborted);
+    assert_eq!(report.iterations, 3);
+}
+
+#[test]
+fn counts_evaluations() {
+    let samples = parabola_samples();
+    let residual_calls = Cell::new(0);
+    let jacobian_calls = Cell::new(0);
+    for damping_strategy in [DampingStrategy::Multiplicative, DampingStrategy::Nielsen] {
+        residual_calls.set(0);
+        jacobian_calls.set(0);
+        let report = optimize_report(
+            Config {
+                threshold: 1e-12,
+                damping_strategy,
+                ..Config::default()
+            },
+            Vector3::zeros(),
+            |model, delta| model + delta,
+            |model| {
+                residual_calls.set(residual_calls.get() + 1);
+                residuals(&samples, model)
+            },
+            |_| {
+                jacobian_calls.set(jacobian_calls.get() + 1);
+                samples.iter().map(|&(x, _)| jacobian(x))
+            },
+        );
+
+        assert_eq!(report.residual_evaluations, residual_calls.get());
+        assert_eq!(report.jacobian_evaluations, jacobian_calls.get());
+        assert!(report.jacobian_evaluations <= report.iterations + 1);
+    }
+}
+
+#[test]
+fn cancelled_before_starting_returns_init() {
+    let samples = parabola_samples();
+    let problem = ClosureProblem::new(
+        |model: &Vector3<f64>, delta| model + delta,
+        |model: &Vector3<f64>| residuals(&samples, model),
+        |_: &Vector3<f64>| samples.iter().map(|&(x, _)| jacobian(x)),
+    );
+    let init = Vector3::new(1.0, 1.0, 1.0);
+    let report = LevenbergMarquardt::new(Config::default(), init, &problem)
+        .unwrap()
+        .run_cancellable(&problem, &AtomicBool::new(true));
+
+    assert_eq!(report.termination, TerminationReason::Cancelled);
+    assert_eq!(report.iterations, 0);
+    assert_eq!(report.model, init);
+}
+
+#[test]
+fn cancellation_stops_at_next_iteration() {
+    let samples = parabola_samples();
+    let stop = AtomicBool::new(false);
+    let evaluations = Cell::new(0);
+    let problem = ClosureProblem::new(
+        |model: &Vector3<f64>, delta| model + delta,
+        |model: &Vector3<f64>| {
+            // Cancel after the initial evaluation and the first step that is tested.
+            evaluations.set(evaluations.get() + 1);
+            if evaluations.get() == 2 {
+                stop.store(true, Ordering::Relaxed);
+            }
+            residuals(&samples, model)
+        },
+        |_: &Vector3<f64>| samples.iter().map(|&(x, _)| jacobian(x)),
+    );
+    let config = Config {
+        threshold: 1e-12,
+        ..Config::default()
+    };
+    let report = LevenbergMarquardt::new(config, Vector3::zeros(), &problem)
+        .unwrap()
+        .run_cancellable(&problem, &stop);
+
+    assert_eq!(report.termination, TerminationReason::Cancelled);
+    assert_eq!(report.iterations, 1);
+    assert!(report.sum_of_squares < residuals(&samples, &Vector3::zeros()).norm_squared());
+}
+
+#[test]
+fn deadline_stops_before_next_iteration() {
+    let samples = parabola_samples();
+    let init = Vector3::zeros();
+    let problem = ClosureProblem::new(
+        |model: &Vector3<f64>, delta| model + delta,
+        |model: &Vector3<f64>| residuals(&samples, model),
+        |_: &Vector3<f64>| samples.iter().map(|&(x, _)| jacobian(x)),
+    );
+    let checks = Cell::new(0);
+    let fit = |iterations| {
+        checks.set(0);
+        let config = Config {
+            threshold: 1e-12,
+            ..Config::default()
+        };
+        LevenbergMarquardt::new(config, init, &problem)
+            .unwrap()
+            .run_with_deadline(&problem, || {
+                checks.set(checks.get() + 1);
+                checks.get() > iterations
+            })
+    };
+
+    let expired = fit(0);
+    assert_eq!(expired.termination, TerminationReason::Deadline);
+    assert_eq!(expired.iterations, 0);
+    assert_eq!(expired.model, init);
+
+    let report = fit(2);
+    assert_eq!(report.termination, TerminationReason::Deadline);
+    assert_eq!(report.iterations, 2);
+    assert_eq!(checks.get(), 3);
+    assert!(report.sum_of_squares < residuals(&samples, &init).norm_squared());
+}
+
+#[test]
+fn stops_mid_iteration_when_budget_is_spent() {
+    let samples = parabola_samples();
+    let evaluations = Cell::new(0);
+    let fit = |max_function_evaluations| {
+        evaluations.set(0);
+        optimize_report(
+            Config {
+                max_function_evaluations,
+                ..Config::default()
+            },
+            Vector3::zeros(),
+            |model, delta| model + delta,
+            |model| {
+                evaluations.set(evaluations.get() + 1);
+                residuals(&samples, model)
+            },
+            |_| samples.iter().map(|&(x, _)| jacobian(x)),
+        )
+    };
+
+    // Two lambdas are tested per iteration, so the budget runs out between the two candidates
+    // of the second iteration.
+    let report = fit(4);
+    assert_eq!(report.termination, TerminationReason::BudgetExhausted);
+    assert_eq!(report.iterations, 2);
+    assert_eq!(report.residual_evaluations, 4);
+    assert_eq!(evaluations.get(), 4);
+
+    // The initial guess uses up the whole budget, so no iterations run.
+    let report = fit(1);
+    assert_eq!(report.termination, TerminationReason::BudgetExhausted);
+    assert_eq!(report.iterations, 0);
+    assert_eq!(evaluations.get(), 1);
+}
+
+#[test]
+fn warm_started_lambda_tracks_drifting_data() {
+    // Track `y = 2x² - 3x + c` as `c` drifts each frame, either resetting lambda every frame or
+    // carrying it over from the previous one.
+    let track = |warm_start: bool| {
+        let mut model = Vector3::new(2.0, -3.0, 1.0);
+        let mut lambda = None;
+        let mut iterations = 0;
+        for frame in 1..=10 {
+            let offset = 1.0 + 0.1 * f64::from(frame);
+            let samples: Vec<(f64, f64)> = parabola_samples()
+                .into_iter()
+                .map(|(x, y)| (x, y - 1.0 + offset))
+                .collect();
+            let report = optimize_report(
+                Config {
+                    threshold: 1e-12,
+                    initial_lambda_from: lambda,
+                    ..Config::default()
+                },
+                model,
+                |model, delta| model + delta,
+                |model| residuals(&samples, model),
+                |_| samples.iter().map(|&(x, _)| jacobian(x)),
+            );
+            assert_eq!(report.termination, TerminationReason::BelowThreshold);
+            assert!((report.model - Vector3::new(2.0, -3.0, offset)).norm() < 1e-4);
+            model = report.model;
+            iterations += report.iterations;
+            if warm_start {
+                lambda = report.lambda;
+            }
+        }
+        iterations
+    };
+
+    assert!(track(true) < track(false));
+}
+
+#[test]
+fn warm_started_lambda_is_clamped() {
+    let samples = parabola_samples();
+    let config = Config {
+        max_iterations: 1,
+        initial_lambda_from: Some(1e30),
+        max_lambda: 1e3,
+        ..Config::default()
+    };
+    let clamped = optimize_report(
+        config,
+        Vector3::zeros(),
+        |model, delta| model + delta,
+        |model| residuals(&samples, model),
+        |_| samples.iter().map(|&(x, _)| jacobian(x)),
+    );
+    let at_max = optimize_report(
+        Config {
+            initial_lambda_from: Some(1e3),
+            ..config
+        },
+        Vector3::zeros(),
+        |model, delta| model + delta,
+        |model| residuals(&samples, model),
+        |_| samples.iter().map(|&(x, _)| jacobian(x)),
+    );
+    assert_eq!(clamped, at_max);
+}
+
+#[test]
+fn stops_when_lambda_saturates() {
+    // The Jacobians have the wrong sign, so every step goes uphill no matter the damping.
+    let samples = parabola_samples();
+    let report = optimize_report(
+        Config {
+            consecutive_divergence_limit: 100,
+            max_lambda: 1e3,
+            ..Config::default()
+        },
+        Vector3::zeros(),
+        |model, delta| model + delta,
+        |model| residuals(&samples, model),
+        |_| samples.iter().map(|&(x, _)| -jacobian(x)),
+    );
+
+    assert_eq!(report.termination, TerminationReason::LambdaSaturated);
+    assert_eq!(report.lambda, Some(1e3));
+    assert_eq!(report.model, Vector3::zeros());
+    assert!(report.iterations < 100);
+}
+
+#[test]
+fn exact_initial_guess_is_already_optimal() {
+    let samples = parabola_samples();
+    let init = Vector3::new(2.0, -3.0, 1.0);
+    let report = optimize_report(
+        Config::default(),
+        init,
+        |model, delta| model + delta,
+        |model| residuals(&samples, model),
+        |_| samples.iter().map(|&(x, _)| jacobian(x)),
+    );
+
+    assert_eq!(report.termination, TerminationReason::AlreadyOptimal);
+    assert_eq!(report.iterations, 0);
+    assert_eq!(report.residual_evaluations, 1);
+    assert_eq!(report.model, init);
+}
+
+#[test]
+fn threshold_kind_chooses_what_is_compared() {
+    let samples = parabola_samples();
+    // Every residual is -0.1, so the mean-of-squares is 0.01, the root-mean-square is 0.1 and
+    // the sum-of-squares is 0.21.
+    let already_optimal = |threshold, threshold_kind| {
+        optimize_report(
+            Config {
+                threshold,
+                threshold_kind,
+                ..Config::default()
+            },
+            Vector3::new(2.0, -3.0, 1.1),
+            |model, delta| model + delta,
+            |model| residuals(&samples, model),
+            |_| samples.iter().map(|&(x, _)| jacobian(x)),
+        )
+        .termination
+            == TerminationReason::AlreadyOptimal
+    };
+
+    assert!(already_optimal(0.05, ThresholdKind::MeanSquared));
+    assert!(!already_optimal(0.05, ThresholdKind::Rmse));
+    assert!(already_optimal(0.15, ThresholdKind::Rmse));
+    assert!(!already_optimal(0.15, ThresholdKind::TotalSquared));
+    assert!(already_optimal(0.25, ThresholdKind::TotalSquared));
+}
+
+#[test]
+fn final_gradient_is_at_returned_model() {
+    let samples = parabola_samples();
+    let report = optimize_report(
+        Config {
+            max_iterations: 3,
+            ..Config::default()
+        },
+        Vector3::zeros(),
+        |model, delta| model + delta,
+        |model| residuals(&samples, model),
+        |_| samples.iter().map(|&(x, _)| jacobian(x)),
+    );
+
+    let res = residuals(&samples, &report.model);
+    let gradient = samples
+        .iter()
+        .zip(res.iter())
+        .fold(Vector3::zeros(), |gradient, (&(x, _), &r)| {
+            gradient + jacobian(x) * r
+        });
+    assert_eq!(report.termination, TerminationReason::MaxIterations);
+    assert!(gradient.amax() > 1e-3);
+    assert!((report.final_gradient - gradient).amax() < 1e-9 * gradient.amax());
+}
+
+#[test]
+fn restarts_before_giving_up_on_divergence() {
+    let samples = parabola_samples();
+    let config = Config {
+        initial_lambda: InitialLambda::Fixed(50.0),
+        restart_limit: 2,
+        ..Config::default()
+    };
+    // The Jacobian has the wrong sign, so every step diverges and each restart gives up again.
+    let report = optimize_report(
+        config,
+        Vector3::zeros(),
+        |model, delta| model + delta,
+        |model| residuals(&samples, model),
+        |_| samples.iter().map(|&(x, _)| -jacobian(x)),
+    );
+
+    assert_eq!(report.termination, TerminationReason::ConsecutiveDivergence);
+    assert_eq!(report.restarts, 2);
+    assert_eq!(report.iterations, 3 * config.consecutive_divergence_limit);
+    // Lambda only increased by the divergences since the last restart.
+    let lambda = 50.0 * config.lambda_diverge.powi(5);
+    assert!((report.lambda.unwrap() / lambda - 1.0).abs() < 1e-12);
+}
+
+#[test]
+fn counts_accepted_and_rejected_steps() {
+    let samples = parabola_samples();
+    let report = optimize_report(
+        Config::default(),
+        Vector3::zeros(),
+        |model, delta| model + delta,
+        |model| residuals(&samples, model),
+        |_| samples.iter().map(|&(x, _)| jacobian(x)),
+    );
+    assert!(report.accepted_steps > 0);
+    assert_eq!(
+        report.accepted_steps + report.rejected_steps,
+        report.iterations
+    );
+
+    // The Jacobian has the wrong sign, so every step is rejected.
+    let report = optimize_report(
+        Config::default(),
+        Vector3::zeros(),
+        |model, delta| model + delta,
+        |model| residuals(&samples, model),
+        |_| samples.iter().map(|&(x, _)| -jacobian(x)),
+    );
+    assert_eq!(report.accepted_steps, 0);
+    assert_eq!(report.rejected_steps, report.iterations);
+}
+
+#[test]
+fn callback_passes_iteration_index_to_apply_delta() {
+    let samples = parabola_samples();
+    let iteration = Cell::new(0);
+    let indices = std::cell::RefCell::new(Vec::new());
+    let report = optimize_with_callback(
+        Config {
+            threshold: 1e-12,
+            ..Config::default()
+        },
+        Vector3::zeros(),
+        |model, delta| {
+            indices.borrow_mut().push(iteration.get());
+            // Only take half of each step while the fit is still coarse.
+            if iteration.get() < 3 {
+                model + delta * 0.5
+            } else {
+                model + delta
+            }
+        },
+        |model| residuals(&samples, model),
+        |_| samples.iter().map(|&(x, _)| jacobian(x)),
+        |index, _, _| {
+            iteration.set(index + 1);
+            ControlFlow::Continue(())
+        },
+    );
+
+    assert_eq!(report.termination, TerminationReason::BelowThreshold);
+    assert!((report.model - Vector3::new(2.0, -3.0, 1.0)).norm() < 1e-4);
+    let indices = indices.into_inner();
+    assert_eq!(indices.first(), Some(&0));
+    assert_eq!(indices.last(), Some(&(report.iterations - 1)));
+    assert!(indices.windows(2).all(|pair| pair[0] <= pair[1]));
+}
+
+#[test]
+fn flags_whether_fit_was_still_improving() {
+    // Noise keeps the fit from becoming exact, so it converges by `ftol`.
+    let samples: Vec<(f64, f64)> = parabola_samples()
+        .into_iter()
+        .enumerate()
+        .map(|(i, (x, y))| (x, if i % 2 == 0 { y + 0.1 } else { y - 0.1 }))
+        .collect();
+    let fit = |config| {
+        optimize_report(
+            config,
+            Vector3::zeros(),
+            |model, delta| model + delta,
+            |model| residuals(&samples, model),
+            |_| samples.iter().map(|&(x, _)| jacobian(x)),
+        )
+    };
+    let config = Config {
+        ftol: 1e-6,
+        ..Config::default()
+    };
+
+    // The damping keeps the first steps short, so the fit is cut off mid-improvement.
+    let cut_off = fit(Config {
+        max_iterations: 3,
+        ..config
+    });
+    assert_eq!(cut_off.termination, TerminationReason::MaxIterations);
+    assert!(cut_off.still_improving);
+
+    let converged = fit(config);
+    assert_eq!(converged.termination, TerminationReason::ReductionTooSmall);
+    assert!(!converged.still_improving);
+}
@@ -0,0 +1,247 @@
+use levenberg_marquardt::{
+    optimize_problem, Cauchy, ClosureProblem, Config, Huber, LossFunction, MinimizationReport,
+    RobustProblem, SoftL1, Squared, Tukey,
+};
+use nalgebra::{dimension::U2, Vector2};
+use std::cell::Cell;
+
+mod common;
+
+use common::line::{jacobian, problem, residuals, samples};
+
+/// Samples of the line with a small amount of noise where every sample in `outliers` is offset
+/// by a gross error.
+fn noisy_samples(outliers: impl Fn(usize) -> bool) -> Vec<(f64, f64)> {
+    samples()
+        .into_iter()
+        .enumerate()
+        .map(|(i, (x, y))| {
+            let noise = if i % 2 == 0 { 0.05 } else { -0.05 };
+            let outlier = if outliers(i) { 100.0 } else { 0.0 };
+            (x, y + noise + outlier)
+        })
+        .collect()
+}
+
+/// Samples of the line where every fourth sample is offset by a moderate error, and the noise of
+/// the inliers puts many of their residuals near a `delta` of `0.5`.
+fn moderate_samples() -> Vec<(f64, f64)> {
+    samples()
+        .into_iter()
+        .enumerate()
+        .map(|(i, (x, y))| {
+            let noise = 0.3 * (i as f64 * 1.7).sin();
+            let outlier = if i % 4 == 1 { 3.0 } else { 0.0 };
+            (x, y + noise + outlier)
+        })
+        .collect()
+}
+
+fn fit_with_config(
+    config: Config<f64>,
+    samples: &[(f64, f64)],
+    loss: impl LossFunction<f64>,
+    init: Vector2<f64>,
+) -> Vector2<f64> {
+    optimize_problem(config, init, &RobustProblem::new(problem(samples), loss)).model
+}
+
+fn fit(samples: &[(f64, f64)], loss: impl LossFunction<f64>, init: Vector2<f64>) -> Vector2<f64> {
+    fit_with_config(Config::default(), samples, loss, init)
+}
+
+#[test]
+fn squared_is_least_squares() {
+    let samples = noisy_samples(|i| i == 7);
+    let least_squares =
+        optimize_problem(Config::default(), Vector2::zeros(), &problem(&samples)).model;
+    assert_eq!(fit(&samples, Squared, Vector2::zeros()), least_squares);
+    // The outlier skews the fit.
+    assert!((least_squares.x - 3.0).abs() > 1.0);
+}
+
+#[test]
+fn huber_ignores_outlier() {
+    let samples = noisy_samples(|i| i == 7);
+    let loss = Huber { delta: 0.5 };
+    assert!((fit(&samples, loss, Vector2::zeros()).x - 3.0).abs() < 0.05);
+}
+
+#[test]
+fn huber_reaches_m_estimate() {
+    let samples = moderate_samples();
+    let loss = Huber { delta: 0.5 };
+    let config = Config {
+        gradient_threshold: 1e-10,
+        ..Config::default()
+    };
+    let model = fit_with_config(config, &samples, loss, Vector2::zeros());
+    // The gradient of the total Huber cost vanishes at its minimum.
+    let gradient: Vector2<f64> = residuals(&samples, &model)
+        .iter()
+        .zip(&samples)
+        .map(|(&r, &(x, _))| jacobian(x) * (-2.0 * loss.weight(r * r) * r))
+        .sum();
+    assert!(gradient.norm() < 1e-6);
+}
+
+#[test]
+fn cauchy_recovers_model_from_many_outliers() {
+    // Every third sample is an outlier.
+    let samples = noisy_samples(|i| i % 3 == 1);
+    let loss = Cauchy { scale: 0.1 };
+    let model = fit(&samples, loss, Vector2::new(2.5, 0.5));
+    assert!((model - Vector2::new(3.0, 1.0)).norm() < 0.05);
+}
+
+#[test]
+fn tukey_rejects_outliers_entirely() {
+    let samples = noisy_samples(|i| i == 2 || i == 6);
+    let inliers: Vec<(f64, f64)> = samples
+        .iter()
+        .copied()
+        .filter(|&(x, y)| (y - (3.0 * x + 1.0)).abs() < 1.0)
+        .collect();
+    let loss = Tukey { c: 0.5 };
+    let init = Vector2::new(2.9, 1.1);
+    let with_outliers = fit(&samples, loss, init);
+    let without_outliers = fit(&inliers, loss, init);
+    // The outliers only add a constant to the cost, which still rounds the sum-of-squares that
+    // decides when the fits stop improving.
+    assert!((with_outliers - without_outliers).norm() < 1e-8);
+    assert!((with_outliers - Vector2::new(3.0, 1.0)).norm() < 0.05);
+}
+
+/// The Geman-McClure loss, which isn't provided by the crate.
+struct GemanMcClure {
+    scale: f64,
+}
+
+impl LossFunction<f64> for GemanMcClure {
+    fn weight(&self, squared_residual: f64) -> f64 {
+        let squared_scale = self.scale * self.scale;
+        (squared_scale / (squared_scale + squared_residual)).powi(2)
+    }
+
+    fn cost(&self, squared_residual: f64) -> f64 {
+        let squared_scale = self.scale * self.scale;
+        squared_scale * squared_residual / (squared_scale + squared_residual)
+    }
+}
+
+#[test]
+fn custom_loss() {
+    let samples = noisy_samples(|i| i % 3 == 1);
+    let loss = GemanMcClure { scale: 0.5 };
+    let model = fit(&samples, loss, Vector2::new(2.5, 0.5));
+    assert!((model - Vector2::new(3.0, 1.0)).norm() < 0.05);
+}
+
+#[test]
+fn residuals_computed_once_per_evaluation() {
+    let samples = noisy_samples(|i| i == 7);
+    let calls = Cell::new(0);
+    let problem = ClosureProblem::new(
+        |model: &Vector2<f64>, delta| model + delta,
+        |model: &Vector2<f64>| {
+            calls.set(calls.get() + 1);
+            residuals(&samples, model)
+        },
+        |_: &Vector2<f64>| samples.iter().map(|&(x, _)| jacobian(x)),
+    );
+    let loss = Huber { delta: 0.5 };
+    let report = optimize_problem(
+        Config::default(),
+        Vector2::zeros(),
+        &RobustProblem::new(problem, loss),
+    );
+    // The weights reuse the residuals that were computed to evaluate each guess.
+    assert_eq!(calls.get(), report.residual_evaluations);
+}
+
+/// The total cost of the samples under `loss`.
+fn total_cost(samples: &[(f64, f64)], loss: &impl LossFunction<f64>, model: &Vector2<f64>) -> f64 {
+    residuals(samples, model)
+        .iter()
+        .map(|&r| loss.cost(r * r))
+        .sum()
+}
+
+/// Finds the minimum of a convex cost by searching ever finer grids around the best point.
+fn brute_force_minimum(cost: impl Fn(&Vector2<f64>) -> f64) -> Vector2<f64> {
+    let mut center = Vector2::new(3.0, 1.0);
+    let mut radius = 1.0;
+    while radius > 1e-10 {
+        let mut best = center;
+        for i in -10..=10 {
+            for j in -10..=10 {
+                let candidate = center + Vector2::new(f64::from(i), f64::from(j)) * radius / 10.0;
+                if cost(&candidate) < cost(&best) {
+                    best = candidate;
+                }
+            }
+        }
+        center = best;
+        radius /= 2.0;
+    }
+    center
+}
+
+/// The largest change between neighbouring estimates of the curvature of the total cost as the
+/// intercept of `model` is swept over `[-0.5, 0.5]` around it.
+fn largest_curvature_jump(
+    samples: &[(f64, f64)],
+    loss: &impl LossFunction<f64>,
+    model: &Vector2<f64>,
+) -> f64 {
+    let step = 1e-3;
+    let cost = |offset: f64| total_cost(samples, loss, &(model + Vector2::new(0.0, offset)));
+    let curvatures: Vec<f64> = (-500..=500)
+        .map(|i| {
+            let offset = f64::from(i) * step;
+            (cost(offset - step) - 2.0 * cost(offset) + cost(offset + step)) / (step * step)
+        })
+        .collect();
+    curvatures
+        .windows(2)
+        .map(|pair| (pair[1] - pair[0]).abs())
+        .fold(0.0, f64::max)
+}
+
+#[test]
+fn soft_l1_is_smooth_where_huber_has_kinks() {
+    fn fit_report(
+        samples: &[(f64, f64)],
+        loss: impl LossFunction<f64>,
+    ) -> MinimizationReport<Vector2<f64>, f64, U2> {
+        let config = Config {
+            gradient_threshold: 1e-10,
+            ..Config::default()
+        };
+        optimize_problem(
+            config,
+            Vector2::zeros(),
+            &RobustProblem::new(problem(samples), loss),
+        )
+    }
+    let samples = moderate_samples();
+    let huber = Huber { delta: 0.5 };
+    let soft_l1 = SoftL1 { delta: 0.5 };
+
+    // Each fit is the minimum of the cost of its own loss.
+    let huber_fit = fit_report(&samples, huber);
+    let huber_minimum = brute_force_minimum(|model| total_cost(&samples, &huber, model));
+    assert!((huber_fit.model - huber_minimum).norm() < 1e-6);
+    assert!(huber_fit.sum_of_squares <= total_cost(&samples, &huber, &huber_minimum) + 1e-9);
+    let soft_l1_fit = fit_report(&samples, soft_l1);
+    let soft_l1_minimum = brute_force_minimum(|model| total_cost(&samples, &soft_l1, model));
+    assert!((soft_l1_fit.model - soft_l1_minimum).norm() < 1e-6);
+    assert!(soft_l1_fit.sum_of_squares <= total_cost(&samples, &soft_l1, &soft_l1_minimum) + 1e-9);
+
+    // The curvature of the Huber cost jumps by `2` whenever a residual crosses `delta`, while
+    // that of the soft L1 cost changes gradually.
+    let huber_jump = largest_curvature_jump(&samples, &huber, &huber_minimum);
+    let soft_l1_jump = largest_curvature_jump(&samples, &soft_l1, &soft_l1_minimum);
+    assert!(huber_jump > 1.0);
+    assert!(soft_l1_jump < 0.1);
+}
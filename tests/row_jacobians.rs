@@ -0,0 +1,58 @@
+use levenberg_marquardt::{optimize_report, optimize_row_jacobians, Config};
+use nalgebra::{Dynamic, Matrix, Matrix2x3, Matrix3x2, VecStorage, Vector3, U2};
+
+type Residuals = Matrix<f64, U2, Dynamic, VecStorage<f64, U2, Dynamic>>;
+
+/// Samples of the point `(3x + 1, -2x)` at `x`.
+fn samples() -> Vec<(f64, [f64; 2])> {
+    (0..8)
+        .map(|i| {
+            let x = f64::from(i) * 0.5;
+            (x, [3.0 * x + 1.0, -2.0 * x])
+        })
+        .collect()
+}
+
+/// Fits the point `(ax + b, cx)` as the model `(a, b, c)`, with two residuals per sample.
+fn residuals(samples: &[(f64, [f64; 2])], model: &Vector3<f64>) -> Residuals {
+    Residuals::from_iterator(
+        samples.len(),
+        samples
+            .iter()
+            .flat_map(|&(x, [u, v])| vec![u - (model.x * x + model.y), v - model.z * x]),
+    )
+}
+
+#[test]
+fn matches_transposed_jacobians() {
+    let samples = samples();
+    let config = Config {
+        threshold: 1e-20,
+        ..Config::default()
+    };
+    let rows = optimize_row_jacobians(
+        config,
+        Vector3::zeros(),
+        |model, delta: Vector3<f64>| model + delta,
+        |model| residuals(&samples, model),
+        |_| {
+            samples
+                .iter()
+                .map(|&(x, _)| Matrix2x3::new(x, 1.0, 0.0, 0.0, 0.0, x))
+        },
+    );
+    let columns = optimize_report(
+        config,
+        Vector3::zeros(),
+        |model, delta: Vector3<f64>| model + delta,
+        |model| residuals(&samples, model),
+        |_| {
+            samples
+                .iter()
+                .map(|&(x, _)| Matrix3x2::new(x, 0.0, 1.0, 0.0, 0.0, x))
+        },
+    );
+
+    assert_eq!(rows, columns);
+    assert!((rows.model - Vector3::new(3.0, 1.0, -2.0)).norm() < 1e-9);
+}
@@ -0,0 +1,68 @@
+use levenberg_marquardt::{
+    optimize_problem, ClosureProblem, Config, DampingMode, InitialLambda, ScaledProblem,
+    TerminationReason,
+};
+use nalgebra::Vector2;
+
+mod common;
+
+use common::Residuals;
+
+/// Samples of `y = 1000exp(-2e-9t)`, which has an amplitude and a rate that differ by twelve
+/// orders of magnitude.
+fn samples() -> Vec<(f64, f64)> {
+    (0..50)
+        .map(|t| {
+            let t = f64::from(t) * 2e7;
+            (t, 1e3 * (-2e-9 * t).exp())
+        })
+        .collect()
+}
+
+fn residuals(samples: &[(f64, f64)], model: &Vector2<f64>) -> Residuals {
+    Residuals::from_iterator(
+        samples.len(),
+        samples
+            .iter()
+            .map(|&(t, y)| y - model.x * (-model.y * t).exp()),
+    )
+}
+
+fn jacobians<'a>(
+    samples: &'a [(f64, f64)],
+    model: &Vector2<f64>,
+) -> impl Iterator<Item = Vector2<f64>> + 'a {
+    let model = *model;
+    samples.iter().map(move |&(t, _)| {
+        let exp = (-model.y * t).exp();
+        Vector2::new(exp, -model.x * t * exp)
+    })
+}
+
+#[test]
+fn scaling_fixes_badly_scaled_problem() {
+    let samples = samples();
+    let config = Config {
+        damping_mode: DampingMode::Identity,
+        initial_lambda: InitialLambda::Fixed(1.0),
+        threshold: 1e-16,
+        ..Config::default()
+    };
+    let init = Vector2::new(500.0, 1e-9);
+    let problem = ClosureProblem::new(
+        |model: &Vector2<f64>, delta| model + delta,
+        |model: &Vector2<f64>| residuals(&samples, model),
+        |model: &Vector2<f64>| jacobians(&samples, model),
+    );
+    let unscaled = optimize_problem(config, init, &problem);
+    let scaled = optimize_problem(
+        config,
+        init,
+        &ScaledProblem::new(problem, Vector2::new(1e3, 1e-9)),
+    );
+
+    assert_eq!(scaled.termination, TerminationReason::BelowThreshold);
+    assert!((scaled.model.x - 1e3).abs() < 1e-6);
+    assert!((scaled.model.y - 2e-9).abs() < 1e-18);
+    assert!(scaled.iterations < unscaled.iterations);
+}
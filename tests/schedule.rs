@@ -0,0 +1,64 @@
+use levenberg_marquardt::{
+    optimize_problem, ClosureProblem, Config, InterpolatedSchedule, LambdaSchedule,
+    LeastSquaresProblem, LevenbergMarquardt, TerminationReason,
+};
+use nalgebra::{
+    dimension::{U1, U3},
+    Dynamic, VecStorage, Vector3,
+};
+
+mod common;
+
+use common::exponential::{jacobian, residuals, samples};
+
+fn problem(
+    samples: &[(f64, f64)],
+) -> impl LeastSquaresProblem<
+    f64,
+    U3,
+    Dynamic,
+    U1,
+    Model = Vector3<f64>,
+    ResidualStorage = VecStorage<f64, U1, Dynamic>,
+> + '_ {
+    ClosureProblem::new(
+        |model: &Vector3<f64>, delta| model + delta,
+        move |model: &Vector3<f64>| residuals(samples, model),
+        move |&model: &Vector3<f64>| samples.iter().map(move |&(x, _)| jacobian(&model, x)),
+    )
+}
+
+#[test]
+fn config_schedule_matches_fixed_factors() {
+    let samples = samples();
+    let problem = problem(&samples);
+    let config = Config::default();
+    let scheduled = LevenbergMarquardt::new(config, Vector3::new(1.0, 1.0, 0.0), &problem)
+        .unwrap()
+        .run_with_schedule(&problem, &config);
+    let fixed = optimize_problem(config, Vector3::new(1.0, 1.0, 0.0), &problem);
+    assert_eq!(scheduled, fixed);
+}
+
+#[test]
+fn interpolated_schedule_tightens_as_fit_improves() {
+    let schedule = InterpolatedSchedule::new((0.5f64, 4.0), (0.9, 2.0));
+    assert_eq!(schedule.factors(0, 100.0), (0.5, 4.0));
+    let (converge, diverge) = schedule.factors(1, 50.0);
+    assert!((converge - 0.7).abs() < 1e-12 && (diverge - 3.0).abs() < 1e-12);
+    assert_eq!(schedule.factors(2, 0.0), (0.9, 2.0));
+    // A new optimization starts from the aggressive factors again.
+    assert_eq!(schedule.factors(0, 10.0), (0.5, 4.0));
+
+    let samples = samples();
+    let problem = problem(&samples);
+    let config = Config {
+        threshold: 1e-12,
+        ..Config::default()
+    };
+    let report = LevenbergMarquardt::new(config, Vector3::new(1.0, 1.0, 0.0), &problem)
+        .unwrap()
+        .run_with_schedule(&problem, &schedule);
+    assert_eq!(report.termination, TerminationReason::BelowThreshold);
+    assert!((report.model - Vector3::new(2.0, 0.5, 1.0)).norm() < 1e-4);
+}
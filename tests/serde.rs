@@ -0,0 +1,14 @@
+#![cfg(feature = "serde")]
+
+use levenberg_marquardt::{Config, DampingStrategy};
+
+#[test]
+fn config_round_trips_through_json() {
+    let config = Config::<f64> {
+        max_iterations: 200,
+        damping_strategy: DampingStrategy::Nielsen,
+        ..Config::default()
+    };
+    let json = serde_json::to_string(&config).unwrap();
+    assert_eq!(serde_json::from_str::<Config<f64>>(&json).unwrap(), config);
+}
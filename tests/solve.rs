@@ -0,0 +1,250 @@
+use levenberg_marquardt::{
+    optimize_report, Config, DampingMode, InitialLambda, Method, MinimizationReport, SolveMethod,
+    TerminationReason,
+};
+use nalgebra::{
+    dimension::{U1, U2},
+    DVector, Dynamic, Matrix, VecStorage, Vector1, Vector2,
+};
+
+mod common;
+
+type Residuals = Matrix<f32, U1, Dynamic, VecStorage<f32, U1, Dynamic>>;
+
+/// Samples of a linear model whose Jacobian is the Läuchli matrix. The Jacobian has full rank,
+/// but `JJᵀ` rounds to a singular matrix in `f32`.
+fn lauchli_samples() -> Vec<(Vector2<f32>, f32)> {
+    let epsilon = 1e-4;
+    let truth = Vector2::new(1.0, 2.0);
+    [
+        Vector2::new(1.0, 1.0),
+        Vector2::new(epsilon, 0.0),
+        Vector2::new(0.0, epsilon),
+    ]
+    .iter()
+    .map(|row| (*row, row.dot(&truth)))
+    .collect()
+}
+
+fn fit(solve_method: SolveMethod<f32>) -> (Vector2<f32>, TerminationReason) {
+    let samples = lauchli_samples();
+    let report = optimize_report(
+        Config {
+            initial_lambda: InitialLambda::Fixed(0.0),
+            damping_mode: DampingMode::Identity,
+            solve_method,
+            ..Config::default()
+        },
+        Vector2::zeros(),
+        |model, delta: Vector2<f32>| model + delta,
+        |model| {
+            Residuals::from_iterator(
+                samples.len(),
+                samples.iter().map(|(row, y)| y - row.dot(model)),
+            )
+        },
+        |_| samples.iter().map(|&(row, _)| row),
+    );
+    (report.model, report.termination)
+}
+
+#[test]
+fn normal_equations_lose_precision() {
+    assert_eq!(
+        fit(SolveMethod::NormalEquations).1,
+        TerminationReason::Inverted
+    );
+}
+
+#[test]
+fn qr_solves_ill_conditioned_system() {
+    let (model, _) = fit(SolveMethod::Qr);
+    assert!((model - Vector2::new(1.0, 2.0)).amax() < 1e-3);
+}
+
+/// Fits `y = (a + b)x`, where only the sum of the parameters is determined by the samples.
+fn fit_over_parameterized(
+    solve_method: SolveMethod<f64>,
+) -> MinimizationReport<Vector2<f64>, f64, U2> {
+    let samples: Vec<(f64, f64)> = (1..10)
+        .map(|x| (f64::from(x), 3.0 * f64::from(x)))
+        .collect();
+    optimize_report(
+        Config {
+            method: Method::GaussNewton,
+            threshold: 1e-12,
+            solve_method,
+            ..Config::default()
+        },
+        Vector2::zeros(),
+        |model, delta: Vector2<f64>| model + delta,
+        |model| {
+            common::Residuals::from_iterator(
+                samples.len(),
+                samples.iter().map(|&(x, y)| y - (model.x + model.y) * x),
+            )
+        },
+        |_| samples.iter().map(|&(x, _)| Vector2::new(x, x)),
+    )
+}
+
+#[test]
+fn svd_steps_within_determined_directions() {
+    let report = fit_over_parameterized(SolveMethod::Svd {
+        rank_tolerance: 1e-9,
+    });
+    assert_eq!(report.termination, TerminationReason::BelowThreshold);
+    assert_eq!(report.rank, Some(1));
+    // The pseudo-inverse gives the smallest step, which splits the sum evenly.
+    assert!((report.model - Vector2::new(1.5, 1.5)).amax() < 1e-9);
+}
+
+#[test]
+fn rank_is_only_detected_by_svd() {
+    let report = fit_over_parameterized(SolveMethod::NormalEquations);
+    assert_eq!(report.rank, None);
+}
+
+#[test]
+fn over_parameterized_model_warns_about_rank() {
+    for &solve_method in &[
+        SolveMethod::NormalEquations,
+        SolveMethod::Qr,
+        SolveMethod::Svd {
+            rank_tolerance: 1e-9,
+        },
+    ] {
+        assert!(fit_over_parameterized(solve_method).rank_warning);
+    }
+}
+
+#[test]
+fn condition_estimate_bounds_condition_number() {
+    // Fit `y = ax + b`, whose approximate Hessian is the sum of `(x, 1)(x, 1)ᵀ`.
+    let samples = common::line::samples();
+    let hessian = samples
+        .iter()
+        .fold(nalgebra::Matrix2::zeros(), |h, &(x, _)| {
+            h + Vector2::new(x, 1.0) * Vector2::new(x, 1.0).transpose()
+        });
+    let eigenvalues = hessian.symmetric_eigenvalues();
+    let condition = eigenvalues.max() / eigenvalues.min();
+
+    let fit = |solve_method| {
+        optimize_report(
+            Config {
+                solve_method,
+                ..Config::default()
+            },
+            Vector2::zeros(),
+            |model, delta: Vector2<f64>| model + delta,
+            |model| common::line::residuals(&samples, model),
+            |_| samples.iter().map(|&(x, _)| Vector2::new(x, 1.0)),
+        )
+    };
+
+    let svd = fit(SolveMethod::Svd {
+        rank_tolerance: 0.0,
+    });
+    assert!((svd.condition_estimate.unwrap() / condition - 1.0).abs() < 1e-9);
+    assert!(!svd.rank_warning);
+    // The diagonal of a triangular factor can only underestimate its condition number.
+    for &solve_method in &[SolveMethod::NormalEquations, SolveMethod::Qr] {
+        let report = fit(solve_method);
+        let estimate = report.condition_estimate.unwrap();
+        assert!(estimate > 1.0 && estimate <= condition * (1.0 + 1e-9));
+        assert!(!report.rank_warning);
+    }
+}
+
+#[test]
+fn fifty_parameter_system_is_solved_exactly() {
+    // A single Gauss-Newton step solves a linear fit, so the model it gives is only as accurate
+    // as the solve of the 50x50 system.
+    let p = 50;
+    let basis = |x: f64| {
+        DVector::from_fn(p, |k, _| {
+            let frequency = (k / 2 + 1) as f64;
+            if k % 2 == 0 {
+                (frequency * x).sin()
+            } else {
+                (frequency * x).cos()
+            }
+        })
+    };
+    let truth = DVector::from_fn(p, |k, _| 1.0 / (k + 1) as f64);
+    let samples: Vec<(f64, f64)> = (0..200)
+        .map(|x| {
+            let x = f64::from(x) * 0.03;
+            (x, basis(x).dot(&truth))
+        })
+        .collect();
+
+    for &solve_method in &[SolveMethod::NormalEquations, SolveMethod::Qr] {
+        let report = optimize_report(
+            Config {
+                method: Method::GaussNewton,
+                max_iterations: 1,
+                solve_method,
+                ..Config::default()
+            },
+            DVector::zeros(p),
+            |model, delta| model + delta,
+            |model| {
+                Matrix::<f64, U1, Dynamic, VecStorage<f64, U1, Dynamic>>::from_iterator(
+                    samples.len(),
+                    samples.iter().map(|&(x, y)| y - basis(x).dot(model)),
+                )
+            },
+            |_| samples.iter().map(|&(x, _)| basis(x)),
+        );
+        assert!((report.model - &truth).amax() < 1e-8);
+    }
+}
+
+#[test]
+fn compensated_accumulation_stays_accurate() {
+    // A single Gauss-Newton step from zero gives `Σ(0.1 * 0.3) / Σ(0.1²)`, which is exactly `3`,
+    // but a million small contributions round away much of the naive `f32` sums.
+    let samples = 1_000_000;
+    let step = |compensated_accumulation| {
+        optimize_report(
+            Config {
+                method: Method::GaussNewton,
+                max_iterations: 1,
+                compensated_accumulation,
+                ..Config::default()
+            },
+            Vector1::zeros(),
+            |model, delta: Vector1<f32>| model + delta,
+            |model| Residuals::from_element(samples, 0.3 - 0.1 * model.x),
+            |_| (0..samples).map(|_| Vector1::new(0.1f32)),
+        )
+        .model
+        .x
+    };
+    assert!((step(false) - 3.0).abs() > 1e-3);
+    assert!((step(true) - 3.0).abs() < 1e-5);
+}
+
+#[test]
+fn svd_treats_unconverged_eigendecomposition_as_singular() {
+    // A non-finite Jacobian keeps the Jacobi rotations from ever converging, so no eigenvalues
+    // can be read off of the diagonal.
+    let samples = common::line::samples();
+    let report = optimize_report(
+        Config {
+            solve_method: SolveMethod::Svd {
+                rank_tolerance: 1e-9,
+            },
+            ..Config::default()
+        },
+        Vector2::zeros(),
+        |model, delta: Vector2<f64>| model + delta,
+        |model| common::line::residuals(&samples, model),
+        |_| samples.iter().map(|&(x, _)| Vector2::new(x, f64::NAN)),
+    );
+    assert_eq!(report.termination, TerminationReason::Inverted);
+    assert_eq!(report.rank, None);
+    assert_eq!(report.condition_estimate, None);
+}
@@ -0,0 +1,180 @@
+use levenberg_marquardt::{
+    optimize_problem, Config, InitialLambda, LeastSquaresProblem, LevenbergMarquardt, SolveMethod,
+    StepOutcome, TerminationReason, Workspace,
+};
+use nalgebra::{
+    dimension::{U1, U3},
+    storage::Owned,
+    Dynamic, VecStorage, Vector3,
+};
+
+mod common;
+
+use common::{
+    exponential::{jacobian, residuals, samples},
+    Residuals,
+};
+
+/// The exponential fit as a problem which owns its samples.
+struct Exponential {
+    samples: Vec<(f64, f64)>,
+}
+
+impl LeastSquaresProblem<f64, U3, Dynamic, U1> for Exponential {
+    type Model = Vector3<f64>;
+    type ResidualStorage = VecStorage<f64, U1, Dynamic>;
+    type JacobianStorage = Owned<f64, U3, U1>;
+    type Jacobians<'a> = std::vec::IntoIter<Vector3<f64>>;
+
+    fn apply_delta(&self, model: &Vector3<f64>, delta: Vector3<f64>) -> Vector3<f64> {
+        model + delta
+    }
+
+    fn residuals(&self, model: &Vector3<f64>) -> Residuals {
+        residuals(&self.samples, model)
+    }
+
+    fn jacobians(&self, model: &Vector3<f64>) -> Self::Jacobians<'_> {
+        self.samples
+            .iter()
+            .map(|&(x, _)| jacobian(model, x))
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+}
+
+fn problem() -> Exponential {
+    Exponential { samples: samples() }
+}
+
+fn config() -> Config<f64> {
+    Config {
+        threshold: 1e-20,
+        ..Config::default()
+    }
+}
+
+#[test]
+fn stepping_matches_optimize_problem() {
+    let problem = problem();
+    let init = Vector3::new(1.0, 1.0, 0.0);
+    let mut lm = LevenbergMarquardt::new(config(), init, &problem).unwrap();
+    let termination = loop {
+        match lm.step(&problem) {
+            StepOutcome::Improved | StepOutcome::Rejected => {}
+            StepOutcome::Terminated(termination) => break termination,
+        }
+    };
+
+    let report = optimize_problem(config(), init, &problem);
+    assert_eq!(termination, report.termination);
+    assert_eq!(lm.iterations(), report.iterations);
+    assert_eq!(*lm.best_guess(), report.model);
+    assert_eq!(lm.best_sum_of_squares(), report.sum_of_squares);
+    assert!((report.model - Vector3::new(2.0, 0.5, 1.0)).norm() < 1e-6);
+}
+
+#[test]
+fn state_can_be_restored() {
+    let problem = problem();
+    let mut lm = LevenbergMarquardt::new(config(), Vector3::new(1.0, 1.0, 0.0), &problem).unwrap();
+    lm.step(&problem);
+    let saved = lm.clone();
+    let outcomes: Vec<_> = (0..3).map(|_| lm.step(&problem)).collect();
+    let after = (*lm.guess(), lm.lambda(), lm.iterations());
+
+    lm = saved;
+    assert_eq!(lm.iterations(), 1);
+    let replayed: Vec<_> = (0..3).map(|_| lm.step(&problem)).collect();
+    assert_eq!(outcomes, replayed);
+    assert_eq!((*lm.guess(), lm.lambda(), lm.iterations()), after);
+}
+
+#[test]
+fn terminated_steps_do_nothing() {
+    let problem = problem();
+    let mut lm = LevenbergMarquardt::new(
+        Config {
+            threshold: 1e10,
+            ..Config::default()
+        },
+        Vector3::new(1.0, 1.0, 0.0),
+        &problem,
+    )
+    .unwrap();
+    // The initial guess is already below the threshold, so no step is ever taken.
+    let terminated = StepOutcome::Terminated(TerminationReason::AlreadyOptimal);
+    assert_eq!(lm.step(&problem), terminated);
+    assert_eq!(lm.step(&problem), terminated);
+    assert_eq!(*lm.guess(), Vector3::new(1.0, 1.0, 0.0));
+    assert_eq!(lm.iterations(), 0);
+    assert_eq!(lm.termination(), Some(TerminationReason::AlreadyOptimal));
+    assert_eq!(lm.sum_of_squares(), lm.residuals().norm_squared());
+}
+
+#[test]
+fn initial_residuals_skip_an_evaluation() {
+    let problem = problem();
+    let init = Vector3::new(1.0, 1.0, 0.0);
+    let evaluated = LevenbergMarquardt::new(config(), init, &problem)
+        .unwrap()
+        .run(&problem);
+    let provided =
+        LevenbergMarquardt::with_residuals(config(), init, problem.residuals(&init), &problem)
+            .unwrap()
+            .run(&problem);
+
+    assert_eq!(evaluated, optimize_problem(config(), init, &problem));
+    assert_eq!(
+        provided.residual_evaluations,
+        evaluated.residual_evaluations - 1
+    );
+    assert_eq!(provided.model, evaluated.model);
+    assert_eq!(provided.iterations, evaluated.iterations);
+}
+
+#[test]
+fn reused_workspace_matches_run() {
+    let problem = problem();
+    let mut workspace = Workspace::new();
+    for &solve_method in &[
+        SolveMethod::NormalEquations,
+        SolveMethod::Qr,
+        SolveMethod::Svd {
+            rank_tolerance: 0.0,
+        },
+    ] {
+        let config = Config {
+            solve_method,
+            ..config()
+        };
+        for &init in &[Vector3::new(1.0, 1.0, 0.0), Vector3::new(3.0, 1.0, 2.0)] {
+            let report = LevenbergMarquardt::new(config, init, &problem)
+                .unwrap()
+                .run(&problem);
+            let reused = LevenbergMarquardt::new(config, init, &problem)
+                .unwrap()
+                .run_in(&problem, &mut workspace);
+            assert_eq!(reused, report);
+        }
+    }
+}
+
+#[test]
+fn chosen_lambda_carries_forward() {
+    // With a huge lambda, the smallest candidate is always the best, so each accepted step
+    // should continue from it.
+    let problem = problem();
+    let config = Config {
+        initial_lambda: InitialLambda::Fixed(1e6),
+        lambda_candidates: 4,
+        ..config()
+    };
+    let smallest = config.lambda_convege.powi(3);
+    let mut lm = LevenbergMarquardt::new(config, Vector3::new(1.0, 1.0, 0.0), &problem).unwrap();
+    assert_eq!(lm.step(&problem), StepOutcome::Improved);
+    assert_eq!(lm.lambda(), 1e6 * smallest);
+    assert_eq!(lm.residual_evaluations(), 1 + 4);
+    assert_eq!(lm.step(&problem), StepOutcome::Improved);
+    assert_eq!(lm.lambda(), 1e6 * smallest * smallest);
+}
@@ -0,0 +1,64 @@
+use levenberg_marquardt::{optimize_report, Config, InitialLambda, TerminationReason};
+use nalgebra::{MatrixMN, Vector2, Vector3, U1, U8};
+use std::cell::RefCell;
+
+mod common;
+
+use common::exponential::{jacobian, residuals, samples};
+
+#[test]
+fn caps_every_step() {
+    let samples = samples();
+    let config = Config {
+        threshold: 1e-12,
+        max_step_norm: Some(0.1),
+        ..Config::default()
+    };
+    let deltas = RefCell::new(Vec::new());
+    let report = optimize_report(
+        config,
+        Vector3::new(1.0, 1.0, 0.0),
+        |model, delta: Vector3<f64>| {
+            deltas.borrow_mut().push(delta);
+            model + delta
+        },
+        |model| residuals(&samples, model),
+        |&model| samples.iter().map(move |&(x, _)| jacobian(&model, x)),
+    );
+
+    assert_eq!(report.termination, TerminationReason::BelowThreshold);
+    assert!((report.model - Vector3::new(2.0, 0.5, 1.0)).norm() < 1e-4);
+    let deltas = deltas.borrow();
+    assert!(deltas.iter().all(|delta| delta.norm() <= 0.1 + 1e-12));
+    // The cap was actually hit, since the solution is much further than one step away.
+    assert!(deltas
+        .iter()
+        .any(|delta| (delta.norm() - 0.1).abs() < 1e-12));
+}
+
+#[test]
+fn clamped_steps_are_accepted_on_a_linear_problem() {
+    // Fits `y = 3x + 1`, whose linearization is exact, so every clamped step along the
+    // Gauss-Newton direction reduces the sum-of-squares by what was predicted for it.
+    let xs = [0.0, 0.5, 1.0, 1.5, 2.0, 2.5, 3.0, 3.5];
+    let report = optimize_report(
+        Config {
+            initial_lambda: InitialLambda::Fixed(1e-9),
+            threshold: 1e-20,
+            max_step_norm: Some(0.5),
+            ..Config::default()
+        },
+        Vector2::zeros(),
+        |model, delta: Vector2<f64>| model + delta,
+        |model| {
+            MatrixMN::<f64, U1, U8>::from_fn(|_, i| 3.0 * xs[i] + 1.0 - (model.x * xs[i] + model.y))
+        },
+        |_| xs.iter().map(|&x| Vector2::new(x, 1.0)),
+    );
+
+    assert_eq!(report.termination, TerminationReason::BelowThreshold);
+    assert!((report.model - Vector2::new(3.0, 1.0)).norm() < 1e-9);
+    // The solution is over six caps away, and no step along the way is rejected.
+    assert!(report.accepted_steps > 6);
+    assert_eq!(report.rejected_steps, 0);
+}
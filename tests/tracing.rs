@@ -0,0 +1,121 @@
+#![cfg(feature = "tracing")]
+
+use levenberg_marquardt::{optimize_report, ClosureProblem, Config, LevenbergMarquardt};
+use nalgebra::Vector3;
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+};
+use tracing::{
+    field::{Field, Visit},
+    span::{Attributes, Id, Record},
+    Event, Level, Metadata, Subscriber,
+};
+
+mod common;
+
+use common::exponential::{jacobian, residuals, samples};
+
+/// Records the name of every span and the fields of every event.
+#[derive(Default)]
+struct Recorder {
+    spans: Mutex<Vec<&'static str>>,
+    events: Mutex<Vec<(Level, Vec<String>)>>,
+}
+
+struct Fields(Vec<String>);
+
+impl Visit for Fields {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0.push(format!("{}={:?}", field.name(), value));
+    }
+}
+
+impl Subscriber for Recorder {
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, span: &Attributes<'_>) -> Id {
+        let mut spans = self.spans.lock().unwrap();
+        spans.push(span.metadata().name());
+        Id::from_u64(spans.len() as u64)
+    }
+
+    fn record(&self, _: &Id, _: &Record<'_>) {}
+
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut fields = Fields(Vec::new());
+        event.record(&mut fields);
+        self.events
+            .lock()
+            .unwrap()
+            .push((*event.metadata().level(), fields.0));
+    }
+
+    fn enter(&self, _: &Id) {}
+
+    fn exit(&self, _: &Id) {}
+}
+
+#[test]
+fn emits_span_and_events() {
+    let recorder = Arc::new(Recorder::default());
+    let samples = samples();
+    let report = tracing::subscriber::with_default(recorder.clone(), || {
+        optimize_report(
+            Config {
+                threshold: 1e-20,
+                ..Config::default()
+            },
+            Vector3::new(1.0, 1.0, 0.0),
+            |model, delta| model + delta,
+            |model| residuals(&samples, model),
+            |&model| samples.iter().map(move |&(x, _)| jacobian(&model, x)),
+        )
+    });
+
+    assert_eq!(*recorder.spans.lock().unwrap(), ["optimize"]);
+    let events = recorder.events.lock().unwrap();
+    let iterations: Vec<_> = events
+        .iter()
+        .filter(|(level, _)| *level == Level::TRACE)
+        .collect();
+    assert_eq!(iterations.len(), report.iterations);
+    assert_eq!(iterations[0].1[0], "iteration=1");
+    assert!(iterations
+        .iter()
+        .any(|(_, fields)| fields.iter().any(|field| field == "accepted=true")));
+    let (level, fields) = events.last().unwrap();
+    assert_eq!(*level, Level::DEBUG);
+    assert!(fields.contains(&format!("termination={:?}", report.termination)));
+}
+
+#[test]
+fn batched_runs_emit_span_and_termination() {
+    let recorder = Arc::new(Recorder::default());
+    let samples = samples();
+    let mut problem = ClosureProblem::new(
+        |model: &Vector3<f64>, delta| model + delta,
+        |model: &Vector3<f64>| residuals(&samples, model),
+        |&model: &Vector3<f64>| samples.iter().map(move |&(x, _)| jacobian(&model, x)),
+    );
+    let config = Config {
+        max_iterations: 5,
+        ..Config::default()
+    };
+    let report = tracing::subscriber::with_default(recorder.clone(), || {
+        LevenbergMarquardt::new(config, Vector3::new(1.0, 1.0, 0.0), &problem)
+            .unwrap()
+            .run_batched(&mut problem, |_| {})
+            .unwrap()
+    });
+
+    assert_eq!(*recorder.spans.lock().unwrap(), ["optimize"]);
+    let events = recorder.events.lock().unwrap();
+    let (level, fields) = events.last().unwrap();
+    assert_eq!(*level, Level::DEBUG);
+    assert!(fields.contains(&format!("termination={:?}", report.termination)));
+}
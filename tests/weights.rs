@@ -0,0 +1,130 @@
+use levenberg_marquardt::{
+    optimize_problem, optimize_report, ClosureProblem, Config, InformationWeightedProblem,
+    WeightedProblem,
+};
+use nalgebra::{Dynamic, Matrix, Matrix1, Matrix2, VecStorage, Vector2, U2};
+
+mod common;
+
+use common::{
+    line::{jacobian, problem, residuals, samples},
+    Residuals,
+};
+
+type Residuals2 = Matrix<f64, U2, Dynamic, VecStorage<f64, U2, Dynamic>>;
+
+/// Samples of the line where the last sample is much noisier than the rest, along with the
+/// standard deviation of each sample.
+fn noisy_samples() -> (Vec<(f64, f64)>, Vec<f64>) {
+    let mut samples = samples();
+    let mut sigmas = vec![0.01; samples.len()];
+    samples.last_mut().unwrap().1 += 5.0;
+    *sigmas.last_mut().unwrap() = 10.0;
+    (samples, sigmas)
+}
+
+#[test]
+fn unweighted_noisy_point_skews_fit() {
+    let (samples, _) = noisy_samples();
+    let report = optimize_report(
+        Config::default(),
+        Vector2::zeros(),
+        |model, delta: Vector2<f64>| model + delta,
+        |model| residuals(&samples, model),
+        |_| samples.iter().map(|&(x, _)| jacobian(x)),
+    );
+
+    assert!((report.model - Vector2::new(3.0, 1.0)).norm() > 0.1);
+}
+
+#[test]
+fn weighting_noisy_point_down_recovers_clean_fit() {
+    let (samples, sigmas) = noisy_samples();
+    let problem = WeightedProblem::new(
+        ClosureProblem::new(
+            |model: &Vector2<f64>, delta| model + delta,
+            |model: &Vector2<f64>| residuals(&samples, model),
+            |_: &Vector2<f64>| samples.iter().map(|&(x, _)| jacobian(x)),
+        ),
+        |_: &Vector2<f64>| {
+            Residuals::from_iterator(sigmas.len(), sigmas.iter().map(|sigma| 1.0 / sigma))
+        },
+    );
+    let report = optimize_problem(Config::default(), Vector2::zeros(), &problem);
+
+    assert!((report.model - Vector2::new(3.0, 1.0)).norm() < 1e-3);
+}
+
+#[test]
+fn diagonal_factors_match_weights() {
+    let (samples, sigmas) = noisy_samples();
+    let weighted = optimize_problem(
+        Config::default(),
+        Vector2::zeros(),
+        &WeightedProblem::new(problem(&samples), |_: &Vector2<f64>| {
+            Residuals::from_iterator(sigmas.len(), sigmas.iter().map(|sigma| 1.0 / sigma))
+        }),
+    );
+    let information_weighted = optimize_problem(
+        Config::default(),
+        Vector2::zeros(),
+        &InformationWeightedProblem::new(problem(&samples), |_: &Vector2<f64>| {
+            sigmas.iter().map(|sigma| Matrix1::new(1.0 / sigma))
+        }),
+    );
+
+    assert_eq!(information_weighted, weighted);
+}
+
+#[test]
+fn correlated_measurements_minimize_mahalanobis_distance() {
+    // Two measurements of a point whose errors are correlated differently.
+    let measurements = [
+        (
+            Vector2::new(1.0, 2.0),
+            Matrix2::new(2.0, 0.9, 0.9, 1.0).try_inverse().unwrap(),
+        ),
+        (
+            Vector2::new(1.5, 1.0),
+            Matrix2::new(0.5, -0.2, -0.2, 3.0).try_inverse().unwrap(),
+        ),
+    ];
+    let report = optimize_problem(
+        Config {
+            threshold: 0.0,
+            ..Config::default()
+        },
+        Vector2::zeros(),
+        &InformationWeightedProblem::new(
+            ClosureProblem::new(
+                |model: &Vector2<f64>, delta| model + delta,
+                |model: &Vector2<f64>| {
+                    Residuals2::from_iterator(
+                        measurements.len(),
+                        measurements.iter().flat_map(|(measurement, _)| {
+                            let residual = measurement - model;
+                            vec![residual.x, residual.y]
+                        }),
+                    )
+                },
+                |_: &Vector2<f64>| measurements.iter().map(|_| Matrix2::identity()),
+            ),
+            |_: &Vector2<f64>| {
+                measurements
+                    .iter()
+                    .map(|(_, information)| information.cholesky().unwrap().unpack())
+            },
+        ),
+    );
+
+    // The point which minimizes the sum of `rᵀWr` is `(ΣW)⁻¹ΣWz`.
+    let total: Matrix2<f64> = measurements.iter().map(|(_, w)| w).sum();
+    let weighted: Vector2<f64> = measurements.iter().map(|(z, w)| w * z).sum();
+    let expected = total.try_inverse().unwrap() * weighted;
+    assert!((report.model - expected).norm() < 1e-9);
+    let sum_of_squares: f64 = measurements
+        .iter()
+        .map(|(z, w)| (z - expected).dot(&(w * (z - expected))))
+        .sum();
+    assert!((report.sum_of_squares - sum_of_squares).abs() < 1e-9);
+}